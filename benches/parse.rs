@@ -0,0 +1,30 @@
+//! Parsing time of the long inputs, to check that it grows linearly with the length. Run with "cargo bench", prints
+//! the time of each input for the doubling lengths.
+use std::time::{Duration, Instant};
+
+/// The input of the length n.
+type Input = fn(usize) -> String;
+
+fn time_parse(input: &str) -> Duration {
+    let input = input.to_string();
+    let start = Instant::now();
+    let _ = tscalc::parser::parse_expr(&input);
+    start.elapsed()
+}
+
+fn main() {
+    let cases: [(&str, Input); 5] = [
+        ("duration chain with garbage", |n| "1h".repeat(n) + "x"),
+        ("sum", |n| vec!["1s"; n].join(" + ")),
+        ("sum with garbage", |n| vec!["1s"; n].join(" + ") + " zz"),
+        ("type assertions", |n| {
+            "1s".to_string() + &" :: duration".repeat(n)
+        }),
+        ("comparisons", |n| "1s".to_string() + &" < 2s".repeat(n)),
+    ];
+    for (name, input) in cases {
+        for n in [100, 200, 400, 800] {
+            println!("{:30} n={:4} {:?}", name, n, time_parse(&input(n)));
+        }
+    }
+}
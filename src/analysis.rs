@@ -0,0 +1,871 @@
+//! Analysis of expressions for editors: tokens, parse errors and completion candidates at the cursor, and the
+//! canonical form of the expressions to store. The positions are byte offsets in the input.
+
+use crate::parser::{
+    function_names, match_value,
+    number::{format_scaled, SCALE},
+    parse_expr,
+    zone::{zone_names, UTC},
+    Node, ShortFormat, DURATION_UNITS,
+};
+use chrono::Timelike;
+use std::fmt::{self, Write};
+
+pub use crate::parser::KEYWORDS;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TokenKind {
+    DateTime,
+    /// Grafana relative time, like "now-1d/d".
+    RelativeTime,
+    Duration,
+    Number,
+    String,
+    /// Literal like "now", or "map" and "if".
+    Keyword,
+    Function,
+    Variable,
+    Operator,
+    /// Brackets and commas.
+    Punctuation,
+    /// Anything else, one character at a time.
+    Unknown,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            TokenKind::DateTime => "date-time",
+            TokenKind::RelativeTime => "relative time",
+            TokenKind::Duration => "duration",
+            TokenKind::Number => "number",
+            TokenKind::String => "string",
+            TokenKind::Keyword => "keyword",
+            TokenKind::Function => "function",
+            TokenKind::Variable => "variable",
+            TokenKind::Operator => "operator",
+            TokenKind::Punctuation => "punctuation",
+            TokenKind::Unknown => "unknown",
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    /// Start of the token, inclusive.
+    pub start: usize,
+    /// End of the token, exclusive.
+    pub end: usize,
+}
+
+/// Parse error of the expression.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    /// Start of the range where the parsing failed, inclusive.
+    pub start: usize,
+    /// End of the range, exclusive. The range covers the token at the error position.
+    pub end: usize,
+    pub message: String,
+    /// What would be accepted at the start of the range.
+    pub expected: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CompletionKind {
+    Keyword,
+    Function,
+    /// Duration unit, like "h" in "1h".
+    Unit,
+    TimeZone,
+    /// Variable used elsewhere in the input, like "x" bound by map.
+    Variable,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Completion {
+    /// The completed word, to replace the input between start and the cursor.
+    pub text: String,
+    pub kind: CompletionKind,
+    /// Start of the word being completed.
+    pub start: usize,
+}
+
+/// Operators, the longer ones first so "<=" is not split into "<" and "=".
+const OPERATORS: [&str; 11] = ["|>", "..", "->", "<=", ">=", "==", "!=", "<", ">", "+", "-"];
+
+const PUNCTUATION: [char; 5] = ['(', ')', '[', ']', ','];
+
+/// Split the input into tokens, skipping whitespace. Never fails, the characters that are not recognised are
+/// returned as unknown tokens.
+pub fn tokenize(input: &str) -> Vec<Token> {
+    let functions = function_names();
+    let identifier = regex::Regex::new(r"^[a-zA-Z_][a-zA-Z0-9_]*").unwrap();
+    let mut tokens = vec![];
+    let mut pos = 0;
+    while let Some(c) = input[pos..].chars().next() {
+        if c.is_whitespace() {
+            pos += c.len_utf8();
+            continue;
+        }
+        let rest = &input[pos..];
+        let (kind, len) = if let Some((node, len)) = match_value(&rest.to_string()) {
+            let kind = match node {
+                Node::DateTime(_)
+                | Node::LocalDateTime { .. }
+                | Node::NamedMonthDate { .. }
+                | Node::NumericDate(_) => TokenKind::DateTime,
+                Node::Duration(_) => TokenKind::Duration,
+                Node::Number(_) => TokenKind::Number,
+                Node::String(_) => TokenKind::String,
+                _ => TokenKind::RelativeTime,
+            };
+            (kind, len)
+        } else if let Some(m) = identifier.find(rest) {
+            let word = m.as_str();
+            let kind = if KEYWORDS.contains(&word) {
+                TokenKind::Keyword
+            } else if functions.iter().any(|name| name == word) {
+                TokenKind::Function
+            } else {
+                TokenKind::Variable
+            };
+            (kind, word.len())
+        } else if let Some(oper) = OPERATORS.iter().find(|oper| rest.starts_with(*oper)) {
+            (TokenKind::Operator, oper.len())
+        } else if PUNCTUATION.contains(&c) {
+            (TokenKind::Punctuation, c.len_utf8())
+        } else {
+            (TokenKind::Unknown, c.len_utf8())
+        };
+        tokens.push(Token {
+            kind,
+            start: pos,
+            end: pos + len,
+        });
+        pos += len;
+    }
+    tokens
+}
+
+/// Return the parse errors of the expression, none if the expression parses. The evaluation errors are not reported,
+/// since they depend on the evaluation context.
+pub fn diagnostics(input: &str) -> Vec<Diagnostic> {
+    let input = input.to_string();
+    let parse_err = match parse_expr(&input) {
+        Ok(_) => return vec![],
+        Err(parse_err) => parse_err,
+    };
+    let start = parse_err.pointer.pos;
+    let end = match parse_err.len {
+        0 => tokenize(&input)
+            .iter()
+            .find(|token| token.start == start)
+            .map_or(input.len(), |token| token.end),
+        len => start + len,
+    };
+    vec![Diagnostic {
+        start,
+        end,
+        message: parse_err.message,
+        expected: parse_err.expected,
+    }]
+}
+
+/// Return the completion candidates of the word before the cursor. Words starting with a digit are completed with
+/// duration units, capitalised words with time zone names, and the other words with keywords, function names and
+/// the variables used elsewhere in the input.
+pub fn complete(input: &str, cursor: usize) -> Vec<Completion> {
+    if !input.is_char_boundary(cursor) {
+        return vec![];
+    }
+    let before = &input[..cursor];
+    let start = before
+        .rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == '/'))
+        .map_or(0, |i| i + before[i..].chars().next().unwrap().len_utf8());
+    let word = &before[start..];
+    let completion = |text: String, kind: CompletionKind| Completion { text, kind, start };
+    if word.starts_with(|c: char| c.is_ascii_digit()) {
+        // Only the unit after the last number is completed, like "m" in "1h30m".
+        let number_end = word.rfind(|c: char| c.is_ascii_digit()).unwrap() + 1;
+        let (number, unit) = word.split_at(number_end);
+        return DURATION_UNITS
+            .iter()
+            .filter(|u| u.starts_with(unit))
+            .map(|u| completion(format!("{}{}", number, u), CompletionKind::Unit))
+            .collect();
+    }
+    if word.starts_with(|c: char| c.is_uppercase()) {
+        return zone_names()
+            .into_iter()
+            .filter(|name| name.starts_with(word))
+            .map(|name| completion(name.to_string(), CompletionKind::TimeZone))
+            .collect();
+    }
+    let keywords = KEYWORDS
+        .iter()
+        .map(|keyword| (keyword.to_string(), CompletionKind::Keyword));
+    let functions = function_names()
+        .into_iter()
+        .map(|name| (name, CompletionKind::Function));
+    let mut variables: Vec<String> = vec![];
+    for token in tokenize(input) {
+        let variable = &input[token.start..token.end];
+        if token.kind == TokenKind::Variable
+            && token.start != start
+            && !variables.iter().any(|v| v == variable)
+        {
+            variables.push(variable.to_string());
+        }
+    }
+    let variables = variables
+        .into_iter()
+        .map(|variable| (variable, CompletionKind::Variable));
+    keywords
+        .chain(functions)
+        .chain(variables)
+        .filter(|(text, _)| text.starts_with(word))
+        .map(|(text, kind)| completion(text, kind))
+        .collect()
+}
+
+/// Return the parsed expression with the grouping made explicit with brackets, like "(a - b) + c" for "a - b + c".
+/// The terms of the expression parsed with parse_expr_spanned are written as in the input, the other ones as parsed.
+pub fn grouping(node: &Node, input: &str) -> String {
+    group(node, input, false)
+}
+
+/// The node as in grouping, in brackets if `bracket` and the node is an operation.
+fn group(node: &Node, input: &str, bracket: bool) -> String {
+    let wrap = |s: String| if bracket { format!("({})", s) } else { s };
+    let args = |args: &[&Node]| {
+        let args: Vec<String> = args.iter().map(|arg| group(arg, input, false)).collect();
+        args.join(", ")
+    };
+    match node {
+        Node::Spanned { start, end, node } => match node.as_ref() {
+            // The terms with the inner expressions show the grouping inside.
+            Node::Expr(_)
+            | Node::FuncAry1 { .. }
+            | Node::FuncAry2 { .. }
+            | Node::FuncAry3 { .. }
+            | Node::FuncAry4 { .. }
+            | Node::List(_)
+            | Node::Map { .. }
+            | Node::If { .. }
+            | Node::Interval { .. }
+            | Node::Compare { .. }
+            | Node::TypeAssertion { .. } => group(node, input, bracket),
+            _ => input[*start..*end].to_string(),
+        },
+        Node::Expr(nodes) => {
+            let mut nodes = terms(nodes).into_iter();
+            let Some(first) = nodes.next() else {
+                return String::new();
+            };
+            let mut s = group(first, input, true);
+            let mut opers = 0;
+            for node in nodes {
+                if opers > 0 {
+                    s = format!("({})", s);
+                }
+                s = format!("{} {}", s, group(node, input, true));
+                opers += 1;
+            }
+            match opers {
+                0 => group(first, input, bracket),
+                _ => wrap(s),
+            }
+        }
+        Node::OperNode { oper, node } => format!("{} {}", oper, group(node, input, true)),
+        Node::Compare { oper, left, right } => wrap(format!(
+            "{} {} {}",
+            group(left, input, true),
+            oper,
+            group(right, input, true)
+        )),
+        Node::Interval { start, end } => wrap(format!(
+            "{}..{}",
+            group(start, input, true),
+            group(end, input, true)
+        )),
+        Node::TypeAssertion { node, expected } => {
+            wrap(format!("{} :: {}", group(node, input, true), expected))
+        }
+        Node::FuncAry1 { name, arg1 } => format!("{}({})", name, args(&[arg1])),
+        Node::FuncAry2 { name, arg1, arg2 } => format!("{}({})", name, args(&[arg1, arg2])),
+        Node::FuncAry3 {
+            name,
+            arg1,
+            arg2,
+            arg3,
+        } => format!("{}({})", name, args(&[arg1, arg2, arg3])),
+        Node::FuncAry4 {
+            name,
+            arg1,
+            arg2,
+            arg3,
+            arg4,
+        } => format!("{}({})", name, args(&[arg1, arg2, arg3, arg4])),
+        Node::List(elements) => {
+            let elements: Vec<&Node> = elements.iter().collect();
+            format!("[{}]", args(&elements))
+        }
+        Node::Map {
+            list,
+            variable,
+            body,
+        } => format!(
+            "map({}, {} -> {})",
+            group(list, input, false),
+            variable,
+            group(body, input, false)
+        ),
+        Node::If {
+            cond,
+            then,
+            otherwise,
+        } => format!("if({})", args(&[cond, then, otherwise])),
+        Node::Duration(duration) => duration.as_short_format(),
+        Node::DateTime(datetime) => datetime.to_rfc3339(),
+        Node::LocalDateTime { date, time, tz } => {
+            let date = date.map(|date| format!("{} ", date)).unwrap_or_default();
+            let tz = tz.map(|tz| format!(" {}", tz)).unwrap_or_default();
+            format!("{}{}{}", date, time, tz)
+        }
+        Node::NamedMonthDate {
+            day,
+            month,
+            year,
+            time,
+            tz,
+        } => {
+            let tz = tz.map(|tz| format!(" {}", tz)).unwrap_or_default();
+            format!("{} {} {} {}{}", day, month, year, time, tz)
+        }
+        Node::NumericDate(s) => s.clone(),
+        Node::Now => "now".to_string(),
+        Node::Uptime => "uptime".to_string(),
+        Node::NtpNow => "ntp_now".to_string(),
+        Node::Epoch => "epoch".to_string(),
+        Node::MaxTime => "max_time".to_string(),
+        Node::MinTime => "min_time".to_string(),
+        Node::Literal { literal, .. } => literal.clone(),
+        Node::Variable(name) => name.clone(),
+        Node::String(s) => format!("{:?}", s),
+        Node::Number(value) => (*value as f64 / SCALE as f64).to_string(),
+    }
+}
+
+/// The literal terms of the expression parsed with parse_expr_spanned, like the dates, the times, the durations and
+/// the numbers, with their positions [start, end) in the input, in the order of the input. The keywords, the
+/// variables and the strings are left out, as well as the terms with inner expressions, whose literals are listed.
+pub fn literal_terms(node: &Node) -> Vec<(usize, usize, &Node)> {
+    let mut found = vec![];
+    collect_literals(node, &mut found);
+    found.sort_by_key(|(start, _, _)| *start);
+    found
+}
+
+fn collect_literals<'a>(node: &'a Node, found: &mut Vec<(usize, usize, &'a Node)>) {
+    let mut collect = |nodes: &[&'a Node]| {
+        for node in nodes {
+            collect_literals(node, found);
+        }
+    };
+    match node {
+        Node::Spanned { start, end, node } => match node.as_ref() {
+            Node::Duration(_)
+            | Node::DateTime(_)
+            | Node::LocalDateTime { .. }
+            | Node::NamedMonthDate { .. }
+            | Node::NumericDate(_)
+            | Node::Number(_) => found.push((*start, *end, node)),
+            node => collect(&[node]),
+        },
+        Node::Expr(nodes) | Node::List(nodes) => collect(&nodes.iter().collect::<Vec<&Node>>()),
+        Node::OperNode { node, .. } | Node::TypeAssertion { node, .. } => collect(&[node]),
+        Node::Compare { left, right, .. } => collect(&[left, right]),
+        Node::Interval { start, end } => collect(&[start, end]),
+        Node::FuncAry1 { arg1, .. } => collect(&[arg1]),
+        Node::FuncAry2 { arg1, arg2, .. } => collect(&[arg1, arg2]),
+        Node::FuncAry3 {
+            arg1, arg2, arg3, ..
+        } => collect(&[arg1, arg2, arg3]),
+        Node::FuncAry4 {
+            arg1,
+            arg2,
+            arg3,
+            arg4,
+            ..
+        } => collect(&[arg1, arg2, arg3, arg4]),
+        Node::Map { list, body, .. } => collect(&[list, body]),
+        Node::If {
+            cond,
+            then,
+            otherwise,
+        } => collect(&[cond, then, otherwise]),
+        _ => (),
+    }
+}
+
+/// Parse the expression and render it canonically, like "now - 1d + 2h" for "(now-1d)  +  2h" or "full_day(now)" for
+/// "now |> full_day": the terms as parsed, like the durations in the short format and the date-times in RFC 3339,
+/// the function calls instead of the pipelines, and the brackets only where needed. Evaluating the canonical form
+/// gives the same result as the input, and normalizing it gives it back.
+pub fn normalize(input: &str) -> Result<String, String> {
+    let input = input.to_string();
+    match parse_expr(&input) {
+        Ok(parse_ok) => Ok(canonical(&parse_ok.node)),
+        Err(parse_err) => {
+            // Phrases like "noon tomorrow" are not expressions, but are mapped onto them.
+            #[cfg(feature = "natural")]
+            if let Ok(node) = crate::parser::natural::natural_to_node(&input) {
+                return Ok(canonical(&node));
+            }
+            Err(parse_err.message)
+        }
+    }
+}
+
+/// How tightly the node binds: the operands binding less tightly than the operator need brackets. The sums are
+/// left-associative, so their first term can be a sum.
+fn binding(node: &Node) -> u8 {
+    match node {
+        Node::TypeAssertion { .. } => 0,
+        Node::Compare { .. } => 1,
+        Node::Interval { .. } => 2,
+        Node::Expr(nodes) => match terms(nodes).as_slice() {
+            [single] => binding(single),
+            _ => 3,
+        },
+        Node::Spanned { node, .. } => binding(node),
+        _ => 4,
+    }
+}
+
+/// The node in canonical form, in brackets if it binds less tightly than `min_binding`.
+fn operand(node: &Node, min_binding: u8) -> String {
+    match binding(node) < min_binding {
+        true => format!("({})", canonical(node)),
+        false => canonical(node),
+    }
+}
+
+fn canonical(node: &Node) -> String {
+    let args = |args: &[&Node]| {
+        let args: Vec<String> = args.iter().map(|arg| operand(arg, 0)).collect();
+        args.join(", ")
+    };
+    match node {
+        Node::Spanned { node, .. } => canonical(node),
+        Node::Expr(nodes) => {
+            let mut nodes = terms(nodes).into_iter().peekable();
+            let Some(first) = nodes.next() else {
+                return String::new();
+            };
+            if nodes.peek().is_none() {
+                return canonical(first);
+            }
+            let mut s = operand(first, 3);
+            for node in nodes {
+                match node {
+                    Node::OperNode { oper, node } => write!(s, " {} {}", oper, operand(node, 4)),
+                    node => write!(s, " {}", operand(node, 4)),
+                }
+                .unwrap();
+            }
+            s
+        }
+        Node::OperNode { oper, node } => format!("{} {}", oper, operand(node, 4)),
+        Node::Compare { oper, left, right } => {
+            format!("{} {} {}", operand(left, 1), oper, operand(right, 2))
+        }
+        Node::Interval { start, end } => format!("{}..{}", operand(start, 3), operand(end, 3)),
+        Node::TypeAssertion { node, expected } => format!(
+            "{} :: {}",
+            operand(node, 0),
+            expected.name().map_or(expected.to_string(), str::to_string)
+        ),
+        Node::FuncAry1 { name, arg1 } => format!("{}({})", name, args(&[arg1])),
+        Node::FuncAry2 { name, arg1, arg2 } => format!("{}({})", name, args(&[arg1, arg2])),
+        Node::FuncAry3 {
+            name,
+            arg1,
+            arg2,
+            arg3,
+        } => format!("{}({})", name, args(&[arg1, arg2, arg3])),
+        Node::FuncAry4 {
+            name,
+            arg1,
+            arg2,
+            arg3,
+            arg4,
+        } => format!("{}({})", name, args(&[arg1, arg2, arg3, arg4])),
+        Node::List(elements) => {
+            let elements: Vec<&Node> = elements.iter().collect();
+            format!("[{}]", args(&elements))
+        }
+        Node::Map {
+            list,
+            variable,
+            body,
+        } => format!(
+            "map({}, {} -> {})",
+            operand(list, 0),
+            variable,
+            operand(body, 0)
+        ),
+        Node::If {
+            cond,
+            then,
+            otherwise,
+        } => format!("if({})", args(&[cond, then, otherwise])),
+        Node::Duration(duration) => duration.as_short_format(),
+        // The offsets without the zone are parsed as UTC.
+        Node::DateTime(datetime) if datetime.timezone() == UTC => {
+            datetime.format("%Y-%m-%dT%H:%M:%S%.fZ").to_string()
+        }
+        Node::DateTime(datetime) => format!(
+            "{}[{}]",
+            datetime.format("%Y-%m-%dT%H:%M:%S%.f%:z"),
+            datetime.timezone()
+        ),
+        Node::LocalDateTime { date, time, tz } => {
+            let time = time_of_day(time, date.is_some());
+            let date = date.map(|date| format!("{} ", date)).unwrap_or_default();
+            let tz = tz.map(|tz| format!(" {}", tz)).unwrap_or_default();
+            format!("{}{}{}", date, time, tz)
+        }
+        Node::NamedMonthDate {
+            day,
+            month,
+            year,
+            time,
+            tz,
+        } => {
+            // The zone follows the time, so midnight is written with the zone.
+            let time = match (time, tz) {
+                (&chrono::NaiveTime::MIN, None) => String::new(),
+                (time, tz) => {
+                    let tz = tz.map(|tz| format!(" {}", tz)).unwrap_or_default();
+                    format!(" {}{}", time_of_day(time, true), tz)
+                }
+            };
+            format!("{} {} {}{}", day, month, year, time)
+        }
+        Node::NumericDate(s) => s.clone(),
+        Node::Now => "now".to_string(),
+        Node::Uptime => "uptime".to_string(),
+        Node::NtpNow => "ntp_now".to_string(),
+        Node::Epoch => "epoch".to_string(),
+        Node::MaxTime => "max_time".to_string(),
+        Node::MinTime => "min_time".to_string(),
+        Node::Literal { literal, .. } => literal.clone(),
+        Node::Variable(name) => name.clone(),
+        // There is no escaping in the strings, they cannot contain the quote.
+        Node::String(s) => format!("\"{}\"", s),
+        Node::Number(value) => format_scaled(*value),
+    }
+}
+
+/// The time of day as parsed, with the seconds and their fraction only if not zero. Without a date "HH:MM:SS" is a
+/// duration, so the seconds are written with AM or PM.
+fn time_of_day(time: &chrono::NaiveTime, after_date: bool) -> String {
+    match (time.second() + time.nanosecond(), after_date) {
+        (0, _) => time.format("%H:%M").to_string(),
+        (_, true) => time.format("%H:%M:%S%.f").to_string(),
+        (_, false) => time.format("%-I:%M:%S%.f %p").to_string(),
+    }
+}
+
+/// The first term and the operator nodes of the expression, without the skipped literals. The operator nodes are
+/// parsed as an inner expression.
+fn terms(nodes: &[Node]) -> Vec<&Node> {
+    let mut flat = vec![];
+    for node in nodes {
+        match node {
+            Node::Literal { .. } => (),
+            Node::Expr(inner)
+                if inner
+                    .iter()
+                    .all(|node| matches!(node, Node::OperNode { .. } | Node::Literal { .. })) =>
+            {
+                flat.extend(terms(inner))
+            }
+            node => flat.push(node),
+        }
+    }
+    flat
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        complete, diagnostics, grouping, literal_terms, normalize, tokenize, CompletionKind, Token,
+        TokenKind,
+    };
+    use crate::parser::{
+        evaluate, parse_expr, parse_expr_spanned, registry::Registry, zone::UTC, EvalContext,
+    };
+
+    #[test]
+    fn tokens() {
+        let input = "full_day(now-1d/d) + 1h30m |> fmt(\"%H\") >= x..2024-05-17T00:00:00Z ?";
+        let tokens: Vec<(TokenKind, &str)> = tokenize(input)
+            .iter()
+            .map(|token| (token.kind, &input[token.start..token.end]))
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                (TokenKind::Function, "full_day"),
+                (TokenKind::Punctuation, "("),
+                (TokenKind::RelativeTime, "now-1d/d"),
+                (TokenKind::Punctuation, ")"),
+                (TokenKind::Operator, "+"),
+                (TokenKind::Duration, "1h30m"),
+                (TokenKind::Operator, "|>"),
+                (TokenKind::Function, "fmt"),
+                (TokenKind::Punctuation, "("),
+                (TokenKind::String, "\"%H\""),
+                (TokenKind::Punctuation, ")"),
+                (TokenKind::Operator, ">="),
+                (TokenKind::Variable, "x"),
+                (TokenKind::Operator, ".."),
+                (TokenKind::DateTime, "2024-05-17T00:00:00Z"),
+                (TokenKind::Unknown, "?"),
+            ]
+        );
+        assert_eq!(
+            tokenize(" ł"),
+            vec![Token {
+                kind: TokenKind::Unknown,
+                start: 1,
+                end: 3
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(diagnostics("now + 1h"), vec![]);
+        let errors = diagnostics("now + 1h zz");
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].start, errors[0].end), (9, 11));
+        assert_eq!(errors[0].expected, vec!["end of input".to_string()]);
+        let errors = diagnostics("");
+        assert_eq!((errors[0].start, errors[0].end), (0, 0));
+        assert!(errors[0].expected.contains(&"now".to_string()));
+        let errors = diagnostics("now + ful_day(now)");
+        assert_eq!((errors[0].start, errors[0].end), (6, 13));
+        assert_eq!(errors[0].expected, vec!["full_day".to_string()]);
+        let errors = diagnostics("now + 1 day 300000000 years - 1h");
+        assert_eq!((errors[0].start, errors[0].end), (6, 27));
+    }
+
+    #[test]
+    fn explicit_grouping() {
+        let group = |input: &str| {
+            let input = input.to_string();
+            let node = parse_expr_spanned(&input, &Registry::builtin())
+                .unwrap()
+                .node;
+            grouping(&node, &input)
+        };
+        assert_eq!(group("now"), "now");
+        assert_eq!(group(" now - 1d + 2h "), "(now - 1d) + 2h");
+        assert_eq!(group("now - (1d + 2h)"), "now - (1d + 2h)");
+        assert_eq!(
+            group("full_day(now - 1d - 1h) < now"),
+            "full_day((now - 1d) - 1h) < now"
+        );
+        assert_eq!(
+            group("map([now, epoch], x -> x + 1h)"),
+            "map([now, epoch], x -> x + 1h)"
+        );
+        assert_eq!(
+            group("now..now + 1d :: interval"),
+            "(now..(now + 1d)) :: interval"
+        );
+        let node = parse_expr(&"1h + 2024-05-17T13:00:00Z".to_string())
+            .unwrap()
+            .node;
+        assert_eq!(grouping(&node, ""), "1h + 2024-05-17T13:00:00+00:00");
+    }
+
+    #[test]
+    fn literals() {
+        let literals = |input: &str| -> Vec<String> {
+            let input = input.to_string();
+            let node = parse_expr_spanned(&input, &Registry::builtin())
+                .unwrap()
+                .node;
+            literal_terms(&node)
+                .into_iter()
+                .map(|(start, end, _)| input[start..end].to_string())
+                .collect()
+        };
+        assert_eq!(literals("now"), Vec::<String>::new());
+        assert_eq!(
+            literals("1716000000 + 1h - full_day(5/17/24)"),
+            vec!["1716000000", "1h", "5/17/24"]
+        );
+        assert_eq!(
+            literals("map([17 May 2024, 13:00], x -> x + 2d)"),
+            vec!["17 May 2024", "13:00", "2d"]
+        );
+    }
+
+    #[test]
+    fn normalized() {
+        assert_eq!(normalize("(now-1d)  +  2h").unwrap(), "now - 1d + 2h");
+        assert_eq!(normalize("now - (1d + 2h)").unwrap(), "now - (1d + 2h)");
+        assert_eq!(normalize("now |> full_day").unwrap(), "full_day(now)");
+        assert_eq!(normalize("now-7d/d").unwrap(), "full_day(now - 7d)");
+        assert_eq!(
+            normalize("now-1M/M").unwrap(),
+            "full_month(add_months(now, -1))"
+        );
+        assert_eq!(
+            normalize("2024-05-17T15:00:00+02:00 - 90m").unwrap(),
+            "2024-05-17T13:00:00Z - 1h30m"
+        );
+        assert_eq!(normalize("1716000000.500").unwrap(), "1716000000.5");
+        assert!(normalize("now +").is_err());
+    }
+
+    /// Evaluating the canonical form gives the same result as the input, and normalizing it gives it back.
+    #[test]
+    fn normalized_round_trip() {
+        let eval = |input: &str| {
+            let ctx = EvalContext::new(
+                chrono::DateTime::parse_from_rfc3339("2024-05-17T13:00:00Z")
+                    .unwrap()
+                    .with_timezone(&UTC),
+            );
+            let node = parse_expr(&input.to_string()).unwrap().node;
+            format!("{:?}", evaluate(node, &ctx))
+        };
+        let mut inputs = vec![
+            "now",
+            " now - 1d + 2h ",
+            "now - (1d + 2h) - -1h",
+            "1h + (now - 1d)",
+            "2024-05-17T13:00:00.250Z - 2024-05-17T10:00:00+02:00",
+            "+12024-05-17T13:00:00Z - -0044-03-15 12:00",
+            "2024-05-17 13:00:05.25 - 1:00:05.5 PM",
+            "May 17th, 2024 13:00:05 UTC",
+            "1716000000 + 1h30m",
+            "-1716000000.000000001 + 1ns",
+            "0x65f00000",
+            "epoch..max_time",
+            "(now..now + 1d) :: interval",
+            "now - 1h < now == 1d > 2h",
+            "now < now + 1h :: bool",
+            "(now < now + 1h) == (1h > 2h)",
+            "full_day(now - 1d - 1h) < now",
+            "now |> full_day |> add_months(1)",
+            "clamp(now + 1d, epoch, max_time)",
+            "map([now, epoch, 5/17/24], x -> x + 1h)",
+            "if(now > epoch, 1h, 2h)",
+            "13:00 + 1h",
+            "1:00:30 pm - 12:00:01 AM",
+            "2024-05-17 13:00 UTC",
+            "17 May 2024 + 13h",
+            "May 17th, 2024 1:00 PM UTC",
+            "17 May 2024 0:00 UTC",
+            "17.05.24 - 1d",
+            "now-1d/d",
+            "now+2y-1M/M",
+            "from_snowflake(175928847299117063)",
+            "now + now",
+            "1d :: datetime",
+        ];
+        if cfg!(feature = "tz") {
+            inputs.extend([
+                "2024-05-17T13:00:00+02:00[Europe/Warsaw] |> full_day",
+                "2024-05-17T13:00:00[Europe/Warsaw] + 1d",
+                "2024-05-17 13:00:00 PST",
+                "May 17, 2024 1:00:30 pm Europe/Warsaw",
+                "May 17th, 2024 13:00:05 Europe/Warsaw",
+            ]);
+        }
+        for input in inputs {
+            let normalized = normalize(input).unwrap();
+            assert_eq!(normalize(&normalized).unwrap(), normalized, "{}", input);
+            assert_eq!(
+                eval(&normalized),
+                eval(input),
+                "{} vs {}",
+                input,
+                normalized
+            );
+        }
+    }
+
+    #[cfg(feature = "natural")]
+    #[test]
+    fn normalized_natural() {
+        let ctx = EvalContext::new(
+            chrono::DateTime::parse_from_rfc3339("2024-05-17T13:00:00Z")
+                .unwrap()
+                .with_timezone(&UTC),
+        );
+        assert_eq!(normalize("2 hours ago").unwrap(), "now - 2h");
+        for input in ["noon tomorrow", "next friday", "in 3 days", "tomorrow 3pm"] {
+            let normalized = normalize(input).unwrap();
+            let node = crate::parser::natural::natural_to_node(input).unwrap();
+            let normalized_node = parse_expr(&normalized).unwrap().node;
+            assert_eq!(
+                evaluate(normalized_node, &ctx),
+                evaluate(node, &ctx),
+                "{}",
+                input
+            );
+            assert_eq!(normalize(&normalized).unwrap(), normalized);
+        }
+    }
+
+    #[test]
+    fn completion() {
+        let texts = |input: &str, cursor: usize| -> Vec<String> {
+            complete(input, cursor)
+                .into_iter()
+                .map(|completion| completion.text)
+                .collect()
+        };
+        assert_eq!(texts("now + full_d", 12), vec!["full_day"]);
+        assert_eq!(
+            texts("now + full_d", 8),
+            vec![
+                "full_day",
+                "full_hour",
+                "full_minute",
+                "full_second",
+                "full_millis",
+                "full_micros",
+                "full_week",
+                "full_month",
+                "full_quarter",
+                "full_year"
+            ]
+        );
+        assert_eq!(texts("ep", 2), vec!["epoch"]);
+        assert_eq!(
+            texts("now - 15", 8),
+            vec!["15y", "15w", "15d", "15h", "15m", "15s", "15ms", "15us", "15ns"]
+        );
+        assert_eq!(texts("now - 1h30m", 11), vec!["1h30m", "1h30ms"]);
+        #[cfg(feature = "tz")]
+        assert_eq!(texts("Europe/Wars", 11), vec!["Europe/Warsaw"]);
+        assert_eq!(texts("ł", 1), Vec::<String>::new());
+        assert_eq!(texts("map(l, item -> it", 17), vec!["item"]);
+        let completions = complete("x + n", 5);
+        assert_eq!(completions[0].start, 4);
+        assert_eq!(completions[0].kind, CompletionKind::Keyword);
+        assert!(completions
+            .iter()
+            .any(|c| c.text == "next_cron" && c.kind == CompletionKind::Function));
+    }
+}
@@ -0,0 +1,328 @@
+use crate::parser::zone::Tz;
+use chrono::{DateTime, TimeDelta};
+
+use crate::parser::EvaluationResult;
+
+/// Processing of the results of all the stdin lines together, instead of printing each result on its own.
+pub trait Batch {
+    /// Take the result of the next line and return a row to print, if any.
+    fn push(&mut self, result: EvaluationResult) -> Result<Option<Vec<EvaluationResult>>, String>;
+
+    /// Return the last row to print, after all the lines were pushed.
+    fn finish(&mut self) -> Option<Vec<EvaluationResult>>;
+}
+
+/// Coalesce sorted date-times into intervals, starting a new interval when the gap to the previous date-time is
+/// larger than the threshold. Each interval is printed as start, end and duration.
+pub struct MergeGaps {
+    gap: TimeDelta,
+    current: Option<(DateTime<Tz>, DateTime<Tz>)>,
+}
+
+impl MergeGaps {
+    pub fn new(gap: TimeDelta) -> MergeGaps {
+        MergeGaps { gap, current: None }
+    }
+}
+
+impl Batch for MergeGaps {
+    fn push(&mut self, result: EvaluationResult) -> Result<Option<Vec<EvaluationResult>>, String> {
+        let datetime = if let EvaluationResult::DateTime(datetime) = result {
+            datetime
+        } else {
+            return Err(format!("--merge-gaps expects date-times, got {}", result));
+        };
+        let (start, end) = match self.current {
+            Some(current) => current,
+            None => {
+                self.current = Some((datetime, datetime));
+                return Ok(None);
+            }
+        };
+        if datetime < end {
+            return Err(format!(
+                "--merge-gaps expects sorted date-times, got {} after {}",
+                datetime, end
+            ));
+        }
+        if datetime - end > self.gap {
+            self.current = Some((datetime, datetime));
+            Ok(Some(interval_row(start, end)))
+        } else {
+            self.current = Some((start, datetime));
+            Ok(None)
+        }
+    }
+
+    fn finish(&mut self) -> Option<Vec<EvaluationResult>> {
+        self.current
+            .take()
+            .map(|(start, end)| interval_row(start, end))
+    }
+}
+
+/// Print the difference between each date-time and the previous one, 0s for the first one.
+pub struct Diff {
+    previous: Option<DateTime<Tz>>,
+}
+
+impl Diff {
+    pub fn new() -> Diff {
+        Diff { previous: None }
+    }
+}
+
+impl Batch for Diff {
+    fn push(&mut self, result: EvaluationResult) -> Result<Option<Vec<EvaluationResult>>, String> {
+        let datetime = if let EvaluationResult::DateTime(datetime) = result {
+            datetime
+        } else {
+            return Err(format!("--diff expects date-times, got {}", result));
+        };
+        let delta = datetime - self.previous.unwrap_or(datetime);
+        self.previous = Some(datetime);
+        Ok(Some(vec![EvaluationResult::TimeDelta(delta)]))
+    }
+
+    fn finish(&mut self) -> Option<Vec<EvaluationResult>> {
+        None
+    }
+}
+
+/// Print the offset of each date-time from the reference date-time, negative for the date-times before it.
+pub struct Since {
+    reference: DateTime<Tz>,
+}
+
+impl Since {
+    pub fn new(reference: DateTime<Tz>) -> Since {
+        Since { reference }
+    }
+}
+
+impl Batch for Since {
+    fn push(&mut self, result: EvaluationResult) -> Result<Option<Vec<EvaluationResult>>, String> {
+        if let EvaluationResult::DateTime(datetime) = result {
+            Ok(Some(vec![EvaluationResult::TimeDelta(
+                datetime - self.reference,
+            )]))
+        } else {
+            Err(format!("--since expects date-times, got {}", result))
+        }
+    }
+
+    fn finish(&mut self) -> Option<Vec<EvaluationResult>> {
+        None
+    }
+}
+
+/// Move each date-time forward to the oldest one still kept with the retention, so a requested start does not point
+/// before the available data.
+pub struct RetentionFloor {
+    oldest: DateTime<Tz>,
+}
+
+impl RetentionFloor {
+    pub fn new(oldest: DateTime<Tz>) -> RetentionFloor {
+        RetentionFloor { oldest }
+    }
+}
+
+impl Batch for RetentionFloor {
+    fn push(&mut self, result: EvaluationResult) -> Result<Option<Vec<EvaluationResult>>, String> {
+        if let EvaluationResult::DateTime(datetime) = result {
+            Ok(Some(vec![EvaluationResult::DateTime(
+                datetime.max(self.oldest),
+            )]))
+        } else {
+            Err(format!("--retention expects date-times, got {}", result))
+        }
+    }
+
+    fn finish(&mut self) -> Option<Vec<EvaluationResult>> {
+        None
+    }
+}
+
+/// Check that the date-times do not decrease, and fail on the first one that is before the previous one. Prints
+/// nothing.
+pub struct AssertMonotonic {
+    line: usize,
+    previous: Option<DateTime<Tz>>,
+}
+
+impl AssertMonotonic {
+    pub fn new() -> AssertMonotonic {
+        AssertMonotonic {
+            line: 0,
+            previous: None,
+        }
+    }
+}
+
+impl Batch for AssertMonotonic {
+    fn push(&mut self, result: EvaluationResult) -> Result<Option<Vec<EvaluationResult>>, String> {
+        self.line += 1;
+        let datetime = if let EvaluationResult::DateTime(datetime) = result {
+            datetime
+        } else {
+            return Err(format!(
+                "line {}: --assert-monotonic expects date-times, got {}",
+                self.line, result
+            ));
+        };
+        match self.previous {
+            Some(previous) if datetime < previous => Err(format!(
+                "line {} ({}) is before line {} ({})",
+                self.line,
+                datetime,
+                self.line - 1,
+                previous
+            )),
+            _ => {
+                self.previous = Some(datetime);
+                Ok(None)
+            }
+        }
+    }
+
+    fn finish(&mut self) -> Option<Vec<EvaluationResult>> {
+        None
+    }
+}
+
+fn interval_row(start: DateTime<Tz>, end: DateTime<Tz>) -> Vec<EvaluationResult> {
+    vec![
+        EvaluationResult::DateTime(start),
+        EvaluationResult::DateTime(end),
+        EvaluationResult::TimeDelta(end - start),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AssertMonotonic, Batch, Diff, MergeGaps, RetentionFloor, Since};
+    use crate::parser::zone::UTC;
+    use crate::parser::EvaluationResult;
+    use chrono::TimeDelta;
+
+    #[test]
+    fn merge_gaps() {
+        let t0 = chrono::DateTime::from_timestamp(1_716_000_000, 0)
+            .unwrap()
+            .with_timezone(&UTC);
+        let at = |minutes: i64| EvaluationResult::DateTime(t0 + TimeDelta::minutes(minutes));
+        let mut batch = MergeGaps::new(TimeDelta::minutes(5));
+        let mut rows = vec![];
+        for minutes in [0, 1, 5, 10, 20, 21] {
+            rows.extend(batch.push(at(minutes)).unwrap());
+        }
+        rows.extend(batch.finish());
+        assert_eq!(
+            rows,
+            vec![
+                vec![
+                    at(0),
+                    at(10),
+                    EvaluationResult::TimeDelta(TimeDelta::minutes(10))
+                ],
+                vec![
+                    at(20),
+                    at(21),
+                    EvaluationResult::TimeDelta(TimeDelta::minutes(1))
+                ],
+            ]
+        );
+        assert!(batch.push(at(5)).is_ok());
+        assert!(batch.push(at(4)).is_err());
+        assert!(batch
+            .push(EvaluationResult::TimeDelta(TimeDelta::zero()))
+            .is_err());
+    }
+
+    #[test]
+    fn diff() {
+        let t0 = chrono::DateTime::from_timestamp(1_716_000_000, 0)
+            .unwrap()
+            .with_timezone(&UTC);
+        let at = |seconds: i64| EvaluationResult::DateTime(t0 + TimeDelta::seconds(seconds));
+        let mut batch = Diff::new();
+        let rows: Vec<Vec<EvaluationResult>> = [0, 30, 25, 100]
+            .into_iter()
+            .filter_map(|seconds| batch.push(at(seconds)).unwrap())
+            .collect();
+        let deltas: Vec<Vec<EvaluationResult>> = [0, 30, -5, 75]
+            .into_iter()
+            .map(|seconds| vec![EvaluationResult::TimeDelta(TimeDelta::seconds(seconds))])
+            .collect();
+        assert_eq!(rows, deltas);
+        assert_eq!(batch.finish(), None);
+        assert_eq!(
+            batch.push(EvaluationResult::TimeDelta(TimeDelta::hours(1))),
+            Err("--diff expects date-times, got 1h".to_string())
+        );
+    }
+
+    #[test]
+    fn since() {
+        let t0 = chrono::DateTime::from_timestamp(1_716_000_000, 0)
+            .unwrap()
+            .with_timezone(&UTC);
+        let mut batch = Since::new(t0);
+        assert_eq!(
+            batch.push(EvaluationResult::DateTime(t0 - TimeDelta::hours(1))),
+            Ok(Some(vec![EvaluationResult::TimeDelta(TimeDelta::hours(
+                -1
+            ))]))
+        );
+        assert_eq!(
+            batch.push(EvaluationResult::DateTime(t0 + TimeDelta::days(2))),
+            Ok(Some(vec![EvaluationResult::TimeDelta(TimeDelta::days(2))]))
+        );
+        assert!(batch
+            .push(EvaluationResult::TimeDelta(TimeDelta::zero()))
+            .is_err());
+    }
+
+    #[test]
+    fn retention_floor() {
+        let t0 = chrono::DateTime::from_timestamp(1_716_000_000, 0)
+            .unwrap()
+            .with_timezone(&UTC);
+        let mut batch = RetentionFloor::new(t0);
+        assert_eq!(
+            batch.push(EvaluationResult::DateTime(t0 - TimeDelta::days(3))),
+            Ok(Some(vec![EvaluationResult::DateTime(t0)]))
+        );
+        assert_eq!(
+            batch.push(EvaluationResult::DateTime(t0 + TimeDelta::hours(1))),
+            Ok(Some(vec![EvaluationResult::DateTime(
+                t0 + TimeDelta::hours(1)
+            )]))
+        );
+        assert!(batch
+            .push(EvaluationResult::TimeDelta(TimeDelta::zero()))
+            .is_err());
+        assert_eq!(batch.finish(), None);
+    }
+
+    #[test]
+    fn assert_monotonic() {
+        let t0 = chrono::DateTime::from_timestamp(1_716_000_000, 0)
+            .unwrap()
+            .with_timezone(&UTC);
+        let at = |seconds: i64| EvaluationResult::DateTime(t0 + TimeDelta::seconds(seconds));
+        let mut batch = AssertMonotonic::new();
+        for seconds in [0, 1, 1, 5] {
+            assert_eq!(batch.push(at(seconds)), Ok(None));
+        }
+        assert_eq!(
+            batch.push(at(4)),
+            Err(
+                "line 5 (2024-05-18 02:40:04 UTC) is before line 4 (2024-05-18 02:40:05 UTC)"
+                    .to_string()
+            )
+        );
+        assert_eq!(batch.finish(), None);
+    }
+}
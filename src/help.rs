@@ -0,0 +1,1147 @@
+//! The help of tscalc as data: the literals, the functions, the commands and the flags with the descriptions and the
+//! examples. The -h text, the list of the functions of --help-functions and the man page are rendered from it, so the
+//! three stay in sync, and the tests check that the registered functions and the parsed flags are all described.
+use std::fmt::Write;
+
+use tscalc::parser::{
+    epoch::EPOCHS,
+    registry::{Registry, MAX_ARITY},
+};
+
+/// Width of the wrapped lines of the help.
+const WIDTH: usize = 116;
+
+/// A literal, a function, a command or a flag.
+pub struct Topic {
+    /// The name as written, like "full_day", "timer start NAME" or "-tz, --tz". The alternatives are separated with
+    /// ", ".
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Expression using the literal or the function, checked by the tests.
+    pub example: Option<&'static str>,
+    /// The cargo feature needed, if not in every build.
+    pub feature: Option<&'static str>,
+}
+
+impl Topic {
+    const fn new(name: &'static str, description: &'static str) -> Topic {
+        Topic {
+            name,
+            description,
+            example: None,
+            feature: None,
+        }
+    }
+
+    const fn example(self, example: &'static str) -> Topic {
+        Topic {
+            example: Some(example),
+            ..self
+        }
+    }
+
+    const fn feature(self, feature: &'static str) -> Topic {
+        Topic {
+            feature: Some(feature),
+            ..self
+        }
+    }
+
+    /// The names of the alternatives, like "time" and "time_of_day".
+    pub fn names(&self) -> impl Iterator<Item = &'static str> {
+        self.name.split(", ")
+    }
+
+    /// The description with the feature it needs.
+    fn text(&self) -> String {
+        match self.feature {
+            Some(feature) => format!("{} Needs the {} feature.", self.description, feature),
+            None => self.description.to_string(),
+        }
+    }
+
+    /// The alternate epochs of from_<epoch> and to_<epoch>, listed after the latter.
+    fn details(&self) -> Vec<(&'static str, &'static str)> {
+        if self.name == "to_<epoch>" {
+            EPOCHS
+                .iter()
+                .map(|epoch| (epoch.name, epoch.description))
+                .collect()
+        } else {
+            vec![]
+        }
+    }
+}
+
+/// A group of the examples listed by the examples command.
+pub struct Category {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// The names of the literals and the functions whose examples are in the group.
+    pub topics: &'static [&'static str],
+}
+
+const INTRO: &str = "Simple calculator for date-time and durations.";
+
+/// The syntax beyond the literals, in paragraphs.
+const SYNTAX: &[&str] = &[
+    "Relative time like in Grafana and Elasticsearch, without whitespace: now-1h, now-7d/d, now/M. The units are s, \
+     m, h, d, w, M (month) and y (year). The /unit suffix rounds down to the beginning of the unit.",
+    "Scheduling phrases like next tuesday 3pm, noon tomorrow, midnight, in 2 hours or 2 days ago at noon are accepted \
+     as the whole expression, as in at(1). A day is its midnight, and midnight or at 3pm alone is the next \
+     occurrence. Needs the natural feature.",
+];
+
+/// The environment variables, described in the man page.
+const ENVIRONMENT: &[Topic] = &[
+    Topic::new(
+        "TSCALC_LOG",
+        "Log level when -v is not given: 0 or off, 1 or eval, 2 or parse.",
+    ),
+    Topic::new(
+        "TSCALC_TIMERS",
+        "File with the running timers, by default tscalc/timers in $XDG_STATE_HOME or in ~/.local/state, and in \
+         %LOCALAPPDATA% on Windows.",
+    ),
+    Topic::new(
+        "TSCALC_HISTORY",
+        "File with the history of the repl, by default ~/.local/share/tscalc/history, and %APPDATA%\\tscalc\\history \
+         on Windows.",
+    )
+    .feature("repl"),
+];
+
+/// Split the text into lines of at most the width, at the spaces. Longer words get their own line.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in text.split(' ') {
+        if !line.is_empty() && line.len() + 1 + word.len() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    lines.push(line);
+    lines
+}
+
+/// Render the topics like "- full_day\tReturn full day of the date-time.", with the continued lines after a tab.
+fn help_entries(topics: &[Topic], prefix: &str, out: &mut String) {
+    for topic in topics {
+        let text = topic.text();
+        let first = wrap(&text, WIDTH.saturating_sub(prefix.len() + topic.name.len())).remove(0);
+        writeln!(out, "{}{}\t{}", prefix, topic.name, first).unwrap();
+        let rest = text[first.len()..].trim_start();
+        if !rest.is_empty() {
+            for line in wrap(rest, WIDTH - 8) {
+                writeln!(out, "\t{}", line).unwrap();
+            }
+        }
+        for (name, description) in topic.details() {
+            writeln!(out, "  - {}\t{}", name, description).unwrap();
+        }
+    }
+}
+
+/// The text printed with -h.
+pub fn help() -> String {
+    let mut out = String::new();
+    writeln!(out, "{}\n\nLiterals:", INTRO).unwrap();
+    help_entries(LITERALS, "- ", &mut out);
+    for paragraph in SYNTAX {
+        writeln!(out, "\n{}", wrap(paragraph, WIDTH).join("\n")).unwrap();
+    }
+    writeln!(out, "\nBuilt-in functions:").unwrap();
+    help_entries(FUNCTIONS, "- ", &mut out);
+    writeln!(out, "\nCommands:").unwrap();
+    help_entries(COMMANDS, "- ", &mut out);
+    writeln!(out).unwrap();
+    help_entries(FLAGS, "", &mut out);
+    out.trim_end().to_string()
+}
+
+/// The numbers of arguments of the function, like "2 or 3", if all the alternatives are registered with the same ones.
+fn arities(topic: &Topic, registry: &Registry) -> Option<String> {
+    let arities = |name: &str| -> Vec<usize> {
+        (1..=MAX_ARITY)
+            .filter(|arity| registry.names_with_arity(*arity).iter().any(|n| n == name))
+            .collect()
+    };
+    let mut names = topic.names();
+    let first = arities(names.next()?);
+    if first.is_empty() || names.any(|name| arities(name) != first) {
+        return None;
+    }
+    let first: Vec<String> = first.iter().map(|arity| arity.to_string()).collect();
+    Some(first.join(" or "))
+}
+
+/// The functions with the descriptions, the numbers of arguments and the examples, printed with --help-functions.
+pub fn functions_help(registry: &Registry) -> String {
+    let mut out = String::new();
+    for topic in FUNCTIONS {
+        writeln!(out, "{}", topic.name).unwrap();
+        for line in wrap(&topic.text(), WIDTH - 4) {
+            writeln!(out, "    {}", line).unwrap();
+        }
+        for (name, description) in topic.details() {
+            writeln!(out, "    - {}: {}", name, description).unwrap();
+        }
+        if let Some(arities) = arities(topic, registry) {
+            writeln!(out, "    Arguments: {}", arities).unwrap();
+        }
+        if let Some(example) = topic.example {
+            writeln!(out, "    Example: {}", example).unwrap();
+        }
+        writeln!(out).unwrap();
+    }
+    out.trim_end().to_string()
+}
+
+/// Escape the text for roff, so the dashes are not hyphens and the lines do not start a request.
+fn roff(text: &str) -> String {
+    let escaped = text.replace('\\', "\\e").replace('-', "\\-");
+    if escaped.starts_with(['.', '\'']) {
+        format!("\\&{}", escaped)
+    } else {
+        escaped
+    }
+}
+
+/// Render the topics as tagged paragraphs of the man page.
+fn man_entries(topics: &[Topic], out: &mut String) {
+    for topic in topics {
+        writeln!(out, ".TP\n.B {}\n{}", roff(topic.name), roff(&topic.text())).unwrap();
+        for (name, description) in topic.details() {
+            writeln!(out, ".br\n{}: {}", roff(name), roff(description)).unwrap();
+        }
+        if let Some(example) = topic.example {
+            writeln!(out, ".br\nExample: \\fB{}\\fR", roff(example)).unwrap();
+        }
+    }
+}
+
+/// The man page in the roff format, printed by the man command, like "tscalc man > tscalc.1".
+pub fn man_page() -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        ".TH TSCALC 1 \"\" \"tscalc {}\" \"User Commands\"",
+        env!("CARGO_PKG_VERSION")
+    )
+    .unwrap();
+    writeln!(
+        out,
+        ".SH NAME\ntscalc \\- calculator for date\\-time and durations"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        ".SH SYNOPSIS\n.B tscalc\n[\\fIFLAGS\\fR] [\\fB\\-\\-\\fR \\fIEXPRESSION\\fR]\n.br\n.B tscalc\n\\fICOMMAND\\fR \
+         [\\fIARGUMENTS\\fR] [\\fIFLAGS\\fR]"
+    )
+    .unwrap();
+    writeln!(out, ".SH DESCRIPTION\n{}", roff(INTRO)).unwrap();
+    for paragraph in SYNTAX {
+        writeln!(out, ".PP\n{}", roff(paragraph)).unwrap();
+    }
+    let sections = [
+        ("LITERALS", LITERALS),
+        ("FUNCTIONS", FUNCTIONS),
+        ("COMMANDS", COMMANDS),
+        ("FLAGS", FLAGS),
+        ("ENVIRONMENT", ENVIRONMENT),
+    ];
+    for (section, topics) in sections {
+        writeln!(out, ".SH {}", section).unwrap();
+        man_entries(topics, &mut out);
+    }
+    out
+}
+
+/// The example of the literal or the function with the name.
+fn example_of(name: &str) -> Option<&'static str> {
+    LITERALS
+        .iter()
+        .chain(FUNCTIONS)
+        .find(|topic| topic.name == name)
+        .and_then(|topic| topic.example)
+}
+
+/// The examples of all the categories, or of the one named, with the category. They are numbered across all the
+/// categories, from 1, so the number given to --run is the same with and without the category.
+fn numbered_examples(
+    category: Option<&str>,
+) -> Result<Vec<(usize, &'static Category, &'static str)>, String> {
+    if let Some(name) = category {
+        if !CATEGORIES.iter().any(|category| category.name == name) {
+            let names: Vec<&str> = CATEGORIES.iter().map(|category| category.name).collect();
+            return Err(format!(
+                "unknown example category {:?}, expected one of {}",
+                name,
+                names.join(", ")
+            ));
+        }
+    }
+    Ok(CATEGORIES
+        .iter()
+        .flat_map(|category| {
+            category
+                .topics
+                .iter()
+                .filter_map(move |name| Some((category, example_of(name)?)))
+        })
+        .enumerate()
+        .map(|(index, (category, example))| (index + 1, category, example))
+        .filter(|(_, example_category, _)| {
+            category.is_none_or(|name| example_category.name == name)
+        })
+        .collect())
+}
+
+/// The numbered examples under the names of the categories, printed by the examples command.
+pub fn examples_help(category: Option<&str>) -> Result<String, String> {
+    let mut out = String::new();
+    let examples = numbered_examples(category)?;
+    let mut last: Option<&str> = None;
+    for (number, category, example) in &examples {
+        if last != Some(category.name) {
+            if last.is_some() {
+                writeln!(out).unwrap();
+            }
+            writeln!(out, "{}: {}", category.name, category.description).unwrap();
+            last = Some(category.name);
+        }
+        writeln!(out, "{:>4}. {}", number, example).unwrap();
+    }
+    // The numbers are the same with and without the category, so the example is run by the number alone.
+    if let Some((first, _, _)) = examples.first() {
+        writeln!(
+            out,
+            "\nEvaluate one of them with --run N, like tscalc examples --run {}.",
+            first
+        )
+        .unwrap();
+    }
+    Ok(out.trim_end().to_string())
+}
+
+/// The expression of the example with the number, as listed by the examples command.
+pub fn example(category: Option<&str>, number: usize) -> Result<&'static str, String> {
+    let examples = numbered_examples(category)?;
+    examples
+        .iter()
+        .find(|(example_number, _, _)| *example_number == number)
+        .map(|(_, _, example)| *example)
+        .ok_or_else(|| match (examples.first(), examples.last()) {
+            (Some((first, _, _)), Some((last, _, _))) => {
+                format!("no example {}, expected {} to {}", number, first, last)
+            }
+            _ => format!("no example {}", number),
+        })
+}
+
+/// The categories of the examples, with the literals and the functions shown in each.
+pub const CATEGORIES: &[Category] = &[
+    Category {
+        name: "offsets",
+        description: "Move the date-times by durations and calendar units, and round them.",
+        topics: &[
+            "1h30m",
+            "full_day",
+            "full_week",
+            "ceil_day, ceil_hour, ceil_minute, ceil_second, ceil_millis, ceil_micros",
+            "add_months",
+            "at",
+            "with_<component>",
+            "|>",
+            "shift",
+        ],
+    },
+    Category {
+        name: "conversions",
+        description: "Convert the date-times from and to epochs, IDs, tokens and formats.",
+        topics: &[
+            "17 May 2024",
+            "from_<epoch>",
+            "to_<epoch>",
+            "fmt",
+            "from_objectid",
+            "from_uuid7",
+            "jwt_exp",
+            "timespan",
+        ],
+    },
+    Category {
+        name: "logs",
+        description: "Group, compare and bound the timestamps of log lines.",
+        topics: &[
+            "bucket",
+            "bucket_index",
+            "from_snowflake",
+            "overlap",
+            "seq",
+            "sum, mean, min, max, span",
+            "retention_floor",
+            "round_delta",
+        ],
+    },
+];
+
+/// The literals of the expressions.
+pub const LITERALS: &[Topic] = &[
+    Topic::new(
+        "now",
+        "Current time, rounded to seconds.",
+    )
+    .example("now"),
+    Topic::new(
+        "HH:MM",
+        "Time of day today, like 13:00, or with AM or PM, like 3pm or 3:30 PM, in the time zone set with -tz. Also \
+         with date, like 2024-05-17 13:00 or 2024-05-17 7:05am, and with the zone name, like 2024-05-17 13:00 \
+         Europe/Warsaw or 2024-05-17T13:00:00[Europe/Warsaw].",
+    )
+    .example("13:00"),
+    Topic::new(
+        "17 May 2024",
+        "Date with the month name, also like May 17th, 2024, and with the time and the zone, like May 17th, 2024 \
+         1:00 PM UTC.",
+    )
+    .example("17 May 2024 13:00"),
+    Topic::new(
+        "ntp_now",
+        "Network time from NTP server set with --ntp, pool.ntp.org by default.",
+    )
+    .example("ntp_now - now")
+    .feature("ntp"),
+    Topic::new(
+        "epoch",
+        "Unix epoch, 1970-01-01T00:00:00Z.",
+    )
+    .example("now - epoch"),
+    Topic::new(
+        "max_time, min_time",
+        "The latest and the earliest date-time that can be represented, +262142-12-31T23:59:59.999999999Z and \
+         -262143-01-01T00:00:00Z, like clamp(x, epoch, max_time).",
+    )
+    .example("clamp(now, epoch, max_time)"),
+    Topic::new(
+        "uptime",
+        "System boot time, so now - uptime is time since boot. Supported on Linux, macOS, FreeBSD and Windows.",
+    )
+    .example("now - uptime")
+    .feature("uptime"),
+    Topic::new(
+        "1h30m",
+        "Duration, with the units y, w, d, h, m, s, ms, us and ns. The long names like 1 day 2 hours, 90 seconds, \
+         1hr or 5 mins are accepted too, as pasted from tickets, also separated with spaces like 1h 30 mins.",
+    )
+    .example("now + 1 day 2 hours"),
+];
+
+/// The built-in functions and the operators.
+pub const FUNCTIONS: &[Topic] = &[
+    Topic::new(
+        "full_day",
+        "Return full day of the date-time.",
+    )
+    .example("full_day(now)"),
+    Topic::new(
+        "time, time_of_day",
+        "Return the time of day of the date-time as duration since midnight, like time(now). The date-time is \
+         full_day(x) + time_of_day(x).",
+    )
+    .example("time_of_day(now)"),
+    Topic::new(
+        "subsec",
+        "Return the fraction of second of the date-time as duration, like subsec(now).",
+    )
+    .example("subsec(now)"),
+    Topic::new(
+        "full_hour",
+        "Return full hour of the date-time.",
+    )
+    .example("full_hour(now)"),
+    Topic::new(
+        "full_minute",
+        "Return full minute of the date-time.",
+    )
+    .example("full_minute(now)"),
+    Topic::new(
+        "full_second, full_millis, full_micros",
+        "Return the date-time truncated to the second, millisecond or microsecond.",
+    )
+    .example("full_millis(now)"),
+    Topic::new(
+        "ceil_day, ceil_hour, ceil_minute, ceil_second, ceil_millis, ceil_micros",
+        "Return the date-time rounded up to the day, hour, minute, second, millisecond or microsecond, like \
+         ceil_hour(now). A date-time that is already full is not changed.",
+    )
+    .example("ceil_hour(now)"),
+    Topic::new(
+        "full_week",
+        "Return the beginning of the week (Monday) of the date-time.",
+    )
+    .example("full_week(now)"),
+    Topic::new(
+        "full_month",
+        "Return the beginning of the month of the date-time.",
+    )
+    .example("full_month(now)"),
+    Topic::new(
+        "full_quarter",
+        "Return the beginning of the quarter of the date-time.",
+    )
+    .example("full_quarter(now)"),
+    Topic::new(
+        "full_year",
+        "Return the beginning of the year of the date-time.",
+    )
+    .example("full_year(now)"),
+    Topic::new(
+        "at",
+        "Replace the time of the date-time, like at(now + 1d, \"14:30\").",
+    )
+    .example("at(now + 1d, \"14:30\")"),
+    Topic::new(
+        "on",
+        "Replace the date of the date-time, like on(now, \"2024-06-01\").",
+    )
+    .example("on(now, \"2024-06-01\")"),
+    Topic::new(
+        "with_<component>",
+        "Set year, month, day, hour, minute or second of the date-time, like with_day(now, 1) or with_hour(now, 0).",
+    )
+    .example("with_day(now, 1)"),
+    Topic::new(
+        "fmt",
+        "Format the date-time as string, in the time zone of the date-time, like fmt(now, \"%Y-%m-%d\").",
+    )
+    .example("fmt(now, \"%Y-%m-%d\")"),
+    Topic::new(
+        "add_months",
+        "Add calendar months to the date-time, clamping the day to the end of month, like add_months(now, -1).",
+    )
+    .example("add_months(now, -1)"),
+    Topic::new(
+        "from_<epoch>",
+        "Convert timestamp of an alternate epoch to date-time, like from_filetime(133575168000000000).",
+    )
+    .example("from_filetime(133575168000000000)"),
+    Topic::new(
+        "to_<epoch>",
+        "Convert date-time to whole ticks of an alternate epoch, like to_cocoa(now).",
+    )
+    .example("to_cocoa(now)"),
+    Topic::new(
+        "from_objectid",
+        "Return creation time of MongoDB ObjectId, like from_objectid(\"65f0c0ffee0123456789abcd\").",
+    )
+    .example("from_objectid(\"65f0c0ffee0123456789abcd\")"),
+    Topic::new(
+        "from_uuid1",
+        "Return the timestamp embedded in UUIDv1.",
+    )
+    .example("from_uuid1(\"c232ab00-9414-11ec-b3c8-9f6bdeced846\")"),
+    Topic::new(
+        "from_uuid7",
+        "Return the timestamp embedded in UUIDv7, like from_uuid7(\"018f...\").",
+    )
+    .example("from_uuid7(\"018f8a9e-6c00-7000-8000-000000000000\")"),
+    Topic::new(
+        "jwt_exp",
+        "Return the expiry time from the exp claim of JWT, like jwt_exp(\"eyJ...\") - now. The signature is not \
+         verified.",
+    )
+    .example("jwt_exp(\"eyJhbGciOiJub25lIn0.eyJleHAiOjE3MTYwMDAwMDB9.\") - now"),
+    Topic::new(
+        "from_snowflake",
+        "Return the timestamp of snowflake ID, with optional epoch \"twitter\" (default), \"discord\" or a \
+         date-time, like from_snowflake(175928847299117063, \"discord\").",
+    )
+    .example("from_snowflake(175928847299117063, \"discord\")"),
+    Topic::new(
+        "overlap",
+        "Return the overlap of two time windows as duration, 0s if disjoint, like overlap(start1, end1, start2, \
+         end2).",
+    )
+    .example("overlap(now, now + 2h, now + 1h, now + 3h)"),
+    Topic::new(
+        "intersects",
+        "Return true if two time windows have common time, like intersects(start1, end1, start2, end2).",
+    )
+    .example("intersects(now, now + 2h, now + 1h, now + 3h)"),
+    Topic::new(
+        "interval",
+        "Return interval [start, end), same as start..end, like interval(now, now + 1d) or now..now + 1d.",
+    )
+    .example("interval(now, now + 1d)"),
+    Topic::new(
+        "contains",
+        "Return true if the interval contains the date-time or the other interval, like contains(now..now + 1d, x).",
+    )
+    .example("contains(now..now + 1d, now + 1h)"),
+    Topic::new(
+        "duration",
+        "Return the length of the interval.",
+    )
+    .example("duration(now..now + 1d)"),
+    Topic::new(
+        "shift",
+        "Move the interval by the duration, like shift(now..now + 1h, 1d).",
+    )
+    .example("shift(now..now + 1h, 1d)"),
+    Topic::new(
+        "age",
+        "Return age in calendar years, months and days at the date-time, or now, like age(2021-02-28T00:00:00Z) or \
+         age(birth, asof).",
+    )
+    .example("age(2021-02-28T00:00:00Z)"),
+    Topic::new(
+        "bucket",
+        "Return the start of the bucket of the date-time, with buckets of the duration since the Unix epoch, like \
+         bucket(now, 5m).",
+    )
+    .example("bucket(now, 5m)"),
+    Topic::new(
+        "bucket_index",
+        "Return the number of the bucket of the date-time since the origin, the Unix epoch by default, like \
+         bucket_index(now, 1d, 2024-01-01T00:00:00Z).",
+    )
+    .example("bucket_index(now, 1d, 2024-01-01T00:00:00Z)"),
+    Topic::new(
+        "random_between",
+        "Return random date-time or duration between the two, like random_between(now - 1d, now). See --seed.",
+    )
+    .example("random_between(now - 1d, now)"),
+    Topic::new(
+        "seq",
+        "Return list of date-times from the start, before the end, every step, like seq(now, now + 1d, 1h).",
+    )
+    .example("seq(now, now + 1d, 1h)"),
+    Topic::new(
+        "map",
+        "Evaluate the expression for each element of the list, bound to the variable, like map([now, now - 1d], x -> \
+         full_day(x)). Lists are written like [now, now - 1d] and printed one element per line.",
+    )
+    .example("map([now, now - 1d], x -> full_day(x))"),
+    Topic::new(
+        "sum, mean, min, max, span",
+        "Aggregate list of durations or date-times, like span(seq(...)) for max - min. Sum is only for durations.",
+    )
+    .example("span(seq(now, now + 1d, 1h))"),
+    Topic::new(
+        "|>",
+        "Pass the value as the first argument of the function, like now |> full_day |> add_months(1), which is the \
+         same as add_months(full_day(now), 1).",
+    )
+    .example("now |> full_day |> add_months(1)"),
+    Topic::new(
+        "retention_floor",
+        "Return the date-time, or now minus the retention if the date-time is older, like retention_floor(start, \
+         30d).",
+    )
+    .example("retention_floor(now - 90d, 30d)"),
+    Topic::new(
+        "round_delta",
+        "Round the duration to the nearest multiple of the unit, the halves away from zero, like round_delta(1m30s, \
+         1m), which is 2m.",
+    )
+    .example("round_delta(1m30s, 1m)"),
+    Topic::new(
+        "trunc_delta",
+        "Truncate the duration toward zero to a multiple of the unit, like trunc_delta(1m59s, 1m), which is 1m.",
+    )
+    .example("trunc_delta(1m59s, 1m)"),
+    Topic::new(
+        "clamp",
+        "Limit the date-time or duration to the bounds, like clamp(start, now - 30d, now) or clamp(x, 1m, 1h).",
+    )
+    .example("clamp(now - 90d, now - 30d, now)"),
+    Topic::new(
+        "sunrise, sunset",
+        "Return the sunrise or sunset on the date at the latitude and longitude, like sunset(now, 52.23, 21.01) - \
+         now.",
+    )
+    .example("sunset(now, 52.23, 21.01) - now")
+    .feature("astro"),
+    Topic::new(
+        "<, <=, >, >=, ==, !=",
+        "Compare date-times, durations, integers or strings, like now > deadline.",
+    )
+    .example("now > 2024-05-17T00:00:00Z"),
+    Topic::new(
+        "if",
+        "Return the second or the third argument depending on the condition, like if(now > deadline, 0s, deadline - \
+         now). Only the chosen one is evaluated.",
+    )
+    .example("if(now > 2024-05-17T00:00:00Z, 0s, 2024-05-17T00:00:00Z - now)"),
+    Topic::new(
+        "::",
+        "Assert the type of the result, like now - deadline :: duration, and fail if it differs. The types are \
+         datetime, duration, integer, bool, interval, string, list and record.",
+    )
+    .example("now - 2024-05-17T00:00:00Z :: duration"),
+    Topic::new(
+        "totp_window",
+        "Return TOTP counter of the date-time for the time step, and the start and end of the window, like \
+         totp_window(now, 30s).",
+    )
+    .example("totp_window(now, 30s)"),
+    Topic::new(
+        "mtime",
+        "Return modification time of the file, like now - mtime(\"build.log\").",
+    )
+    .example("now - mtime(\"build.log\")")
+    .feature("fs"),
+    Topic::new(
+        "ctime",
+        "Return status change time of the file (creation time on non-unix platforms).",
+    )
+    .example("now - ctime(\"build.log\")")
+    .feature("fs"),
+    Topic::new(
+        "next_cron",
+        "Return the next time a cron schedule fires after the date-time, like next_cron(\"*/15 * * * *\", now).",
+    )
+    .example("next_cron(\"*/15 * * * *\", now)"),
+    Topic::new(
+        "prev_cron",
+        "Return the last time a cron schedule fired before the date-time.",
+    )
+    .example("prev_cron(\"*/15 * * * *\", now)"),
+    Topic::new(
+        "next_rrule",
+        "Return the next occurrence of RFC 5545 recurrence rule after the date-time, like \
+         next_rrule(\"FREQ=WEEKLY;BYDAY=MO,WE\", now).",
+    )
+    .example("next_rrule(\"FREQ=WEEKLY;BYDAY=MO,WE\", now)"),
+    Topic::new(
+        "next_systemd",
+        "Return the next time a systemd calendar event elapses after now or after the date-time, like \
+         next_systemd(\"Mon..Fri 10:00\") or next_systemd(\"*-*-01 00:00:00 Europe/Warsaw\", now + 1d).",
+    )
+    .example("next_systemd(\"Mon..Fri 10:00\")"),
+    Topic::new(
+        "timespan",
+        "Return the duration of a systemd time span, like timespan(\"2weeks 1day\"). Months and years are 30.44 and \
+         365.25 days.",
+    )
+    .example("timespan(\"2weeks 1day\")"),
+];
+
+/// The commands, given as the first argument.
+pub const COMMANDS: &[Topic] = &[
+    Topic::new(
+        "timer start NAME",
+        "Start the named timer. The timers are kept in $TSCALC_TIMERS or $XDG_STATE_HOME/tscalc/timers.",
+    ),
+    Topic::new(
+        "timer stop NAME",
+        "Stop the named timer and print the elapsed time.",
+    ),
+    Topic::new(
+        "plan EXPR",
+        "Show the date-time in each of the time zones passed with -tz, like plan 2024-06-01T16:00:00Z -tz US/Pacific \
+         -tz Asia/Tokyo.",
+    ),
+    Topic::new(
+        "cron SCHEDULE",
+        "Show the next fire times of the cron schedule in the time zone set with -tz, like cron '*/5 * * * *' -tz \
+         Europe/Warsaw --count 10. The --count is 5 by default.",
+    ),
+    Topic::new(
+        "assert EXPR",
+        "Check that the expression is true, like assert 'full_day(x) == 2024-05-17T00:00:00Z'. On failure, print \
+         both sides of the comparison and exit with non-zero status.",
+    ),
+    Topic::new(
+        "check FILE",
+        "Evaluate each \"expr => expected\" line of the file and report the lines where the output differs from the \
+         expected one. Empty lines and lines starting with # are skipped.",
+    ),
+    Topic::new(
+        "repl",
+        "Evaluate the expressions line by line, with tab completion of function names, keywords, duration units, \
+         time zone names and variables. The history is kept in $TSCALC_HISTORY or ~/.local/share/tscalc/history.",
+    )
+    .feature("repl"),
+    Topic::new(
+        "x509 FILE",
+        "Print notBefore and notAfter of the first certificate in the PEM file, and the time until it expires.",
+    )
+    .feature("x509"),
+    Topic::new(
+        "http-time URL",
+        "Send HEAD request to the URL, print the Date header and the skew of the local clock.",
+    )
+    .feature("http"),
+    Topic::new(
+        "examples [CATEGORY]",
+        "List the numbered examples of the expressions, for offsets, conversions and logs, or only for the category. \
+         Evaluate one of them with --run N, like tscalc examples --run 3 -tz Europe/Warsaw.",
+    ),
+    Topic::new(
+        "man",
+        "Print the manual page in the roff format, like tscalc man > tscalc.1.",
+    ),
+];
+
+/// The flags.
+pub const FLAGS: &[Topic] = &[
+    Topic::new(
+        "-i",
+        "Read input from stdin and process line by line.",
+    ),
+    Topic::new(
+        "--merge-gaps",
+        "Read sorted date-times from stdin and coalesce them into intervals separated by gaps larger than the \
+         duration, like --merge-gaps 5m. Prints start, end and duration of each interval.",
+    ),
+    Topic::new(
+        "--diff",
+        "Read date-times from stdin and print the difference between each one and the previous one, 0s for the \
+         first.",
+    ),
+    Topic::new(
+        "--since",
+        "Read date-times from stdin and print the offset of each one from the date-time the next argument evaluates \
+         to, like --since 2024-05-01T00:00:00Z. The offsets before the reference are negative.",
+    ),
+    Topic::new(
+        "--retention",
+        "Read requested start date-times from stdin and move the ones older than the retention to now minus the \
+         retention, like --retention 30d. The same as retention_floor(x, 30d) for each line.",
+    ),
+    Topic::new(
+        "--assert-monotonic",
+        "Read date-times from stdin and check that they do not decrease. Report the first violation and exit with \
+         non-zero status.",
+    ),
+    Topic::new(
+        "-s",
+        "Output time as epoch seconds, and duration as seconds, like -0.500.",
+    ),
+    Topic::new(
+        "-S",
+        "Output time as epoch seconds, without the decimal part. Date-times and durations are truncated toward zero.",
+    ),
+    Topic::new(
+        "--trunc",
+        "With -s and -S, drop the digits beyond the precision, the default. Date-times are rounded down with -s, so \
+         the output is within the second of the date-time, and toward zero with -S, like durations.",
+    ),
+    Topic::new(
+        "--round",
+        "With -s and -S, round to the nearest, and the halves to even (banker's rounding), like 2 for 2.5s.",
+    ),
+    Topic::new(
+        "-tz, --tz",
+        "Timezone like US/Eastern or Europe/Warsaw , as in https://docs.rs/chrono-tz/latest/chrono_tz/enum.Tz.html, \
+         abbreviation like PST or CEST, or offset like +02:00. Ambiguous abbreviations like IST fail with the list of zones to choose \
+         from.",
+    ),
+    Topic::new(
+        "-f",
+        "Format output datetime, with specifiers from \
+         https://docs.rs/chrono/latest/chrono/format/strftime/index.html",
+    ),
+    Topic::new(
+        "--time-only",
+        "Output only the time of day of date-times, like 08:30:00.",
+    ),
+    Topic::new(
+        "--relative",
+        "Output date-time relative to now, like \"in 2 days\" or \"3 hours ago\".",
+    ),
+    Topic::new(
+        "--relative-units",
+        "How many units to show in the relative output, 1 by default. With 2 the output is like \"in 2 days 3 \
+         hours\".",
+    ),
+    Topic::new(
+        "--count",
+        "Number of the fire times printed by the cron command, 5 by default.",
+    ),
+    Topic::new(
+        "--watch",
+        "Re-evaluate the expression every interval, 1s by default, like --watch 5s. Rewrites the output in place.",
+    ),
+    Topic::new(
+        "--wait",
+        "Sleep until the date-time the next argument evaluates to, like --wait 'full_hour(now) + 1h', and exit.",
+    ),
+    Topic::new(
+        "--wait-for",
+        "Sleep for the duration the next argument evaluates to, like --wait-for 5m, and exit.",
+    ),
+    Topic::new(
+        "--errors json",
+        "Print parse and evaluation errors of the expression as JSON, with the position in characters, the expected \
+         tokens and the message. The default is text.",
+    ),
+    Topic::new(
+        "--delta-clock",
+        "Output durations as H:MM:SS, like 1:30:00 instead of 1h30m, and -0:00:00.500 for -500ms.",
+    ),
+    Topic::new(
+        "--delta-go",
+        "Output durations as Go time.Duration, like 1h30m0s or 1.5µs.",
+    ),
+    Topic::new(
+        "--delta-unit",
+        "Output durations as decimal number of the unit: y, w, d, h, m, s, ms, us or ns, like 1.500 for 90m with \
+         --delta-unit h. Handy for spreadsheets. Rounded as set with --round or --trunc.",
+    ),
+    Topic::new(
+        "--decimals",
+        "Number of fractional digits with --delta-unit, 0 to 9, 3 by default.",
+    ),
+    Topic::new(
+        "--metric-suffix",
+        "Output durations as integer number of the largest unit that fits, like 90m or 1500ms, as in Prometheus. The \
+         parts below millisecond are rounded as set with --round or --trunc.",
+    ),
+    Topic::new(
+        "--utc-z",
+        "Use Z instead of +00:00 for UTC date-times.",
+    ),
+    Topic::new(
+        "--ixdtf",
+        "Output date-times with the time zone name as in RFC 9557, like 2024-05-17T13:00:00+02:00[Europe/Warsaw]. \
+         The date-times are printed in their own time zone, the one of the literal, or the one set with -tz for now.",
+    ),
+    Topic::new(
+        "--space-sep",
+        "Separate date and time with space instead of T.",
+    ),
+    Topic::new(
+        "--frac",
+        "Print fixed number of fractional second digits, 0 to 9. By default as many as needed, and 3 with -s. With \
+         -s and 9 digits the epoch seconds keep the nanoseconds, like 1715950800.123456789.",
+    ),
+    Topic::new(
+        "--epoch-unit",
+        "Unit of bare numbers used as date-time: s (default, none with --strict-parse), ms, us, ns or auto. The auto \
+         unit is guessed from the magnitude: below 10^11 seconds, below 10^14 milliseconds, below 10^17 \
+         microseconds, otherwise nanoseconds.",
+    ),
+    Topic::new(
+        "--dialect",
+        "Input language: tscalc (default), splunk for relative time modifiers like -24h@h or @d+8h, or elastic for \
+         date math like now-1d/d or 2024-01-01T00:00:00Z||+1M/d.",
+    ),
+    Topic::new(
+        "--seed",
+        "Seed of random_between, so the output is reproducible, like --seed 42.",
+    ),
+    Topic::new(
+        "--locale",
+        "Locale of the month names in the input, like 17 maja 2024 with --locale pl, and of the month and day names \
+         in the output with -f, like -f '%d %B %Y'. English month names are always accepted.",
+    )
+    .feature("locale"),
+    Topic::new(
+        "--date-order",
+        "Order of numeric dates like 5/17/24 or 17.05.24: mdy, dmy or ymd. Without it, a date valid in more than one \
+         order, like 5/6/24, is an error. Two-digit years are 1969 to 2068.",
+    ),
+    Topic::new(
+        "--dry-run",
+        "Parse the expression and check the types, like date-time minus duration, without evaluating it. Print the \
+         type of the result, like date-time or duration. Without expression, check each line of the input.",
+    ),
+    Topic::new(
+        "--check",
+        "Check the types like --dry-run, but print only the errors, with the terms marked, and exit with 1 if any. \
+         Without expression, check each line of the input.",
+    ),
+    Topic::new(
+        "--strict-parse",
+        "Reject the inputs that need a guess: bare numbers as date-times without --epoch-unit, numeric dates without \
+         --date-order or with two-digit year, time zone abbreviations like PST, and natural phrases.",
+    ),
+    Topic::new(
+        "--ntp",
+        "NTP server used by ntp_now. Without expression, print the network time and the offset of the local clock.",
+    )
+    .feature("ntp"),
+    Topic::new(
+        "--trace-eval",
+        "Print each step of the evaluation to stderr, the operator or the function with the operands and the result, \
+         like 2024-05-17T13:00:00+00:00 - 1h = 2024-05-17T12:00:00+00:00. The steps of the operands come first, \
+         indented.",
+    ),
+    Topic::new(
+        "--explain",
+        "Print how the expression is evaluated before the result: the tokens of the input, the grouping of the \
+         terms, like (a - b) + c, and the steps with the intermediate values, as with --trace-eval. Without \
+         expression, explain each line of the input.",
+    ),
+    Topic::new(
+        "--echo-input",
+        "Print to stderr how the dates, the times, the durations and the numbers of the input were interpreted, \
+         before the result, like 1716000000 → 2024-05-18T02:40:00+00:00 (epoch seconds).",
+    ),
+    Topic::new(
+        "-w0, -w1",
+        "Do not print the warnings about the guesses to stderr: the unit of epoch timestamps, two-digit years, time \
+         zone abbreviations, local times repeated or skipped by the DST change, and durations added across the DST \
+         change. -w1 prints them, the default.",
+    ),
+    Topic::new(
+        "-v, -vv, --verbose",
+        "Log the evaluation to stderr, and with -vv also the trace of the parsers, to see why the expression does \
+         not parse. The level can also be set with $TSCALC_LOG: 0 or off, 1 or eval, 2 or parse.",
+    ),
+    Topic::new(
+        "--run",
+        "With the examples command, evaluate the example with the number instead of listing them, like --run 3.",
+    ),
+    Topic::new(
+        "--capabilities",
+        "Print the functions with the numbers of arguments, the keywords, the units, the dialects, the output \
+         formats and the enabled features as JSON, for the tools that wrap tscalc.",
+    ),
+    Topic::new(
+        "--help-functions",
+        "Print the built-in functions with the description, the numbers of arguments and an example of each.",
+    ),
+    Topic::new(
+        "-h, --help",
+        "Print this help.",
+    ),
+    Topic::new(
+        "--",
+        "After this sentinel, concatenate all the arguments into a single expression.",
+    ),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        example, example_of, examples_help, functions_help, help, man_page, Topic, CATEGORIES,
+        COMMANDS, FLAGS, FUNCTIONS, LITERALS,
+    };
+    use crate::{dry_run, enabled_features};
+    use tscalc::parser::{registry::Registry, relative::Dialect};
+
+    /// True if one of the topics describes the name, also with the pattern like "with_<component>" for "with_day".
+    /// The arguments after the name, like in "--errors json", are skipped.
+    fn described(topics: &[Topic], name: &str) -> bool {
+        topics
+            .iter()
+            .flat_map(|topic| topic.names())
+            .map(|topic_name| topic_name.split(' ').next().unwrap())
+            .any(|topic_name| match topic_name.split_once('<') {
+                Some((prefix, _)) => name.starts_with(prefix),
+                None => topic_name == name,
+            })
+    }
+
+    /// The string literals in the source after the marker, like the flags in `arg == "--frac"`.
+    fn quoted_after<'a>(source: &'a str, marker: &str) -> Vec<&'a str> {
+        source
+            .split(marker)
+            .skip(1)
+            .map(|rest| &rest[..rest.find('"').unwrap()])
+            .collect()
+    }
+
+    #[test]
+    fn all_described() {
+        for name in Registry::builtin().names() {
+            assert!(described(FUNCTIONS, &name), "function {}", name);
+        }
+        let source = include_str!("main.rs");
+        for flag in quoted_after(source, "arg == \"") {
+            assert!(described(FLAGS, flag), "flag {}", flag);
+        }
+        for command in quoted_after(source, "as_str()) == Some(\"") {
+            assert!(described(COMMANDS, command), "command {}", command);
+        }
+    }
+
+    #[test]
+    fn examples() {
+        let features = enabled_features();
+        for topic in LITERALS.iter().chain(FUNCTIONS) {
+            let (Some(example), true) = (
+                topic.example,
+                topic
+                    .feature
+                    .is_none_or(|feature| features.contains(&feature)),
+            ) else {
+                continue;
+            };
+            let result = dry_run(&example.to_string(), Dialect::Tscalc, false);
+            assert!(result.is_ok(), "{}: {:?}", example, result);
+        }
+    }
+
+    #[test]
+    fn categories() {
+        for category in CATEGORIES {
+            for name in category.topics {
+                let topic = LITERALS.iter().chain(FUNCTIONS).find(|t| t.name == *name);
+                // The examples that need a feature would be numbered differently in each build.
+                assert!(
+                    topic.is_some_and(|topic| topic.feature.is_none())
+                        && example_of(name).is_some(),
+                    "{}: {}",
+                    category.name,
+                    name
+                );
+            }
+        }
+        let all = examples_help(None).unwrap();
+        assert!(all.starts_with("offsets: Move the date-times"));
+        assert!(all.contains("\n   2. full_day(now)\n"));
+        assert!(
+            all.ends_with("\n\nEvaluate one of them with --run N, like tscalc examples --run 1.")
+        );
+        let logs = examples_help(Some("logs")).unwrap();
+        assert!(logs.starts_with(
+            "logs: Group, compare and bound the timestamps of log lines.\n  18. bucket(now, 5m)\n"
+        ));
+        assert!(!logs.contains("full_day"));
+        assert_eq!(example(None, 2), Ok("full_day(now)"));
+        assert_eq!(example(Some("logs"), 18), Ok("bucket(now, 5m)"));
+        assert_eq!(
+            example(Some("logs"), 2),
+            Err("no example 2, expected 18 to 25".to_string())
+        );
+        assert_eq!(
+            example(Some("log"), 18),
+            Err(
+                "unknown example category \"log\", expected one of offsets, conversions, logs"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn rendered() {
+        let help = help();
+        assert!(help
+            .starts_with("Simple calculator for date-time and durations.\n\nLiterals:\n- now\t"));
+        assert!(help.contains(
+            "\n- full_day\tReturn full day of the date-time.\n- time, time_of_day\tReturn the time of day"
+        ));
+        assert!(help.contains("\n  - filetime\t"));
+        assert!(help.contains("\n--frac\tPrint fixed number of fractional second digits"));
+        let functions = functions_help(&Registry::builtin());
+        assert!(functions.contains(
+            "bucket_index\n    Return the number of the bucket of the date-time since the origin, the Unix epoch by \
+             default, like\n    bucket_index(now, 1d, 2024-01-01T00:00:00Z).\n    Arguments: 2 or 3\n    Example: \
+             bucket_index(now, 1d, 2024-01-01T00:00:00Z)\n"
+        ));
+        let man = man_page();
+        assert!(man.starts_with(".TH TSCALC 1 "));
+        assert!(man.contains("\n.TP\n.B \\-\\-frac\n"));
+        assert!(man.contains("\n.br\nExample: \\fBfull_day(now)\\fR\n"));
+    }
+}
@@ -0,0 +1,54 @@
+use chrono::{DateTime, TimeDelta, Utc};
+
+/// Server time from the Date header, with the skew of the local clock, positive when the local clock is behind.
+#[derive(Debug, PartialEq)]
+pub struct HttpTime {
+    pub date: DateTime<Utc>,
+    pub skew: TimeDelta,
+}
+
+/// Send HEAD request to the URL and compare the Date header of the response with the local clock. The Date header
+/// has only seconds precision, so the skew is accurate to about a second.
+#[cfg(feature = "http")]
+pub fn query(url: &str) -> Result<HttpTime, String> {
+    let response = match ureq::head(url).call() {
+        Ok(response) => response,
+        // Error statuses still carry the Date header.
+        Err(ureq::Error::Status(_, response)) => response,
+        Err(e) => return Err(format!("HEAD {} failed: {}", url, e)),
+    };
+    let local = Utc::now();
+    let header = response
+        .header("Date")
+        .ok_or_else(|| format!("no Date header in response from {}", url))?;
+    let date = parse_http_date(header)?;
+    Ok(HttpTime {
+        date,
+        skew: date - local,
+    })
+}
+
+#[cfg(not(feature = "http"))]
+pub fn query(_url: &str) -> Result<HttpTime, String> {
+    Err("http-time is not supported, build with the http feature".to_string())
+}
+
+/// Parse HTTP date like "Sun, 06 Nov 1994 08:49:37 GMT", which is a subset of RFC 2822.
+#[cfg_attr(not(feature = "http"), allow(dead_code))]
+pub fn parse_http_date(s: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc2822(s.trim())
+        .map(|date| date.with_timezone(&Utc))
+        .map_err(|e| format!("bad Date header {:?}: {}", s, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_http_date;
+
+    #[test]
+    fn http_date() {
+        let actual = parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+        assert_eq!(actual.to_rfc3339(), "1994-11-06T08:49:37+00:00");
+        assert!(parse_http_date("1994-11-06T08:49:37Z").is_err());
+    }
+}
@@ -0,0 +1,13 @@
+//! Calculator for date-time and durations. The library exposes the parser and the evaluation used by the tscalc
+//! binary, and the analysis of expressions for editors. The syntax module, with the literals only, needs no std: without
+//! the std feature the crate is no_std with only that module.
+#![cfg_attr(not(feature = "std"), no_std)]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod analysis;
+#[cfg(feature = "std")]
+pub mod log;
+#[cfg(feature = "std")]
+pub mod parser;
+pub mod syntax;
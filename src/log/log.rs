@@ -1,12 +1,60 @@
-pub fn debug_nested_log(nesting: usize, _s: String) {
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Nothing is logged.
+pub const LOG_OFF: u8 = 0;
+/// The evaluation of the nodes is logged.
+pub const LOG_EVAL: u8 = 1;
+/// The evaluation and the nested trace of the parsers is logged.
+pub const LOG_PARSE: u8 = 2;
+
+/// The tests log everything, shown with --nocapture.
+static LEVEL: AtomicU8 = AtomicU8::new(if cfg!(test) { LOG_PARSE } else { LOG_OFF });
+
+/// Set the level of the logs written to stderr, LOG_OFF, LOG_EVAL or LOG_PARSE. Higher levels are the same as LOG_PARSE.
+pub fn set_log_level(level: u8) {
+    LEVEL.store(level.min(LOG_PARSE), Ordering::Relaxed);
+}
+
+/// Parse the level of $TSCALC_LOG: 0 or off, 1 or eval, 2 or parse.
+pub fn parse_log_level(s: &str) -> Result<u8, String> {
+    match s.trim() {
+        "0" | "off" => Ok(LOG_OFF),
+        "1" | "eval" => Ok(LOG_EVAL),
+        "2" | "parse" => Ok(LOG_PARSE),
+        _ => Err(format!(
+            "bad log level {:?}, expected 0 (off), 1 (eval) or 2 (parse)",
+            s
+        )),
+    }
+}
+
+fn enabled(level: u8) -> bool {
+    LEVEL.load(Ordering::Relaxed) >= level
+}
+
+pub fn debug_nested_log(nesting: usize, s: String) {
+    if !enabled(LOG_PARSE) {
+        return;
+    }
     let space = String::from("| ").repeat(nesting / 2) + (if nesting % 2 == 1 { "|" } else { "" });
-    debug_log(format!("{:02}{}{}", nesting, space, _s));
+    eprintln!("{:02}{}{}", nesting, space, s);
 }
 
-#[cfg(test)]
 pub fn debug_log(s: String) {
-    println!("{}", s);
+    if enabled(LOG_EVAL) {
+        eprintln!("{}", s);
+    }
 }
 
-#[cfg(not(test))]
-pub fn debug_log(_s: String) {}
+#[cfg(test)]
+mod tests {
+    use super::{parse_log_level, LOG_EVAL, LOG_OFF, LOG_PARSE};
+
+    #[test]
+    fn log_level() {
+        assert_eq!(parse_log_level("off"), Ok(LOG_OFF));
+        assert_eq!(parse_log_level("1"), Ok(LOG_EVAL));
+        assert_eq!(parse_log_level("parse"), Ok(LOG_PARSE));
+        assert!(parse_log_level("3").is_err());
+    }
+}
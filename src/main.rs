@@ -1,34 +1,125 @@
 use std::{
     env,
     error::Error,
-    io::{self, BufRead},
+    fs,
+    io::{self, BufRead, Write as _},
     process,
     str::FromStr,
+    thread,
 };
 
-mod log;
-
-mod parser;
-use chrono::SubsecRound;
-use chrono_tz;
-use chrono_tz::{Tz, UTC};
-use parser::{evaluate, parse_expr, ShortFormat};
+mod batch;
+mod help;
+mod http_time;
+use batch::{AssertMonotonic, Batch, Diff, MergeGaps, RetentionFloor, Since};
+mod repl;
+mod timer;
+mod x509;
+use chrono::{Offset, SubsecRound, TimeDelta};
+use parser::{
+    check_strict_zones,
+    cron::CronSchedule,
+    epoch::EPOCHS,
+    evaluate,
+    locale::{format_localized, parse_locale, Locale},
+    nanos_as_seconds,
+    number::EpochUnit,
+    numeric_date::DateOrder,
+    parse_expr, parse_expr_spanned,
+    random::Random,
+    registry::{Registry, MAX_ARITY},
+    relative::{elastic_to_node, splunk_to_node, Dialect},
+    types::{check_types, TypeErr, TYPE_NAMES},
+    zone::{parse_tz, Tz, UTC},
+    zone_warnings, ClockFormat, EvalContext, GoFormat, MetricFormat, Node, Rounding, SecondsFormat,
+    ShortFormat, Trace, DURATION_UNITS, KEYWORDS,
+};
 use std::fmt::Write;
+use std::rc::Rc;
+use tscalc::{
+    analysis::{grouping, literal_terms, tokenize, Token},
+    log, parser,
+};
 
 // See https://docs.rs/chrono/latest/chrono/format/strftime/index.html
 const DEFAULT_FORMAT: &str = "%+";
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let args = parse_cli_args()?;
+    let mut args = parse_cli_args()?;
+    if let Some(level) = args.log_level {
+        log::set_log_level(level);
+    } else if let Ok(level) = env::var("TSCALC_LOG") {
+        log::set_log_level(log::parse_log_level(&level)?);
+    }
+    if let Some(url) = &args.http_time_url {
+        let report =
+            http_time_report(url).map(|result| format_result(&result, &args.output_options));
+        match report {
+            Ok(output) => println!("{}", output),
+            Err(message) => {
+                println!("{}", message);
+                process::exit(1);
+            }
+        }
+        process::exit(0);
+    }
+    if let Some(path) = &args.x509_path {
+        let report = x509_report(path).map(|result| format_result(&result, &args.output_options));
+        match report {
+            Ok(output) => println!("{}", output),
+            Err(message) => {
+                println!("{}", message);
+                process::exit(1);
+            }
+        }
+        process::exit(0);
+    }
+    if let Some((command, name)) = &args.timer {
+        match run_timer(command, name) {
+            Ok(output) if output.is_empty() => (),
+            Ok(output) => println!("{}", output),
+            Err(message) => {
+                println!("{}", message);
+                process::exit(1);
+            }
+        }
+        process::exit(0);
+    }
     if args.print_help {
         print_help();
         process::exit(0);
     }
+    if args.print_functions {
+        println!("{}", help::functions_help(&Registry::builtin()));
+        process::exit(0);
+    }
+    if args.print_man {
+        print!("{}", help::man_page());
+        process::exit(0);
+    }
+    if args.examples {
+        let category = args.example_category.as_deref();
+        let examples = match args.run_example {
+            // The example is evaluated like the expression, with the other flags.
+            Some(number) => help::example(category, number).map(|example| {
+                args.expression = Some(example.to_string());
+            }),
+            None => help::examples_help(category).map(|output| {
+                println!("{}", output);
+                process::exit(0);
+            }),
+        };
+        if let Err(message) = examples {
+            println!("{}", message);
+            process::exit(1);
+        }
+    }
+    if args.capabilities {
+        println!("{}", capabilities());
+        process::exit(0);
+    }
     let stdin = io::stdin();
-    // Intentionally truncate to seconds to make the calculator more practical (although less precise).
-    let now = chrono::Utc::now()
-        .trunc_subsecs(0)
-        .with_timezone(&args.timezone.unwrap_or(UTC));
+    let now = current_time(args.timezone);
 
     let print_result_or_exit = |eval_result: Result<String, String>| {
         match eval_result {
@@ -40,38 +131,131 @@ fn main() -> Result<(), Box<dyn Error>> {
         };
     };
 
-    let output_tz = args.timezone.unwrap_or(UTC);
+    args.output_options.now = now;
+    let ctx = args.eval_context(now);
 
-    if let Some(input) = args.expression {
-        let eval_result = parse_and_eval(
-            &input,
-            args.output_format,
-            &args.datetime_format,
-            &output_tz,
-            now,
-        );
+    if let Some(input) = &args.since {
+        match parse_and_eval_result(input, &ctx, args.dialect) {
+            Ok(parser::EvaluationResult::DateTime(reference)) => {
+                args.batch = Some(Box::new(Since::new(reference)))
+            }
+            Ok(other) => {
+                print_result_or_exit(Err(format!("--since expects date-time, got {}", other)))
+            }
+            Err(message) => print_result_or_exit(Err(message)),
+        }
+    }
+
+    if let Some(input) = &args.retention {
+        match parse_and_eval_result(input, &ctx, args.dialect) {
+            Ok(parser::EvaluationResult::TimeDelta(retention)) => {
+                args.batch = Some(Box::new(RetentionFloor::new(now - retention)))
+            }
+            Ok(other) => {
+                print_result_or_exit(Err(format!("--retention expects duration, got {}", other)))
+            }
+            Err(message) => print_result_or_exit(Err(message)),
+        }
+    }
+
+    if let Some(input) = &args.plan {
+        print_result_or_exit(plan(input, &args.zones, &ctx, args.dialect));
+    } else if let Some(schedule) = &args.cron {
+        print_result_or_exit(cron_preview(schedule, args.count, &args.output_options));
+    } else if let Some(input) = &args.assertion {
+        match assert_expr(input, &args.output_options, &ctx, args.dialect) {
+            Ok(_) => process::exit(0),
+            Err(message) => print_result_or_exit(Err(message)),
+        }
+    } else if let Some(path) = &args.check_path {
+        let report = fs::read_to_string(path)
+            .map_err(|err| format!("cannot read {}: {}", path, err))
+            .and_then(|script| check_script(&script, &args.output_options, &ctx, args.dialect));
+        print_result_or_exit(report);
+    } else if args.repl {
+        // Each line is evaluated with its own "now", like in watch.
+        let result = repl::run(|line| {
+            let now = current_time(args.timezone);
+            let output_options = OutputOptions {
+                now,
+                ..args.output_options.clone()
+            };
+            parse_and_eval(line, &output_options, &args.eval_context(now), args.dialect)
+        });
+        if let Err(message) = result {
+            print_result_or_exit(Err(message));
+        }
+    } else if let Some(wait) = &args.wait {
+        match wait_until(wait, &ctx, args.dialect) {
+            Ok(()) => process::exit(0),
+            Err(message) => {
+                println!("{}", message);
+                process::exit(1);
+            }
+        }
+    } else if args.check {
+        let inputs: Vec<String> = match &args.expression {
+            Some(input) => vec![input.clone()],
+            None => stdin.lock().lines().map(|line| line.unwrap()).collect(),
+        };
+        // All the inputs are checked, so one run reports all the errors.
+        let mut failed = false;
+        for input in inputs {
+            if let Err(message) = dry_run(&input, args.dialect, args.strict) {
+                println!("{}", message);
+                failed = true;
+            }
+        }
+        process::exit(if failed { 1 } else { 0 });
+    } else if args.dry_run {
+        if let Some(input) = &args.expression {
+            print_result_or_exit(dry_run(input, args.dialect, args.strict));
+        } else {
+            // Each line is checked on its own, like expressions stored in a config file.
+            for line in stdin.lock().lines() {
+                print_result_or_exit(dry_run(&line.unwrap(), args.dialect, args.strict));
+            }
+        }
+    } else if args.explain {
+        if let Some(input) = &args.expression {
+            print_result_or_exit(explain(input, &args.output_options, &ctx, args.dialect));
+        } else {
+            for line in stdin.lock().lines() {
+                let explained = explain(&line.unwrap(), &args.output_options, &ctx, args.dialect);
+                print_result_or_exit(explained);
+            }
+        }
+    } else if let Some(interval) = args.watch {
+        let input = args.expression.clone().unwrap_or("now".to_string());
+        watch(&input, &mut args, interval);
+    } else if let Some(input) = args.expression {
+        let eval_result = parse_and_eval(&input, &args.output_options, &ctx, args.dialect);
         print_result_or_exit(eval_result);
+    } else if let (true, Some(batch)) = (args.read_from_stdin, args.batch.as_mut()) {
+        for line in stdin.lock().lines() {
+            let line = line.unwrap();
+            let row = parse_and_eval_result(&line, &ctx, args.dialect)
+                .and_then(|result| batch.push(result));
+            print_warnings(&line, args.dialect, &ctx);
+            if let Some(row) = row.transpose() {
+                print_result_or_exit(row.map(|row| format_row(&row, &args.output_options)));
+            }
+        }
+        if let Some(row) = batch.finish() {
+            println!("{}", format_row(&row, &args.output_options));
+        }
     } else if args.read_from_stdin {
         for line in stdin.lock().lines() {
             let line = line.unwrap();
-            let eval_result = parse_and_eval(
-                &line,
-                args.output_format,
-                &args.datetime_format,
-                &output_tz,
-                now.into(),
-            );
+            let eval_result = parse_and_eval(&line, &args.output_options, &ctx, args.dialect);
             print_result_or_exit(eval_result);
         }
+    } else if let Some(server) = &args.ntp_server {
+        let report = ntp_report(server).map(|result| format_result(&result, &args.output_options));
+        print_result_or_exit(report);
     } else {
         let input = "now".to_string();
-        let eval_result = parse_and_eval(
-            &input,
-            args.output_format,
-            &args.datetime_format,
-            &output_tz,
-            now.into(),
-        );
+        let eval_result = parse_and_eval(&input, &args.output_options, &ctx, args.dialect);
         print_result_or_exit(eval_result);
     };
     Ok(())
@@ -79,28 +263,354 @@ fn main() -> Result<(), Box<dyn Error>> {
 
 // #[derive(Debug)]
 struct Args {
-    output_format: OutputFormat,
-    datetime_format: String,
+    output_options: OutputOptions,
     print_help: bool,
+    /// Print the built-in functions with the examples.
+    print_functions: bool,
+    /// Print the man page.
+    print_man: bool,
+    /// Set by the examples command, list or run the examples.
+    examples: bool,
+    /// Category of the examples, all if not set.
+    example_category: Option<String>,
+    /// Number of the example to evaluate, set by --run.
+    run_example: Option<usize>,
+    /// Print the JSON description of the functions, units, dialects and output formats.
+    capabilities: bool,
     expression: Option<String>,
     read_from_stdin: bool,
     //timezone: chrono::FixedOffset,
     timezone: Option<Tz>,
+    /// Set by --epoch-unit, seconds if not set, unless strict.
+    epoch_unit: Option<EpochUnit>,
+    ntp_server: Option<String>,
+    dialect: Dialect,
+    /// Order of the numeric dates like "5/17/24", inferred if not set.
+    date_order: Option<DateOrder>,
+    /// Reject the inputs that need a guess.
+    strict: bool,
+    /// Print the type of the result instead of evaluating.
+    dry_run: bool,
+    /// Check the types instead of evaluating, and print only the errors.
+    check: bool,
+    /// Seed of random_between, for reproducible output.
+    seed: Option<u64>,
+    /// Set by the http-time command.
+    http_time_url: Option<String>,
+    /// Set by the x509 command.
+    x509_path: Option<String>,
+    /// Set by the timer command, e.g. ("start", "build").
+    timer: Option<(String, String)>,
+    /// Set by the assert command, the expression that must evaluate to true.
+    assertion: Option<String>,
+    /// Set by the check command, the script with "expr => expected" lines.
+    check_path: Option<String>,
+    /// Set by the repl command.
+    repl: bool,
+    /// Set by the plan command, the expression to show in all the time zones.
+    plan: Option<String>,
+    /// Set by the cron command, the schedule to show the next fire times of.
+    cron: Option<String>,
+    /// Number of the fire times shown by the cron command.
+    count: usize,
+    /// All the time zones passed with -tz, in order.
+    zones: Vec<Tz>,
+    /// Re-evaluate the expression with this interval.
+    watch: Option<TimeDelta>,
+    /// Sleep until the date-time, or for the duration.
+    wait: Option<Wait>,
+    /// Reference date-time of the --since batch, evaluated when the context is known.
+    since: Option<String>,
+    /// Retention of the --retention batch, evaluated when the context is known.
+    retention: Option<String>,
+    /// Process all the stdin lines together.
+    batch: Option<Box<dyn Batch>>,
+    /// Set by -v and -vv, overrides $TSCALC_LOG.
+    log_level: Option<u8>,
+    /// Print the steps of the evaluation to stderr.
+    trace_eval: bool,
+    /// Print the tokens, the grouping and the steps of the evaluation before the result.
+    explain: bool,
+    /// Print how the literals of the input were interpreted to stderr, before the result.
+    echo_input: bool,
+    /// Print the warnings about the guesses to stderr, unless -w0.
+    warnings: bool,
+}
+
+enum Wait {
+    /// Expression evaluating to date-time.
+    Until(String),
+    /// Expression evaluating to duration.
+    For(String),
+}
+
+/// Sleep until the date-time, or for the duration, the expression evaluates to. Date-times in the past and negative
+/// durations return immediately.
+fn wait_until(wait: &Wait, ctx: &EvalContext, dialect: Dialect) -> Result<(), String> {
+    let sleep_for = match wait {
+        Wait::Until(input) => match parse_and_eval_result(input, ctx, dialect)? {
+            parser::EvaluationResult::DateTime(datetime) => {
+                datetime.with_timezone(&chrono::Utc) - chrono::Utc::now()
+            }
+            other => return Err(format!("--wait expects date-time, got {}", other)),
+        },
+        Wait::For(input) => match parse_and_eval_result(input, ctx, dialect)? {
+            parser::EvaluationResult::TimeDelta(delta) => delta,
+            other => return Err(format!("--wait-for expects duration, got {}", other)),
+        },
+    };
+    if let Ok(duration) = sleep_for.to_std() {
+        thread::sleep(duration);
+    }
+    Ok(())
+}
+
+impl Args {
+    fn eval_context(&self, now: chrono::DateTime<Tz>) -> EvalContext {
+        EvalContext {
+            // In strict mode there is no default unit, the bare numbers need --epoch-unit.
+            epoch_unit: self.epoch_unit.unwrap_or(if self.strict {
+                EpochUnit::Auto
+            } else {
+                EpochUnit::Seconds
+            }),
+            ntp_server: self.ntp_server.clone(),
+            random: self.seed.map_or(Random::from_clock(), Random::new),
+            locale: self.output_options.locale,
+            date_order: self.date_order,
+            strict: self.strict,
+            trace: self.trace_eval.then(|| Rc::new(Trace::default())),
+            epochs: self.echo_input.then(Rc::default),
+            warnings: self.warnings.then(Rc::default),
+            ..EvalContext::new(now)
+        }
+    }
+}
+
+/// Start or stop the named timer. Stopping prints the elapsed time.
+fn run_timer(command: &str, name: &str) -> Result<String, String> {
+    let path = timer::state_file()?;
+    match command {
+        "start" => timer::start(&path, name, chrono::Utc::now()).map(|_| String::new()),
+        "stop" => {
+            timer::stop(&path, name, chrono::Utc::now()).map(|elapsed| elapsed.as_short_format())
+        }
+        _ => Err(format!(
+            "bad timer command {:?}, expected start or stop",
+            command
+        )),
+    }
+}
+
+/// Show the date-time the expression evaluates to in each of the time zones, a row per zone with the local time and
+/// the day of week. Without zones, show UTC.
+fn plan(
+    input: &String,
+    zones: &[Tz],
+    ctx: &EvalContext,
+    dialect: Dialect,
+) -> Result<String, String> {
+    let datetime = match parse_and_eval_result(input, ctx, dialect)? {
+        parser::EvaluationResult::DateTime(datetime) => datetime,
+        other => return Err(format!("plan expects date-time, got {}", other)),
+    };
+    let zones = if zones.is_empty() { &[UTC][..] } else { zones };
+    let width = zones.iter().map(|tz| tz.name().len()).max().unwrap_or(0);
+    let rows: Vec<String> = zones
+        .iter()
+        .map(|tz| {
+            let local = datetime.with_timezone(tz);
+            format!(
+                "{:width$}  {}",
+                tz.name(),
+                local.format("%Y-%m-%d %H:%M %a %:z"),
+                width = width
+            )
+        })
+        .collect();
+    Ok(rows.join("\n"))
+}
+
+/// Return the next fire times of the cron schedule after now, one per line. The schedule is evaluated in the time
+/// zone of now, the one set with -tz.
+fn cron_preview(
+    schedule: &str,
+    count: usize,
+    output_options: &OutputOptions,
+) -> Result<String, String> {
+    let schedule = CronSchedule::parse(schedule)?;
+    let mut from = output_options.now;
+    let mut rows = vec![];
+    for _ in 0..count {
+        from = schedule
+            .next_after(&from)
+            .ok_or_else(|| format!("cron schedule never fires after {}", from))?;
+        rows.push(format_result(
+            &parser::EvaluationResult::DateTime(from),
+            output_options,
+        ));
+    }
+    Ok(rows.join("\n"))
+}
+
+/// Return current time in the time zone.
+fn current_time(tz: Option<Tz>) -> chrono::DateTime<Tz> {
+    // Intentionally truncate to seconds to make the calculator more practical (although less precise).
+    chrono::Utc::now()
+        .trunc_subsecs(0)
+        .with_timezone(&tz.unwrap_or(UTC))
+}
+
+/// Re-evaluate the expression every interval, rewriting the previous output in place. Runs until interrupted.
+fn watch(input: &String, args: &mut Args, interval: TimeDelta) -> ! {
+    #[cfg(windows)]
+    enable_escape_codes();
+    let mut previous_lines = 0;
+    loop {
+        let now = current_time(args.timezone);
+        args.output_options.now = now;
+        let ctx = args.eval_context(now);
+        match parse_and_eval(input, &args.output_options, &ctx, args.dialect) {
+            Ok(output) => {
+                print!("{}", rewrite_lines(previous_lines, &output));
+                io::stdout().flush().unwrap();
+                previous_lines = output.lines().count();
+            }
+            Err(message) => {
+                println!("\n{}", message);
+                process::exit(1);
+            }
+        }
+        thread::sleep(interval.to_std().unwrap());
+    }
+}
+
+/// Prefix the output with terminal escape codes that move the cursor to the beginning of the previous output, and
+/// clear it.
+fn rewrite_lines(previous_lines: usize, output: &str) -> String {
+    let mut s = String::new();
+    if previous_lines > 1 {
+        write!(s, "\x1b[{}F", previous_lines - 1).unwrap();
+    }
+    write!(s, "\r\x1b[J{}", output).unwrap();
+    s
+}
+
+/// Turn on the terminal escape codes of rewrite_lines in the Windows console, where the classic console host has them
+/// off by default. Without a console, like with the output redirected to a file, nothing changes.
+#[cfg(windows)]
+fn enable_escape_codes() {
+    use std::ffi::c_void;
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetStdHandle(std_handle: u32) -> *mut c_void;
+        fn GetConsoleMode(console: *mut c_void, mode: *mut u32) -> i32;
+        fn SetConsoleMode(console: *mut c_void, mode: u32) -> i32;
+    }
+    const STD_OUTPUT_HANDLE: u32 = -11_i32 as u32;
+    const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x0004;
+    // SAFETY: the handle comes from GetStdHandle and the mode is written by GetConsoleMode before it is read.
+    unsafe {
+        let console = GetStdHandle(STD_OUTPUT_HANDLE);
+        let mut mode = 0;
+        if GetConsoleMode(console, &mut mode) != 0 {
+            SetConsoleMode(console, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING);
+        }
+    }
 }
 
 fn parse_cli_args() -> Result<Args, String> {
     let mut output = Args {
-        output_format: OutputFormat::ISO,
+        output_options: OutputOptions::new(UTC),
         print_help: false,
+        print_functions: false,
+        print_man: false,
+        examples: false,
+        example_category: None,
+        run_example: None,
+        capabilities: false,
         expression: None,
         read_from_stdin: false,
         timezone: None,
-        datetime_format: DEFAULT_FORMAT.to_owned(),
+        epoch_unit: None,
+        ntp_server: None,
+        http_time_url: None,
+        seed: None,
+        x509_path: None,
+        timer: None,
+        plan: None,
+        cron: None,
+        count: 5,
+        assertion: None,
+        check_path: None,
+        repl: false,
+        zones: vec![],
+        dialect: Dialect::Tscalc,
+        date_order: None,
+        strict: false,
+        dry_run: false,
+        check: false,
+        watch: None,
+        wait: None,
+        since: None,
+        retention: None,
+        batch: None,
+        log_level: None,
+        trace_eval: false,
+        explain: false,
+        echo_input: false,
+        warnings: true,
     };
     let args: Vec<String> = env::args().collect();
     let mut found_sentinel = false;
-    let mut iter_args = args.iter();
+    let mut iter_args = args.iter().peekable();
     iter_args.next();
+    if iter_args.peek().map(|arg| arg.as_str()) == Some("http-time") {
+        iter_args.next();
+        let url = iter_args.next().ok_or("expected URL".to_string())?;
+        output.http_time_url = Some(url.to_owned());
+    } else if iter_args.peek().map(|arg| arg.as_str()) == Some("x509") {
+        iter_args.next();
+        let path = iter_args.next().ok_or("expected PEM file".to_string())?;
+        output.x509_path = Some(path.to_owned());
+    } else if iter_args.peek().map(|arg| arg.as_str()) == Some("timer") {
+        iter_args.next();
+        let command = iter_args
+            .next()
+            .ok_or("expected timer command".to_string())?;
+        let name = iter_args.next().ok_or("expected timer name".to_string())?;
+        output.timer = Some((command.to_owned(), name.to_owned()));
+    } else if iter_args.peek().map(|arg| arg.as_str()) == Some("plan") {
+        iter_args.next();
+        let input = iter_args.next().ok_or("expected expression".to_string())?;
+        output.plan = Some(input.to_owned());
+    } else if iter_args.peek().map(|arg| arg.as_str()) == Some("cron") {
+        iter_args.next();
+        let schedule = iter_args
+            .next()
+            .ok_or("expected cron schedule".to_string())?;
+        output.cron = Some(schedule.to_owned());
+    } else if iter_args.peek().map(|arg| arg.as_str()) == Some("assert") {
+        iter_args.next();
+        let input = iter_args.next().ok_or("expected expression".to_string())?;
+        output.assertion = Some(input.to_owned());
+    } else if iter_args.peek().map(|arg| arg.as_str()) == Some("check") {
+        iter_args.next();
+        let path = iter_args.next().ok_or("expected script file".to_string())?;
+        output.check_path = Some(path.to_owned());
+    } else if iter_args.peek().map(|arg| arg.as_str()) == Some("repl") {
+        iter_args.next();
+        output.repl = true;
+    } else if iter_args.peek().map(|arg| arg.as_str()) == Some("man") {
+        iter_args.next();
+        output.print_man = true;
+    } else if iter_args.peek().map(|arg| arg.as_str()) == Some("examples") {
+        iter_args.next();
+        output.examples = true;
+        output.example_category = iter_args
+            .next_if(|arg| !arg.starts_with('-'))
+            .map(|category| category.to_owned());
+    }
     while let Some(arg) = iter_args.next() {
         if found_sentinel {
             output = Args {
@@ -112,34 +622,230 @@ fn parse_cli_args() -> Result<Args, String> {
                 read_from_stdin: true,
                 ..output
             };
-        } else if arg == "-h" {
+        } else if arg == "-h" || arg == "--help" {
             output = Args {
                 print_help: true,
                 ..output
             };
+        } else if arg == "--help-functions" {
+            output.print_functions = true;
+        } else if arg == "--run" {
+            let number = iter_args
+                .next()
+                .ok_or("expected example number".to_string())?;
+            output.run_example = match number.parse::<usize>() {
+                Ok(number) if number > 0 => Some(number),
+                _ => return Err(format!("bad example number {:?}", number)),
+            };
+        } else if arg == "--capabilities" {
+            output.capabilities = true;
+        } else if arg == "-v" || arg == "--verbose" {
+            output.log_level = Some(output.log_level.unwrap_or(log::LOG_OFF) + 1);
+        } else if arg == "-vv" {
+            output.log_level = Some(log::LOG_PARSE);
+        } else if arg == "--trace-eval" {
+            output.trace_eval = true;
+        } else if arg == "--explain" {
+            output.explain = true;
+        } else if arg == "--echo-input" {
+            output.echo_input = true;
+        } else if arg == "-w0" {
+            output.warnings = false;
+        } else if arg == "-w1" {
+            output.warnings = true;
         } else if arg == "-s" {
+            output.output_options.format = OutputFormat::EpochSeconds;
+        } else if arg == "-S" {
+            output.output_options.format = OutputFormat::FullEpochSeconds;
+        } else if arg == "-tz" || arg == "--tz" {
+            let tz_str = iter_args.next().ok_or("expected timezone".to_string())?;
+            let tz = parse_tz(tz_str)?;
+            output.output_options.tz = tz;
+            output.zones.push(tz);
             output = Args {
-                output_format: OutputFormat::EpochSeconds,
+                timezone: Some(tz),
                 ..output
             }
-        } else if arg == "-S" {
+        } else if arg == "--count" {
+            let count = iter_args.next().ok_or("expected count".to_string())?;
+            output.count = match count.parse::<usize>() {
+                Ok(count) if count > 0 => count,
+                _ => return Err(format!("bad count {:?}", count)),
+            };
+        } else if arg == "-f" {
+            let format = iter_args.next().ok_or("expected format".to_string())?;
+            output.output_options.datetime_format = format.to_owned();
+        } else if arg == "--wait" {
+            let input = iter_args.next().ok_or("expected expression".to_string())?;
+            output.wait = Some(Wait::Until(input.to_owned()));
+        } else if arg == "--wait-for" {
+            let input = iter_args.next().ok_or("expected expression".to_string())?;
+            output.wait = Some(Wait::For(input.to_owned()));
+        } else if arg == "--watch" {
+            // The interval is optional.
+            let interval = iter_args
+                .peek()
+                .and_then(|next| TimeDelta::from_short_format(next).ok());
+            if interval.is_some() {
+                iter_args.next();
+            }
+            let interval = interval.unwrap_or(TimeDelta::seconds(1));
+            if interval <= TimeDelta::zero() {
+                return Err(format!(
+                    "watch interval should be positive, was {}",
+                    interval.as_short_format()
+                ));
+            }
+            output.watch = Some(interval);
+        } else if arg == "--merge-gaps" {
+            let gap = iter_args.next().ok_or("expected gap".to_string())?;
+            let gap = TimeDelta::from_short_format(gap)?;
+            output.read_from_stdin = true;
+            output.batch = Some(Box::new(MergeGaps::new(gap)));
+        } else if arg == "--diff" {
+            output.read_from_stdin = true;
+            output.batch = Some(Box::new(Diff::new()));
+        } else if arg == "--since" {
+            let input = iter_args.next().ok_or("expected expression".to_string())?;
+            output.read_from_stdin = true;
+            output.since = Some(input.to_owned());
+        } else if arg == "--retention" {
+            let input = iter_args.next().ok_or("expected expression".to_string())?;
+            output.read_from_stdin = true;
+            output.retention = Some(input.to_owned());
+        } else if arg == "--assert-monotonic" {
+            output.read_from_stdin = true;
+            output.batch = Some(Box::new(AssertMonotonic::new()));
+        } else if arg == "--delta-unit" {
+            let unit = iter_args
+                .next()
+                .ok_or("expected duration unit".to_string())?;
+            let unit = DURATION_UNITS
+                .into_iter()
+                .find(|known| known == unit)
+                .ok_or_else(|| {
+                    format!(
+                        "unknown duration unit {:?}, expected one of {}",
+                        unit,
+                        DURATION_UNITS.join(", ")
+                    )
+                })?;
+            output.output_options.delta_unit = Some(unit);
+        } else if arg == "--decimals" {
+            let decimals = iter_args
+                .next()
+                .ok_or("expected number of decimals".to_string())?;
+            output.output_options.decimals = match decimals.parse::<usize>() {
+                Ok(decimals) if decimals <= 9 => decimals,
+                _ => {
+                    return Err(format!(
+                        "bad number of decimals {:?}, expected 0 to 9",
+                        decimals
+                    ))
+                }
+            };
+        } else if arg == "--metric-suffix" {
+            output.output_options.metric_suffix = true;
+        } else if arg == "--round" {
+            output.output_options.rounding = Rounding::HalfEven;
+        } else if arg == "--trunc" {
+            output.output_options.rounding = Rounding::Trunc;
+        } else if arg == "--time-only" {
+            output.output_options.format = OutputFormat::TimeOnly;
+        } else if arg == "--ixdtf" {
+            output.output_options.format = OutputFormat::Ixdtf;
+        } else if arg == "--relative" {
+            output.output_options.format = OutputFormat::Relative;
+        } else if arg == "--relative-units" {
+            let units = iter_args
+                .next()
+                .ok_or("expected number of units".to_string())?;
+            output.output_options.relative_units = match units.parse::<usize>() {
+                Ok(units) if units > 0 => units,
+                _ => return Err(format!("bad number of units {:?}", units)),
+            };
+        } else if arg == "--errors" {
+            let format = iter_args
+                .next()
+                .ok_or("expected error format".to_string())?;
+            output.output_options.json_errors = match format.as_str() {
+                "json" => true,
+                "text" => false,
+                _ => {
+                    return Err(format!(
+                        "bad error format {:?}, expected json or text",
+                        format
+                    ))
+                }
+            };
+        } else if arg == "--delta-clock" {
+            output.output_options.delta_clock = true;
+        } else if arg == "--delta-go" {
+            output.output_options.delta_go = true;
+        } else if arg == "--utc-z" {
+            output.output_options.utc_z = true;
+        } else if arg == "--space-sep" {
+            output.output_options.space_sep = true;
+        } else if arg == "--frac" {
+            let digits = iter_args
+                .next()
+                .ok_or("expected number of digits".to_string())?;
+            let digits = match digits.parse::<usize>() {
+                Ok(value) if value <= 9 => value,
+                _ => {
+                    return Err(format!(
+                        "bad number of fractional digits {:?}, expected 0 to 9",
+                        digits
+                    ))
+                }
+            };
+            output.output_options.frac_digits = Some(digits);
+        } else if arg == "--epoch-unit" {
+            let unit = iter_args.next().ok_or("expected epoch unit".to_string())?;
             output = Args {
-                output_format: OutputFormat::FullEpochSeconds,
+                epoch_unit: Some(EpochUnit::from_str(unit)?),
                 ..output
             }
-        } else if arg == "-tz" {
-            let tz_str = iter_args.next().ok_or("expected timezone".to_string())?;
-            let tz = Tz::from_str(&tz_str).map_err(|err: chrono_tz::ParseError| {
-                format!("failed to parse {:?}: {}", tz_str, err)
-            })?;
+        } else if arg == "--dialect" {
+            let dialect = iter_args.next().ok_or("expected dialect".to_string())?;
             output = Args {
-                timezone: Some(tz),
+                dialect: Dialect::from_str(dialect)?,
                 ..output
             }
-        } else if arg == "-f" {
-            let format = iter_args.next().ok_or("expected format".to_string())?;
+        } else if arg == "--seed" {
+            let seed = iter_args.next().ok_or("expected seed".to_string())?;
+            let seed = seed
+                .parse::<u64>()
+                .map_err(|_| format!("bad seed {:?}, expected non-negative integer", seed))?;
+            output.seed = Some(seed);
+        } else if arg == "--check" {
             output = Args {
-                datetime_format: format.to_owned(),
+                check: true,
+                ..output
+            }
+        } else if arg == "--dry-run" {
+            output = Args {
+                dry_run: true,
+                ..output
+            }
+        } else if arg == "--strict-parse" {
+            output = Args {
+                strict: true,
+                ..output
+            }
+        } else if arg == "--date-order" {
+            let order = iter_args.next().ok_or("expected date order".to_string())?;
+            output = Args {
+                date_order: Some(DateOrder::from_str(order)?),
+                ..output
+            }
+        } else if arg == "--locale" {
+            let locale = iter_args.next().ok_or("expected locale".to_string())?;
+            output.output_options.locale = parse_locale(locale)?;
+        } else if arg == "--ntp" {
+            let server = iter_args.next().ok_or("expected NTP server".to_string())?;
+            output = Args {
+                ntp_server: Some(server.to_owned()),
                 ..output
             }
         } else if arg == "--" {
@@ -148,160 +854,2962 @@ fn parse_cli_args() -> Result<Args, String> {
             return Err(format!("unknown param {:?}", arg));
         }
     }
+    if output.run_example.is_some() && !output.examples {
+        return Err("--run needs the examples command".to_string());
+    }
     Ok(output)
 }
 
 fn print_help() {
-    let help = "
-Simple calculator for date-time and durations.
-
-Built-in functions:
-- full_day\tReturn full day of the date-time.
-- full_hour\tReturn full hour of the date-time.
-
--i\tRead input from stdin and process line by line.
--s\tOutput time as epoch seconds.
--S\tOutput time as epoch seconds, without the decimal part.
--tz\tTimezone like US/Eastern or Europe/Warsaw , as in https://docs.rs/chrono-tz/latest/chrono_tz/enum.Tz.html
--f\tFormat output datetime, with specifiers from https://docs.rs/chrono/latest/chrono/format/strftime/index.html
--h\tPrint this help.
---\tAfter this sentinel, concatenate all the arguments into a single expression.
-";
-    println!("{}", help.trim());
+    println!("{}", help::help());
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Clone, Copy, Debug)]
 enum OutputFormat {
     ISO,
     EpochSeconds,
     FullEpochSeconds,
+    /// Date-time relative to now, like "3 hours ago".
+    Relative,
+    /// Only the time of day of date-time, like "08:30:00".
+    TimeOnly,
+    /// Date-time in its own time zone with the zone name (RFC 9557), like "2024-05-17T13:00:00+02:00[Europe/Warsaw]".
+    Ixdtf,
 }
 
-fn parse_and_eval(
-    input: &String,
-    output_format: OutputFormat,
-    datetime_format: &str,
-    output_tz: &chrono_tz::Tz,
+/// How to render the results of evaluation.
+#[derive(Clone, Debug)]
+struct OutputOptions {
+    format: OutputFormat,
+    datetime_format: String,
+    tz: Tz,
+    /// Use "Z" instead of "+00:00" for UTC in RFC 3339 output.
+    utc_z: bool,
+    /// Separate date and time with space instead of "T" in RFC 3339 output.
+    space_sep: bool,
+    /// Fixed number of fractional second digits in RFC 3339 output. By default as many digits as needed.
+    frac_digits: Option<usize>,
+    /// How to drop the digits of the epoch seconds output beyond the precision.
+    rounding: Rounding,
+    /// Reference time of the relative output.
     now: chrono::DateTime<Tz>,
-) -> Result<String, String> {
-    let parse_result = parse_expr(input);
-    if let Err(parse_err) = parse_result {
-        let mut m = String::from("");
-        write!(m, "{}", parse_err.pointer.input).unwrap();
-        write!(m, "\n{}^", "_".repeat(parse_err.pointer.pos)).unwrap();
-        write!(m, "\n{}", parse_err.message).unwrap();
-        return Err(m);
-    }
-    let parse_ok = parse_result.unwrap();
-    let eval_result = evaluate(parse_ok.node, now)?;
-    return Ok(match eval_result {
-        parser::EvaluationResult::DateTime(datetime) => match output_format {
-            OutputFormat::ISO => datetime
-                .with_timezone(output_tz)
-                .format(datetime_format)
-                .to_string(),
-            OutputFormat::EpochSeconds => {
-                format!("{:.3}", (datetime.timestamp_millis() as f64) / 1000.0)
-            }
-            OutputFormat::FullEpochSeconds => format!("{}", (datetime.timestamp_millis() / 1000)),
-        },
-        parser::EvaluationResult::TimeDelta(delta) => match output_format {
-            OutputFormat::ISO => delta.as_short_format(),
-            OutputFormat::EpochSeconds => todo!("display delta as seconds"),
-            OutputFormat::FullEpochSeconds => todo!("display delta as full seconds"),
-        },
-    });
+    /// How many units to show in the relative output, like 2 for "in 2 days 3 hours".
+    relative_units: usize,
+    /// Format durations as HH:MM:SS instead of like 1h30m.
+    delta_clock: bool,
+    /// Format durations as Go time.Duration, like 1h30m0s.
+    delta_go: bool,
+    /// Format durations as decimal number of the unit, like 1.500 for 90m in hours, in all output formats.
+    delta_unit: Option<&'static str>,
+    /// Number of fractional digits of the durations with delta_unit.
+    decimals: usize,
+    /// Format durations as integer number of a single unit, like 90m, in the Prometheus syntax, in all output formats.
+    metric_suffix: bool,
+    /// Print parse and evaluation errors as JSON objects, for editor plugins and wrappers.
+    json_errors: bool,
+    /// Locale of the month and day names with -f.
+    locale: Locale,
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::parse_and_eval;
-    use chrono_tz::{Tz, UTC};
-
-    #[test]
-    fn test_eval_garbage_on_right() {
-        check_parse_and_eval("1h + 2h + 2000-01-01T00:00:00Z garbage", None);
+impl OutputOptions {
+    fn new(tz: Tz) -> OutputOptions {
+        OutputOptions {
+            format: OutputFormat::ISO,
+            datetime_format: DEFAULT_FORMAT.to_owned(),
+            tz,
+            utc_z: false,
+            space_sep: false,
+            frac_digits: None,
+            rounding: Rounding::default(),
+            now: chrono::Utc::now().with_timezone(&tz),
+            relative_units: 1,
+            delta_clock: false,
+            delta_go: false,
+            delta_unit: None,
+            decimals: 3,
+            metric_suffix: false,
+            json_errors: false,
+            locale: Locale::default(),
+        }
     }
 
-    #[test]
-    fn test_eval_with_now() {
-        check_parse_and_eval("1s + now", Some("2001-01-01T01:01:02+00:00"));
+    fn is_default_rfc3339(&self) -> bool {
+        !self.utc_z && !self.space_sep && self.frac_digits.is_none()
     }
+}
 
-    #[test]
-    fn test_eval_brackets_1() {
-        check_parse_and_eval("now - (1s - 1s)", Some("2001-01-01T01:01:01+00:00"));
-    }
+/// Units of the relative output. Months and years are approximate.
+const RELATIVE_UNITS: [(&str, i64); 7] = [
+    ("year", 365 * 24 * 3600),
+    ("month", 30 * 24 * 3600),
+    ("week", 7 * 24 * 3600),
+    ("day", 24 * 3600),
+    ("hour", 3600),
+    ("minute", 60),
+    ("second", 1),
+];
 
-    #[test]
-    fn test_eval_brackets_2() {
-        check_parse_and_eval(
-            "(1s - (2s - 1s)) + now - (1s - (2s - 1s))",
-            Some("2001-01-01T01:01:01+00:00"),
-        );
+/// Render the difference from now like "in 2 days" or "3 hours ago", with at most `max_units` units. The value is
+/// rounded to the smallest unit shown.
+fn format_relative(delta: chrono::TimeDelta, max_units: usize) -> String {
+    let seconds = delta.num_seconds().unsigned_abs() as i64;
+    let first_unit = RELATIVE_UNITS
+        .iter()
+        .position(|(_, unit_seconds)| seconds >= *unit_seconds);
+    let first_unit = if let Some(first_unit) = first_unit {
+        first_unit
+    } else {
+        return "now".to_string();
+    };
+    let smallest_unit =
+        RELATIVE_UNITS[(first_unit + max_units - 1).min(RELATIVE_UNITS.len() - 1)].1;
+    let mut rest = (seconds + smallest_unit / 2) / smallest_unit * smallest_unit;
+    let mut parts: Vec<String> = vec![];
+    for (name, unit_seconds) in RELATIVE_UNITS.iter() {
+        let count = rest / unit_seconds;
+        rest -= count * unit_seconds;
+        if count > 0 {
+            let plural = if count == 1 { "" } else { "s" };
+            parts.push(format!("{} {}{}", count, name, plural));
+        }
     }
-
-    #[test]
-    fn test_eval_brackets_3() {
-        check_parse_and_eval(
-            "(now - (now - 1d)) + now - (now - (now - 1d))",
-            Some("2001-01-01T01:01:01+00:00"),
-        );
+    if delta < chrono::TimeDelta::zero() {
+        format!("{} ago", parts.join(" "))
+    } else {
+        format!("in {}", parts.join(" "))
     }
+}
 
-    #[test]
-    fn test_eval_func_full_day_1() {
-        check_parse_and_eval("full_day(now)", Some("2001-01-01T00:00:00+00:00"));
+/// Render calendar difference like "2 years 3 months 5 days", skipping zero units.
+fn format_calendar_delta(years: i64, months: i64, days: i64) -> String {
+    let sign = if years < 0 || months < 0 || days < 0 {
+        "-"
+    } else {
+        ""
+    };
+    let parts: Vec<String> = [("year", years), ("month", months), ("day", days)]
+        .iter()
+        .filter(|(_, count)| *count != 0)
+        .map(|(name, count)| {
+            let plural = if count.abs() == 1 { "" } else { "s" };
+            format!("{} {}{}", count.abs(), name, plural)
+        })
+        .collect();
+    if parts.is_empty() {
+        "0 days".to_string()
+    } else {
+        format!("{}{}", sign, parts.join(" "))
     }
+}
 
-    #[test]
-    fn test_eval_timestamp_1() {
-        check_parse_and_eval("1234567890.000", Some("2009-02-13T23:31:30+00:00"));
+/// Render date-time like the "%+" format, adjusted with the RFC 3339 options.
+fn format_rfc3339(datetime: &chrono::DateTime<Tz>, output_options: &OutputOptions) -> String {
+    let mut format = String::from("%Y-%m-%d");
+    format.push_str(if output_options.space_sep { " " } else { "T" });
+    format.push_str("%H:%M:%S");
+    match output_options.frac_digits {
+        Some(0) => {}
+        // The format has the digits only for 3, 6 and 9, the fraction is truncated like there.
+        Some(digits) => {
+            let nanos = datetime.timestamp_subsec_nanos() % 1_000_000_000;
+            format.push('.');
+            format.push_str(&format!("{:09}", nanos)[..digits]);
+        }
+        None => format.push_str("%.f"),
     }
-    #[test]
-    fn test_eval_timestamp_2() {
-        check_parse_and_eval("0.0 + (0.0 - 1.0)", Some("1969-12-31T23:59:59+00:00"));
+    if output_options.utc_z && datetime.offset().fix().local_minus_utc() == 0 {
+        format.push('Z');
+    } else {
+        format.push_str("%:z");
     }
+    datetime.format(&format).to_string()
+}
 
-    #[test]
-    fn test_eval_timestamp_3() {
-        check_parse_and_eval("0", Some("1970-01-01T00:00:00+00:00"));
-    }
+/// Format the date-time as epoch seconds with the number of fractional digits, from the nanoseconds without floats.
+/// With truncation the digits beyond are dropped rounding down, so the value is within the second of the date-time.
+fn format_epoch_seconds(
+    datetime: &chrono::DateTime<Tz>,
+    digits: usize,
+    rounding: Rounding,
+) -> String {
+    let nanos = i128::from(datetime.timestamp()) * 1_000_000_000
+        + i128::from(datetime.timestamp_subsec_nanos());
+    let rounded = rounding.apply(nanos, 10i128.pow(9 - digits as u32));
+    let sign = if rounded < 0 { "-" } else { "" };
+    format!(
+        "{}{}",
+        sign,
+        nanos_as_seconds(rounded.unsigned_abs(), digits, Rounding::Trunc)
+    )
+}
 
-    #[test]
-    fn test_eval_timestamp_4() {
-        check_parse_and_eval("0.0", Some("1970-01-01T00:00:00+00:00"));
-    }
+/// Format the date-time in its own time zone with the zone name in brackets, like
+/// "2024-05-17T13:00:00+02:00[Europe/Warsaw]", which parses back to the same date-time and zone. Only the fractional
+/// digits of the RFC 3339 options apply, the separator and the offset are fixed.
+fn format_ixdtf(datetime: &chrono::DateTime<Tz>, output_options: &OutputOptions) -> String {
+    let options = OutputOptions {
+        utc_z: false,
+        space_sep: false,
+        ..output_options.clone()
+    };
+    format!(
+        "{}[{}]",
+        format_rfc3339(datetime, &options),
+        datetime.timezone().name()
+    )
+}
 
-    #[test]
-    fn test_eval_timestamp_5() {
-        check_parse_and_eval("0.1", Some("1970-01-01T00:00:00.100+00:00"));
+fn parse_and_eval(
+    input: &String,
+    output_options: &OutputOptions,
+    ctx: &EvalContext,
+    dialect: Dialect,
+) -> Result<String, String> {
+    let json_errors = output_options.json_errors;
+    // The literals are echoed as written, from the spanned terms.
+    let echo = ctx.epochs.is_some();
+    let node = parse_node(input, dialect, ctx.strict, echo, json_errors)?;
+    let eval_result = evaluate(node.clone(), ctx).map_err(|message| {
+        if json_errors {
+            json_error("eval", input, None, &[], &message)
+        } else {
+            mark_type_err(input, dialect, message)
+        }
+    });
+    if echo {
+        for line in echo_input(input, &node, ctx, output_options) {
+            eprintln!("{}", line);
+        }
     }
+    print_warnings(input, dialect, ctx);
+    print_trace(ctx);
+    Ok(format_result(&eval_result?, output_options))
+}
 
-    #[test]
-    fn test_eval_timestamp_6() {
-        check_parse_and_eval("0.12345", Some("1970-01-01T00:00:00.123450+00:00"));
+/// Describe how the literals of the input were interpreted for --echo-input, like "1716000000 →
+/// 2024-05-18T02:40:00+00:00 (epoch seconds)", after the evaluation that recorded the numbers taken as epoch
+/// timestamps. The numbers used otherwise, like the arguments of functions, are echoed as numbers.
+fn echo_input(
+    input: &str,
+    node: &Node,
+    ctx: &EvalContext,
+    output_options: &OutputOptions,
+) -> Vec<String> {
+    let mut epochs = ctx
+        .epochs
+        .as_ref()
+        .map(|epochs| epochs.take())
+        .unwrap_or_default();
+    let term_ctx = EvalContext {
+        trace: None,
+        epochs: None,
+        ..ctx.clone()
+    };
+    let zone = |tz: &Option<Tz>| tz.unwrap_or(ctx.now.timezone()).to_string();
+    let mut lines = vec![];
+    for (start, end, term) in literal_terms(node) {
+        let text = &input[start..end];
+        let (value, kind) = match term {
+            Node::Number(value) => match epochs.iter().position(|epoch| epoch.value == *value) {
+                Some(i) => {
+                    let epoch = epochs.remove(i);
+                    let guessed = match ctx.epoch_unit {
+                        EpochUnit::Auto => ", guessed",
+                        _ => "",
+                    };
+                    let value = parser::EvaluationResult::DateTime(epoch.datetime);
+                    let kind = format!("epoch {}{}", epoch.unit.name(), guessed);
+                    (format_result(&value, output_options), kind)
+                }
+                None => (text.to_string(), "number".to_string()),
+            },
+            term => {
+                // The errors of the terms are reported with the result.
+                let Ok(value) = evaluate(term.clone(), &term_ctx) else {
+                    continue;
+                };
+                let kind = match term {
+                    Node::LocalDateTime { date: None, tz, .. } => {
+                        format!("time today in {}", zone(tz))
+                    }
+                    Node::LocalDateTime { tz, .. } => format!("date-time in {}", zone(tz)),
+                    Node::NamedMonthDate { time, tz, .. } if *time != chrono::NaiveTime::MIN => {
+                        format!("date-time in {}", zone(tz))
+                    }
+                    Node::NamedMonthDate { tz, .. } => format!("date in {}", zone(tz)),
+                    Node::NumericDate(_) => format!("numeric date in {}", zone(&None)),
+                    Node::Duration(_) => "duration".to_string(),
+                    _ => "date-time".to_string(),
+                };
+                (format_result(&value, output_options), kind)
+            }
+        };
+        lines.push(format!("{} → {} ({})", text, value, kind));
     }
+    lines
+}
 
-    #[test]
-    fn test_eval_missing_bracket_1() {
-        check_parse_and_eval("0.0 + (0.0 - 1.0", None);
+/// Explain the evaluation for --explain: the tokens of the input, the grouping of the terms chosen by the parser, the
+/// steps of the evaluation with the intermediate values, and the result.
+fn explain(
+    input: &String,
+    output_options: &OutputOptions,
+    ctx: &EvalContext,
+    dialect: Dialect,
+) -> Result<String, String> {
+    let node = parse_node(input, dialect, ctx.strict, true, false)?;
+    let mut s = String::new();
+    // The other dialects are translated to the nodes, their tokens are not the ones of tscalc.
+    if dialect == Dialect::Tscalc {
+        let tokens = tokenize(input);
+        let text = |token: &Token| &input[token.start..token.end];
+        let width = tokens
+            .iter()
+            .map(|token| text(token).chars().count())
+            .max()
+            .unwrap_or(0);
+        s += "Tokens:\n";
+        for token in &tokens {
+            writeln!(s, "  {:width$}  {}", text(token), token.kind).unwrap();
+        }
     }
-
-    #[test]
-    fn test_eval_duration_6() {
+    writeln!(s, "Grouping:\n  {}", grouping(&node, input)).unwrap();
+    let trace = Rc::new(Trace::default());
+    let ctx = EvalContext {
+        trace: Some(trace.clone()),
+        ..ctx.clone()
+    };
+    let result = evaluate(node, &ctx);
+    s += "Steps:\n";
+    let steps = trace.take_steps();
+    if steps.is_empty() {
+        s += "  none\n";
+    }
+    for step in steps {
+        writeln!(s, "  {}", step).unwrap();
+    }
+    let warnings = take_warnings(input, dialect, &ctx);
+    if !warnings.is_empty() {
+        s += "Warnings:\n";
+    }
+    for warning in warnings {
+        writeln!(s, "  {}", warning).unwrap();
+    }
+    let (header, result) = match result {
+        Ok(result) => ("Result:", Ok(format_result(&result, output_options))),
+        Err(message) => ("Error:", Err(mark_type_err(input, dialect, message))),
+    };
+    s += header;
+    // Indented line by line, so the marks under the terms stay aligned.
+    for line in result.as_ref().unwrap_or_else(|message| message).lines() {
+        write!(s, "\n  {}", line).unwrap();
+    }
+    match result {
+        Ok(_) => Ok(s),
+        Err(_) => Err(s),
+    }
+}
+
+/// Print the warnings about the guesses made for the input to stderr, unless -w0.
+fn print_warnings(input: &String, dialect: Dialect, ctx: &EvalContext) {
+    for warning in take_warnings(input, dialect, ctx) {
+        eprintln!("warning: {}", warning);
+    }
+}
+
+/// The warnings about the guesses made for the input, if collected: the time zone abbreviations found in the input,
+/// and the warnings recorded by the evaluation, which start over.
+fn take_warnings(input: &String, dialect: Dialect, ctx: &EvalContext) -> Vec<String> {
+    let Some(warnings) = &ctx.warnings else {
+        return vec![];
+    };
+    let mut found = match dialect {
+        Dialect::Tscalc => zone_warnings(input),
+        _ => vec![],
+    };
+    found.extend(warnings.take());
+    found
+}
+
+/// Print the steps of the evaluation recorded with --trace-eval to stderr, so they do not mix with the results.
+fn print_trace(ctx: &EvalContext) {
+    if let Some(trace) = &ctx.trace {
+        for step in trace.take_steps() {
+            eprintln!("{}", step);
+        }
+    }
+}
+
+fn parse_and_eval_result(
+    input: &String,
+    ctx: &EvalContext,
+    dialect: Dialect,
+) -> Result<parser::EvaluationResult, String> {
+    evaluate(parse_node(input, dialect, ctx.strict, false, false)?, ctx)
+        .map_err(|message| mark_type_err(input, dialect, message))
+}
+
+/// Mark the terms under the input if the evaluation error is a type error found also by check_types, like in
+/// "now + now". The other errors are returned as they are.
+fn mark_type_err(input: &String, dialect: Dialect, message: String) -> String {
+    if dialect != Dialect::Tscalc {
+        return message;
+    }
+    let spanned = parse_expr_spanned(input, &Registry::builtin());
+    match spanned.map(|parse_ok| check_types(&parse_ok.node)) {
+        Ok(Err(err)) if err.message == message => format_type_err(input, &err),
+        _ => message,
+    }
+}
+
+/// Parse the input and check the types without evaluating it, and return the type of the result, like "date-time".
+/// The type errors point at the terms, like the parse errors.
+fn dry_run(input: &String, dialect: Dialect, strict: bool) -> Result<String, String> {
+    let node = parse_node(input, dialect, strict, true, false)?;
+    check_types(&node)
+        .map(|result| result.to_string())
+        .map_err(|err| format_type_err(input, &err))
+}
+
+/// Format the type error with the spans marked under the input, or only the message without the spans.
+fn format_type_err(input: &str, err: &TypeErr) -> String {
+    if err.spans.is_empty() {
+        return err.message.clone();
+    }
+    let mut spans = err.spans.clone();
+    spans.sort_by_key(|span| span.start);
+    let mut marks = String::new();
+    for span in spans {
+        let start = span.start.max(marks.len());
+        marks.push_str(&" ".repeat(start - marks.len()));
+        marks.push_str(&"^".repeat(span.end.saturating_sub(start).max(1)));
+    }
+    format!("{}\n{}\n{}", input, marks, err.message)
+}
+
+/// Parse the input in the dialect, with the parse error pointing at the position in the input, as text or as JSON.
+/// In strict mode, the time zone abbreviations and the natural phrases are errors. With spans, the terms are wrapped
+/// in Node::Spanned, for check_types.
+fn parse_node(
+    input: &String,
+    dialect: Dialect,
+    strict: bool,
+    spans: bool,
+    json_errors: bool,
+) -> Result<Node, String> {
+    let node = match dialect {
+        Dialect::Tscalc => {
+            let parse_result = if spans {
+                parse_expr_spanned(input, &Registry::builtin())
+            } else {
+                parse_expr(input)
+            };
+            let parse_result = parse_result.and_then(|parse_ok| {
+                if strict {
+                    check_strict_zones(input)?;
+                }
+                Ok(parse_ok)
+            });
+            if let Err(parse_err) = parse_result {
+                // Phrases like "noon tomorrow" are not expressions, but can be mapped onto them.
+                #[cfg(feature = "natural")]
+                if let (false, Ok(node)) = (strict, parser::natural::natural_to_node(input)) {
+                    return Ok(node);
+                }
+                if json_errors {
+                    let position = input[..parse_err.pointer.pos].chars().count();
+                    return Err(json_error(
+                        "parse",
+                        input,
+                        Some(position),
+                        &parse_err.expected,
+                        &parse_err.message,
+                    ));
+                }
+                let mut m = String::from("");
+                write!(m, "{}", parse_err.pointer.input).unwrap();
+                write!(
+                    m,
+                    "\n{}{}",
+                    "_".repeat(parse_err.pointer.pos),
+                    "^".repeat(parse_err.len.max(1))
+                )
+                .unwrap();
+                write!(m, "\n{}", parse_err.message).unwrap();
+                return Err(m);
+            }
+            Ok(parse_result.unwrap().node)
+        }
+        Dialect::Splunk => splunk_to_node(input),
+        Dialect::Elastic => elastic_to_node(input),
+    };
+    node.map_err(|message| {
+        if json_errors {
+            json_error("parse", input, None, &[], &message)
+        } else {
+            message
+        }
+    })
+}
+
+/// Format the error as a single line JSON object. The position is in characters from the start of the input.
+fn json_error(
+    kind: &str,
+    input: &str,
+    position: Option<usize>,
+    expected: &[String],
+    message: &str,
+) -> String {
+    let position = position.map_or("null".to_string(), |position| position.to_string());
+    let expected: Vec<String> = expected.iter().map(|token| json_string(token)).collect();
+    format!(
+        "{{\"kind\":{},\"input\":{},\"position\":{},\"expected\":[{}],\"message\":{}}}",
+        json_string(kind),
+        json_string(input),
+        position,
+        expected.join(","),
+        json_string(message)
+    )
+}
+
+/// The cargo features of this build.
+fn enabled_features() -> Vec<&'static str> {
+    [
+        ("tz", cfg!(feature = "tz")),
+        ("fs", cfg!(feature = "fs")),
+        ("uptime", cfg!(feature = "uptime")),
+        ("ntp", cfg!(feature = "ntp")),
+        ("http", cfg!(feature = "http")),
+        ("x509", cfg!(feature = "x509")),
+        ("repl", cfg!(feature = "repl")),
+        ("astro", cfg!(feature = "astro")),
+        ("natural", cfg!(feature = "natural")),
+        ("locale", cfg!(feature = "locale")),
+        ("jiff", cfg!(feature = "jiff")),
+    ]
+    .iter()
+    .filter(|(_, enabled)| *enabled)
+    .map(|(name, _)| *name)
+    .collect()
+}
+
+/// Describe what this build accepts and prints, as a single line JSON object. The "schema" is increased when the
+/// existing keys change, the new keys can be added without it.
+fn capabilities() -> String {
+    let strings = |values: &[&str]| {
+        let quoted: Vec<String> = values.iter().map(|value| json_string(value)).collect();
+        format!("[{}]", quoted.join(","))
+    };
+    // The formats with the flag selecting them, null for the default.
+    let flagged = |formats: &[(&str, Option<&str>)]| {
+        let objects: Vec<String> = formats
+            .iter()
+            .map(|(name, flag)| {
+                format!(
+                    "{{\"name\":{},\"flag\":{}}}",
+                    json_string(name),
+                    flag.map_or("null".to_string(), json_string)
+                )
+            })
+            .collect();
+        format!("[{}]", objects.join(","))
+    };
+    let registry = Registry::builtin();
+    let functions: Vec<String> = registry
+        .names()
+        .iter()
+        .map(|name| {
+            let arities: Vec<String> = (1..=MAX_ARITY)
+                .filter(|arity| registry.names_with_arity(*arity).contains(name))
+                .map(|arity| arity.to_string())
+                .collect();
+            format!(
+                "{{\"name\":{},\"arity\":[{}]}}",
+                json_string(name),
+                arities.join(",")
+            )
+        })
+        .collect();
+    let epochs: Vec<&str> = EPOCHS.iter().map(|epoch| epoch.name).collect();
+    let features = enabled_features();
+    let fields = [
+        ("schema", "1".to_string()),
+        ("version", json_string(env!("CARGO_PKG_VERSION"))),
+        ("functions", format!("[{}]", functions.join(","))),
+        ("keywords", strings(&KEYWORDS)),
+        ("types", strings(&TYPE_NAMES)),
+        ("duration_units", strings(&DURATION_UNITS)),
+        (
+            "relative_units",
+            strings(&["s", "m", "h", "d", "w", "M", "y"]),
+        ),
+        ("epoch_units", strings(&["s", "ms", "us", "ns", "auto"])),
+        ("epochs", strings(&epochs)),
+        ("dialects", strings(&["tscalc", "splunk", "elastic"])),
+        ("date_orders", strings(&["mdy", "dmy", "ymd"])),
+        (
+            "output_formats",
+            flagged(&[
+                ("rfc3339", None),
+                ("epoch_seconds", Some("-s")),
+                ("full_epoch_seconds", Some("-S")),
+                ("relative", Some("--relative")),
+                ("time_only", Some("--time-only")),
+                ("ixdtf", Some("--ixdtf")),
+                ("strftime", Some("-f")),
+            ]),
+        ),
+        (
+            "duration_formats",
+            flagged(&[
+                ("short", None),
+                ("seconds", Some("-s")),
+                ("clock", Some("--delta-clock")),
+                ("go", Some("--delta-go")),
+                ("unit", Some("--delta-unit")),
+                ("metric", Some("--metric-suffix")),
+            ]),
+        ),
+        ("error_formats", strings(&["text", "json"])),
+        ("features", strings(&features)),
+    ];
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(key, value)| format!("{}:{}", json_string(key), value))
+        .collect();
+    format!("{{{}}}", fields.join(","))
+}
+
+/// Quote and escape the string as JSON string.
+fn json_string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if (c as u32) < 0x20 => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Check that the expression evaluates to true. On failure, return the message with the values of both sides of
+/// the comparison, if the expression is a comparison.
+fn assert_expr(
+    input: &String,
+    output_options: &OutputOptions,
+    ctx: &EvalContext,
+    dialect: Dialect,
+) -> Result<String, String> {
+    let mut node = parse_node(input, dialect, ctx.strict, false, false)?;
+    while let Node::Expr(nodes) = &node {
+        match nodes.as_slice() {
+            [inner] => node = inner.clone(),
+            _ => break,
+        }
+    }
+    let result = evaluate(node.clone(), ctx);
+    print_warnings(input, dialect, ctx);
+    print_trace(ctx);
+    match result? {
+        parser::EvaluationResult::Bool(true) => Ok(String::new()),
+        parser::EvaluationResult::Bool(false) => {
+            let mut m = format!("assertion failed: {}", input);
+            if let Node::Compare {
+                oper: _,
+                left,
+                right,
+            } = node
+            {
+                // The steps and the warnings of the sides are already printed.
+                let ctx = &EvalContext {
+                    trace: None,
+                    warnings: None,
+                    ..ctx.clone()
+                };
+                let left = evaluate(left.as_ref().clone(), ctx)?;
+                let right = evaluate(right.as_ref().clone(), ctx)?;
+                write!(m, "\n-  left: {}", format_result(&left, output_options)).unwrap();
+                write!(m, "\n+ right: {}", format_result(&right, output_options)).unwrap();
+            }
+            Err(m)
+        }
+        other => Err(format!("assert expects bool, got {:?}", other)),
+    }
+}
+
+/// Evaluate each "expr => expected" line of the script and compare the output with the expected one. Empty lines and
+/// lines starting with "#" are skipped. Return the number of passed lines, or the mismatches with line numbers.
+fn check_script(
+    script: &str,
+    output_options: &OutputOptions,
+    ctx: &EvalContext,
+    dialect: Dialect,
+) -> Result<String, String> {
+    let mut passed = 0;
+    let mut failures = vec![];
+    for (number, line) in script.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (input, expected) = match line.rsplit_once("=>") {
+            Some((input, expected)) => (input.trim().to_string(), expected.trim()),
+            None => {
+                failures.push(format!(
+                    "line {}: expected \"expr => expected\"",
+                    number + 1
+                ));
+                continue;
+            }
+        };
+        let actual = match parse_and_eval(&input, output_options, ctx, dialect) {
+            Ok(actual) => actual,
+            Err(message) => format!("error: {}", message),
+        };
+        if actual == expected {
+            passed += 1;
+        } else {
+            let mut m = format!("line {}: {}", number + 1, input);
+            write!(m, "\n- expected: {}", expected).unwrap();
+            write!(m, "\n+   actual: {}", actual).unwrap();
+            failures.push(m);
+        }
+    }
+    if failures.is_empty() {
+        Ok(format!("{} passed", passed))
+    } else {
+        failures.push(format!("{} passed, {} failed", passed, failures.len()));
+        Err(failures.join("\n"))
+    }
+}
+
+/// Fetch the Date header of the URL and the skew of the local clock.
+fn http_time_report(url: &str) -> Result<parser::EvaluationResult, String> {
+    let http_time = http_time::query(url)?;
+    Ok(parser::EvaluationResult::Record(vec![
+        (
+            "date".to_string(),
+            parser::EvaluationResult::DateTime(http_time.date.with_timezone(&UTC)),
+        ),
+        (
+            "skew".to_string(),
+            parser::EvaluationResult::TimeDelta(http_time.skew),
+        ),
+    ]))
+}
+
+/// Read the validity of the certificate and the time left until it expires, negative if already expired.
+fn x509_report(path: &str) -> Result<parser::EvaluationResult, String> {
+    let validity = x509::read_validity(path)?;
+    Ok(parser::EvaluationResult::Record(vec![
+        (
+            "not_before".to_string(),
+            parser::EvaluationResult::DateTime(validity.not_before.with_timezone(&UTC)),
+        ),
+        (
+            "not_after".to_string(),
+            parser::EvaluationResult::DateTime(validity.not_after.with_timezone(&UTC)),
+        ),
+        (
+            "expires_in".to_string(),
+            parser::EvaluationResult::TimeDelta(
+                validity.not_after.with_timezone(&UTC) - current_time(None),
+            ),
+        ),
+    ]))
+}
+
+/// Query network time and the offset of the local clock, positive when the local clock is behind.
+#[cfg(feature = "ntp")]
+fn ntp_report(server: &str) -> Result<parser::EvaluationResult, String> {
+    let ntp_time = parser::ntp::query(server)?;
+    Ok(parser::EvaluationResult::Record(vec![
+        (
+            "ntp_now".to_string(),
+            parser::EvaluationResult::DateTime(ntp_time.time.with_timezone(&UTC)),
+        ),
+        (
+            "offset".to_string(),
+            parser::EvaluationResult::TimeDelta(ntp_time.offset),
+        ),
+    ]))
+}
+
+#[cfg(not(feature = "ntp"))]
+fn ntp_report(_server: &str) -> Result<parser::EvaluationResult, String> {
+    Err("--ntp is not supported, build with the ntp feature".to_string())
+}
+
+/// Render the columns of a batch row separated with spaces.
+fn format_row(row: &[parser::EvaluationResult], output_options: &OutputOptions) -> String {
+    row.iter()
+        .map(|result| format_result(result, output_options))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn format_result(eval_result: &parser::EvaluationResult, output_options: &OutputOptions) -> String {
+    match eval_result {
+        parser::EvaluationResult::DateTime(datetime) => match output_options.format {
+            OutputFormat::ISO => {
+                let datetime = datetime.with_timezone(&output_options.tz);
+                if output_options.datetime_format == DEFAULT_FORMAT
+                    && !output_options.is_default_rfc3339()
+                {
+                    format_rfc3339(&datetime, output_options)
+                } else {
+                    format_localized(
+                        &datetime,
+                        &output_options.datetime_format,
+                        output_options.locale,
+                    )
+                }
+            }
+            OutputFormat::EpochSeconds => format_epoch_seconds(
+                datetime,
+                output_options.frac_digits.unwrap_or(3),
+                output_options.rounding,
+            ),
+            // Truncated toward zero, as -S always printed the date-times before 1970.
+            OutputFormat::FullEpochSeconds => match output_options.rounding {
+                Rounding::Trunc => (datetime.timestamp_millis() / 1000).to_string(),
+                Rounding::HalfEven => format_epoch_seconds(datetime, 0, Rounding::HalfEven),
+            },
+            OutputFormat::Relative => format_relative(
+                *datetime - output_options.now,
+                output_options.relative_units,
+            ),
+            OutputFormat::TimeOnly => datetime
+                .with_timezone(&output_options.tz)
+                .format("%H:%M:%S")
+                .to_string(),
+            OutputFormat::Ixdtf => format_ixdtf(datetime, output_options),
+        },
+        parser::EvaluationResult::TimeDelta(delta) => match output_options.format {
+            _ if output_options.delta_unit.is_some() => delta.as_unit_format(
+                output_options.delta_unit.unwrap(),
+                output_options.decimals,
+                output_options.rounding,
+            ),
+            _ if output_options.metric_suffix => delta.as_metric_format(output_options.rounding),
+            OutputFormat::ISO
+            | OutputFormat::Relative
+            | OutputFormat::TimeOnly
+            | OutputFormat::Ixdtf
+                if output_options.delta_clock =>
+            {
+                delta.as_clock_format()
+            }
+            OutputFormat::ISO
+            | OutputFormat::Relative
+            | OutputFormat::TimeOnly
+            | OutputFormat::Ixdtf
+                if output_options.delta_go =>
+            {
+                delta.as_go_format()
+            }
+            OutputFormat::ISO
+            | OutputFormat::Relative
+            | OutputFormat::TimeOnly
+            | OutputFormat::Ixdtf => delta.as_short_format(),
+            OutputFormat::EpochSeconds => delta.as_seconds_format_with(
+                output_options.frac_digits.unwrap_or(3),
+                output_options.rounding,
+            ),
+            OutputFormat::FullEpochSeconds => {
+                delta.as_seconds_format_with(0, output_options.rounding)
+            }
+        },
+        parser::EvaluationResult::Integer(value) => value.to_string(),
+        parser::EvaluationResult::String(s) => s.clone(),
+        parser::EvaluationResult::Bool(value) => value.to_string(),
+        parser::EvaluationResult::CalendarDelta {
+            years,
+            months,
+            days,
+        } => format_calendar_delta(*years, *months, *days),
+        parser::EvaluationResult::Interval(start, end) => format!(
+            "{}..{}",
+            format_result(&parser::EvaluationResult::DateTime(*start), output_options),
+            format_result(&parser::EvaluationResult::DateTime(*end), output_options)
+        ),
+        parser::EvaluationResult::List(elements) => elements
+            .iter()
+            .map(|element| format_result(element, output_options))
+            .collect::<Vec<String>>()
+            .join("\n"),
+        parser::EvaluationResult::Record(fields) => fields
+            .iter()
+            .map(|(name, value)| {
+                let value = format_result(value, output_options);
+                format!("{}: {}", name, value)
+            })
+            .collect::<Vec<String>>()
+            .join("\n"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::zone::{parse_tz, Tz, UTC};
+    use crate::parser::{
+        evaluate,
+        number::EpochUnit,
+        numeric_date::DateOrder,
+        random::Random,
+        registry::Registry,
+        relative::{grafana_to_node, Dialect},
+        EvalContext, ShortFormat,
+    };
+    use crate::{
+        assert_expr, capabilities, check_script, dry_run, echo_input, explain, parse_and_eval,
+        parse_and_eval_result, parse_node, plan, take_warnings, OutputFormat, OutputOptions,
+        Rounding,
+    };
+    use std::rc::Rc;
+
+    #[test]
+    fn test_eval_garbage_on_right() {
+        check_parse_and_eval("1h + 2h + 2000-01-01T00:00:00Z garbage", None);
+    }
+
+    #[test]
+    fn test_eval_with_now() {
+        check_parse_and_eval("1s + now", Some("2001-01-01T01:01:02+00:00"));
+    }
+
+    #[test]
+    fn test_eval_brackets_1() {
+        check_parse_and_eval("now - (1s - 1s)", Some("2001-01-01T01:01:01+00:00"));
+    }
+
+    #[test]
+    fn test_eval_brackets_2() {
+        check_parse_and_eval(
+            "(1s - (2s - 1s)) + now - (1s - (2s - 1s))",
+            Some("2001-01-01T01:01:01+00:00"),
+        );
+    }
+
+    #[test]
+    fn test_eval_brackets_3() {
+        check_parse_and_eval(
+            "(now - (now - 1d)) + now - (now - (now - 1d))",
+            Some("2001-01-01T01:01:01+00:00"),
+        );
+    }
+
+    #[test]
+    fn test_eval_func_full_day_1() {
+        check_parse_and_eval("full_day(now)", Some("2001-01-01T00:00:00+00:00"));
+    }
+
+    #[test]
+    fn test_eval_func_from_filetime() {
+        check_parse_and_eval(
+            "from_filetime(133575168000000000)",
+            Some("2024-04-13T21:20:00+00:00"),
+        );
+        check_parse_and_eval(
+            "from_filetime(116444736000000001) + 1s",
+            Some("1970-01-01T00:00:01.000000100+00:00"),
+        );
+        check_parse_and_eval("from_filetime(-1)", None);
+    }
+
+    #[test]
+    fn test_eval_func_to_filetime() {
+        check_parse_and_eval(
+            "to_filetime(2024-04-13T21:20:00Z)",
+            Some("133575168000000000"),
+        );
+        check_parse_and_eval("to_filetime(0)", Some("116444736000000000"));
+        check_parse_and_eval(
+            "from_filetime(to_filetime(now))",
+            Some("2001-01-01T01:01:01+00:00"),
+        );
+        check_parse_and_eval("to_filetime(now) + 1s", None);
+    }
+
+    #[test]
+    fn test_eval_func_alt_epochs() {
+        check_parse_and_eval("from_cocoa(0)", Some("2001-01-01T00:00:00+00:00"));
+        check_parse_and_eval("from_cocoa(-0.5)", Some("2000-12-31T23:59:59.500+00:00"));
+        check_parse_and_eval("from_hfs(3786912000)", Some("2024-01-01T00:00:00+00:00"));
+        check_parse_and_eval("to_hfs(2024-01-01T00:00:00Z)", Some("3786912000"));
+        check_parse_and_eval("to_cocoa(now)", Some("3661"));
+        check_parse_and_eval("from_hfs(-1)", None);
+        check_parse_and_eval("from_unknown(1)", None);
+    }
+
+    #[test]
+    fn test_eval_func_ids() {
+        check_parse_and_eval(
+            "from_objectid(\"65f0c0ffee0123456789abcd\") + 1s",
+            Some("2024-03-12T20:54:24+00:00"),
+        );
+        check_parse_and_eval(
+            "from_uuid1(\"c232ab00-9414-11ec-b3c8-9f6bdeced846\")",
+            Some("2022-02-22T19:22:22+00:00"),
+        );
+        check_parse_and_eval(
+            "from_uuid7(\"017f22e2-79b0-7cc3-98c4-dc0c0c07398f\")",
+            Some("2022-02-22T19:22:22+00:00"),
+        );
+        check_parse_and_eval("from_uuid7(\"c232ab00-9414-11ec-b3c8-9f6bdeced846\")", None);
+        check_parse_and_eval("from_objectid(now)", None);
+    }
+
+    #[test]
+    fn test_eval_func_jwt_exp() {
+        check_parse_and_eval(
+            "jwt_exp(\"eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.eyJzdWIiOiIxMjM0NTY3ODkwIiwiZXhwIjoxNzE2MDAwMDAwfQ.c2ln\")",
+            Some("2024-05-18T02:40:00+00:00"),
+        );
+        check_parse_and_eval("jwt_exp(\"abc\")", None);
+    }
+
+    #[test]
+    fn test_eval_func_from_snowflake() {
+        check_parse_and_eval(
+            "from_snowflake(1223395535877046272)",
+            Some("2020-02-01T00:00:00+00:00"),
+        );
+        check_parse_and_eval(
+            "from_snowflake(\"175928847299117063\", \"discord\") + 1h",
+            Some("2016-04-30T12:18:25.796+00:00"),
+        );
+        check_parse_and_eval(
+            "from_snowflake(4194304, 2000-01-01T00:00:00Z)",
+            Some("2000-01-01T00:00:00.001+00:00"),
+        );
+        check_parse_and_eval(
+            "from_snowflake(4194304, 946684800)",
+            Some("2000-01-01T00:00:00.001+00:00"),
+        );
+        check_parse_and_eval("from_snowflake(1, \"unknown\")", None);
+        check_parse_and_eval("from_snowflake(1.5)", None);
+    }
+
+    #[test]
+    fn test_eval_func_totp_window() {
+        check_parse_and_eval(
+            "totp_window(2024-05-18T02:40:10Z, 30s)",
+            Some(
+                "counter: 57200000\nstart: 2024-05-18T02:40:00+00:00\nend: 2024-05-18T02:40:30+00:00",
+            ),
+        );
+        check_parse_and_eval(
+            "totp_window(59, 30s)",
+            Some("counter: 1\nstart: 1970-01-01T00:00:30+00:00\nend: 1970-01-01T00:01:00+00:00"),
+        );
+        check_parse_and_eval("totp_window(now, 0s)", None);
+        check_parse_and_eval("totp_window(now, 30s) + 1s", None);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_eval_func_at_on() {
+        check_parse_and_eval("at(now, \"14:30\")", Some("2001-01-01T14:30:00+00:00"));
+        check_parse_and_eval(
+            "at(now + 1d, \"07:05:09\")",
+            Some("2001-01-02T07:05:09+00:00"),
+        );
+        check_parse_and_eval("on(now, \"2024-06-01\")", Some("2024-06-01T01:01:01+00:00"));
+        check_parse_and_eval(
+            "at(on(now, \"2024-02-29\"), \"00:00\")",
+            Some("2024-02-29T00:00:00+00:00"),
+        );
+        check_parse_and_eval("on(now, \"2023-02-29\")", None);
+        check_parse_and_eval("at(now, \"25:00\")", None);
+        check_parse_and_eval("at(now, 1h)", None);
+        let ctx = EvalContext::new(now().with_timezone(&Tz::from(chrono_tz::Europe::Warsaw)));
+        check_parse_and_eval_ctx(
+            "at(now, \"14:30\")",
+            Some("2001-01-01T13:30:00+00:00"),
+            &UTC,
+            &ctx,
+        );
+    }
+
+    #[test]
+    fn test_eval_func_with_component() {
+        check_parse_and_eval("with_year(now, 2025)", Some("2025-01-01T01:01:01+00:00"));
+        check_parse_and_eval("with_month(now, 12)", Some("2001-12-01T01:01:01+00:00"));
+        check_parse_and_eval("with_day(now, 31)", Some("2001-01-31T01:01:01+00:00"));
+        check_parse_and_eval(
+            "with_hour(with_minute(with_second(now, 0), 0), 0)",
+            Some("2001-01-01T00:00:00+00:00"),
+        );
+        check_parse_and_eval("with_day(with_month(now, 2), 30)", None);
+        check_parse_and_eval("with_hour(now, 24)", None);
+        check_parse_and_eval("with_month(now, -1)", None);
+        check_parse_and_eval("with_day(now, 1.5)", None);
+    }
+
+    #[test]
+    fn test_eval_func_age() {
+        check_parse_and_eval(
+            "age(1990-06-15T00:00:00Z)",
+            Some("10 years 6 months 17 days"),
+        );
+        check_parse_and_eval(
+            "age(2024-01-31T00:00:00Z, 2024-03-01T00:00:00Z)",
+            Some("1 month 1 day"),
+        );
+        check_parse_and_eval(
+            "age(2024-01-31T00:00:00Z, 2024-02-29T23:00:00Z)",
+            Some("29 days"),
+        );
+        check_parse_and_eval(
+            "age(2020-02-29T00:00:00Z, 2021-02-28T00:00:00Z)",
+            Some("11 months 30 days"),
+        );
+        check_parse_and_eval("age(now, now + 1h)", Some("0 days"));
+        check_parse_and_eval(
+            "age(2024-03-01T00:00:00Z, 2023-02-01T00:00:00Z)",
+            Some("-1 year 1 month"),
+        );
+        check_parse_and_eval("age(1h)", None);
+    }
+
+    #[test]
+    fn test_eval_func_bucket() {
+        check_parse_and_eval("bucket(now, 5m)", Some("2001-01-01T01:00:00+00:00"));
+        check_parse_and_eval("bucket(now, 1h)", Some("2001-01-01T01:00:00+00:00"));
+        check_parse_and_eval("bucket(-1, 1m)", Some("1969-12-31T23:59:00+00:00"));
+        check_parse_and_eval("bucket_index(now, 1d)", Some("11323"));
+        check_parse_and_eval("bucket_index(now, 1h, 2001-01-01T00:00:00Z)", Some("1"));
+        check_parse_and_eval("bucket_index(now, 1h, now + 1s)", Some("-1"));
+        check_parse_and_eval("bucket(now, 0s)", None);
+        check_parse_and_eval("bucket_index(now, 1h, 1h)", None);
+        let eval = |input: &str| {
+            parse_and_eval(
+                &input.to_string(),
+                &OutputOptions::new(UTC),
+                &EvalContext::new(now()),
+                Dialect::Tscalc,
+            )
+        };
+        assert_eq!(
+            eval("bucket(now, 0s)"),
+            Err("the second argument to bucket should be positive duration, was: 0s".to_string())
+        );
+        assert_eq!(
+            eval("bucket_index(now, 1h, 1h)"),
+            Err("the third argument to bucket_index should be datetime, was: 1h".to_string())
+        );
+    }
+
+    #[test]
+    fn test_eval_func_random_between() {
+        let ctx = EvalContext {
+            random: Random::new(42),
+            ..EvalContext::new(now())
+        };
+        let input = "random_between(now, now + 1d)".to_string();
+        let output_options = OutputOptions::new(UTC);
+        let first = parse_and_eval(&input, &output_options, &ctx, Dialect::Tscalc).unwrap();
+        let second = parse_and_eval(&input, &output_options, &ctx, Dialect::Tscalc).unwrap();
+        assert_ne!(first, second);
+        for value in [first, second] {
+            assert!(
+                value.as_str() >= "2001-01-01T01:01:01" && value.as_str() < "2001-01-02T01:01:01"
+            );
+        }
+        let ctx = EvalContext {
+            random: Random::new(42),
+            ..EvalContext::new(now())
+        };
+        let repeated = parse_and_eval(&input, &output_options, &ctx, Dialect::Tscalc).unwrap();
+        let ctx = EvalContext {
+            random: Random::new(42),
+            ..EvalContext::new(now())
+        };
+        assert_eq!(
+            parse_and_eval(&input, &output_options, &ctx, Dialect::Tscalc),
+            Ok(repeated)
+        );
+        check_parse_and_eval("random_between(1s, 1s)", None);
+        check_parse_and_eval("random_between(now, 1h)", None);
+        check_parse_and_eval("random_between(0s, max_time - min_time)", None);
+        check_parse_and_eval(
+            "random_between(min_time, max_time) < max_time",
+            Some("true"),
+        );
+    }
+
+    #[test]
+    fn test_eval_lists() {
+        check_parse_and_eval(
+            "[now, now - 1d, 0]",
+            Some("2001-01-01T01:01:01+00:00\n2000-12-31T01:01:01+00:00\n1970-01-01T00:00:00+00:00"),
+        );
+        check_parse_and_eval("[1h]", Some("1h"));
+        check_parse_and_eval("[(1h), (2h)]", Some("1h\n2h"));
+        check_parse_and_eval(
+            "seq(now, now + 3h, 1h)",
+            Some("2001-01-01T01:01:01+00:00\n2001-01-01T02:01:01+00:00\n2001-01-01T03:01:01+00:00"),
+        );
+        check_parse_and_eval(
+            "map([now, now + 1d], x -> full_day(x) + 1h)",
+            Some("2001-01-01T01:00:00+00:00\n2001-01-02T01:00:00+00:00"),
+        );
+        check_parse_and_eval(
+            "map(seq(now, now + 2h, 1h), t -> map([1m, 2m], d -> t + d - now))",
+            Some("1m\n2m\n1h1m\n1h2m"),
+        );
+        check_parse_and_eval("map(now, x -> x)", None);
+        check_parse_and_eval("map([now], x -> y)", None);
+        check_parse_and_eval("x", None);
+        check_parse_and_eval("seq(now, now + 1d, 0s)", None);
+        check_parse_and_eval("seq(now, now + 1d, 1ms)", None);
+    }
+
+    #[test]
+    fn test_eval_aggregates() {
+        check_parse_and_eval("sum([1h, 30m, 15m])", Some("1h45m"));
+        check_parse_and_eval("mean([1h, 30m, 15m])", Some("35m"));
+        check_parse_and_eval("min([1h, -30m])", Some("-30m"));
+        check_parse_and_eval("max([1h, -30m])", Some("1h"));
+        check_parse_and_eval("span([1h, -30m])", Some("1h30m"));
+        check_parse_and_eval("min([now, now - 1d])", Some("2000-12-31T01:01:01+00:00"));
+        check_parse_and_eval(
+            "max(seq(now, now + 1d, 1h))",
+            Some("2001-01-02T00:01:01+00:00"),
+        );
+        check_parse_and_eval("mean([now, now + 1h])", Some("2001-01-01T01:31:01+00:00"));
+        check_parse_and_eval("span(seq(now, now + 1d, 1h))", Some("23h"));
+        check_parse_and_eval("sum([now])", None);
+        check_parse_and_eval("min([now, 1h])", None);
+        check_parse_and_eval("max(now)", None);
+        // Beyond the nanoseconds of i64, about 292 years.
+        check_parse_and_eval("min([max_time - min_time])", None);
+        check_parse_and_eval("mean([max_time - min_time, 1s])", None);
+        check_parse_and_eval("span([1970-01-01T00:00:00Z, 2300-01-01T00:00:00Z])", None);
+        check_parse_and_eval(
+            "span([1970-01-01T00:00:00Z, 2200-01-01T00:00:00Z])",
+            Some("84006d"),
+        );
+    }
+
+    #[test]
+    fn test_eval_named_datetimes() {
+        check_parse_and_eval("epoch", Some("1970-01-01T00:00:00+00:00"));
+        check_parse_and_eval("epoch + 1d", Some("1970-01-02T00:00:00+00:00"));
+        check_parse_and_eval("max_time", Some("+262142-12-31T23:59:59.999999999+00:00"));
+        check_parse_and_eval("min_time", Some("-262143-01-01T00:00:00+00:00"));
+        check_parse_and_eval("max_time + 1ns", None);
+        check_parse_and_eval("min_time - 1ns", None);
+        check_parse_and_eval("now < max_time", Some("true"));
+        check_parse_and_eval("min_time < epoch", Some("true"));
+        check_parse_and_eval(
+            "max(seq(epoch, epoch + 3d, 1d))",
+            Some("1970-01-03T00:00:00+00:00"),
+        );
+    }
+
+    #[test]
+    fn test_eval_func_retention_floor() {
+        check_parse_and_eval(
+            "retention_floor(now - 40d, 30d)",
+            Some("2000-12-02T01:01:01+00:00"),
+        );
+        check_parse_and_eval(
+            "retention_floor(now - 1d, 30d)",
+            Some("2000-12-31T01:01:01+00:00"),
+        );
+        check_parse_and_eval("retention_floor(now - 1d, -30d)", None);
+        check_parse_and_eval("retention_floor(1d, 30d)", None);
+    }
+
+    #[test]
+    fn test_eval_func_round_delta() {
+        check_parse_and_eval("round_delta(1m29s, 1m)", Some("1m"));
+        check_parse_and_eval("round_delta(1m30s, 1m)", Some("2m"));
+        check_parse_and_eval("round_delta(0s - 1m30s, 1m)", Some("-2m"));
+        check_parse_and_eval("round_delta(1h1m1s123ms, 1s)", Some("1h1m1s"));
+        check_parse_and_eval("round_delta(1m, 0s)", None);
+        check_parse_and_eval("round_delta(now, 1m)", None);
+    }
+
+    #[test]
+    fn test_eval_func_trunc_delta() {
+        check_parse_and_eval("trunc_delta(1m59s, 1m)", Some("1m"));
+        check_parse_and_eval("trunc_delta(0s - 1m59s, 1m)", Some("-1m"));
+        check_parse_and_eval("trunc_delta(1s999ms, 1s)", Some("1s"));
+        check_parse_and_eval("trunc_delta(1m, 0s - 1s)", None);
+    }
+
+    #[cfg(feature = "astro")]
+    #[test]
+    fn test_eval_func_sun_event() {
+        check_parse_and_eval(
+            "sunset(2024-06-21T12:00:00Z, 52.2297, 21.0122)",
+            Some("2024-06-21T19:01:04+00:00"),
+        );
+        check_parse_and_eval(
+            "sunrise(2024-12-21T12:00:00Z, 37.7749, -122.4194)",
+            Some("2024-12-21T15:21:43+00:00"),
+        );
+        check_parse_and_eval(
+            "sunset(2024-06-21T12:00:00Z, 52.2297, 21.0122) - 2024-06-21T12:00:00Z",
+            Some("7h1m4s"),
+        );
+        check_parse_and_eval("sunset(2024-06-21T12:00:00Z, 91, 0)", None);
+        check_parse_and_eval("sunset(2024-06-21T12:00:00Z, 78.22, 15.65)", None);
+        check_parse_and_eval("sunset(1h, 0, 0)", None);
+    }
+
+    #[cfg(not(feature = "astro"))]
+    #[test]
+    fn test_eval_func_sun_event() {
+        check_parse_and_eval("sunset(now, 52.2297, 21.0122)", None);
+    }
+
+    #[test]
+    fn test_eval_func_clamp() {
+        check_parse_and_eval(
+            "clamp(now - 40d, now - 30d, now)",
+            Some("2000-12-02T01:01:01+00:00"),
+        );
+        check_parse_and_eval(
+            "clamp(now + 1d, now - 30d, now)",
+            Some("2001-01-01T01:01:01+00:00"),
+        );
+        check_parse_and_eval(
+            "clamp(now - 1d, now - 30d, now)",
+            Some("2000-12-31T01:01:01+00:00"),
+        );
+        check_parse_and_eval("clamp(10s, 1m, 1h)", Some("1m"));
+        check_parse_and_eval("clamp(2h, 1m, 1h)", Some("1h"));
+        check_parse_and_eval("clamp(30m, 1m, 1h)", Some("30m"));
+        check_parse_and_eval("clamp(30m, 1h, 1m)", None);
+        check_parse_and_eval("clamp(now, 1m, 1h)", None);
+        let eval = |input: &str| {
+            parse_and_eval(
+                &input.to_string(),
+                &OutputOptions::new(UTC),
+                &EvalContext::new(now()),
+                Dialect::Tscalc,
+            )
+        };
+        assert_eq!(
+            eval("clamp(30m, 1h, 1m)"),
+            Err("the lower bound of clamp is above the upper bound: 1h > 1m".to_string())
+        );
+        assert_eq!(
+            eval("clamp(2024-01-01T00:00:00Z, 1m, 1h)"),
+            Err(
+                "the arguments to clamp should be all datetimes or all durations, were: \
+                2024-01-01T00:00:00+00:00, 1m, 1h"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_eval_compare() {
+        check_parse_and_eval("now > now - 1h", Some("true"));
+        check_parse_and_eval("now<now - 1h", Some("false"));
+        check_parse_and_eval("now - 1h <= now - 60m", Some("true"));
+        check_parse_and_eval("1h >= 2h", Some("false"));
+        check_parse_and_eval("full_day(now) == 2001-01-01T00:00:00Z", Some("true"));
+        check_parse_and_eval("fmt(now, \"%Y\") != \"2001\"", Some("false"));
+        check_parse_and_eval("now |> full_day == now |> full_day", Some("true"));
+        check_parse_and_eval("now > 1h", None);
+        check_parse_and_eval("now > now - 1h > now", None);
+    }
+
+    #[test]
+    fn test_eval_if() {
+        check_parse_and_eval("if(now > now - 1h, 1h, 2h)", Some("1h"));
+        check_parse_and_eval("if(now < now - 1h, 1h, 2h)", Some("2h"));
+        check_parse_and_eval(
+            "if(now > 2000-01-01T00:00:00Z, 0s, 2000-01-01T00:00:00Z - now)",
+            Some("0s"),
+        );
+        // the branch not taken is not evaluated
+        check_parse_and_eval(
+            "if(now > now - 1h, now, fmt(1h, \"%Y\"))",
+            Some("2001-01-01T01:01:01+00:00"),
+        );
+        check_parse_and_eval("if(1h, 1h, 2h)", None);
+        check_parse_and_eval("map([1h, 3h], x -> if(x > 2h, x, 2h))", Some("2h\n3h"));
+    }
+
+    #[test]
+    fn test_eval_func_fmt() {
+        check_parse_and_eval("fmt(now, \"%Y-%m-%d\")", Some("2001-01-01"));
+        check_parse_and_eval(
+            "now |> full_day |> fmt(\"%Y-%m-%d %H:%M\")",
+            Some("2001-01-01 00:00"),
+        );
+        check_parse_and_eval(
+            "map([now, now + 1d], x -> fmt(x, \"%a\"))",
+            Some("Mon\nTue"),
+        );
+        check_parse_and_eval("fmt(1h, \"%Y\")", None);
+        check_parse_and_eval("fmt(now, 1)", None);
+        check_parse_and_eval("fmt(now, \"%Q\")", None);
+    }
+
+    #[test]
+    fn test_eval_pipeline() {
+        check_parse_and_eval("now |> full_day", Some("2001-01-01T00:00:00+00:00"));
+        check_parse_and_eval(
+            "now + 1d |> full_day |> add_months(1)",
+            Some("2001-02-02T00:00:00+00:00"),
+        );
+        check_parse_and_eval("now..now + 1h|>shift(1d)|>duration", Some("1h"));
+        check_parse_and_eval("now |> bucket_index(1h, now - 1d)", Some("24"));
+        check_parse_and_eval("now |> overlap(now + 1h, now + 30m, now + 2h)", Some("30m"));
+        check_parse_and_eval(
+            "map([now], x -> x |> full_day)",
+            Some("2001-01-01T00:00:00+00:00"),
+        );
+        check_parse_and_eval("seq(now, now + 3h, 1h) |> span", Some("2h"));
+        check_parse_and_eval("now |> no_such_function", None);
+        check_parse_and_eval("now |>", None);
+    }
+
+    #[test]
+    fn test_eval_func_overlap() {
+        check_parse_and_eval(
+            "overlap(2024-06-01T08:00:00Z, 2024-06-01T16:00:00Z, 2024-06-01T14:00:00Z, 2024-06-01T22:00:00Z)",
+            Some("2h"),
+        );
+        check_parse_and_eval("overlap(now, now + 1h, now + 1h, now + 2h)", Some("0s"));
+        check_parse_and_eval(
+            "intersects(now, now + 1h, now + 30m, now + 2h)",
+            Some("true"),
+        );
+        check_parse_and_eval(
+            "intersects(now, now + 1h, now + 1h, now + 2h)",
+            Some("false"),
+        );
+        check_parse_and_eval("overlap(now + 1h, now, now, now + 1h)", None);
+        check_parse_and_eval("overlap(now, 1h, now, now + 1h)", None);
+    }
+
+    #[test]
+    fn test_eval_interval() {
+        check_parse_and_eval(
+            "now..now + 1d",
+            Some("2001-01-01T01:01:01+00:00..2001-01-02T01:01:01+00:00"),
+        );
+        check_parse_and_eval(
+            "interval(now, now + 1h)",
+            Some("2001-01-01T01:01:01+00:00..2001-01-01T02:01:01+00:00"),
+        );
+        check_parse_and_eval("duration(now - 1h .. now + 30m)", Some("1h30m"));
+        check_parse_and_eval(
+            "shift(now..now + 1h, 1d)",
+            Some("2001-01-02T01:01:01+00:00..2001-01-02T02:01:01+00:00"),
+        );
+        check_parse_and_eval("contains(now..now + 1h, now)", Some("true"));
+        check_parse_and_eval("contains(now..now + 1h, now + 1h)", Some("false"));
+        check_parse_and_eval("contains(now..now + 1d, now + 1h..now + 2h)", Some("true"));
+        check_parse_and_eval("now + 1h..now", None);
+        check_parse_and_eval("now..now + 1h..now + 2h", None);
+        check_parse_and_eval("now..1h", None);
+        check_parse_and_eval("(now..now + 1h) + 1h", None);
+    }
+
+    #[test]
+    fn test_eval_func_mtime() {
+        let path = std::env::temp_dir().join(format!("tscalc-mtime-{}", std::process::id()));
+        let file = std::fs::File::create(&path).unwrap();
+        let modified = std::time::UNIX_EPOCH + std::time::Duration::from_secs(946684800);
+        file.set_modified(modified).unwrap();
+        let input = format!("mtime({:?}) + 1h", path.to_str().unwrap());
+        check_parse_and_eval(&input, Some("2000-01-01T01:00:00+00:00"));
+        let input = format!("now - ctime({:?}) + now", path.to_str().unwrap());
+        let result = parse_and_eval(
+            &input,
+            &OutputOptions::new(UTC),
+            &EvalContext::new(now()),
+            Dialect::Tscalc,
+        );
+        assert!(result.is_ok(), "{:?}", result);
+        std::fs::remove_file(&path).unwrap();
+        check_parse_and_eval("mtime(\"/nonexistent/tscalc\")", None);
+    }
+
+    #[cfg(all(feature = "uptime", target_os = "linux"))]
+    #[test]
+    fn test_eval_uptime() {
+        let result = parse_and_eval(
+            &"now - uptime".to_string(),
+            &OutputOptions::new(UTC),
+            &EvalContext::new(now()),
+            Dialect::Tscalc,
+        );
+        assert!(result.is_ok(), "{:?}", result);
+        assert!(!result.unwrap().starts_with('-'));
+    }
+
+    #[test]
+    fn test_eval_grafana_relative_time() {
+        check_parse_and_eval("now-1h", Some("2001-01-01T00:01:01+00:00"));
+        check_parse_and_eval("now-7d/d", Some("2000-12-25T00:00:00+00:00"));
+        check_parse_and_eval("now/d + 1h", Some("2001-01-01T01:00:00+00:00"));
+        check_parse_and_eval("now-1M/M", Some("2000-12-01T00:00:00+00:00"));
+        check_parse_and_eval("now+1y-2w/w", Some("2001-12-17T00:00:00+00:00"));
+        check_parse_and_eval("now/m", Some("2001-01-01T01:01:00+00:00"));
+        check_parse_and_eval("now-1x", None);
+    }
+
+    #[test]
+    fn test_eval_dialects() {
+        let check = |input: &str, expected: Option<&str>, dialect: Dialect| {
+            let result = parse_and_eval(
+                &input.to_string(),
+                &OutputOptions::new(UTC),
+                &EvalContext::new(now()),
+                dialect,
+            );
+            match expected {
+                Some(expected) => assert_eq!(result, Ok(expected.to_string()), "{}", input),
+                None => assert!(result.is_err(), "{} {:?}", input, result),
+            }
+        };
+        check("-24h@h", Some("2000-12-31T01:00:00+00:00"), Dialect::Splunk);
+        check("@d+8h", Some("2001-01-01T08:00:00+00:00"), Dialect::Splunk);
+        check("-1q@q", Some("2000-10-01T00:00:00+00:00"), Dialect::Splunk);
+        // 2001-01-01 is Monday.
+        check("@w0", Some("2000-12-31T00:00:00+00:00"), Dialect::Splunk);
+        check("now - 1h", None, Dialect::Splunk);
+        check(
+            "now-1d/d",
+            Some("2000-12-31T00:00:00+00:00"),
+            Dialect::Elastic,
+        );
+        check(
+            "2024-01-31T10:00:00Z||+1M/d",
+            Some("2024-02-29T00:00:00+00:00"),
+            Dialect::Elastic,
+        );
+        check("-1d", None, Dialect::Elastic);
+    }
+
+    #[test]
+    fn test_output_rfc3339_options() {
+        let check = |input: &str, expected: &str, options: &OutputOptions| {
+            let result = parse_and_eval(
+                &input.to_string(),
+                options,
+                &EvalContext::new(now()),
+                Dialect::Tscalc,
+            );
+            assert_eq!(result, Ok(expected.to_string()));
+        };
+        let options = OutputOptions {
+            utc_z: true,
+            space_sep: true,
+            frac_digits: Some(3),
+            ..OutputOptions::new(UTC)
+        };
+        check("now", "2001-01-01 01:01:01.000Z", &options);
+        let options = OutputOptions {
+            utc_z: true,
+            frac_digits: Some(6),
+            ..OutputOptions::new(Tz::from(chrono_tz::Europe::Warsaw))
+        };
+        check("now + 1ms", "2001-01-01T02:01:01.001000+01:00", &options);
+        let options = OutputOptions {
+            frac_digits: Some(0),
+            ..OutputOptions::new(UTC)
+        };
+        check("now + 1ms", "2001-01-01T01:01:01+00:00", &options);
+        let options = OutputOptions {
+            frac_digits: Some(5),
+            ..OutputOptions::new(UTC)
+        };
+        check(
+            "now + 123456789ns",
+            "2001-01-01T01:01:01.12345+00:00",
+            &options,
+        );
+        let options = OutputOptions {
+            utc_z: true,
+            ..OutputOptions::new(UTC)
+        };
+        check("now + 1ms", "2001-01-01T01:01:01.001Z", &options);
+    }
+
+    #[test]
+    fn test_output_ixdtf() {
+        let check = |input: &str, expected: &str, options: &OutputOptions| {
+            let result = parse_and_eval(
+                &input.to_string(),
+                options,
+                &EvalContext::new(now().with_timezone(&Tz::from(chrono_tz::Europe::Warsaw))),
+                Dialect::Tscalc,
+            );
+            assert_eq!(result, Ok(expected.to_string()), "{}", input);
+        };
+        let options = OutputOptions {
+            format: OutputFormat::Ixdtf,
+            ..OutputOptions::new(UTC)
+        };
+        check("now", "2001-01-01T02:01:01+01:00[Europe/Warsaw]", &options);
+        check(
+            "2024-05-17 13:00 Asia/Tokyo + 1500ms",
+            "2024-05-17T13:00:01.500+09:00[Asia/Tokyo]",
+            &options,
+        );
+        check(
+            "2024-05-17T13:00:00Z",
+            "2024-05-17T13:00:00+00:00[UTC]",
+            &options,
+        );
+        check("1h", "1h", &options);
+        // The output parses back to the same date-time and zone.
+        for input in [
+            "2024-05-17T13:00:00[Europe/Warsaw]",
+            "2024-11-03T01:30:00-05:00[America/New_York] + 1h",
+        ] {
+            let output = parse_and_eval(
+                &input.to_string(),
+                &options,
+                &EvalContext::new(now()),
+                Dialect::Tscalc,
+            )
+            .unwrap();
+            check(&output, &output, &options);
+        }
+        let options = OutputOptions {
+            utc_z: true,
+            space_sep: true,
+            frac_digits: Some(3),
+            ..options
+        };
+        check(
+            "2024-05-17T13:00:00[Europe/Warsaw]",
+            "2024-05-17T13:00:00.000+02:00[Europe/Warsaw]",
+            &options,
+        );
+    }
+
+    #[test]
+    fn test_wait() {
+        let ctx = EvalContext::new(now());
+        let wait = crate::Wait::Until("now - 1h".to_string());
+        assert_eq!(crate::wait_until(&wait, &ctx, Dialect::Tscalc), Ok(()));
+        let wait = crate::Wait::For("1ms".to_string());
+        assert_eq!(crate::wait_until(&wait, &ctx, Dialect::Tscalc), Ok(()));
+        let wait = crate::Wait::For("-1h".to_string());
+        assert_eq!(crate::wait_until(&wait, &ctx, Dialect::Tscalc), Ok(()));
+        let wait = crate::Wait::Until("1h".to_string());
+        assert!(crate::wait_until(&wait, &ctx, Dialect::Tscalc).is_err());
+        let wait = crate::Wait::For("now".to_string());
+        assert!(crate::wait_until(&wait, &ctx, Dialect::Tscalc).is_err());
+    }
+
+    #[test]
+    fn test_plan() {
+        let ctx = EvalContext::new(now());
+        let zones = [
+            Tz::from(chrono_tz::US::Pacific),
+            Tz::from(chrono_tz::Europe::Warsaw),
+            Tz::from(chrono_tz::Asia::Tokyo),
+        ];
+        let input = "2024-06-01T16:00:00Z".to_string();
+        assert_eq!(
+            plan(&input, &zones, &ctx, Dialect::Tscalc),
+            Ok([
+                "US/Pacific     2024-06-01 09:00 Sat -07:00",
+                "Europe/Warsaw  2024-06-01 18:00 Sat +02:00",
+                "Asia/Tokyo     2024-06-02 01:00 Sun +09:00",
+            ]
+            .join("\n"))
+        );
+        assert_eq!(
+            plan(&input, &[], &ctx, Dialect::Tscalc),
+            Ok("UTC  2024-06-01 16:00 Sat +00:00".to_string())
+        );
+        assert!(plan(&"1h".to_string(), &zones, &ctx, Dialect::Tscalc).is_err());
+    }
+
+    #[test]
+    fn test_cron_preview() {
+        let tz = Tz::from(chrono_tz::Europe::Warsaw);
+        let options = OutputOptions {
+            now: now().with_timezone(&tz),
+            ..OutputOptions::new(tz)
+        };
+        // 2001-01-01 is Monday, and 01:01:01Z is 02:01:01 in Warsaw.
+        assert_eq!(
+            crate::cron_preview("*/5 * * * *", 3, &options),
+            Ok([
+                "2001-01-01T02:05:00+01:00",
+                "2001-01-01T02:10:00+01:00",
+                "2001-01-01T02:15:00+01:00",
+            ]
+            .join("\n"))
+        );
+        let options = OutputOptions {
+            format: OutputFormat::EpochSeconds,
+            ..options
+        };
+        assert_eq!(
+            crate::cron_preview("0 9 * * SAT,SUN", 2, &options),
+            Ok(["978768000.000", "978854400.000"].join("\n"))
+        );
+        assert!(crate::cron_preview("* * *", 3, &options).is_err());
+        assert!(crate::cron_preview("0 0 30 2 *", 3, &options).is_err());
+    }
+
+    #[test]
+    fn test_assert() {
+        let ctx = EvalContext::new(now());
+        let options = OutputOptions::new(UTC);
+        let check = |input: &str| assert_expr(&input.to_string(), &options, &ctx, Dialect::Tscalc);
+        assert_eq!(
+            check("full_day(2024-05-17T10:00:00Z) == 2024-05-17T00:00:00Z"),
+            Ok(String::new())
+        );
+        assert_eq!(check("(now > epoch)"), Ok(String::new()));
+        assert_eq!(
+            check("full_hour(2024-05-17T10:30:00Z) == 2024-05-17T10:30:00Z"),
+            Err([
+                "assertion failed: full_hour(2024-05-17T10:30:00Z) == 2024-05-17T10:30:00Z",
+                "-  left: 2024-05-17T10:00:00+00:00",
+                "+ right: 2024-05-17T10:30:00+00:00",
+            ]
+            .join("\n"))
+        );
+        assert_eq!(
+            check("contains(now..now + 1h, now - 1s)"),
+            Err("assertion failed: contains(now..now + 1h, now - 1s)".to_string())
+        );
+        assert!(check("now").is_err());
+        assert!(check("now ==").is_err());
+    }
+
+    #[test]
+    fn test_explain() {
+        let ctx = EvalContext::new(now());
+        let options = OutputOptions::new(UTC);
+        let check = |input: &str| explain(&input.to_string(), &options, &ctx, Dialect::Tscalc);
+        assert_eq!(
+            check("2024-05-17T13:00:00Z - full_day(2024-05-17T13:00:00Z) + epoch"),
+            Ok([
+                "Tokens:",
+                "  2024-05-17T13:00:00Z  date-time",
+                "  -                     operator",
+                "  full_day              function",
+                "  (                     punctuation",
+                "  2024-05-17T13:00:00Z  date-time",
+                "  )                     punctuation",
+                "  +                     operator",
+                "  epoch                 keyword",
+                "Grouping:",
+                "  (2024-05-17T13:00:00Z - full_day(2024-05-17T13:00:00Z)) + epoch",
+                "Steps:",
+                "    full_day(2024-05-17T13:00:00+00:00) = 2024-05-17T00:00:00+00:00",
+                "  2024-05-17T13:00:00+00:00 - 2024-05-17T00:00:00+00:00 = 13h",
+                "  13h + 1970-01-01T00:00:00+00:00 = 1970-01-01T13:00:00+00:00",
+                "Result:",
+                "  1970-01-01T13:00:00+00:00",
+            ]
+            .join("\n"))
+        );
+        let explained = check("now + now").unwrap_err();
+        assert!(explained.contains("Steps:\n  none\nError:\n  now + now\n  ^^^   ^^^\n"));
+        assert!(check("now +").is_err());
+    }
+
+    #[test]
+    fn test_echo_input() {
+        let options = OutputOptions::new(UTC);
+        let echo = |input: &str, epoch_unit: EpochUnit| {
+            let ctx = EvalContext {
+                epoch_unit,
+                epochs: Some(Rc::default()),
+                ..EvalContext::new(now())
+            };
+            let input = input.to_string();
+            let node = parse_node(&input, Dialect::Tscalc, false, true, false).unwrap();
+            let _ = evaluate(node.clone(), &ctx);
+            echo_input(&input, &node, &ctx, &options)
+        };
+        assert_eq!(
+            echo("1716000000 + 1h30m - 13:00", EpochUnit::Seconds),
+            vec![
+                "1716000000 → 2024-05-18T02:40:00+00:00 (epoch seconds)",
+                "1h30m → 1h30m (duration)",
+                "13:00 → 2001-01-01T13:00:00+00:00 (time today in UTC)",
+            ]
+        );
+        assert_eq!(
+            echo(
+                "[1716000000123, 5/17/24, May 17th, 2024 1:00 PM Europe/Warsaw]",
+                EpochUnit::Auto
+            ),
+            vec![
+                "1716000000123 → 2024-05-18T02:40:00.123+00:00 (epoch milliseconds, guessed)",
+                "5/17/24 → 2024-05-17T00:00:00+00:00 (numeric date in UTC)",
+                "May 17th, 2024 1:00 PM Europe/Warsaw → 2024-05-17T11:00:00+00:00 (date-time in Europe/Warsaw)",
+            ]
+        );
+        assert_eq!(
+            echo("from_snowflake(175928847299117063)", EpochUnit::Seconds),
+            vec!["175928847299117063 → 175928847299117063 (number)"]
+        );
+        assert_eq!(echo("now", EpochUnit::Seconds), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_warnings() {
+        let ctx = EvalContext {
+            warnings: Some(Rc::default()),
+            ..EvalContext::new(now())
+        };
+        let input = "2024-05-17 13:00 PST - 5/17/24".to_string();
+        parse_and_eval_result(&input, &ctx, Dialect::Tscalc).unwrap();
+        assert_eq!(
+            take_warnings(&input, Dialect::Tscalc, &ctx),
+            vec![
+                "time zone abbreviation \"PST\" is taken as -08:00 of America/Los_Angeles, use the zone name to be sure",
+                "two-digit year in \"5/17/24\" is taken as 2024, use four digits to be sure",
+            ]
+        );
+        assert_eq!(
+            take_warnings(&input, Dialect::Tscalc, &ctx),
+            vec!["time zone abbreviation \"PST\" is taken as -08:00 of America/Los_Angeles, use the zone name to be sure"]
+        );
+        let ctx = EvalContext::new(now());
+        parse_and_eval_result(&input, &ctx, Dialect::Tscalc).unwrap();
+        assert_eq!(
+            take_warnings(&input, Dialect::Tscalc, &ctx),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let capabilities = capabilities();
+        assert!(
+            capabilities.starts_with("{\"schema\":1,"),
+            "{}",
+            capabilities
+        );
+        assert!(capabilities.contains("{\"name\":\"full_day\",\"arity\":[1]}"));
+        assert!(capabilities.contains("{\"name\":\"bucket_index\",\"arity\":[2,3]}"));
+        assert!(capabilities.contains("{\"name\":\"epoch_seconds\",\"flag\":\"-s\"}"));
+        for name in Registry::builtin().names() {
+            assert!(capabilities.contains(&format!("{{\"name\":\"{}\",", name)));
+        }
+        // The names are the ones accepted by the flags and in the expressions.
+        let list = |key: &str| -> Vec<String> {
+            let start = capabilities.find(&format!("\"{}\":[", key)).unwrap() + key.len() + 4;
+            let end = start + capabilities[start..].find(']').unwrap();
+            capabilities[start..end]
+                .split(',')
+                .map(|name| name.trim_matches('"').to_string())
+                .collect()
+        };
+        for unit in list("epoch_units") {
+            assert!(unit.parse::<EpochUnit>().is_ok(), "{}", unit);
+        }
+        for dialect in list("dialects") {
+            assert!(dialect.parse::<Dialect>().is_ok(), "{}", dialect);
+        }
+        for order in list("date_orders") {
+            assert!(order.parse::<DateOrder>().is_ok(), "{}", order);
+        }
+        for unit in list("relative_units") {
+            let relative = format!("now-1{}", unit);
+            assert!(grafana_to_node(&relative).is_ok(), "{}", relative);
+        }
+        for unit in list("duration_units") {
+            let duration = format!("1{}", unit);
+            assert!(
+                chrono::TimeDelta::from_short_format(&duration).is_ok(),
+                "{}",
+                duration
+            );
+        }
+    }
+
+    #[test]
+    fn test_json_errors() {
+        let ctx = EvalContext::new(now());
+        let mut options = OutputOptions::new(UTC);
+        options.json_errors = true;
+        let eval =
+            |input: &str| parse_and_eval(&input.to_string(), &options, &ctx, Dialect::Tscalc);
+        assert_eq!(
+            eval("now - 1h"),
+            Ok("2001-01-01T00:01:01+00:00".to_string())
+        );
+        assert_eq!(
+            eval("\"ł\" + 1x"),
+            Err("{\"kind\":\"parse\",\"input\":\"\\\"ł\\\" + 1x\",\"position\":7,\"expected\":[\"end of input\"],\"message\":\"not all input matched\"}".to_string())
+        );
+        assert_eq!(
+            eval("full_day(1h)"),
+            Err("{\"kind\":\"eval\",\"input\":\"full_day(1h)\",\"position\":null,\"expected\":[],\"message\":\"the first argument to full_day should be datetime, was: TimeDelta(TimeDelta { secs: 3600, nanos: 0 })\"}".to_string())
+        );
+        let message = eval(")").expect_err("expected error");
+        assert!(
+            message.contains("\"position\":0,\"expected\":[\"date-time\","),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_check_script() {
+        let ctx = EvalContext::new(now());
+        let options = OutputOptions::new(UTC);
+        let script = [
+            "# rounding",
+            "full_day(2024-05-17T10:00:00Z) => 2024-05-17T00:00:00+00:00",
+            "",
+            "1h + 30m => 1h30m",
+        ]
+        .join("\n");
+        assert_eq!(
+            check_script(&script, &options, &ctx, Dialect::Tscalc),
+            Ok("2 passed".to_string())
+        );
+        let script = [
+            "1h + 30m => 1h30m",
+            "full_hour(2024-05-17T10:30:00Z) => 2024-05-17T10:30:00+00:00",
+            "1h + 30m",
+            "foo => 1h",
+        ]
+        .join("\n");
+        let result = check_script(&script, &options, &ctx, Dialect::Tscalc);
+        let message = result.expect_err("expected mismatches");
+        assert!(message.starts_with(
+            &[
+                "line 2: full_hour(2024-05-17T10:30:00Z)",
+                "- expected: 2024-05-17T10:30:00+00:00",
+                "+   actual: 2024-05-17T10:00:00+00:00",
+                "line 3: expected \"expr => expected\"",
+                "line 4: foo",
+                "- expected: 1h",
+                "+   actual: error: ",
+            ]
+            .join("\n")
+        ));
+        assert!(message.ends_with("1 passed, 3 failed"));
+    }
+
+    #[test]
+    fn test_duration_out_of_range() {
+        check_parse_and_eval("now + 292y", Some("2292-10-22T01:01:01+00:00"));
+        check_parse_and_eval("now + 300y", Some("2300-10-21T01:01:01+00:00"));
+        check_parse_and_eval("-292471208y", Some("-106751990920d"));
+        check_parse_and_eval("1000000:00:00", Some("41666d16h"));
+        for input in ["300000000y", "now - 1 day 300000000 years"] {
+            check_parse_and_eval(input, None);
+        }
+        assert_eq!(
+            parse_node(
+                &"now + 300000000y - 1h".to_string(),
+                Dialect::Tscalc,
+                false,
+                false,
+                false
+            )
+            .map(|_| ()),
+            Err("now + 300000000y - 1h\n______^^^^^^^^^^\n\
+                duration \"300000000y\" is out of range, the limit is about 292 million years"
+                .to_string())
+        );
+    }
+
+    #[test]
+    fn test_far_duration_round_trip() {
+        let ctx = EvalContext::new(now());
+        let delta = parse_and_eval(
+            &"2500-01-01T00:00:00.5Z - 2000-01-01T00:00:00Z".to_string(),
+            &OutputOptions::new(UTC),
+            &ctx,
+            Dialect::Tscalc,
+        )
+        .unwrap();
+        assert_eq!(delta, "182622d500ms");
+        check_parse_and_eval(
+            &format!("2000-01-01T00:00:00Z + {}", delta),
+            Some("2500-01-01T00:00:00.500+00:00"),
+        );
+    }
+
+    #[test]
+    fn test_eval_clock_duration() {
+        check_parse_and_eval("01:30:00 + 15m", Some("1h45m"));
+        check_parse_and_eval("now + 90:00", Some("2001-01-01T02:31:01+00:00"));
+        check_parse_and_eval("now - 00:00:01.5", Some("2001-01-01T01:00:59.500+00:00"));
+        check_parse_and_eval("01:61:00", None);
+        let output_options = OutputOptions {
+            delta_clock: true,
+            ..OutputOptions::new(UTC)
+        };
+        let ctx = EvalContext::new(now());
+        assert_eq!(
+            parse_and_eval(
+                &"1d + 30m".to_string(),
+                &output_options,
+                &ctx,
+                Dialect::Tscalc
+            ),
+            Ok("24:30:00".to_string())
+        );
+        assert_eq!(
+            parse_and_eval(
+                &"-500ms".to_string(),
+                &output_options,
+                &ctx,
+                Dialect::Tscalc
+            ),
+            Ok("-0:00:00.500".to_string())
+        );
+    }
+
+    #[test]
+    fn test_eval_named_month_date() {
+        check_parse_and_eval("17 May 2024", Some("2024-05-17T00:00:00+00:00"));
+        check_parse_and_eval("17. may 2024 + 13h", Some("2024-05-17T13:00:00+00:00"));
+        check_parse_and_eval("1 Sep 2024", Some("2024-09-01T00:00:00+00:00"));
+        check_parse_and_eval("31 Feb 2024", None);
+        check_parse_and_eval("17 maja 2024", None);
+        check_parse_and_eval("17 May 20245", None);
+    }
+
+    #[test]
+    fn test_eval_named_month_date_prose() {
+        check_parse_and_eval(
+            "May 17th, 2024 1:00 PM UTC",
+            Some("2024-05-17T13:00:00+00:00"),
+        );
+        check_parse_and_eval("May 17, 2024", Some("2024-05-17T00:00:00+00:00"));
+        check_parse_and_eval("21st May 2024", Some("2024-05-21T00:00:00+00:00"));
+        check_parse_and_eval(
+            "May 17th, 2024 13:00 Europe/Warsaw",
+            Some("2024-05-17T11:00:00+00:00"),
+        );
+        check_parse_and_eval(
+            "[17 May 2024, 13:00]",
+            Some("2024-05-17T00:00:00+00:00\n2001-01-01T13:00:00+00:00"),
+        );
+        check_parse_and_eval("Jan 2nd 2024 3pm", Some("2024-01-02T15:00:00+00:00"));
+        check_parse_and_eval("May 17 2024 13:00:00", Some("2024-05-17T13:00:00+00:00"));
+        check_parse_and_eval(
+            "17 May 2024 13:00:05.25 Europe/Warsaw",
+            Some("2024-05-17T11:00:05.250+00:00"),
+        );
+        check_parse_and_eval(
+            "2024-05-17 13:00:05.5",
+            Some("2024-05-17T13:00:05.500+00:00"),
+        );
+        // The comma after the year separates the elements of a list, so the time cannot follow it.
+        check_parse_and_eval("May 17th, 2024, 1:00 PM", None);
+        check_parse_and_eval(
+            "[May 17, 2024, May 18, 2024]",
+            Some("2024-05-17T00:00:00+00:00\n2024-05-18T00:00:00+00:00"),
+        );
+        check_parse_and_eval("May 17nd, 2024", None);
+        check_parse_and_eval("May 11st, 2024", None);
+        check_parse_and_eval("May 17th, 2024 1:00 PM PXT", None);
+    }
+
+    #[test]
+    fn test_eval_numeric_date() {
+        check_parse_and_eval("5/17/24", Some("2024-05-17T00:00:00+00:00"));
+        check_parse_and_eval("17.05.24 + 13h", Some("2024-05-17T13:00:00+00:00"));
+        check_parse_and_eval("2024/05/17", Some("2024-05-17T00:00:00+00:00"));
+        check_parse_and_eval("5/6/24", None);
+        check_parse_and_eval("5/6.24", None);
+        check_parse_and_eval("5/17/24/1", None);
+        let ctx = EvalContext {
+            date_order: Some(DateOrder::Dmy),
+            ..EvalContext::new(now())
+        };
+        check_parse_and_eval_ctx("5/6/24", Some("2024-06-05T00:00:00+00:00"), &UTC, &ctx);
+        check_parse_and_eval_ctx("5/17/24", None, &UTC, &ctx);
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let check = |input: &str| dry_run(&input.to_string(), Dialect::Tscalc, false);
+        assert_eq!(check("ntp_now - 1d"), Ok("date-time".to_string()));
+        assert_eq!(check("now - full_day(now)"), Ok("duration".to_string()));
+        assert_eq!(check("now-1d/d"), Ok("date-time".to_string()));
+        assert_eq!(check("now > now - 1d"), Ok("bool".to_string()));
+        assert_eq!(
+            check("now + now"),
+            Err("now + now\n^^^   ^^^\ncannot add date-time and date-time, \
+                subtract them for the duration between, or add a duration like + 1h"
+                .to_string())
+        );
+        assert!(check("now +").is_err());
+        assert_eq!(
+            check("full_day(1h)"),
+            Err(
+                "full_day(1h)\n         ^^\nargument 1 of full_day should be date-time, got duration"
+                    .to_string()
+            )
+        );
+        assert!(check("next_cron(\"bad\", now)").is_err());
+        assert_eq!(
+            dry_run(&"now-1d/d".to_string(), Dialect::Elastic, false),
+            Ok("date-time".to_string())
+        );
+    }
+
+    #[test]
+    fn test_eval_type_assertion() {
+        check_parse_and_eval("now - 1h :: datetime", Some("2001-01-01T00:01:01+00:00"));
+        check_parse_and_eval("now - full_day(now)::duration", Some("1h1m1s"));
+        check_parse_and_eval("now > now - 1d :: bool", Some("true"));
+        check_parse_and_eval("1700000000 :: datetime", Some("2023-11-14T22:13:20+00:00"));
+        check_parse_and_eval(
+            "map([now], x -> x) :: list",
+            Some("2001-01-01T01:01:01+00:00"),
+        );
+        check_parse_and_eval(
+            "max([now, now + 1h]) :: datetime",
+            Some("2001-01-01T02:01:01+00:00"),
+        );
+        check_parse_and_eval("if(now > epoch, now, 1h) :: duration", None);
+        check_parse_and_eval("now :: duration", None);
+        check_parse_and_eval("now :: dur", None);
+        assert_eq!(
+            parse_and_eval(
+                &"if(now > epoch, now, 1h) :: duration".to_string(),
+                &OutputOptions::new(UTC),
+                &EvalContext::new(now()),
+                Dialect::Tscalc
+            ),
+            Err("expected duration, got date-time".to_string())
+        );
+        assert_eq!(
+            dry_run(&"now - 1d :: duration".to_string(), Dialect::Tscalc, false),
+            Err("now - 1d :: duration\n^^^^^^^^\nexpected duration, got date-time".to_string())
+        );
+    }
+
+    #[test]
+    fn test_eval_oper_error() {
+        let eval = |input: &str| {
+            parse_and_eval_result(
+                &input.to_string(),
+                &EvalContext::new(now()),
+                Dialect::Tscalc,
+            )
+        };
+        assert_eq!(
+            eval("full_day(now) + now").map_err(|err| err
+                .lines()
+                .take(2)
+                .collect::<Vec<_>>()
+                .join("\n")),
+            Err("full_day(now) + now\n^^^^^^^^^^^^^   ^^^".to_string())
+        );
+        assert_eq!(
+            eval("1d - now"),
+            Err(
+                "1d - now\n^^   ^^^\ncannot subtract date-time from duration, \
+                subtract the duration from the date-time instead, like date-time - 1h"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_type_errors_with_spans() {
+        let check = |input: &str| dry_run(&input.to_string(), Dialect::Tscalc, false);
+        assert_eq!(
+            check("now + 1h + now"),
+            Err(
+                "now + 1h + now\n^^^^^^^^   ^^^\ncannot add date-time and date-time, \
+                subtract them for the duration between, or add a duration like + 1h"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            check("[now] > 1h"),
+            Err("[now] > 1h\n^^^^^   ^^\ncannot compare list of date-time > duration".to_string())
+        );
+    }
+
+    #[test]
+    fn test_eval_strict() {
+        let strict = EvalContext {
+            strict: true,
+            epoch_unit: EpochUnit::Auto,
+            date_order: Some(DateOrder::Dmy),
+            ..EvalContext::new(now())
+        };
+        let check = |input: &str, expected: Option<&str>| {
+            check_parse_and_eval_ctx(input, expected, &UTC, &strict)
+        };
+        check(
+            "2024-05-17 13:00 Europe/Warsaw",
+            Some("2024-05-17T11:00:00+00:00"),
+        );
+        check("17.05.2024 + 1h", Some("2024-05-17T01:00:00+00:00"));
+        check("2024-05-17 13:00 PST", None);
+        check("2024-05-17T13:00:00[pst]", None);
+        check("17.05.24", None);
+        check("1715950800", None);
+        let seconds = EvalContext {
+            epoch_unit: EpochUnit::Seconds,
+            ..strict.clone()
+        };
+        check_parse_and_eval_ctx(
+            "1715950800",
+            Some("2024-05-17T13:00:00+00:00"),
+            &UTC,
+            &seconds,
+        );
+        let no_order = EvalContext {
+            date_order: None,
+            ..strict
+        };
+        check_parse_and_eval_ctx("17.05.2024", None, &UTC, &no_order);
+        assert_eq!(
+            parse_and_eval(
+                &"13:00 PST".to_string(),
+                &OutputOptions::new(UTC),
+                &no_order,
+                Dialect::Tscalc
+            ),
+            Err("13:00 PST\n______^\ntime zone abbreviation \"PST\" is a guess of America/Los_Angeles, use the zone name in strict mode".to_string())
+        );
+    }
+
+    #[cfg(feature = "natural")]
+    #[test]
+    fn test_eval_natural() {
+        // 2001-01-01 is Monday.
+        check_parse_and_eval("midnight", Some("2001-01-02T00:00:00+00:00"));
+        check_parse_and_eval("noon tomorrow", Some("2001-01-02T12:00:00+00:00"));
+        check_parse_and_eval("next tuesday 3pm", Some("2001-01-02T15:00:00+00:00"));
+        check_parse_and_eval("Monday", Some("2001-01-01T00:00:00+00:00"));
+        check_parse_and_eval("next monday", Some("2001-01-08T00:00:00+00:00"));
+        check_parse_and_eval("sun", Some("2001-01-07T00:00:00+00:00"));
+        check_parse_and_eval("3 pm", Some("2001-01-01T15:00:00+00:00"));
+        check_parse_and_eval("at 1am", Some("2001-01-02T01:00:00+00:00"));
+        check_parse_and_eval("yesterday", Some("2000-12-31T00:00:00+00:00"));
+        check_parse_and_eval("in 2 hours", Some("2001-01-01T03:01:01+00:00"));
+        check_parse_and_eval("2 days ago at noon", Some("2000-12-30T12:00:00+00:00"));
+        check_parse_and_eval("tomorrow + 1h", None);
+        check_parse_and_eval("someday", None);
+        // The day does not slip when the clock changes, 2024-03-31 in Warsaw.
+        let tz = Tz::from(chrono_tz::Europe::Warsaw);
+        let ctx = EvalContext::new(
+            chrono::DateTime::parse_from_rfc3339("2024-03-30T23:30:00+01:00")
+                .unwrap()
+                .with_timezone(&tz),
+        );
+        check_parse_and_eval_ctx(
+            "tomorrow noon",
+            Some("2024-03-31T12:00:00+02:00"),
+            &tz,
+            &ctx,
+        );
+        check_parse_and_eval_ctx("next mon", Some("2024-04-01T00:00:00+02:00"), &tz, &ctx);
+        check_parse_and_eval_ctx("sunday", Some("2024-03-31T00:00:00+01:00"), &tz, &ctx);
+    }
+
+    #[cfg(feature = "locale")]
+    #[test]
+    fn test_locale() {
+        let check = |input: &str, locale: &str, format: &str, expected: &str| {
+            let locale = crate::parse_locale(locale).unwrap();
+            let options = OutputOptions {
+                datetime_format: format.to_string(),
+                locale,
+                ..OutputOptions::new(UTC)
+            };
+            let ctx = EvalContext {
+                locale,
+                ..EvalContext::new(now())
+            };
+            let result = parse_and_eval(&input.to_string(), &options, &ctx, Dialect::Tscalc);
+            assert_eq!(result, Ok(expected.to_string()), "{}", input);
+        };
+        check("17 maja 2024", "pl", "%Y-%m-%d", "2024-05-17");
+        check("17. Mai 2024", "de", "%Y-%m-%d", "2024-05-17");
+        check("17 May 2024", "de", "%Y-%m-%d", "2024-05-17");
+        check(
+            "17 May 2024",
+            "de",
+            "%A, %d. %B %Y",
+            "Freitag, 17. Mai 2024",
+        );
+        check("17 May 2024", "pl", "%d %B %Y", "17 maja 2024");
+    }
+
+    #[test]
+    fn test_eval_local_datetime() {
+        check_parse_and_eval("2024-05-17 13:00", Some("2024-05-17T13:00:00+00:00"));
+        check_parse_and_eval(
+            "2024-05-17 13:00:05 + 1h",
+            Some("2024-05-17T14:00:05+00:00"),
+        );
+        check_parse_and_eval("13:00", Some("2001-01-01T13:00:00+00:00"));
+        check_parse_and_eval("13:00 - now", Some("11h58m59s"));
+        check_parse_and_eval("2024-05-17 25:00", None);
+        // HH:MM:SS and MM:SS with minutes above 23 are still durations.
+        check_parse_and_eval("now + 00:13:00", Some("2001-01-01T01:14:01+00:00"));
+        // H:MM that is not a valid time of day is MM:SS duration.
+        check_parse_and_eval("01:30", Some("2001-01-01T01:30:00+00:00"));
+        check_parse_and_eval("23:59", Some("2001-01-01T23:59:00+00:00"));
+        check_parse_and_eval("24:00", Some("24m"));
+        check_parse_and_eval("13:60", None);
+        let ctx = EvalContext::new(now().with_timezone(&Tz::from(chrono_tz::Europe::Warsaw)));
+        check_parse_and_eval_ctx(
+            "2024-05-17 13:00",
+            Some("2024-05-17T11:00:00+00:00"),
+            &UTC,
+            &ctx,
+        );
+        check_parse_and_eval_ctx("09:30", Some("2001-01-01T08:30:00+00:00"), &UTC, &ctx);
+        // The time in the DST gap moves forward by the gap with jiff, and is an error otherwise.
+        let gap = cfg!(feature = "jiff").then_some("2024-03-31T01:30:00+00:00");
+        check_parse_and_eval_ctx("2024-03-31 02:30", gap, &UTC, &ctx);
+    }
+
+    #[test]
+    fn test_eval_local_datetime_12_hour() {
+        check_parse_and_eval("3pm", Some("2001-01-01T15:00:00+00:00"));
+        check_parse_and_eval("3:30 PM", Some("2001-01-01T15:30:00+00:00"));
+        check_parse_and_eval("12am", Some("2001-01-01T00:00:00+00:00"));
+        check_parse_and_eval("12:15pm", Some("2001-01-01T12:15:00+00:00"));
+        check_parse_and_eval("11:59:30 pm", Some("2001-01-01T23:59:30+00:00"));
+        check_parse_and_eval("2024-05-17 7:05am", Some("2024-05-17T07:05:00+00:00"));
+        check_parse_and_eval("2024-05-17 7:05am + 1h", Some("2024-05-17T08:05:00+00:00"));
+        check_parse_and_eval(
+            "2024-05-17 7:05 PM Europe/Warsaw",
+            Some("2024-05-17T17:05:00+00:00"),
+        );
+        check_parse_and_eval("3pm - 1am", Some("14h"));
+        check_parse_and_eval("13pm", None);
+        check_parse_and_eval("0am", None);
+        check_parse_and_eval("3pmx", None);
+        // Without AM or PM a number is still a timestamp and "3m" a duration.
+        check_parse_and_eval("3", Some("1970-01-01T00:00:03+00:00"));
+        check_parse_and_eval("now + 3m", Some("2001-01-01T01:04:01+00:00"));
+        let ctx = EvalContext::new(now().with_timezone(&Tz::from(chrono_tz::US::Eastern)));
+        check_parse_and_eval_ctx(
+            "2024-05-17 9:30 am",
+            Some("2024-05-17T13:30:00+00:00"),
+            &UTC,
+            &ctx,
+        );
+    }
+
+    #[test]
+    fn test_eval_datetime_with_zone_name() {
+        check_parse_and_eval(
+            "2024-05-17T13:00:00[Europe/Warsaw]",
+            Some("2024-05-17T11:00:00+00:00"),
+        );
+        check_parse_and_eval(
+            "2024-05-17T13:00:00+02:00[Europe/Warsaw]",
+            Some("2024-05-17T11:00:00+00:00"),
+        );
+        check_parse_and_eval(
+            "2024-05-17T11:00:00Z[!Europe/Warsaw]",
+            Some("2024-05-17T11:00:00+00:00"),
+        );
+        check_parse_and_eval("2024-05-17T13:00:00+01:00[Europe/Warsaw]", None);
+        check_parse_and_eval("2024-05-17T13:00:00[Europe/Warsw]", None);
+        check_parse_and_eval("2024-05-17T13:00:00", None);
+        check_parse_and_eval(
+            "2024-05-17 13:00 Europe/Warsaw",
+            Some("2024-05-17T11:00:00+00:00"),
+        );
+        // The abbreviation is its offset, also out of its season.
+        check_parse_and_eval("2024-05-17 13:00 PST", Some("2024-05-17T21:00:00+00:00"));
+        check_parse_and_eval("2024-07-01 13:00 PST", Some("2024-07-01T21:00:00+00:00"));
+        check_parse_and_eval("2024-01-01 13:00 PDT", Some("2024-01-01T20:00:00+00:00"));
+        check_parse_and_eval("2024-01-01 13:00 CEST", Some("2024-01-01T11:00:00+00:00"));
+        check_parse_and_eval_tz(
+            "2024-01-01 13:00 CEST",
+            Some("2024-01-01T13:00:00+02:00"),
+            &parse_tz("CEST").unwrap(),
+        );
+        check_parse_and_eval("2024-07-01T13:00:00-07:00[PST]", None);
+        check_parse_and_eval("2024-05-17 13:00 IST", None);
+        let gap = cfg!(feature = "jiff").then_some("2024-03-31T01:30:00+00:00");
+        check_parse_and_eval("2024-03-31 02:30 Europe/Warsaw", gap);
+        // The date of now in the zone, 2001-01-01T10:01:01 in Tokyo.
+        check_parse_and_eval("13:00 Asia/Tokyo", Some("2001-01-01T04:00:00+00:00"));
+        // The calendar functions work in the zone of the value.
+        check_parse_and_eval(
+            "full_day(2024-05-17 01:00 Europe/Warsaw)",
+            Some("2024-05-16T22:00:00+00:00"),
+        );
+        check_parse_and_eval(
+            "add_months(2024-03-17T12:00:00[Europe/Warsaw], 1)",
+            Some("2024-04-17T10:00:00+00:00"),
+        );
+    }
+
+    #[test]
+    fn test_eval_func_sub_second_truncation() {
+        check_parse_and_eval(
+            "full_second(2024-05-17T10:00:01.250Z)",
+            Some("2024-05-17T10:00:01+00:00"),
+        );
+        check_parse_and_eval(
+            "full_millis(2024-05-17T10:00:01.250999Z)",
+            Some("2024-05-17T10:00:01.250+00:00"),
+        );
+        check_parse_and_eval(
+            "full_micros(2024-05-17T10:00:01.250999999Z)",
+            Some("2024-05-17T10:00:01.250999+00:00"),
+        );
+        check_parse_and_eval("full_second(1h)", None);
+    }
+
+    #[test]
+    fn test_eval_func_ceil() {
+        check_parse_and_eval("ceil_day(now)", Some("2001-01-02T00:00:00+00:00"));
+        check_parse_and_eval("ceil_hour(now)", Some("2001-01-01T02:00:00+00:00"));
+        check_parse_and_eval("ceil_minute(now)", Some("2001-01-01T01:02:00+00:00"));
+        check_parse_and_eval("ceil_second(now)", Some("2001-01-01T01:01:01+00:00"));
+        check_parse_and_eval(
+            "ceil_hour(2024-05-17T10:00:00Z)",
+            Some("2024-05-17T10:00:00+00:00"),
+        );
+        check_parse_and_eval(
+            "ceil_millis(2024-05-17T10:00:01.250001Z)",
+            Some("2024-05-17T10:00:01.251+00:00"),
+        );
+        check_parse_and_eval(
+            "ceil_micros(2024-05-17T10:00:01.250000001Z)",
+            Some("2024-05-17T10:00:01.250001+00:00"),
+        );
+        check_parse_and_eval("ceil_day(1h)", None);
+    }
+
+    #[test]
+    fn test_eval_subsec() {
+        check_parse_and_eval("subsec(2024-05-17T10:00:01.250Z)", Some("250ms"));
+        check_parse_and_eval("subsec(2024-05-17T10:00:01.000001Z)", Some("1us"));
+        check_parse_and_eval("subsec(now)", Some("0s"));
+        check_parse_and_eval("subsec(1h)", None);
+    }
+
+    #[test]
+    fn test_eval_time_of_day() {
+        check_parse_and_eval("time(now)", Some("1h1m1s"));
+        check_parse_and_eval("time(23:30 + 9h)", Some("8h30m"));
+        check_parse_and_eval("time(1h)", None);
+        check_parse_and_eval("time_of_day(now)", Some("1h1m1s"));
+        check_parse_and_eval("full_day(now) + time_of_day(now) == now", Some("true"));
+        check_parse_and_eval("time_of_day(1h)", None);
+        let output_options = OutputOptions {
+            format: OutputFormat::TimeOnly,
+            ..OutputOptions::new(UTC)
+        };
+        let ctx = EvalContext::new(now());
+        assert_eq!(
+            parse_and_eval(
+                &"23:30 + 9h".to_string(),
+                &output_options,
+                &ctx,
+                Dialect::Tscalc
+            ),
+            Ok("08:30:00".to_string())
+        );
+    }
+
+    #[test]
+    fn test_rewrite_lines() {
+        assert_eq!(crate::rewrite_lines(0, "a"), "\r\x1b[Ja");
+        assert_eq!(crate::rewrite_lines(1, "b"), "\r\x1b[Jb");
+        assert_eq!(crate::rewrite_lines(3, "c"), "\x1b[2F\r\x1b[Jc");
+    }
+
+    #[test]
+    fn test_output_relative() {
+        let check = |input: &str, expected: &str, relative_units: usize| {
+            let options = OutputOptions {
+                format: crate::OutputFormat::Relative,
+                now: now(),
+                relative_units,
+                ..OutputOptions::new(UTC)
+            };
+            let result = parse_and_eval(
+                &input.to_string(),
+                &options,
+                &EvalContext::new(now()),
+                Dialect::Tscalc,
+            );
+            assert_eq!(result, Ok(expected.to_string()), "{}", input);
+        };
+        check("now - 3h", "3 hours ago", 1);
+        check("now - 3h40m", "4 hours ago", 1);
+        check("now + 2d3h", "in 2 days", 1);
+        check("now + 2d3h", "in 2 days 3 hours", 2);
+        check("now + 1d23h59m", "in 2 days", 2);
+        check("now + 1h", "in 1 hour", 3);
+        check("now + 500ms", "now", 1);
+        check("now + 400d", "in 1 year", 1);
+        check("1h", "1h", 1);
+    }
+
+    #[test]
+    fn test_output_negative_delta() {
+        let check =
+            |input: &str, format: crate::OutputFormat, delta_clock: bool, expected: &str| {
+                let options = OutputOptions {
+                    format,
+                    delta_clock,
+                    ..OutputOptions::new(UTC)
+                };
+                let result = parse_and_eval(
+                    &input.to_string(),
+                    &options,
+                    &EvalContext::new(now()),
+                    Dialect::Tscalc,
+                );
+                assert_eq!(result, Ok(expected.to_string()), "{}", input);
+            };
+        check("0s - 500ms", crate::OutputFormat::ISO, false, "-500ms");
+        check("0s - 500ms", crate::OutputFormat::ISO, true, "-0:00:00.500");
+        check(
+            "0s - 500ms",
+            crate::OutputFormat::EpochSeconds,
+            false,
+            "-0.500",
+        );
+        check(
+            "0s - 500ms",
+            crate::OutputFormat::FullEpochSeconds,
+            false,
+            "0",
+        );
+        check(
+            "0s - 1ns",
+            crate::OutputFormat::EpochSeconds,
+            false,
+            "0.000",
+        );
+        check("1h - 1h", crate::OutputFormat::ISO, true, "0:00:00");
+        check(
+            "min_time - max_time",
+            crate::OutputFormat::ISO,
+            false,
+            "-191491528d23h59m59s999ms999us999ns",
+        );
+    }
+
+    #[test]
+    fn test_output_delta_go() {
+        let options = OutputOptions {
+            delta_go: true,
+            ..OutputOptions::new(UTC)
+        };
+        let check = |input: &str, expected: &str| {
+            let result = parse_and_eval(
+                &input.to_string(),
+                &options,
+                &EvalContext::new(now()),
+                Dialect::Tscalc,
+            );
+            assert_eq!(result, Ok(expected.to_string()), "{}", input);
+        };
+        check("90m", "1h30m0s");
+        check("1.5µs", "1.5µs");
+        check("1h30m0s - 1.5s", "1h29m58.5s");
+        check("now", "2001-01-01T01:01:01+00:00");
+    }
+
+    #[test]
+    fn test_output_delta_unit() {
+        let check = |input: &str, options: &OutputOptions, expected: &str| {
+            let result = parse_and_eval(
+                &input.to_string(),
+                options,
+                &EvalContext::new(now()),
+                Dialect::Tscalc,
+            );
+            assert_eq!(result, Ok(expected.to_string()), "{}", input);
+        };
+        let options = OutputOptions {
+            delta_unit: Some("h"),
+            decimals: 2,
+            ..OutputOptions::new(UTC)
+        };
+        check("90m", &options, "1.50");
+        check("0s - 1m", &options, "-0.01");
+        check("now", &options, "2001-01-01T01:01:01+00:00");
+        let options = OutputOptions {
+            rounding: Rounding::HalfEven,
+            ..options
+        };
+        check("0s - 1m", &options, "-0.02");
+        // The unit applies to all the output formats.
+        let options = OutputOptions {
+            format: OutputFormat::EpochSeconds,
+            delta_unit: Some("m"),
+            decimals: 0,
+            ..OutputOptions::new(UTC)
+        };
+        check("[1h30m, 1d]", &options, "90\n1440");
+    }
+
+    #[test]
+    fn test_output_metric_suffix() {
+        let check = |input: &str, options: &OutputOptions, expected: &str| {
+            let result = parse_and_eval(
+                &input.to_string(),
+                options,
+                &EvalContext::new(now()),
+                Dialect::Tscalc,
+            );
+            assert_eq!(result, Ok(expected.to_string()), "{}", input);
+        };
+        let options = OutputOptions {
+            metric_suffix: true,
+            ..OutputOptions::new(UTC)
+        };
+        check("5400s", &options, "90m");
+        check("1h30m1s", &options, "5401s");
+        check("2w + 1y", &options, "379d");
+        check("now", &options, "2001-01-01T01:01:01+00:00");
+        let options = OutputOptions {
+            format: OutputFormat::EpochSeconds,
+            ..options
+        };
+        check("1500ms", &options, "1500ms");
+    }
+
+    #[test]
+    fn test_output_epoch_rounding() {
+        let check = |input: &str, format: OutputFormat, rounding: Rounding, expected: &str| {
+            let options = OutputOptions {
+                format,
+                rounding,
+                ..OutputOptions::new(UTC)
+            };
+            let result = parse_and_eval(
+                &input.to_string(),
+                &options,
+                &EvalContext::new(now()),
+                Dialect::Tscalc,
+            );
+            assert_eq!(result, Ok(expected.to_string()), "{}", input);
+        };
+        let (s, full) = (OutputFormat::EpochSeconds, OutputFormat::FullEpochSeconds);
+        check("1704067200.0015", s, Rounding::Trunc, "1704067200.001");
+        check("1704067200.0015", s, Rounding::HalfEven, "1704067200.002");
+        check("1704067200.0025", s, Rounding::HalfEven, "1704067200.002");
+        check("1704067202.5", full, Rounding::Trunc, "1704067202");
+        check("1704067202.5", full, Rounding::HalfEven, "1704067202");
+        check("1704067203.5", full, Rounding::HalfEven, "1704067204");
+        check("1704067203.9996", s, Rounding::HalfEven, "1704067204.000");
+        // Truncated date-times before 1970 are rounded down with -s, to the second they are in, and toward zero with -S.
+        check("1969-12-31T23:59:59.5Z", s, Rounding::Trunc, "-0.500");
+        check("1969-12-31T23:59:59.5Z", full, Rounding::Trunc, "0");
+        check("1969-12-31T23:59:58.5Z", full, Rounding::Trunc, "-1");
+        check("1969-12-31T23:59:59.5Z", full, Rounding::HalfEven, "0");
+        // Durations are truncated toward zero.
+        check("0s - 2500ms", full, Rounding::Trunc, "-2");
+        check("0s - 3500ms", full, Rounding::HalfEven, "-4");
+    }
+
+    #[test]
+    fn test_output_epoch_seconds_precision() {
+        let check = |input: &str, frac_digits: Option<usize>, expected: &str| {
+            let options = OutputOptions {
+                format: OutputFormat::EpochSeconds,
+                frac_digits,
+                ..OutputOptions::new(UTC)
+            };
+            let result = parse_and_eval(
+                &input.to_string(),
+                &options,
+                &EvalContext::new(now()),
+                Dialect::Tscalc,
+            );
+            assert_eq!(result, Ok(expected.to_string()), "{}", input);
+        };
+        check("2024-01-01T00:00:00.123456789Z", None, "1704067200.123");
+        check(
+            "2024-01-01T00:00:00.123456789Z",
+            Some(9),
+            "1704067200.123456789",
+        );
+        // Round trip through the number literal.
+        check("1704067200.123456789", Some(9), "1704067200.123456789");
+        check("1704067200.999999999", Some(6), "1704067200.999999");
+        check("1704067200.5", Some(0), "1704067200");
+        check("1704067200.123456789", Some(7), "1704067200.1234567");
+        check("1704067200.123456789", Some(1), "1704067200.1");
+        // Before 1970 the digits are dropped rounding down, within the second of the date-time.
+        check("1969-12-31T23:59:59.9999Z", None, "-0.001");
+        check("1969-12-31T23:59:59.9999Z", Some(9), "-0.000100000");
+        check(
+            "9999-12-31T23:59:59Z + 999999999ns",
+            Some(9),
+            "253402300799.999999999",
+        );
+        check("1ns - 2s", Some(9), "-1.999999999");
+    }
+
+    #[test]
+    fn test_eval_calendar_functions() {
+        check_parse_and_eval(
+            "add_months(2024-01-31T10:00:00Z, 1)",
+            Some("2024-02-29T10:00:00+00:00"),
+        );
+        check_parse_and_eval(
+            "add_months(2024-03-31T10:00:00Z, -13)",
+            Some("2023-02-28T10:00:00+00:00"),
+        );
+        check_parse_and_eval("add_months(now, 1.5)", None);
+        check_parse_and_eval(
+            "full_week(2024-05-19T10:00:00Z)",
+            Some("2024-05-13T00:00:00+00:00"),
+        );
+        check_parse_and_eval(
+            "full_month(2024-05-19T10:00:00Z)",
+            Some("2024-05-01T00:00:00+00:00"),
+        );
+        check_parse_and_eval(
+            "full_year(2024-05-19T10:00:00Z)",
+            Some("2024-01-01T00:00:00+00:00"),
+        );
+    }
+
+    #[test]
+    fn test_eval_func_next_cron_1() {
+        check_parse_and_eval(
+            "next_cron(\"*/15 * * * *\", now)",
+            Some("2001-01-01T01:15:00+00:00"),
+        );
+    }
+
+    #[test]
+    fn test_eval_func_prev_cron_1() {
+        check_parse_and_eval(
+            "prev_cron(\"0 0 * * *\", now) - 1d",
+            Some("2000-12-31T00:00:00+00:00"),
+        );
+    }
+
+    #[test]
+    fn test_eval_func_next_rrule_1() {
+        check_parse_and_eval(
+            "next_rrule(\"FREQ=WEEKLY;BYDAY=MO,WE\", full_day(now))",
+            Some("2001-01-03T00:00:00+00:00"),
+        );
+    }
+
+    #[test]
+    fn test_eval_func_next_systemd() {
+        // 2001-01-01 is Monday.
+        check_parse_and_eval(
+            "next_systemd(\"Mon..Fri 10:00\")",
+            Some("2001-01-01T10:00:00+00:00"),
+        );
+        check_parse_and_eval(
+            "next_systemd(\"Sat,Sun *-*-* 08:00\", now + 1d)",
+            Some("2001-01-06T08:00:00+00:00"),
+        );
+        check_parse_and_eval("next_systemd(\"Mon..Fry\")", None);
+    }
+
+    #[test]
+    fn test_eval_func_timespan() {
+        check_parse_and_eval("timespan(\"2weeks 1day\")", Some("15d"));
+        check_parse_and_eval(
+            "now + timespan(\"1h 30min\")",
+            Some("2001-01-01T02:31:01+00:00"),
+        );
+        check_parse_and_eval("timespan(\"1 fortnight\")", None);
+    }
+
+    #[test]
+    fn test_eval_func_cron_bad_expression() {
+        check_parse_and_eval("next_cron(\"* * *\", now)", None);
+    }
+
+    #[test]
+    fn test_eval_timestamp_1() {
+        check_parse_and_eval("1234567890.000", Some("2009-02-13T23:31:30+00:00"));
+    }
+    #[test]
+    fn test_eval_timestamp_2() {
+        check_parse_and_eval("0.0 + (0.0 - 1.0)", Some("1969-12-31T23:59:59+00:00"));
+    }
+
+    #[test]
+    fn test_eval_timestamp_3() {
+        check_parse_and_eval("0", Some("1970-01-01T00:00:00+00:00"));
+    }
+
+    #[test]
+    fn test_eval_timestamp_4() {
+        check_parse_and_eval("0.0", Some("1970-01-01T00:00:00+00:00"));
+    }
+
+    #[test]
+    fn test_eval_timestamp_5() {
+        check_parse_and_eval("0.1", Some("1970-01-01T00:00:00.100+00:00"));
+    }
+
+    #[test]
+    fn test_eval_timestamp_6() {
+        check_parse_and_eval("0.12345", Some("1970-01-01T00:00:00.123450+00:00"));
+    }
+
+    #[test]
+    fn test_eval_timestamp_scientific() {
+        check_parse_and_eval("1.7e9", Some("2023-11-14T22:13:20+00:00"));
+        check_parse_and_eval("17E8 + 1s", Some("2023-11-14T22:13:21+00:00"));
+    }
+
+    #[test]
+    fn test_eval_timestamp_underscores() {
+        check_parse_and_eval("1_700_000_000", Some("2023-11-14T22:13:20+00:00"));
+        check_parse_and_eval(
+            "1_700_000_000.500_000",
+            Some("2023-11-14T22:13:20.500+00:00"),
+        );
+    }
+
+    #[test]
+    fn test_eval_timestamp_hex_and_binary() {
+        check_parse_and_eval("0x65f00000", Some("2024-03-12T07:10:56+00:00"));
+        check_parse_and_eval("0b1111 + 1s", Some("1970-01-01T00:00:16+00:00"));
+        check_parse_and_eval("0xzz", None);
+    }
+
+    #[test]
+    fn test_eval_timestamp_negative_fraction() {
+        check_parse_and_eval("-1.5", Some("1969-12-31T23:59:58.500+00:00"));
+    }
+
+    #[test]
+    fn test_eval_timestamp_out_of_range() {
+        check_parse_and_eval("1e30", None);
+        check_parse_and_eval("99999999999999999999", None);
+    }
+
+    #[test]
+    fn test_eval_far_years() {
+        check_parse_and_eval(
+            "+12024-01-01T00:00:00Z",
+            Some("+12024-01-01T00:00:00+00:00"),
+        );
+        check_parse_and_eval("-0044-03-15 12:00", Some("-0044-03-15T12:00:00+00:00"));
+        check_parse_and_eval(
+            "0000-01-01T00:00:00Z - 1d",
+            Some("-0001-12-31T00:00:00+00:00"),
+        );
+        check_parse_and_eval(
+            "full_hour(-0044-03-15T12:30:00+01:00)",
+            Some("-0044-03-15T11:00:00+00:00"),
+        );
+        check_parse_and_eval(
+            "full_week(+12024-05-17T13:00:00Z)",
+            Some("+12024-05-13T00:00:00+00:00"),
+        );
+        check_parse_and_eval("+262143-01-01T00:00:00Z", None);
+        check_parse_and_eval("+262142-12-31T00:00:00Z + 1d", None);
+        check_parse_and_eval("-262143-01-01T00:00:00Z - 1d", None);
+        check_parse_and_eval("full_week(-262143-01-01T00:00:00Z)", None);
+        check_parse_and_eval("ceil_hour(+262142-12-31T23:30:00Z)", None);
+        let options = OutputOptions {
+            format: OutputFormat::EpochSeconds,
+            ..OutputOptions::new(UTC)
+        };
+        let result = parse_and_eval(
+            &"-262143-01-01T00:00:00Z".to_string(),
+            &options,
+            &EvalContext::new(now()),
+            Dialect::Tscalc,
+        );
+        assert_eq!(result, Ok("-8334601228800.000".to_string()));
+    }
+
+    #[test]
+    fn test_eval_epoch_unit() {
+        let check = |input: &str, expected: &str, epoch_unit: EpochUnit| {
+            let ctx = EvalContext {
+                epoch_unit,
+                ..EvalContext::new(now())
+            };
+            check_parse_and_eval_ctx(input, Some(expected), &UTC, &ctx)
+        };
+        check(
+            "1716000000123",
+            "2024-05-18T02:40:00.123+00:00",
+            EpochUnit::Millis,
+        );
+        check(
+            "1716000000123 + 1s",
+            "2024-05-18T02:40:01.123+00:00",
+            EpochUnit::Auto,
+        );
+        check(
+            "full_day(1716000000123456)",
+            "2024-05-18T00:00:00+00:00",
+            EpochUnit::Auto,
+        );
+        check(
+            "1716000000123456789 - 1716000000",
+            "123ms456us789ns",
+            EpochUnit::Auto,
+        );
+    }
+
+    #[test]
+    fn test_eval_missing_bracket_1() {
+        check_parse_and_eval("0.0 + (0.0 - 1.0", None);
+    }
+
+    #[test]
+    fn test_eval_duration_6() {
         check_parse_and_eval(
             "2000-01-01T00:00:00Z + 1m2s3ms",
             Some("2000-01-01T00:01:02.003+00:00"),
         );
     }
 
+    #[test]
+    fn test_eval_duration_mixed_order() {
+        check_parse_and_eval(
+            "2000-01-01T00:00:00Z + 30m1h",
+            Some("2000-01-01T01:30:00+00:00"),
+        );
+    }
+
+    #[test]
+    fn test_eval_duration_long_units() {
+        check_parse_and_eval(
+            "2000-01-01T00:00:00Z + 1 day 2 hours 3 minutes - 90 seconds",
+            Some("2000-01-02T02:01:30+00:00"),
+        );
+        check_parse_and_eval("[1hr, 5 mins, 1h 30 minutes]", Some("1h\n5m\n1h30m"));
+        check_parse_and_eval("1 h", None);
+    }
+
     #[test]
     fn test_eval_different_tz_1() {
         check_parse_and_eval_tz(
             "2000-01-02T00:00:00Z",
             Some("2000-01-01T19:00:00-05:00"),
-            &chrono_tz::US::Eastern,
+            &Tz::from(chrono_tz::US::Eastern),
         );
     }
 
@@ -309,17 +3817,21 @@ mod tests {
         check_parse_and_eval_tz(input, expected, &UTC)
     }
 
-    fn check_parse_and_eval_tz(input: &str, expected: Option<&str>, tz: &chrono_tz::Tz) {
+    fn check_parse_and_eval_tz(input: &str, expected: Option<&str>, tz: &Tz) {
+        check_parse_and_eval_ctx(input, expected, tz, &EvalContext::new(now()))
+    }
+
+    fn check_parse_and_eval_ctx(input: &str, expected: Option<&str>, tz: &Tz, ctx: &EvalContext) {
         let result = parse_and_eval(
             &input.to_string(),
-            crate::OutputFormat::ISO,
-            "%+",
-            tz,
-            now(),
+            &OutputOptions::new(*tz),
+            ctx,
+            Dialect::Tscalc,
         );
         let result_str = format!("{:?}", result);
         if let Some(expected) = expected {
-            let actual = result.expect(&format!("expected ok result, got: {}", result_str));
+            let actual =
+                result.unwrap_or_else(|_| panic!("expected ok result, got: {}", result_str));
             assert_eq!(actual, expected);
         } else {
             result.expect_err("expected err result");
@@ -1,8 +1,28 @@
+#[cfg(feature = "astro")]
+pub mod astro;
 pub mod builtin_funcs;
 pub mod core;
+pub mod cron;
 pub mod duration;
+pub mod epoch;
 pub mod eval;
+pub mod ids;
+pub mod locale;
+#[cfg(feature = "natural")]
+pub mod natural;
+#[cfg(feature = "ntp")]
+pub mod ntp;
+pub mod number;
+pub mod numeric_date;
 pub mod parsers;
+pub mod random;
+pub mod registry;
+pub mod relative;
+pub mod rrule;
+pub mod suggest;
+pub mod systemd;
+pub mod types;
+pub mod zone;
 
 pub use builtin_funcs::*;
 pub use core::*;
@@ -0,0 +1,106 @@
+//! Sunrise and sunset with the sunrise equation, see https://en.wikipedia.org/wiki/Sunrise_equation. The times are
+//! accurate to about a minute, enough for "time until sunset", not for navigation.
+use chrono::{DateTime, NaiveDate, Utc};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SunEvent {
+    Sunrise,
+    Sunset,
+}
+
+/// Julian date of 2000-01-01T12:00:00Z.
+const J2000: f64 = 2451545.0;
+/// Julian date of the Unix epoch.
+const JULIAN_UNIX_EPOCH: f64 = 2440587.5;
+/// Tilt of the Earth axis, in degrees.
+const OBLIQUITY: f64 = 23.4397;
+/// Elevation of the sun center at sunrise and sunset, in degrees, for the atmospheric refraction and the sun disc.
+const SUNRISE_ELEVATION: f64 = -0.833;
+
+/// Return the time of sunrise or sunset around the solar noon of the date at the coordinates in degrees, the latitude
+/// positive to the north and the longitude positive to the east. Fails in the polar day and night, when the sun does
+/// not cross the horizon.
+pub fn sun_event(
+    date: NaiveDate,
+    latitude: f64,
+    longitude: f64,
+    event: SunEvent,
+) -> Result<DateTime<Utc>, String> {
+    let days = date
+        .signed_duration_since(NaiveDate::from_ymd_opt(2000, 1, 1).unwrap())
+        .num_days() as f64;
+    let mean_solar_noon = days - longitude / 360.0;
+    let mean_anomaly = (357.5291 + 0.98560028 * mean_solar_noon).rem_euclid(360.0);
+    let m = mean_anomaly.to_radians();
+    let center = 1.9148 * m.sin() + 0.02 * (2.0 * m).sin() + 0.0003 * (3.0 * m).sin();
+    let ecliptic_longitude = (mean_anomaly + center + 180.0 + 102.9372)
+        .rem_euclid(360.0)
+        .to_radians();
+    let solar_transit =
+        J2000 + mean_solar_noon + 0.0053 * m.sin() - 0.0069 * (2.0 * ecliptic_longitude).sin();
+    let declination = (ecliptic_longitude.sin() * OBLIQUITY.to_radians().sin()).asin();
+    let latitude_rad = latitude.to_radians();
+    let cos_hour_angle = (SUNRISE_ELEVATION.to_radians().sin()
+        - latitude_rad.sin() * declination.sin())
+        / (latitude_rad.cos() * declination.cos());
+    if cos_hour_angle > 1.0 {
+        return Err(format!(
+            "the sun does not rise on {} at latitude {}",
+            date, latitude
+        ));
+    }
+    if cos_hour_angle < -1.0 {
+        return Err(format!(
+            "the sun does not set on {} at latitude {}",
+            date, latitude
+        ));
+    }
+    let half_day = cos_hour_angle.acos().to_degrees() / 360.0;
+    let julian = match event {
+        SunEvent::Sunrise => solar_transit - half_day,
+        SunEvent::Sunset => solar_transit + half_day,
+    };
+    let seconds = ((julian - JULIAN_UNIX_EPOCH) * 86400.0).round() as i64;
+    DateTime::from_timestamp(seconds, 0).ok_or(format!("{} out of range", date))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sun_event, SunEvent};
+    use chrono::{DateTime, NaiveDate};
+
+    fn assert_near(actual: DateTime<chrono::Utc>, expected: &str) {
+        let expected = DateTime::parse_from_rfc3339(expected).unwrap();
+        let diff = (actual - expected.to_utc()).num_seconds().abs();
+        assert!(diff <= 120, "{} is {}s from {}", actual, diff, expected);
+    }
+
+    #[test]
+    fn warsaw_midsummer() {
+        let date = NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+        let sunrise = sun_event(date, 52.2297, 21.0122, SunEvent::Sunrise).unwrap();
+        let sunset = sun_event(date, 52.2297, 21.0122, SunEvent::Sunset).unwrap();
+        assert_near(sunrise, "2024-06-21T02:14:00Z");
+        assert_near(sunset, "2024-06-21T19:01:00Z");
+    }
+
+    #[test]
+    fn western_longitude() {
+        // San Francisco, the sunset is after midnight UTC.
+        let date = NaiveDate::from_ymd_opt(2024, 12, 21).unwrap();
+        let sunrise = sun_event(date, 37.7749, -122.4194, SunEvent::Sunrise).unwrap();
+        let sunset = sun_event(date, 37.7749, -122.4194, SunEvent::Sunset).unwrap();
+        assert_near(sunrise, "2024-12-21T15:20:00Z");
+        assert_near(sunset, "2024-12-22T00:54:00Z");
+    }
+
+    #[test]
+    fn polar_day_and_night() {
+        let midsummer = NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+        let midwinter = NaiveDate::from_ymd_opt(2024, 12, 21).unwrap();
+        let err = sun_event(midsummer, 78.22, 15.65, SunEvent::Sunset).unwrap_err();
+        assert!(err.contains("does not set"), "{}", err);
+        let err = sun_event(midwinter, 78.22, 15.65, SunEvent::Sunrise).unwrap_err();
+        assert!(err.contains("does not rise"), "{}", err);
+    }
+}
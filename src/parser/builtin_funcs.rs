@@ -1,6 +1,23 @@
-use chrono::{DurationRound, TimeDelta};
+use super::zone::{from_local, start_of_day, trunc_local, Tz};
+use chrono::{
+    DateTime, Datelike, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Timelike,
+};
+use std::fmt::Write;
 
-use super::State;
+use super::{
+    cron::CronSchedule,
+    duration::ShortFormat,
+    epoch::AltEpoch,
+    ids::{
+        jwt_exp_timestamp, objectid_timestamp, snowflake_timestamp, uuid1_timestamp,
+        uuid7_timestamp, SNOWFLAKE_EPOCHS,
+    },
+    number::SCALE,
+    random::Random,
+    rrule::RecurrenceRule,
+    systemd::{parse_timespan, CalendarEvent},
+    State,
+};
 
 pub fn full_day(arg1: &State) -> Result<State, String> {
     let datetime = if let State::DateTime(datetime) = arg1 {
@@ -11,8 +28,37 @@ pub fn full_day(arg1: &State) -> Result<State, String> {
             arg1
         ));
     };
-    let truncated = datetime.duration_trunc(TimeDelta::days(1)).unwrap();
-    Ok(State::DateTime(truncated))
+    start_of_day(datetime).map(State::DateTime)
+}
+
+/// Return the time of day as duration since midnight, in the time zone of the date-time.
+pub fn time_of_day(func_name: &str, arg1: &State) -> Result<State, String> {
+    let datetime = if let State::DateTime(datetime) = arg1 {
+        datetime
+    } else {
+        return Err(format!(
+            "the first argument to {} should be datetime, was: {:?}",
+            func_name, arg1
+        ));
+    };
+    Ok(State::TimeDelta(
+        datetime.time().signed_duration_since(NaiveTime::MIN),
+    ))
+}
+
+/// Return the fraction of second of the date-time as duration, the part that truncation to whole seconds drops.
+pub fn subsec(arg1: &State) -> Result<State, String> {
+    let datetime = if let State::DateTime(datetime) = arg1 {
+        datetime
+    } else {
+        return Err(format!(
+            "the first argument to subsec should be datetime, was: {:?}",
+            arg1
+        ));
+    };
+    Ok(State::TimeDelta(TimeDelta::nanoseconds(
+        datetime.timestamp_subsec_nanos() as i64,
+    )))
 }
 
 pub fn full_hour(arg1: &State) -> Result<State, String> {
@@ -24,6 +70,1123 @@ pub fn full_hour(arg1: &State) -> Result<State, String> {
             arg1
         ));
     };
-    let truncated = datetime.duration_trunc(TimeDelta::hours(1)).unwrap();
+    let truncated = trunc_local(datetime, TimeDelta::hours(1))?;
+    Ok(State::DateTime(truncated))
+}
+
+pub fn full_minute(arg1: &State) -> Result<State, String> {
+    let datetime = if let State::DateTime(datetime) = arg1 {
+        datetime
+    } else {
+        return Err(format!(
+            "the first argument to full_minute should be datetime, was: {:?}",
+            arg1
+        ));
+    };
+    let truncated = trunc_local(datetime, TimeDelta::minutes(1))?;
     Ok(State::DateTime(truncated))
 }
+
+/// Truncate the date-time to a multiple of the unit, like full_second(x) with the unit of 1s.
+pub fn truncate_to(func_name: &str, arg1: &State, unit: TimeDelta) -> Result<State, String> {
+    let datetime = if let State::DateTime(datetime) = arg1 {
+        datetime
+    } else {
+        return Err(format!(
+            "the first argument to {} should be datetime, was: {:?}",
+            func_name, arg1
+        ));
+    };
+    let truncated = trunc_local(datetime, unit).map_err(|e| format!("{}: {}", func_name, e))?;
+    Ok(State::DateTime(truncated))
+}
+
+/// Round the date-time up to a multiple of the unit. The date-time that is already a multiple of the unit is not
+/// changed, so ceil_hour(10:00) is 10:00 and ceil_hour(10:01) is 11:00.
+pub fn ceil_to(func_name: &str, arg1: &State, unit: TimeDelta) -> Result<State, String> {
+    let datetime = if let State::DateTime(datetime) = arg1 {
+        datetime
+    } else {
+        return Err(format!(
+            "the first argument to {} should be datetime, was: {:?}",
+            func_name, arg1
+        ));
+    };
+    let truncated = trunc_local(datetime, unit).map_err(|e| format!("{}: {}", func_name, e))?;
+    if truncated == *datetime {
+        Ok(State::DateTime(truncated))
+    } else {
+        let ceiled = truncated
+            .checked_add_signed(unit)
+            .ok_or_else(|| format!("{}: {} is out of range", func_name, datetime))?;
+        Ok(State::DateTime(ceiled))
+    }
+}
+
+/// Return the beginning of the week, starting on Monday.
+pub fn full_week(arg1: &State) -> Result<State, String> {
+    truncate_date("full_week", arg1, |date| {
+        date.checked_sub_signed(TimeDelta::days(i64::from(
+            date.weekday().num_days_from_monday(),
+        )))
+    })
+}
+
+pub fn full_month(arg1: &State) -> Result<State, String> {
+    truncate_date("full_month", arg1, |date| date.with_day(1))
+}
+
+pub fn full_quarter(arg1: &State) -> Result<State, String> {
+    truncate_date("full_quarter", arg1, |date| {
+        let first_month = (date.month0() / 3) * 3 + 1;
+        NaiveDate::from_ymd_opt(date.year(), first_month, 1)
+    })
+}
+
+pub fn full_year(arg1: &State) -> Result<State, String> {
+    truncate_date("full_year", arg1, |date| date.with_ordinal(1))
+}
+
+/// Return the midnight of the date returned by `truncate`, in the time zone of the date-time. The date before the
+/// first one of the date-times is an error.
+fn truncate_date(
+    func_name: &str,
+    arg1: &State,
+    truncate: fn(NaiveDate) -> Option<NaiveDate>,
+) -> Result<State, String> {
+    let datetime = if let State::DateTime(datetime) = arg1 {
+        datetime
+    } else {
+        return Err(format!(
+            "the first argument to {} should be datetime, was: {:?}",
+            func_name, arg1
+        ));
+    };
+    let date = truncate(datetime.date_naive())
+        .ok_or_else(|| format!("{}: {} is out of range", func_name, datetime))?;
+    to_local(datetime, date.and_hms_opt(0, 0, 0).unwrap())
+}
+
+/// Add calendar months to the date-time. The day is clamped to the end of the month, so 2024-01-31 plus one month
+/// is 2024-02-29.
+pub fn add_months(arg1: &State, arg2: &State) -> Result<State, String> {
+    let datetime = if let State::DateTime(datetime) = arg1 {
+        datetime
+    } else {
+        return Err(format!(
+            "the first argument to add_months should be datetime, was: {:?}",
+            arg1
+        ));
+    };
+    let months = integer_arg("add_months", arg2)?;
+    let out_of_range = || format!("{} plus {} months is out of range", datetime, months);
+    let abs_months = u32::try_from(months.unsigned_abs()).map_err(|_| out_of_range())?;
+    let local = datetime.naive_local();
+    let shifted = if months < 0 {
+        local.checked_sub_months(Months::new(abs_months))
+    } else {
+        local.checked_add_months(Months::new(abs_months))
+    }
+    .ok_or_else(out_of_range)?;
+    to_local(datetime, shifted)
+}
+
+/// Replace the time of the date-time with time like "14:30" or "14:30:15", keeping the date and the time zone.
+pub fn at(arg1: &State, arg2: &State) -> Result<State, String> {
+    let (datetime, s) = replace_args("at", arg1, arg2)?;
+    let time = NaiveTime::parse_from_str(s, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(s, "%H:%M"))
+        .map_err(|e| format!("bad time {:?}, expected HH:MM or HH:MM:SS: {}", s, e))?;
+    to_local(datetime, datetime.date_naive().and_time(time))
+}
+
+/// Replace the date of the date-time with date like "2024-06-01", keeping the time and the time zone.
+pub fn on(arg1: &State, arg2: &State) -> Result<State, String> {
+    let (datetime, s) = replace_args("on", arg1, arg2)?;
+    let date = NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map_err(|e| format!("bad date {:?}, expected YYYY-MM-DD: {}", s, e))?;
+    to_local(datetime, date.and_time(datetime.time()))
+}
+
+fn replace_args<'a>(
+    func_name: &str,
+    arg1: &'a State,
+    arg2: &'a State,
+) -> Result<(&'a chrono::DateTime<Tz>, &'a str), String> {
+    let datetime = if let State::DateTime(datetime) = arg1 {
+        datetime
+    } else {
+        return Err(format!(
+            "the first argument to {} should be datetime, was: {:?}",
+            func_name, arg1
+        ));
+    };
+    if let State::String(s) = arg2 {
+        Ok((datetime, s))
+    } else {
+        Err(format!(
+            "the second argument to {} should be a string, was: {:?}",
+            func_name, arg2
+        ))
+    }
+}
+
+/// Return the integer second argument of the function, written as a number or computed.
+fn integer_arg(func_name: &str, arg2: &State) -> Result<i128, String> {
+    match arg2 {
+        State::Number(value) if value % SCALE == 0 => Ok(value / SCALE),
+        State::Integer(value) => Ok(*value),
+        _ => Err(format!(
+            "the second argument to {} should be an integer, was: {:?}",
+            func_name, arg2
+        )),
+    }
+}
+
+/// Set a component of the date-time, like the year with with_year(now, 2025). The other components are kept, and
+/// impossible date-times, like February 30, are an error.
+pub fn with_component(name: &str, arg1: &State, arg2: &State) -> Result<State, String> {
+    let func_name = format!("with_{}", name);
+    let datetime = if let State::DateTime(datetime) = arg1 {
+        datetime
+    } else {
+        return Err(format!(
+            "the first argument to {} should be datetime, was: {:?}",
+            func_name, arg1
+        ));
+    };
+    let value = integer_arg(&func_name, arg2)?;
+    let local = datetime.naive_local();
+    let invalid = || format!("cannot set {} of {} to {}", name, local, value);
+    let year = i32::try_from(value).ok();
+    let other = u32::try_from(value).ok();
+    let changed = match name {
+        "year" => year.and_then(|year| local.with_year(year)),
+        "month" => other.and_then(|month| local.with_month(month)),
+        "day" => other.and_then(|day| local.with_day(day)),
+        "hour" => other.and_then(|hour| local.with_hour(hour)),
+        "minute" => other.and_then(|minute| local.with_minute(minute)),
+        "second" => other.and_then(|second| local.with_second(second)),
+        _ => return Err(format!("no such function {:?}", func_name)),
+    };
+    to_local(datetime, changed.ok_or_else(invalid)?)
+}
+
+/// Format the date-time as string with strftime-like format, in the time zone of the date-time.
+pub fn fmt(arg1: &State, arg2: &State) -> Result<State, String> {
+    let datetime = if let State::DateTime(datetime) = arg1 {
+        datetime
+    } else {
+        return Err(format!(
+            "the first argument to fmt should be datetime, was: {:?}",
+            arg1
+        ));
+    };
+    let format = if let State::String(format) = arg2 {
+        format
+    } else {
+        return Err(format!(
+            "the second argument to fmt should be string, was: {:?}",
+            arg2
+        ));
+    };
+    let mut formatted = String::new();
+    write!(formatted, "{}", datetime.format(format))
+        .map_err(|_| format!("bad format {:?}", format))?;
+    Ok(State::String(formatted))
+}
+
+/// Interpret the naive date-time in the time zone of the other date-time, as zone::from_local.
+fn to_local(datetime: &chrono::DateTime<Tz>, naive: NaiveDateTime) -> Result<State, String> {
+    from_local(naive, &datetime.timezone()).map(State::DateTime)
+}
+
+pub fn next_cron(arg1: &State, arg2: &State) -> Result<State, String> {
+    let (schedule, from) = cron_args("next_cron", arg1, arg2)?;
+    match schedule.next_after(&from) {
+        Some(datetime) => Ok(State::DateTime(datetime)),
+        None => Err(format!("cron schedule never fires after {}", from)),
+    }
+}
+
+pub fn prev_cron(arg1: &State, arg2: &State) -> Result<State, String> {
+    let (schedule, from) = cron_args("prev_cron", arg1, arg2)?;
+    match schedule.prev_before(&from) {
+        Some(datetime) => Ok(State::DateTime(datetime)),
+        None => Err(format!("cron schedule never fires before {}", from)),
+    }
+}
+
+fn cron_args(
+    func_name: &str,
+    arg1: &State,
+    arg2: &State,
+) -> Result<(CronSchedule, chrono::DateTime<Tz>), String> {
+    let schedule = if let State::String(s) = arg1 {
+        CronSchedule::parse(s)?
+    } else {
+        return Err(format!(
+            "the first argument to {} should be a cron expression string, was: {:?}",
+            func_name, arg1
+        ));
+    };
+    let from = if let State::DateTime(datetime) = arg2 {
+        *datetime
+    } else {
+        return Err(format!(
+            "the second argument to {} should be datetime, was: {:?}",
+            func_name, arg2
+        ));
+    };
+    Ok((schedule, from))
+}
+
+pub fn next_rrule(arg1: &State, arg2: &State) -> Result<State, String> {
+    let rule = if let State::String(s) = arg1 {
+        RecurrenceRule::parse(s)?
+    } else {
+        return Err(format!(
+            "the first argument to next_rrule should be a recurrence rule string, was: {:?}",
+            arg1
+        ));
+    };
+    let from = if let State::DateTime(datetime) = arg2 {
+        datetime
+    } else {
+        return Err(format!(
+            "the second argument to next_rrule should be datetime, was: {:?}",
+            arg2
+        ));
+    };
+    match rule.next_after(from) {
+        Some(datetime) => Ok(State::DateTime(datetime)),
+        None => Err(format!("recurrence rule has no occurrence after {}", from)),
+    }
+}
+
+/// Return the next time the systemd calendar event like "Mon..Fri 10:00" elapses after the date-time.
+pub fn next_systemd(arg1: &State, arg2: &State) -> Result<State, String> {
+    let event = if let State::String(s) = arg1 {
+        CalendarEvent::parse(s)?
+    } else {
+        return Err(format!(
+            "the first argument to next_systemd should be a calendar event string, was: {:?}",
+            arg1
+        ));
+    };
+    let from = if let State::DateTime(datetime) = arg2 {
+        datetime
+    } else {
+        return Err(format!(
+            "the second argument to next_systemd should be datetime, was: {:?}",
+            arg2
+        ));
+    };
+    match event.next_after(from) {
+        Some(datetime) => Ok(State::DateTime(datetime)),
+        None => Err(format!("calendar event never elapses after {}", from)),
+    }
+}
+
+/// Return the duration of the systemd time span like "2weeks 1day".
+pub fn timespan(arg1: &State) -> Result<State, String> {
+    if let State::String(s) = arg1 {
+        parse_timespan(s).map(State::TimeDelta)
+    } else {
+        Err(format!(
+            "the first argument to timespan should be a time span string, was: {:?}",
+            arg1
+        ))
+    }
+}
+
+/// Return the age at the second date-time of something born at the first one, in whole years, months and days. The
+/// time of day is not taken into account. The age is negative if the second date-time is earlier.
+pub fn age(arg1: &State, arg2: &State) -> Result<State, String> {
+    let (birth, asof) = match (arg1, arg2) {
+        (State::DateTime(birth), State::DateTime(asof)) => (birth, asof),
+        _ => {
+            return Err(format!(
+                "the arguments to age should be datetimes, were: {:?} and {:?}",
+                arg1, arg2
+            ))
+        }
+    };
+    let birth_date = birth.date_naive();
+    let asof_date = asof.with_timezone(&birth.timezone()).date_naive();
+    let (from, to, sign) = if birth_date <= asof_date {
+        (birth_date, asof_date, 1)
+    } else {
+        (asof_date, birth_date, -1)
+    };
+    let mut months = (to.year() - from.year()) * 12 + to.month() as i32 - from.month() as i32;
+    if to.day() < from.day() {
+        months -= 1;
+    }
+    let anchor = from
+        .checked_add_months(Months::new(months as u32))
+        .ok_or_else(|| format!("{} plus {} months is out of range", from, months))?;
+    let days = (to - anchor).num_days();
+    Ok(State::CalendarDelta {
+        years: sign * i64::from(months / 12),
+        months: sign * i64::from(months % 12),
+        days: sign * days,
+    })
+}
+
+/// Return interval [start, end). The start should not be after the end.
+pub fn interval(arg1: &State, arg2: &State) -> Result<State, String> {
+    match (arg1, arg2) {
+        (State::DateTime(start), State::DateTime(end)) if start <= end => {
+            Ok(State::Interval(*start, *end))
+        }
+        (State::DateTime(start), State::DateTime(end)) => Err(format!(
+            "the start of interval should not be after the end, was: {} and {}",
+            start, end
+        )),
+        _ => Err(format!(
+            "the start and end of interval should be datetimes, were: {:?} and {:?}",
+            arg1, arg2
+        )),
+    }
+}
+
+/// Check if the interval contains the date-time, or the whole other interval.
+pub fn interval_contains(arg1: &State, arg2: &State) -> Result<State, String> {
+    let (start, end) = as_interval("contains", arg1)?;
+    match arg2 {
+        State::DateTime(datetime) => Ok(State::Bool(start <= datetime && datetime < end)),
+        State::Interval(other_start, other_end) => {
+            Ok(State::Bool(start <= other_start && other_end <= end))
+        }
+        _ => Err(format!(
+            "the second argument to contains should be datetime or interval, was: {:?}",
+            arg2
+        )),
+    }
+}
+
+/// Return the length of the interval.
+pub fn interval_duration(arg1: &State) -> Result<State, String> {
+    let (start, end) = as_interval("duration", arg1)?;
+    Ok(State::TimeDelta(*end - *start))
+}
+
+/// Move the interval by the duration.
+pub fn interval_shift(arg1: &State, arg2: &State) -> Result<State, String> {
+    let (start, end) = as_interval("shift", arg1)?;
+    if let State::TimeDelta(delta) = arg2 {
+        let shift = |datetime: &DateTime<Tz>| {
+            datetime
+                .checked_add_signed(*delta)
+                .ok_or_else(|| format!("shift: {} + {:?} is out of range", datetime, delta))
+        };
+        Ok(State::Interval(shift(start)?, shift(end)?))
+    } else {
+        Err(format!(
+            "the second argument to shift should be duration, was: {:?}",
+            arg2
+        ))
+    }
+}
+
+fn as_interval<'a>(
+    func_name: &str,
+    arg: &'a State,
+) -> Result<(&'a DateTime<Tz>, &'a DateTime<Tz>), String> {
+    if let State::Interval(start, end) = arg {
+        Ok((start, end))
+    } else {
+        Err(format!(
+            "the first argument to {} should be interval, was: {:?}",
+            func_name, arg
+        ))
+    }
+}
+
+/// Return the start of the bucket of the date-time, with buckets of the duration counted from the Unix epoch, like
+/// bucket(now, 5m).
+pub fn bucket(arg1: &State, arg2: &State) -> Result<State, String> {
+    let (datetime, step, index) = bucket_args("bucket", arg1, arg2, None)?;
+    let start_nanos = index * step;
+    let start = chrono::DateTime::from_timestamp(
+        i64::try_from(start_nanos.div_euclid(SCALE)).map_err(|_| "bucket out of range")?,
+        start_nanos.rem_euclid(SCALE) as u32,
+    )
+    .ok_or("bucket out of range")?;
+    Ok(State::DateTime(start.with_timezone(&datetime.timezone())))
+}
+
+/// Return the number of the bucket of the date-time, with buckets of the duration counted from the origin, the Unix
+/// epoch by default. Buckets before the origin have negative numbers.
+pub fn bucket_index(arg1: &State, arg2: &State, arg3: Option<&State>) -> Result<State, String> {
+    let (_, _, index) = bucket_args("bucket_index", arg1, arg2, arg3)?;
+    Ok(State::Integer(index))
+}
+
+/// Return the date-time, the bucket length in nanoseconds and the bucket index.
+fn bucket_args<'a>(
+    func_name: &str,
+    arg1: &'a State,
+    arg2: &State,
+    arg3: Option<&State>,
+) -> Result<(&'a DateTime<Tz>, i128, i128), String> {
+    let datetime = if let State::DateTime(datetime) = arg1 {
+        datetime
+    } else {
+        return Err(format!(
+            "the first argument to {} should be datetime, was: {}",
+            func_name, arg1
+        ));
+    };
+    let step = match arg2 {
+        State::TimeDelta(step) if *step > TimeDelta::zero() => {
+            i128::from(step.num_nanoseconds().unwrap())
+        }
+        _ => {
+            return Err(format!(
+                "the second argument to {} should be positive duration, was: {}",
+                func_name, arg2
+            ))
+        }
+    };
+    let origin_nanos = match arg3 {
+        None => 0,
+        Some(State::DateTime(origin)) => timestamp_nanos(origin),
+        Some(arg3) => {
+            return Err(format!(
+                "the third argument to {} should be datetime, was: {}",
+                func_name, arg3
+            ))
+        }
+    };
+    let index = (timestamp_nanos(datetime) - origin_nanos).div_euclid(step);
+    Ok((datetime, step, index))
+}
+
+fn timestamp_nanos(datetime: &DateTime<Tz>) -> i128 {
+    i128::from(datetime.timestamp()) * SCALE + i128::from(datetime.timestamp_subsec_nanos())
+}
+
+/// The date-time of the nanoseconds since the Unix epoch, in the time zone.
+fn nanos_to_datetime(func_name: &str, nanos: i128, tz: &Tz) -> Result<DateTime<Tz>, String> {
+    let out_of_range = || format!("{}: date-time out of range", func_name);
+    let seconds = i64::try_from(nanos.div_euclid(SCALE)).map_err(|_| out_of_range())?;
+    chrono::DateTime::from_timestamp(seconds, nanos.rem_euclid(SCALE) as u32)
+        .map(|datetime| datetime.with_timezone(tz))
+        .ok_or_else(out_of_range)
+}
+
+/// The nanoseconds of the duration, if they fit in i64, as in the durations of the other functions.
+fn delta_nanos(func_name: &str, delta: &TimeDelta) -> Result<i128, String> {
+    delta.num_nanoseconds().map(i128::from).ok_or_else(|| {
+        format!(
+            "{}: duration out of range: {}",
+            func_name,
+            delta.as_short_format()
+        )
+    })
+}
+
+/// Return a random date-time or duration in [low, high), like random_between(now - 1d, now).
+pub fn random_between(arg1: &State, arg2: &State, random: &Random) -> Result<State, String> {
+    match (arg1, arg2) {
+        (State::DateTime(low), State::DateTime(high)) if low < high => {
+            let nanos = random.between(timestamp_nanos(low), timestamp_nanos(high));
+            let datetime = nanos_to_datetime("random_between", nanos, &low.timezone())?;
+            Ok(State::DateTime(datetime))
+        }
+        (State::TimeDelta(low), State::TimeDelta(high)) if low < high => {
+            let nanos = random.between(
+                delta_nanos("random_between", low)?,
+                delta_nanos("random_between", high)?,
+            );
+            nanos_to_delta("random_between", nanos)
+        }
+        _ => Err(format!(
+            "the arguments to random_between should be two datetimes or two durations, the first one lower, were: {} and {}",
+            arg1, arg2
+        )),
+    }
+}
+
+/// Aggregate the list of durations or date-times: sum, mean, min, max, or span (max - min). Sum is only defined for
+/// durations.
+pub fn aggregate(func_name: &str, arg1: &State) -> Result<State, String> {
+    let elements = if let State::List(elements) = arg1 {
+        elements
+    } else {
+        return Err(format!(
+            "the argument to {} should be a list, was: {:?}",
+            func_name, arg1
+        ));
+    };
+    if elements.is_empty() {
+        return Err(format!("{} of empty list", func_name));
+    }
+    let is_datetime = matches!(elements[0], State::DateTime(_));
+    // Aggregate as nanoseconds, since the Unix epoch for date-times.
+    let nanos = elements
+        .iter()
+        .map(|element| match element {
+            State::DateTime(datetime) if is_datetime => Ok(timestamp_nanos(datetime)),
+            State::TimeDelta(delta) if !is_datetime => delta_nanos(func_name, delta),
+            _ => Err(format!(
+                "{} expects a list of only durations or only datetimes, got {:?}",
+                func_name, element
+            )),
+        })
+        .collect::<Result<Vec<i128>, String>>()?;
+    let min = *nanos.iter().min().unwrap();
+    let max = *nanos.iter().max().unwrap();
+    let value = match func_name {
+        "sum" if is_datetime => return Err("sum of datetimes is not defined".to_string()),
+        "sum" => nanos.iter().sum(),
+        "mean" => nanos.iter().sum::<i128>().div_euclid(nanos.len() as i128),
+        "min" => min,
+        "max" => max,
+        "span" => return nanos_to_delta(func_name, max - min),
+        _ => return Err(format!("no such function {:?}", func_name)),
+    };
+    match &elements[0] {
+        State::DateTime(first) => Ok(State::DateTime(nanos_to_datetime(
+            func_name,
+            value,
+            &first.timezone(),
+        )?)),
+        _ => nanos_to_delta(func_name, value),
+    }
+}
+
+/// Limit the date-time or duration to [lo, hi], like clamp(start, now - 30d, now).
+pub fn clamp(arg1: &State, arg2: &State, arg3: &State) -> Result<State, String> {
+    match (arg1, arg2, arg3) {
+        (State::DateTime(x), State::DateTime(lo), State::DateTime(hi)) if lo <= hi => {
+            Ok(State::DateTime(*x.clamp(lo, hi)))
+        }
+        (State::TimeDelta(x), State::TimeDelta(lo), State::TimeDelta(hi)) if lo <= hi => {
+            Ok(State::TimeDelta(*x.clamp(lo, hi)))
+        }
+        (State::DateTime(_), State::DateTime(_), State::DateTime(_))
+        | (State::TimeDelta(_), State::TimeDelta(_), State::TimeDelta(_)) => Err(format!(
+            "the lower bound of clamp is above the upper bound: {} > {}",
+            arg2, arg3
+        )),
+        _ => Err(format!(
+            "the arguments to clamp should be all datetimes or all durations, were: {}, {}, {}",
+            arg1, arg2, arg3
+        )),
+    }
+}
+
+/// Truncate the duration toward zero to a multiple of the unit, like trunc_delta(1m30s, 1m), which is 1m.
+pub fn trunc_delta(arg1: &State, arg2: &State) -> Result<State, String> {
+    let (delta, unit) = delta_args("trunc_delta", arg1, arg2)?;
+    nanos_to_delta("trunc_delta", delta - delta % unit)
+}
+
+/// Round the duration to the nearest multiple of the unit, the halves away from zero, like round_delta(1m30s, 1m),
+/// which is 2m.
+pub fn round_delta(arg1: &State, arg2: &State) -> Result<State, String> {
+    let (delta, unit) = delta_args("round_delta", arg1, arg2)?;
+    let rem = delta % unit;
+    let truncated = delta - rem;
+    let rounded = if 2 * rem.abs() >= unit {
+        truncated + rem.signum() * unit
+    } else {
+        truncated
+    };
+    nanos_to_delta("round_delta", rounded)
+}
+
+/// Return the duration and the positive unit in nanoseconds.
+fn delta_args(func_name: &str, arg1: &State, arg2: &State) -> Result<(i128, i128), String> {
+    match (arg1, arg2) {
+        (State::TimeDelta(delta), State::TimeDelta(unit)) if *unit > TimeDelta::zero() => Ok((
+            delta_nanos(func_name, delta)?,
+            delta_nanos(func_name, unit)?,
+        )),
+        _ => Err(format!(
+            "the arguments to {} should be duration and positive duration, were: {:?}, {:?}",
+            func_name, arg1, arg2
+        )),
+    }
+}
+
+fn nanos_to_delta(func_name: &str, nanos: i128) -> Result<State, String> {
+    let nanos =
+        i64::try_from(nanos).map_err(|_| format!("{}: duration out of range", func_name))?;
+    Ok(State::TimeDelta(TimeDelta::nanoseconds(nanos)))
+}
+
+/// Move the date-time forward to the oldest one kept with the retention, i.e. now - retention, if it is older.
+pub fn retention_floor(arg1: &State, arg2: &State, now: &DateTime<Tz>) -> Result<State, String> {
+    match (arg1, arg2) {
+        (State::DateTime(datetime), State::TimeDelta(retention)) if *retention >= TimeDelta::zero() => {
+            // Beyond the range the floor is older than any date-time.
+            let floor = now.checked_sub_signed(*retention);
+            Ok(State::DateTime(floor.map_or(*datetime, |floor| floor.max(*datetime))))
+        }
+        _ => Err(format!(
+            "the arguments to retention_floor should be datetime and non-negative duration, were: {:?}, {:?}",
+            arg1, arg2
+        )),
+    }
+}
+
+/// Most elements returned by seq, so a tiny step does not exhaust the memory.
+const SEQ_MAX_LEN: usize = 100_000;
+
+/// Return list of date-times from the start, before the end, every step, like seq(now, now + 1d, 1h).
+pub fn seq(arg1: &State, arg2: &State, arg3: &State) -> Result<State, String> {
+    let (start, end, step) = match (arg1, arg2, arg3) {
+        (State::DateTime(start), State::DateTime(end), State::TimeDelta(step))
+            if *step > TimeDelta::zero() =>
+        {
+            (start, end, step)
+        }
+        _ => {
+            return Err(format!(
+                "the arguments to seq should be start and end datetimes and positive duration, were: {:?}, {:?} and {:?}",
+                arg1, arg2, arg3
+            ))
+        }
+    };
+    let mut elements = vec![];
+    let mut current = *start;
+    while current < *end {
+        if elements.len() == SEQ_MAX_LEN {
+            return Err(format!("seq is longer than {} elements", SEQ_MAX_LEN));
+        }
+        elements.push(State::DateTime(current));
+        current += *step;
+    }
+    Ok(State::List(elements))
+}
+
+/// Return the overlap of two time windows [start1, end1) and [start2, end2), zero if they are disjoint.
+pub fn overlap(
+    start1: &State,
+    end1: &State,
+    start2: &State,
+    end2: &State,
+) -> Result<State, String> {
+    let (start, end) = window_intersection("overlap", start1, end1, start2, end2)?;
+    Ok(State::TimeDelta((end - start).max(TimeDelta::zero())))
+}
+
+/// Check if two time windows [start1, end1) and [start2, end2) have common time. Windows that only touch do not
+/// intersect.
+pub fn intersects(
+    start1: &State,
+    end1: &State,
+    start2: &State,
+    end2: &State,
+) -> Result<State, String> {
+    let (start, end) = window_intersection("intersects", start1, end1, start2, end2)?;
+    Ok(State::Bool(start < end))
+}
+
+/// Return the later start and the earlier end of the two windows.
+fn window_intersection(
+    func_name: &str,
+    start1: &State,
+    end1: &State,
+    start2: &State,
+    end2: &State,
+) -> Result<(DateTime<Tz>, DateTime<Tz>), String> {
+    let mut datetimes = vec![];
+    for (i, arg) in [start1, end1, start2, end2].iter().enumerate() {
+        if let State::DateTime(datetime) = arg {
+            datetimes.push(*datetime);
+        } else {
+            return Err(format!(
+                "the arguments to {} should be datetimes, argument {} was: {:?}",
+                func_name,
+                i + 1,
+                arg
+            ));
+        }
+    }
+    for window in datetimes.chunks(2) {
+        if window[0] > window[1] {
+            return Err(format!(
+                "{} expects windows with start before end, got {} and {}",
+                func_name, window[0], window[1]
+            ));
+        }
+    }
+    Ok((
+        datetimes[0].max(datetimes[2]),
+        datetimes[1].min(datetimes[3]),
+    ))
+}
+
+/// Return the TOTP (RFC 6238) counter of the date-time for the time step, with the boundaries of the window.
+pub fn totp_window(arg1: &State, arg2: &State) -> Result<State, String> {
+    let datetime = if let State::DateTime(datetime) = arg1 {
+        datetime
+    } else {
+        return Err(format!(
+            "the first argument to totp_window should be datetime, was: {:?}",
+            arg1
+        ));
+    };
+    let step = match arg2 {
+        State::TimeDelta(step) if *step > TimeDelta::zero() => step,
+        _ => {
+            return Err(format!(
+                "the second argument to totp_window should be positive duration, was: {:?}",
+                arg2
+            ))
+        }
+    };
+    let start = trunc_local(datetime, *step)?;
+    let step_nanos = i128::from(step.num_nanoseconds().unwrap());
+    let start_nanos =
+        i128::from(start.timestamp()) * SCALE + i128::from(start.timestamp_subsec_nanos());
+    Ok(State::Record(vec![
+        (
+            "counter".to_string(),
+            State::Integer(start_nanos.div_euclid(step_nanos)),
+        ),
+        ("start".to_string(), State::DateTime(start)),
+        (
+            "end".to_string(),
+            State::DateTime(
+                start
+                    .checked_add_signed(*step)
+                    .ok_or_else(|| format!("totp_window: {} is out of range", datetime))?,
+            ),
+        ),
+    ]))
+}
+
+/// Convert timestamp of the alternate epoch to date-time, like from_filetime(x).
+pub fn from_alt_epoch(epoch: &AltEpoch, arg1: &State) -> Result<State, String> {
+    let ticks = match arg1 {
+        State::Number(value) => *value,
+        State::Integer(value) => value
+            .checked_mul(SCALE)
+            .ok_or_else(|| format!("{} timestamp {} is out of range", epoch.name, value))?,
+        _ => {
+            return Err(format!(
+                "the first argument to from_{} should be a number, was: {:?}",
+                epoch.name, arg1
+            ))
+        }
+    };
+    Ok(State::DateTime(epoch.ticks_to_datetime(ticks)?))
+}
+
+/// Convert date-time to timestamp of the alternate epoch, like to_filetime(x).
+pub fn to_alt_epoch(epoch: &AltEpoch, arg1: &State) -> Result<State, String> {
+    if let State::DateTime(datetime) = arg1 {
+        Ok(State::Integer(epoch.datetime_to_ticks(datetime)?))
+    } else {
+        Err(format!(
+            "the first argument to to_{} should be datetime, was: {:?}",
+            epoch.name, arg1
+        ))
+    }
+}
+
+pub fn from_objectid(arg1: &State) -> Result<State, String> {
+    id_timestamp("from_objectid", arg1, objectid_timestamp)
+}
+
+pub fn from_uuid1(arg1: &State) -> Result<State, String> {
+    id_timestamp("from_uuid1", arg1, uuid1_timestamp)
+}
+
+pub fn from_uuid7(arg1: &State) -> Result<State, String> {
+    id_timestamp("from_uuid7", arg1, uuid7_timestamp)
+}
+
+/// Return the expiry time of JWT, from the exp claim.
+pub fn jwt_exp(arg1: &State) -> Result<State, String> {
+    id_timestamp("jwt_exp", arg1, jwt_exp_timestamp)
+}
+
+/// Return the timestamp of snowflake ID. The optional epoch is a name like "discord" or a date-time, the default is
+/// the Twitter epoch.
+pub fn from_snowflake(arg1: &State, arg2: Option<&State>) -> Result<State, String> {
+    let id = match arg1 {
+        State::String(s) => s
+            .trim()
+            .parse::<u64>()
+            .map_err(|_| format!("bad snowflake ID {:?}", s))?,
+        State::Number(value) if value % SCALE == 0 => u64::try_from(value / SCALE)
+            .map_err(|_| format!("bad snowflake ID {}", value / SCALE))?,
+        State::Integer(value) => {
+            u64::try_from(*value).map_err(|_| format!("bad snowflake ID {}", value))?
+        }
+        _ => {
+            return Err(format!(
+                "the first argument to from_snowflake should be an integer, was: {:?}",
+                arg1
+            ))
+        }
+    };
+    let epoch_millis = match arg2 {
+        None => SNOWFLAKE_EPOCHS[0].1,
+        Some(State::String(name)) => SNOWFLAKE_EPOCHS
+            .iter()
+            .find(|(epoch_name, _)| epoch_name == name)
+            .map(|(_, millis)| *millis)
+            .ok_or_else(|| {
+                let names: Vec<&str> = SNOWFLAKE_EPOCHS.iter().map(|(name, _)| *name).collect();
+                format!(
+                    "unknown snowflake epoch {:?}, expected one of {} or a date-time",
+                    name,
+                    names.join(", ")
+                )
+            })?,
+        Some(State::DateTime(datetime)) => datetime.timestamp_millis(),
+        Some(arg2) => {
+            return Err(format!(
+                "the second argument to from_snowflake should be epoch name or datetime, was: {:?}",
+                arg2
+            ))
+        }
+    };
+    let datetime = snowflake_timestamp(id, epoch_millis)?;
+    Ok(State::DateTime(datetime.with_timezone(&super::zone::UTC)))
+}
+
+fn id_timestamp(
+    func_name: &str,
+    arg1: &State,
+    extract: fn(&str) -> Result<chrono::DateTime<chrono::Utc>, String>,
+) -> Result<State, String> {
+    if let State::String(id) = arg1 {
+        Ok(State::DateTime(
+            extract(id)?.with_timezone(&super::zone::UTC),
+        ))
+    } else {
+        Err(format!(
+            "the first argument to {} should be a string, was: {:?}",
+            func_name, arg1
+        ))
+    }
+}
+
+#[cfg(feature = "fs")]
+pub fn mtime(arg1: &State) -> Result<State, String> {
+    file_time("mtime", arg1, |metadata| metadata.modified())
+}
+
+/// Return status change time on unix, and creation time on the other platforms.
+#[cfg(feature = "fs")]
+pub fn ctime(arg1: &State) -> Result<State, String> {
+    file_time("ctime", arg1, |metadata| {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let changed = std::time::UNIX_EPOCH
+                + std::time::Duration::new(
+                    metadata.ctime().max(0) as u64,
+                    metadata.ctime_nsec() as u32,
+                );
+            Ok(changed)
+        }
+        #[cfg(not(unix))]
+        metadata.created()
+    })
+}
+
+#[cfg(feature = "fs")]
+fn file_time(
+    func_name: &str,
+    arg1: &State,
+    get_time: fn(&std::fs::Metadata) -> std::io::Result<std::time::SystemTime>,
+) -> Result<State, String> {
+    let path = if let State::String(path) = arg1 {
+        path
+    } else {
+        return Err(format!(
+            "the first argument to {} should be a path string, was: {:?}",
+            func_name, arg1
+        ));
+    };
+    let time = std::fs::metadata(path)
+        .and_then(|metadata| get_time(&metadata))
+        .map_err(|e| format!("cannot read {} of {:?}: {}", func_name, path, e))?;
+    let datetime: chrono::DateTime<chrono::Utc> = time.into();
+    Ok(State::DateTime(datetime.with_timezone(&super::zone::UTC)))
+}
+
+/// Return time since the system boot, read from /proc/uptime.
+#[cfg(all(feature = "uptime", target_os = "linux"))]
+pub fn time_since_boot() -> Result<TimeDelta, String> {
+    let content = std::fs::read_to_string("/proc/uptime")
+        .map_err(|e| format!("cannot read /proc/uptime: {}", e))?;
+    let seconds = content
+        .split_whitespace()
+        .next()
+        .and_then(|s| s.parse::<f64>().ok())
+        .ok_or_else(|| format!("bad content of /proc/uptime: {:?}", content))?;
+    Ok(TimeDelta::milliseconds((seconds * 1000.0) as i64))
+}
+
+/// Return time since the system boot, from the boot time read with sysctl(CTL_KERN, KERN_BOOTTIME).
+#[cfg(all(feature = "uptime", any(target_os = "macos", target_os = "freebsd")))]
+pub fn time_since_boot() -> Result<TimeDelta, String> {
+    use std::ffi::{c_int, c_long, c_uint, c_void};
+    /// struct timeval of sys/time.h.
+    #[repr(C)]
+    struct Timeval {
+        tv_sec: c_long,
+        #[cfg(target_os = "macos")]
+        tv_usec: i32,
+        #[cfg(target_os = "freebsd")]
+        tv_usec: c_long,
+    }
+    extern "C" {
+        fn sysctl(
+            name: *const c_int,
+            namelen: c_uint,
+            oldp: *mut c_void,
+            oldlenp: *mut usize,
+            newp: *mut c_void,
+            newlen: usize,
+        ) -> c_int;
+    }
+    const CTL_KERN: c_int = 1;
+    const KERN_BOOTTIME: c_int = 21;
+    let name = [CTL_KERN, KERN_BOOTTIME];
+    let mut boot_time = Timeval {
+        tv_sec: 0,
+        tv_usec: 0,
+    };
+    let mut len = std::mem::size_of::<Timeval>();
+    // SAFETY: the name has the two levels passed in namelen, and sysctl writes at most len bytes to the timeval.
+    let status = unsafe {
+        sysctl(
+            name.as_ptr(),
+            name.len() as c_uint,
+            &mut boot_time as *mut Timeval as *mut c_void,
+            &mut len,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if status != 0 {
+        return Err(format!(
+            "cannot read sysctl kern.boottime: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    // The seconds are i32 on 32-bit FreeBSD.
+    #[allow(clippy::useless_conversion)]
+    let seconds = i64::from(boot_time.tv_sec);
+    let boot = chrono::DateTime::from_timestamp(seconds, boot_time.tv_usec as u32 * 1000)
+        .ok_or_else(|| format!("boot time {} is out of range", seconds))?;
+    Ok(chrono::Utc::now() - boot)
+}
+
+/// Return time since the system boot, counted by GetTickCount64 in milliseconds.
+#[cfg(all(feature = "uptime", windows))]
+pub fn time_since_boot() -> Result<TimeDelta, String> {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetTickCount64() -> u64;
+    }
+    // SAFETY: GetTickCount64 takes no arguments and only reads the tick count.
+    let millis = unsafe { GetTickCount64() };
+    Ok(TimeDelta::milliseconds(millis as i64))
+}
+
+#[cfg(not(all(
+    feature = "uptime",
+    any(
+        target_os = "linux",
+        target_os = "macos",
+        target_os = "freebsd",
+        windows
+    )
+)))]
+pub fn time_since_boot() -> Result<TimeDelta, String> {
+    Err(
+        "uptime is supported only on Linux, macOS, FreeBSD and Windows, with the uptime feature"
+            .to_string(),
+    )
+}
+
+/// Return the sunrise or sunset on the date of the date-time at the latitude and longitude in degrees, like
+/// sunset(now, 52.23, 21.01), in the time zone of the date-time.
+#[cfg(feature = "astro")]
+pub fn sun_event(
+    func_name: &str,
+    arg1: &State,
+    arg2: &State,
+    arg3: &State,
+) -> Result<State, String> {
+    use super::astro::SunEvent;
+    let datetime = if let State::DateTime(datetime) = arg1 {
+        datetime
+    } else {
+        return Err(format!(
+            "the first argument to {} should be datetime, was: {:?}",
+            func_name, arg1
+        ));
+    };
+    let latitude = degrees_arg(func_name, "latitude", arg2, 90.0)?;
+    let longitude = degrees_arg(func_name, "longitude", arg3, 180.0)?;
+    let event = if func_name == "sunrise" {
+        SunEvent::Sunrise
+    } else {
+        SunEvent::Sunset
+    };
+    let time = super::astro::sun_event(datetime.date_naive(), latitude, longitude, event)?;
+    Ok(State::DateTime(time.with_timezone(&datetime.timezone())))
+}
+
+#[cfg(not(feature = "astro"))]
+pub fn sun_event(
+    func_name: &str,
+    _arg1: &State,
+    _arg2: &State,
+    _arg3: &State,
+) -> Result<State, String> {
+    Err(format!(
+        "{} is not supported, build with the astro feature",
+        func_name
+    ))
+}
+
+/// Return the coordinate in degrees, between -limit and limit.
+#[cfg(feature = "astro")]
+fn degrees_arg(func_name: &str, what: &str, arg: &State, limit: f64) -> Result<f64, String> {
+    let degrees = match arg {
+        State::Number(value) => *value as f64 / SCALE as f64,
+        State::Integer(value) => *value as f64,
+        _ => {
+            return Err(format!(
+                "the {} of {} should be a number, was: {:?}",
+                what, func_name, arg
+            ))
+        }
+    };
+    if degrees.abs() > limit {
+        return Err(format!(
+            "the {} of {} should be between -{} and {}, was: {}",
+            what, func_name, limit, limit, degrees
+        ));
+    }
+    Ok(degrees)
+}
+
+/// Query network time from the server, or from the default server.
+#[cfg(feature = "ntp")]
+pub fn ntp_now(server: Option<&str>) -> Result<State, String> {
+    let ntp_time = super::ntp::query(server.unwrap_or(super::ntp::DEFAULT_NTP_SERVER))?;
+    Ok(State::DateTime(
+        ntp_time.time.with_timezone(&super::zone::UTC),
+    ))
+}
+
+#[cfg(not(feature = "ntp"))]
+pub fn ntp_now(_server: Option<&str>) -> Result<State, String> {
+    Err("ntp_now is not supported, build with the ntp feature".to_string())
+}
@@ -1,7 +1,9 @@
 use std::fmt;
 use std::rc::Rc;
 
-use chrono_tz::Tz;
+use super::zone::Tz;
+
+use super::types::Type;
 
 /// A context passed around between the matchers, pointing where in the input is the matched now.
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -13,7 +15,7 @@ pub struct InputPointer<'a> {
 }
 
 impl<'a> InputPointer<'a> {
-    pub fn from_string(s: &String) -> InputPointer {
+    pub fn from_string(s: &String) -> InputPointer<'_> {
         InputPointer { input: s, pos: 0 }
     }
     /// Check if the pointer is at the end of the input.
@@ -24,17 +26,17 @@ impl<'a> InputPointer<'a> {
     /// Get the remainder of the input (at pos).
     pub fn rest(&self) -> &str {
         if self.is_end() {
-            return &"";
+            return "";
         }
         &self.input[self.pos..]
     }
 
     /// Advance the pointer by n bytes.
     pub fn advance(&self, n: usize) -> InputPointer<'a> {
-        return InputPointer {
+        InputPointer {
             input: self.input,
             pos: self.pos + n,
-        };
+        }
     }
 }
 
@@ -53,6 +55,7 @@ impl<'a> fmt::Display for InputPointer<'a> {
     }
 }
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Clone, Debug, PartialEq)]
 pub enum Node {
     Duration(chrono::Duration),
@@ -63,8 +66,39 @@ pub enum Node {
         oper: Oper,
         node: Rc<Node>,
     },
+    /// Date-time without offset, like "2024-05-17 13:00", or only time of day like "13:00". Evaluated in the named
+    /// time zone like in "2024-05-17 13:00 Europe/Warsaw", or in the time zone of "now", with the date of "now" in that
+    /// zone if the date is missing.
+    LocalDateTime {
+        date: Option<chrono::NaiveDate>,
+        time: chrono::NaiveTime,
+        tz: Option<Tz>,
+    },
+    /// Date with the month name, like "17 May 2024", "17. Mai 2024" or "May 17th, 2024", optionally with the time of
+    /// day and the time zone, like "May 17th, 2024 1:00 PM UTC". The name is matched with the locale at evaluation.
+    /// Without the time it is the midnight, and without the zone it is in the time zone of "now".
+    NamedMonthDate {
+        day: u32,
+        month: String,
+        year: i32,
+        time: chrono::NaiveTime,
+        tz: Option<Tz>,
+    },
+    /// Numeric date like "5/17/24" or "17.05.24". The order of the parts is resolved at evaluation, and the date is the
+    /// midnight in the time zone of "now".
+    NumericDate(String),
     /// "now" literal that evaluates to current time.
     Now,
+    /// "uptime" literal that evaluates to system boot time.
+    Uptime,
+    /// "ntp_now" literal that evaluates to network time.
+    NtpNow,
+    /// "epoch" literal, 1970-01-01T00:00:00Z.
+    Epoch,
+    /// "max_time" literal, +262142-12-31T23:59:59.999999999Z, the latest date-time that can be represented.
+    MaxTime,
+    /// "min_time" literal, -262143-01-01T00:00:00Z, the earliest date-time that can be represented.
+    MinTime,
     /// A literal string, e.g. whitespace to skip or function name.
     Literal {
         literal: String,
@@ -76,6 +110,74 @@ pub enum Node {
         name: String,
         arg1: Rc<Node>,
     },
+    /// Function with arity of 2
+    FuncAry2 {
+        /// Name of the function
+        name: String,
+        arg1: Rc<Node>,
+        arg2: Rc<Node>,
+    },
+    /// Function with arity of 3
+    FuncAry3 {
+        /// Name of the function
+        name: String,
+        arg1: Rc<Node>,
+        arg2: Rc<Node>,
+        arg3: Rc<Node>,
+    },
+    /// Function with arity of 4
+    FuncAry4 {
+        /// Name of the function
+        name: String,
+        arg1: Rc<Node>,
+        arg2: Rc<Node>,
+        arg3: Rc<Node>,
+        arg4: Rc<Node>,
+    },
+    /// Time interval [start, end) written as "start..end".
+    Interval {
+        start: Rc<Node>,
+        end: Rc<Node>,
+    },
+    /// List of values, like "[now, now - 1d]".
+    List(Vec<Node>),
+    /// Variable bound by map, like "x" in "map([now], x -> x + 1h)".
+    Variable(String),
+    /// Evaluate the body for each element of the list, with the element bound to the variable.
+    Map {
+        list: Rc<Node>,
+        variable: String,
+        body: Rc<Node>,
+    },
+    /// Comparison of two values, like "now > deadline", that evaluates to bool.
+    Compare {
+        oper: CompareOper,
+        left: Rc<Node>,
+        right: Rc<Node>,
+    },
+    /// Conditional "if(cond, then, otherwise)". Only the taken branch is evaluated.
+    If {
+        cond: Rc<Node>,
+        then: Rc<Node>,
+        otherwise: Rc<Node>,
+    },
+    /// A quoted string, e.g. a cron expression passed to a function.
+    String(String),
+    /// A number, fixed-point scaled by number::SCALE. Evaluated as epoch timestamp when used as date-time.
+    Number(i128),
+    /// Assertion of the type of the result, like "now - deadline :: duration", that fails the evaluation if the type
+    /// differs.
+    TypeAssertion {
+        node: Rc<Node>,
+        expected: Type,
+    },
+    /// A term with its position in the input, as bytes [start, end), from parse_expr_spanned. Evaluated as the
+    /// inner node.
+    Spanned {
+        start: usize,
+        end: usize,
+        node: Rc<Node>,
+    },
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -84,23 +186,65 @@ pub enum Oper {
     Minus,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub enum CompareOper {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+impl fmt::Display for Oper {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Oper::Plus => "+",
+            Oper::Minus => "-",
+        })
+    }
+}
+
+impl fmt::Display for CompareOper {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            CompareOper::Lt => "<",
+            CompareOper::Le => "<=",
+            CompareOper::Gt => ">",
+            CompareOper::Ge => ">=",
+            CompareOper::Eq => "==",
+            CompareOper::Ne => "!=",
+        })
+    }
+}
+
+/// The match of a parser: the pointer after it, and the output, the node of the expression or a typed value of the
+/// intermediate parsers, like the operator of a comparison.
 #[derive(Debug)]
-pub struct ParseOk<'a> {
+pub struct ParseOk<'a, T = Node> {
     pub pointer: InputPointer<'a>,
-    pub node: Node,
+    pub node: T,
 }
 
 #[derive(Debug)]
 pub struct ParseErr<'a> {
     pub pointer: InputPointer<'a>,
     pub message: String,
+    /// The input is recognised but malformed, e.g. a duration with a bad unit. A fatal error stops the parsing
+    /// instead of trying the alternatives, so the message and the pointer reach the user as they are.
+    pub fatal: bool,
+    /// What would be accepted at the pointer, like a literal or "duration". Used to report errors and for completion.
+    pub expected: Vec<String>,
+    /// Length of the malformed input at the pointer, like the whole duration out of range, to mark it under the
+    /// input. Zero marks only the position.
+    pub len: usize,
 }
 
 pub trait DisplayParseResult {
     fn to_string(&self) -> String;
 }
 
-impl DisplayParseResult for Result<ParseOk<'_>, ParseErr<'_>> {
+impl<T: fmt::Debug> DisplayParseResult for Result<ParseOk<'_, T>, ParseErr<'_>> {
     fn to_string(&self) -> String {
         match self {
             Ok(parse_ok) => format!("ParseOk({:?}, {})", parse_ok.node, parse_ok.pointer),
@@ -109,11 +253,13 @@ impl DisplayParseResult for Result<ParseOk<'_>, ParseErr<'_>> {
     }
 }
 
-pub trait Parser {
+/// Parser of the input at the pointer. The parsers of the expression return nodes, and the intermediate parsers can
+/// return typed values, which are combined into the nodes with Map.
+pub trait Parser<Output = Node> {
     /// Nesting argument is only for sake of debugging, nothing else.
     fn parse<'a>(
         &self,
         pointer: InputPointer<'a>,
         nesting: usize,
-    ) -> Result<ParseOk<'a>, ParseErr<'a>>;
+    ) -> Result<ParseOk<'a, Output>, ParseErr<'a>>;
 }
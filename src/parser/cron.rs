@@ -0,0 +1,317 @@
+use chrono::{DateTime, Datelike, NaiveDate, TimeDelta, TimeZone};
+
+/// How many days to scan before giving up on finding a matching time. 400 years is the full Gregorian cycle, so
+/// any schedule that can fire at all (e.g. on Feb 29th that is a Monday) fires within that range.
+const MAX_SCAN_DAYS: i64 = 146097;
+
+const MONTH_NAMES: [&str; 12] = [
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+
+const DAY_OF_WEEK_NAMES: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+/// A parsed cron expression with the five classic fields: minute, hour, day of month, month and day of week.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    /// Day of month field was not "*". Together with `days_of_week_restricted` decides if the days are OR-ed.
+    days_of_month_restricted: bool,
+    days_of_week_restricted: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(s: &str) -> Result<CronSchedule, String> {
+        let s = s.trim();
+        let expanded = match s {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            _ => s,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!(
+                "cron expression should have 5 fields (minute hour day-of-month month day-of-week), got {}: {:?}",
+                fields.len(),
+                s
+            ));
+        }
+        let mut days_of_week = parse_field(fields[4], 0, 7, &DAY_OF_WEEK_NAMES, "day of week")?;
+        // Both 0 and 7 are Sunday.
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+        Ok(CronSchedule {
+            minutes: parse_field(fields[0], 0, 59, &[], "minute")?,
+            hours: parse_field(fields[1], 0, 23, &[], "hour")?,
+            days_of_month: parse_field(fields[2], 1, 31, &[], "day of month")?,
+            months: parse_field(fields[3], 1, 12, &MONTH_NAMES, "month")?,
+            days_of_week,
+            days_of_month_restricted: !fields[2].starts_with('*'),
+            days_of_week_restricted: !fields[4].starts_with('*'),
+        })
+    }
+
+    /// Return the first time the schedule fires strictly after `from`. The schedule is evaluated in the time zone
+    /// of `from`.
+    pub fn next_after<T: TimeZone>(&self, from: &DateTime<T>) -> Option<DateTime<T>> {
+        let tz = from.timezone();
+        let start = from.naive_local().date();
+        for day_offset in 0..MAX_SCAN_DAYS {
+            let date = start.checked_add_signed(TimeDelta::days(day_offset))?;
+            if !self.matches_date(&date) {
+                continue;
+            }
+            for hour in (0..24).filter(|h| is_set(self.hours, *h)) {
+                for minute in (0..60).filter(|m| is_set(self.minutes, *m)) {
+                    let candidate = date.and_hms_opt(hour, minute, 0)?;
+                    if let Some(t) = tz.from_local_datetime(&candidate).earliest() {
+                        if t > *from {
+                            return Some(t);
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Return the last time the schedule fired strictly before `from`. The schedule is evaluated in the time zone
+    /// of `from`.
+    pub fn prev_before<T: TimeZone>(&self, from: &DateTime<T>) -> Option<DateTime<T>> {
+        let tz = from.timezone();
+        let start = from.naive_local().date();
+        for day_offset in 0..MAX_SCAN_DAYS {
+            let date = start.checked_sub_signed(TimeDelta::days(day_offset))?;
+            if !self.matches_date(&date) {
+                continue;
+            }
+            for hour in (0..24).rev().filter(|h| is_set(self.hours, *h)) {
+                for minute in (0..60).rev().filter(|m| is_set(self.minutes, *m)) {
+                    let candidate = date.and_hms_opt(hour, minute, 0)?;
+                    if let Some(t) = tz.from_local_datetime(&candidate).earliest() {
+                        if t < *from {
+                            return Some(t);
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn matches_date(&self, date: &NaiveDate) -> bool {
+        if !is_set(self.months, date.month()) {
+            return false;
+        }
+        let dom = is_set(self.days_of_month, date.day());
+        let dow = is_set(self.days_of_week, date.weekday().num_days_from_sunday());
+        // As in Vixie cron, if both day fields are restricted then the day matches if either of them matches.
+        if self.days_of_month_restricted && self.days_of_week_restricted {
+            dom || dow
+        } else {
+            dom && dow
+        }
+    }
+}
+
+fn is_set(mask: u64, bit: u32) -> bool {
+    mask & (1 << bit) != 0
+}
+
+/// Parse a single cron field like "*/15", "1-5", "MON-FRI" or "0,30" into a bit mask.
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str], what: &str) -> Result<u64, String> {
+    let mut mask: u64 = 0;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => {
+                let step = step
+                    .parse::<u32>()
+                    .map_err(|_| format!("bad step {:?} in {} field {:?}", step, what, field))?;
+                if step == 0 {
+                    return Err(format!("step cannot be 0 in {} field {:?}", what, field));
+                }
+                (range, step)
+            }
+            None => (item, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (
+                parse_value(start, min, max, names, what)?,
+                parse_value(end, min, max, names, what)?,
+            )
+        } else {
+            let start = parse_value(range, min, max, names, what)?;
+            // "5/10" means from 5 to the end of range every 10.
+            (start, if item.contains('/') { max } else { start })
+        };
+        if start > end {
+            return Err(format!("bad range {:?} in {} field", range, what));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+fn parse_value(s: &str, min: u32, max: u32, names: &[&str], what: &str) -> Result<u32, String> {
+    let upper = s.to_uppercase();
+    if let Some(index) = names.iter().position(|name| *name == upper) {
+        // Month names start at 1, day of week names at 0.
+        return Ok(index as u32 + min);
+    }
+    let value = s
+        .parse::<u32>()
+        .map_err(|_| format!("bad value {:?} in {} field", s, what))?;
+    if value < min || value > max {
+        return Err(format!(
+            "value {} out of range {}-{} in {} field",
+            value, min, max, what
+        ));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CronSchedule;
+    use crate::parser::zone::{Tz, UTC};
+
+    #[test]
+    fn next_every_15_minutes() {
+        check_next(
+            "*/15 * * * *",
+            "2024-01-01T10:07:00Z",
+            "2024-01-01T10:15:00Z",
+        );
+        check_next(
+            "*/15 * * * *",
+            "2024-01-01T10:15:00Z",
+            "2024-01-01T10:30:00Z",
+        );
+        check_next(
+            "*/15 * * * *",
+            "2024-01-01T23:59:30Z",
+            "2024-01-02T00:00:00Z",
+        );
+    }
+
+    #[test]
+    fn next_weekday_names() {
+        // 2024-01-06 is Saturday.
+        check_next(
+            "0 9 * * MON-FRI",
+            "2024-01-06T12:00:00Z",
+            "2024-01-08T09:00:00Z",
+        );
+        check_next(
+            "30 8 * JAN sun",
+            "2024-01-06T12:00:00Z",
+            "2024-01-07T08:30:00Z",
+        );
+    }
+
+    #[test]
+    fn next_sunday_as_7() {
+        check_next("0 0 * * 7", "2024-01-01T00:00:00Z", "2024-01-07T00:00:00Z");
+    }
+
+    #[test]
+    fn next_day_of_month_or_day_of_week() {
+        // Both fields restricted, so either the 15th or a Friday matches. 2024-01-05 is Friday.
+        check_next(
+            "0 0 15 * FRI",
+            "2024-01-01T00:00:00Z",
+            "2024-01-05T00:00:00Z",
+        );
+    }
+
+    #[test]
+    fn next_leap_day() {
+        check_next("0 0 29 2 *", "2024-03-01T00:00:00Z", "2028-02-29T00:00:00Z");
+    }
+
+    #[test]
+    fn next_macro() {
+        check_next("@daily", "2024-01-01T10:00:00Z", "2024-01-02T00:00:00Z");
+    }
+
+    #[test]
+    #[cfg(feature = "tz")]
+    fn next_in_timezone() {
+        let from =
+            parse("2024-01-01T10:00:00Z").with_timezone(&Tz::from(chrono_tz::Europe::Warsaw));
+        let actual = CronSchedule::parse("0 12 * * *")
+            .unwrap()
+            .next_after(&from)
+            .unwrap();
+        assert_eq!(actual, parse("2024-01-01T11:00:00Z"));
+    }
+
+    #[test]
+    fn prev_every_15_minutes() {
+        check_prev(
+            "*/15 * * * *",
+            "2024-01-01T10:07:00Z",
+            "2024-01-01T10:00:00Z",
+        );
+        check_prev(
+            "*/15 * * * *",
+            "2024-01-01T10:00:00Z",
+            "2024-01-01T09:45:00Z",
+        );
+        check_prev("0 0 1 * *", "2024-01-01T00:00:00Z", "2023-12-01T00:00:00Z");
+    }
+
+    #[test]
+    fn never_fires() {
+        let schedule = CronSchedule::parse("0 0 30 2 *").unwrap();
+        assert_eq!(schedule.next_after(&parse("2024-01-01T00:00:00Z")), None);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(CronSchedule::parse("* * * *").is_err());
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
+        assert!(CronSchedule::parse("5-1 * * * *").is_err());
+        assert!(CronSchedule::parse("* * * FOO *").is_err());
+    }
+
+    fn check_next(expr: &str, from: &str, expected: &str) {
+        let schedule = CronSchedule::parse(expr).unwrap();
+        assert_eq!(
+            schedule.next_after(&parse(from)),
+            Some(parse(expected)),
+            "{} after {}",
+            expr,
+            from
+        );
+    }
+
+    fn check_prev(expr: &str, from: &str, expected: &str) {
+        let schedule = CronSchedule::parse(expr).unwrap();
+        assert_eq!(
+            schedule.prev_before(&parse(from)),
+            Some(parse(expected)),
+            "{} before {}",
+            expr,
+            from
+        );
+    }
+
+    fn parse(s: &str) -> chrono::DateTime<Tz> {
+        chrono::DateTime::parse_from_rfc3339(s)
+            .unwrap()
+            .with_timezone(&UTC)
+    }
+}
@@ -1,80 +1,293 @@
+use crate::syntax::{
+    bad_part_message, parse_clock_duration, parse_duration, scan_clock_duration, scan_duration,
+    unit_nanos, Duration,
+};
 use chrono::TimeDelta;
 
-pub const NS: i64 = 1;
-pub const US_NS: i64 = 1000 * NS;
-pub const MS_NS: i64 = 1000 * US_NS;
-pub const SECOND_NS: i64 = 1000 * MS_NS;
-pub const MINUTE_NS: i64 = 60 * SECOND_NS;
-pub const HOUR_NS: i64 = 60 * MINUTE_NS;
-pub const DAY_NS: i64 = 24 * HOUR_NS;
+pub use crate::syntax::{
+    DAY_NS, DURATION_UNITS, HOUR_NS, MINUTE_NS, MS_NS, NS, SECOND_NS, US_NS, WEEK_NS, YEAR_NS,
+};
 
-const RE_DURATION: &str = r"^(?<neg>-)?((?<days>\d+)d)?((?<hours>\d+)h)?((?<minutes>\d+)m)?((?<secs>\d+)s)?((?<msecs>\d+)ms)?((?<usecs>\d+)us)?((?<nsecs>\d+)ns)?";
+/// The duration of the syntax module as TimeDelta, which has the same range.
+pub fn time_delta(duration: Duration) -> TimeDelta {
+    TimeDelta::new(duration.secs, duration.nanos).unwrap()
+}
+
+/// Units of the Prometheus duration syntax, from the largest.
+const METRIC_UNITS: [&str; 7] = ["y", "w", "d", "h", "m", "s", "ms"];
 
 pub trait ShortFormat {
     fn from_short_format(s: &str) -> Result<TimeDelta, String>;
     fn as_short_format(&self) -> String;
 }
 
-pub fn match_duration(s: &str) -> Option<&str> {
-    let re = regex::Regex::new(RE_DURATION).unwrap();
-    let m = re.find(s)?;
-    if m.as_str().is_empty() {
-        None
+/// Match duration at the beginning of the input. Return error if the duration is directly followed by letters or
+/// digits, like "1dxyz", so the "1d" prefix is not silently matched.
+pub fn match_duration(s: &str) -> Result<Option<&str>, String> {
+    let m = if let Some(len) = scan_duration(s) {
+        &s[..len]
     } else {
-        Some(m.as_str())
+        return Ok(None);
+    };
+    let rest = &s[m.len()..];
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    let next = if let Some(next) = rest.chars().next() {
+        next
+    } else {
+        return Ok(Some(m));
+    };
+    if !is_word_char(next) {
+        return Ok(Some(m));
+    }
+    // The word spans the fractions, so the bad part is reported whole, e.g. "2.5x" in "1h2.5x".
+    let word_end = rest
+        .find(|c| !(is_word_char(c) || c == '.'))
+        .unwrap_or(rest.len())
+        + m.len();
+    let word = &s[..word_end];
+    if next.is_ascii_digit() {
+        return Err(bad_part_message(word, &s[m.len()..word_end]));
     }
+    // Point at the last number-unit part, so the whole bad unit is reported, e.g. "dxyz" for "1dxyz" or "daysx" for
+    // "1 daysx".
+    let last_part_start = m
+        .trim_end_matches(|c: char| c.is_alphabetic())
+        .trim_end_matches(' ')
+        .trim_end_matches(|c: char| c.is_ascii_digit() || c == '.')
+        .len();
+    Err(bad_part_message(word, &s[last_part_start..word_end]))
 }
 
-impl ShortFormat for TimeDelta {
-    fn from_short_format(s: &str) -> Result<TimeDelta, String> {
-        let pat = regex::Regex::new(RE_DURATION).unwrap();
-        let caps = if let Some(caps) = pat.captures(s) {
-            if caps.get(0).unwrap().len() != s.len() {
-                return Err(format!("did not match entire input {:?}", s));
+fn unit_to_ns(unit: &str) -> i64 {
+    unit_nanos(unit).unwrap_or_else(|| panic!("BUG! unexpected duration unit {:?}", unit))
+}
+
+pub trait ClockFormat {
+    fn from_clock_format(s: &str) -> Result<TimeDelta, String>;
+    fn as_clock_format(&self) -> String;
+}
+
+/// Duration as decimal number of seconds, like the epoch seconds of the date-times, or of another unit.
+pub trait SecondsFormat {
+    /// Format as seconds with milliseconds, like "-0.500".
+    fn as_seconds_format(&self) -> String {
+        self.as_seconds_format_with(3, Rounding::Trunc)
+    }
+    /// Format as whole seconds, truncated toward zero, like "-1" for -1.5s and "0" for -500ms.
+    fn as_full_seconds_format(&self) -> String {
+        self.as_seconds_format_with(0, Rounding::Trunc)
+    }
+    /// Format as seconds with the number of fractional digits, up to 9, like "-0.5" for -500ms with 1 digit. The
+    /// magnitude is rounded, so truncation is toward zero.
+    fn as_seconds_format_with(&self, digits: usize, rounding: Rounding) -> String {
+        self.as_unit_format("s", digits, rounding)
+    }
+    /// Format as decimal number of the unit from DURATION_UNITS, like "1.50" for 90m in hours with 2 digits. The
+    /// magnitude is rounded, so truncation is toward zero.
+    fn as_unit_format(&self, unit: &str, digits: usize, rounding: Rounding) -> String;
+}
+
+/// Duration in the Prometheus syntax, for metrics systems and their configuration.
+pub trait MetricFormat {
+    /// Format as integer number of the largest unit that fits exactly, like "90m" for 1h30m and "1500ms" for 1.5s.
+    /// The parts below millisecond are rounded.
+    fn as_metric_format(&self, rounding: Rounding) -> String;
+}
+
+/// Duration as Go prints time.Duration, common in the configuration of Prometheus and Kubernetes.
+pub trait GoFormat {
+    /// Format like "1h30m0s", with fraction of second like "1.5s", and below second in the largest unit with
+    /// fraction, like "1.5µs".
+    fn as_go_format(&self) -> String;
+}
+
+/// How to drop the digits beyond the precision of the seconds output.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Rounding {
+    /// Drop the digits. Date-times are rounded down, so the output is within the second of the date-time.
+    #[default]
+    Trunc,
+    /// Round to the nearest, and the halves to the even digit (banker's rounding), so they do not drift up on average.
+    HalfEven,
+}
+
+impl Rounding {
+    /// Round the nanoseconds to a multiple of the unit. Truncation rounds down, toward negative infinity.
+    pub fn apply(self, nanos: i128, unit: i128) -> i128 {
+        let (quotient, remainder) = (nanos.div_euclid(unit), nanos.rem_euclid(unit));
+        let round_up = match self {
+            Rounding::Trunc => false,
+            Rounding::HalfEven => {
+                2 * remainder > unit || (2 * remainder == unit && quotient % 2 != 0)
             }
-            caps
-        } else {
-            return Err(format!("could not match {:?}", s));
         };
+        (quotient + i128::from(round_up)) * unit
+    }
+}
 
-        let mut total_nanos: i64 = 0;
-        let mut consume_group = |name, multiplier: i64| {
-            let value = caps
-                .name(name)
-                .map(|m| m.as_str())
-                .unwrap_or("0")
-                .parse::<i64>()
-                .map_err(|e| e.to_string())
-                .expect("failed to parse int");
-            total_nanos = total_nanos + (value * multiplier);
-        };
-        consume_group("days", DAY_NS);
-        consume_group("hours", HOUR_NS);
-        consume_group("minutes", MINUTE_NS);
-        consume_group("secs", SECOND_NS);
-        consume_group("msecs", MS_NS);
-        consume_group("usecs", US_NS);
-        consume_group("nsecs", NS);
-        if caps.name("neg").is_some() {
-            total_nanos = total_nanos * -1;
+/// Split the duration into the sign and the magnitude in nanoseconds. All the formats render the sign once, in
+/// front, and only for a non-zero magnitude, so -500ms is "-500ms" in the short format and never "-0s" when rounded.
+/// The magnitude is wider than i64, so durations above 292 years do not overflow.
+fn sign_and_magnitude(delta: &TimeDelta) -> (&'static str, u128) {
+    let nanos =
+        i128::from(delta.num_seconds()) * i128::from(SECOND_NS) + i128::from(delta.subsec_nanos());
+    (if nanos < 0 { "-" } else { "" }, nanos.unsigned_abs())
+}
+
+/// Format the magnitude in nanoseconds as seconds with the number of fractional digits, up to 9, rounded with the
+/// policy, like "1.500" for 1.5s with 3 digits. Without floats, so no precision is lost for large values.
+pub fn nanos_as_seconds(ns: u128, digits: usize, rounding: Rounding) -> String {
+    nanos_as_decimal(ns, SECOND_NS as u128, digits, rounding)
+}
+
+/// Format the magnitude in nanoseconds as decimal number of the unit with the number of fractional digits, up to 9,
+/// rounded with the policy.
+fn nanos_as_decimal(ns: u128, unit_ns: u128, digits: usize, rounding: Rounding) -> String {
+    let per_unit = 10u128.pow(digits as u32);
+    // The magnitude is below 2^64 seconds, so it fits i128 with the 9 digits.
+    let scaled = (ns * per_unit) as i128;
+    let unit_ns = unit_ns as i128;
+    let value = (rounding.apply(scaled, unit_ns) / unit_ns) as u128;
+    if digits == 0 {
+        value.to_string()
+    } else {
+        format!(
+            "{}.{:0width$}",
+            value / per_unit,
+            value % per_unit,
+            width = digits
+        )
+    }
+}
+
+/// Prefix the formatted magnitude with the sign, dropping the sign when the magnitude rendered as zero.
+fn with_sign(sign: &str, magnitude: String) -> String {
+    if magnitude.chars().all(|c| matches!(c, '0' | '.' | ':')) {
+        magnitude
+    } else {
+        format!("{}{}", sign, magnitude)
+    }
+}
+
+/// Match stopwatch-style duration like "01:30:00" or "90:00" at the beginning of the input. The duration should not
+/// be directly followed by more digits, letters or colons.
+pub fn match_clock_duration(s: &str) -> Option<&str> {
+    let m = &s[..scan_clock_duration(s)?.input_len()];
+    match s[m.len()..].chars().next() {
+        Some(next) if next.is_alphanumeric() || next == '_' || next == ':' || next == '.' => None,
+        _ => Some(m),
+    }
+}
+
+impl ClockFormat for TimeDelta {
+    /// Parse HH:MM:SS or MM:SS. The leading field is not limited, so "90:00" is 90 minutes and "36:00:00" is 36
+    /// hours, but minutes and seconds after it should be below 60.
+    fn from_clock_format(s: &str) -> Result<TimeDelta, String> {
+        parse_clock_duration(s).map(time_delta)
+    }
+
+    /// Format as H:MM:SS, with the hours not padded and above 24 for long durations, like "0:00:05" or "26:03:04".
+    /// The fraction of second, only if not zero, has 3 digits of milliseconds, like "-0:00:00.500", and 6 or 9 digits
+    /// only if there are microseconds or nanoseconds.
+    fn as_clock_format(&self) -> String {
+        let (sign, ns) = sign_and_magnitude(self);
+        let hours = ns / HOUR_NS as u128;
+        let minutes = ns % HOUR_NS as u128 / MINUTE_NS as u128;
+        let seconds = ns % MINUTE_NS as u128 / SECOND_NS as u128;
+        let frac_nanos = ns % SECOND_NS as u128;
+        let mut s = format!("{}:{:02}:{:02}", hours, minutes, seconds);
+        if frac_nanos != 0 {
+            let frac = format!("{:09}", frac_nanos);
+            let digits = match (frac_nanos % MS_NS as u128, frac_nanos % US_NS as u128) {
+                (0, _) => 3,
+                (_, 0) => 6,
+                _ => 9,
+            };
+            s += ".";
+            s += &frac[..digits];
         }
-        Ok(chrono::TimeDelta::nanoseconds(total_nanos))
+        with_sign(sign, s)
     }
+}
 
-    fn as_short_format(&self) -> String {
-        let mut ns = self.num_nanoseconds().unwrap();
-        let mut neg = false;
-        if ns < 0 {
-            ns = -ns;
-            neg = true;
+impl SecondsFormat for TimeDelta {
+    fn as_unit_format(&self, unit: &str, digits: usize, rounding: Rounding) -> String {
+        let (sign, ns) = sign_and_magnitude(self);
+        let unit_ns = unit_to_ns(unit) as u128;
+        with_sign(sign, nanos_as_decimal(ns, unit_ns, digits, rounding))
+    }
+}
+
+impl MetricFormat for TimeDelta {
+    fn as_metric_format(&self, rounding: Rounding) -> String {
+        let (sign, ns) = sign_and_magnitude(self);
+        let ns = rounding.apply(ns as i128, MS_NS as i128) as u128;
+        if ns == 0 {
+            return "0s".to_string();
         }
+        let unit = METRIC_UNITS
+            .into_iter()
+            .find(|unit| ns.is_multiple_of(unit_to_ns(unit) as u128))
+            .unwrap();
+        format!("{}{}{}", sign, ns / unit_to_ns(unit) as u128, unit)
+    }
+}
+
+/// Format the magnitude in nanoseconds as decimal number of the unit, which is a power of 10, with as many
+/// fractional digits as needed, like "1.5" for 1500ns in microseconds.
+fn nanos_as_trimmed_decimal(ns: u128, unit_ns: i64) -> String {
+    let unit_ns = unit_ns as u128;
+    let (whole, frac) = (ns / unit_ns, ns % unit_ns);
+    if frac == 0 {
+        return whole.to_string();
+    }
+    let frac = format!("{:0width$}", frac, width = unit_ns.ilog10() as usize);
+    format!("{}.{}", whole, frac.trim_end_matches('0'))
+}
+
+impl GoFormat for TimeDelta {
+    fn as_go_format(&self) -> String {
+        let (sign, ns) = sign_and_magnitude(self);
+        let s = if ns == 0 {
+            return "0s".to_string();
+        } else if ns < US_NS as u128 {
+            format!("{}ns", ns)
+        } else if ns < MS_NS as u128 {
+            format!("{}µs", nanos_as_trimmed_decimal(ns, US_NS))
+        } else if ns < SECOND_NS as u128 {
+            format!("{}ms", nanos_as_trimmed_decimal(ns, MS_NS))
+        } else {
+            let hours = ns / HOUR_NS as u128;
+            let minutes = ns % HOUR_NS as u128 / MINUTE_NS as u128;
+            let seconds = ns % MINUTE_NS as u128;
+            let mut s = String::new();
+            if hours > 0 {
+                s += &format!("{}h", hours);
+            }
+            if ns >= MINUTE_NS as u128 {
+                s += &format!("{}m", minutes);
+            }
+            s + &format!("{}s", nanos_as_trimmed_decimal(seconds, SECOND_NS))
+        };
+        format!("{}{}", sign, s)
+    }
+}
+
+impl ShortFormat for TimeDelta {
+    fn from_short_format(s: &str) -> Result<TimeDelta, String> {
+        parse_duration(s)
+            .map(time_delta)
+            .map_err(|err| err.to_string())
+    }
 
+    fn as_short_format(&self) -> String {
+        let (sign, mut ns) = sign_and_magnitude(self);
         let mut consume = |part_in_ns: i64| {
-            let c = ns / part_in_ns;
-            ns = ns - c * part_in_ns;
+            let c = ns / part_in_ns as u128;
+            ns -= c * part_in_ns as u128;
             c
         };
-        let mut s = String::from(if neg { "-" } else { "" });
+        let mut s = String::new();
         let mut display = |val, symbol| {
             if val != 0 {
                 s += format!("{}{}", val, symbol).as_str();
@@ -94,30 +307,22 @@ impl ShortFormat for TimeDelta {
         display(micros, "us");
         let nanos = consume(NS);
         display(nanos, "ns");
-        if s == "" {
-            s = "0s".to_string();
+        if s.is_empty() {
+            return "0s".to_string();
         }
-        s.to_string()
+        format!("{}{}", sign, s)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::error::Error;
-
-    use super::ShortFormat;
+    use super::{ClockFormat, GoFormat, MetricFormat, Rounding, SecondsFormat, ShortFormat};
     use crate::parser::duration::*;
 
     #[test]
     fn format_large() {
         let d = chrono::TimeDelta::nanoseconds(
-            1 * DAY_NS
-                + 2 * HOUR_NS
-                + 3 * MINUTE_NS
-                + 4 * SECOND_NS
-                + 5 * MS_NS
-                + 6 * US_NS
-                + 7 * NS,
+            DAY_NS + 2 * HOUR_NS + 3 * MINUTE_NS + 4 * SECOND_NS + 5 * MS_NS + 6 * US_NS + 7 * NS,
         );
         assert_eq!("1d2h3m4s5ms6us7ns", d.as_short_format());
     }
@@ -126,13 +331,7 @@ mod tests {
     fn parse_large() {
         let actual = chrono::TimeDelta::from_short_format("1d2h3m4s5ms6us7ns").unwrap();
         let d = chrono::TimeDelta::nanoseconds(
-            1 * DAY_NS
-                + 2 * HOUR_NS
-                + 3 * MINUTE_NS
-                + 4 * SECOND_NS
-                + 5 * MS_NS
-                + 6 * US_NS
-                + 7 * NS,
+            DAY_NS + 2 * HOUR_NS + 3 * MINUTE_NS + 4 * SECOND_NS + 5 * MS_NS + 6 * US_NS + 7 * NS,
         );
         assert_eq!(actual, d);
     }
@@ -197,4 +396,256 @@ mod tests {
     fn fail_on_not_full_match() {
         assert!(TimeDelta::from_short_format("1dxxx").is_err());
     }
+
+    #[test]
+    fn parse_mixed_order() {
+        let actual = TimeDelta::from_short_format("2s1h").unwrap();
+        assert_eq!(actual, TimeDelta::nanoseconds(HOUR_NS + 2 * SECOND_NS));
+    }
+
+    #[test]
+    fn parse_repeated_units() {
+        let actual = TimeDelta::from_short_format("1h30m1h").unwrap();
+        assert_eq!(actual, TimeDelta::nanoseconds(2 * HOUR_NS + 30 * MINUTE_NS));
+    }
+
+    #[test]
+    fn fail_on_bad_unit() {
+        let err = TimeDelta::from_short_format("1h2x").unwrap_err();
+        assert!(err.contains("\"x\""), "{}", err);
+        assert!(TimeDelta::from_short_format("1h2").is_err());
+        assert!(TimeDelta::from_short_format("-").is_err());
+        assert!(TimeDelta::from_short_format("").is_err());
+    }
+
+    #[test]
+    fn match_duration_word_boundary() {
+        assert_eq!(match_duration("1d + 1h"), Ok(Some("1d")));
+        assert_eq!(match_duration("1d2h)"), Ok(Some("1d2h")));
+        assert_eq!(match_duration("1d"), Ok(Some("1d")));
+        assert_eq!(match_duration("123"), Ok(None));
+        assert_eq!(match_duration("x1d"), Ok(None));
+        let err = match_duration("1dxyz + 1h").unwrap_err();
+        assert!(err.contains("\"dxyz\""), "{}", err);
+        let err = match_duration("1h2mx").unwrap_err();
+        assert!(err.contains("\"mx\""), "{}", err);
+        let err = match_duration("1m30").unwrap_err();
+        assert!(err.contains("missing duration unit"), "{}", err);
+    }
+
+    #[test]
+    fn fail_on_overflow() {
+        assert!(TimeDelta::from_short_format("99999999999999999999d").is_err());
+        assert!(TimeDelta::from_short_format("999999999999d").is_err());
+        assert!(TimeDelta::from_short_format("300y").is_ok());
+    }
+
+    #[test]
+    fn parse_clock_format() {
+        assert_eq!(
+            TimeDelta::from_clock_format("01:30:00"),
+            Ok(TimeDelta::nanoseconds(HOUR_NS + 30 * MINUTE_NS))
+        );
+        assert_eq!(
+            TimeDelta::from_clock_format("90:00"),
+            Ok(TimeDelta::nanoseconds(90 * MINUTE_NS))
+        );
+        assert_eq!(
+            TimeDelta::from_clock_format("-36:00:01.5"),
+            Ok(TimeDelta::nanoseconds(
+                -(36 * HOUR_NS + SECOND_NS + 500 * MS_NS)
+            ))
+        );
+        assert!(TimeDelta::from_clock_format("01:60:00").is_err());
+        assert!(TimeDelta::from_clock_format("10:61").is_err());
+        assert!(TimeDelta::from_clock_format("1:2").is_err());
+    }
+
+    #[test]
+    fn format_clock_format() {
+        let d = TimeDelta::nanoseconds(DAY_NS + 2 * HOUR_NS + 3 * MINUTE_NS + 4 * SECOND_NS);
+        assert_eq!(d.as_clock_format(), "26:03:04");
+        let d = TimeDelta::nanoseconds(-(90 * MINUTE_NS + 250 * MS_NS));
+        assert_eq!(d.as_clock_format(), "-1:30:00.250");
+        assert_eq!(TimeDelta::zero().as_clock_format(), "0:00:00");
+        let d = TimeDelta::nanoseconds(5 * SECOND_NS + 2 * US_NS);
+        assert_eq!(d.as_clock_format(), "0:00:05.000002");
+        assert_eq!(TimeDelta::from_clock_format("0:00:05.000002"), Ok(d));
+    }
+
+    #[test]
+    fn format_tiny_negative() {
+        let d = TimeDelta::nanoseconds(-500 * MS_NS);
+        assert_eq!(d.as_short_format(), "-500ms");
+        assert_eq!(d.as_clock_format(), "-0:00:00.500");
+        assert_eq!(d.as_seconds_format(), "-0.500");
+        assert_eq!(d.as_full_seconds_format(), "0");
+        let d = TimeDelta::nanoseconds(-NS);
+        assert_eq!(d.as_short_format(), "-1ns");
+        assert_eq!(d.as_clock_format(), "-0:00:00.000000001");
+        assert_eq!(d.as_seconds_format(), "0.000");
+        assert_eq!(d.as_full_seconds_format(), "0");
+        let d = TimeDelta::nanoseconds(-1500 * MS_NS);
+        assert_eq!(d.as_seconds_format(), "-1.500");
+        assert_eq!(d.as_full_seconds_format(), "-1");
+        let d = TimeDelta::zero();
+        assert_eq!(d.as_seconds_format(), "0.000");
+        assert_eq!(d.as_full_seconds_format(), "0");
+    }
+
+    #[test]
+    fn format_seconds_digits() {
+        let d = TimeDelta::nanoseconds(-(1500 * MS_NS + 123));
+        assert_eq!(d.as_seconds_format_with(9, Rounding::Trunc), "-1.500000123");
+        assert_eq!(d.as_seconds_format_with(6, Rounding::Trunc), "-1.500000");
+        assert_eq!(d.as_seconds_format_with(1, Rounding::Trunc), "-1.5");
+        assert_eq!(d.as_seconds_format_with(0, Rounding::Trunc), "-1");
+        let d = TimeDelta::nanoseconds(-NS);
+        assert_eq!(d.as_seconds_format_with(9, Rounding::Trunc), "-0.000000001");
+        assert_eq!(d.as_seconds_format_with(6, Rounding::Trunc), "0.000000");
+        assert_eq!(
+            TimeDelta::days(200_000).as_seconds_format_with(9, Rounding::Trunc),
+            "17280000000.000000000"
+        );
+    }
+
+    #[test]
+    fn format_unit() {
+        let d = TimeDelta::nanoseconds(90 * MINUTE_NS);
+        assert_eq!(d.as_unit_format("h", 2, Rounding::Trunc), "1.50");
+        assert_eq!(d.as_unit_format("m", 0, Rounding::Trunc), "90");
+        assert_eq!(d.as_unit_format("s", 1, Rounding::Trunc), "5400.0");
+        assert_eq!(d.as_unit_format("d", 4, Rounding::Trunc), "0.0625");
+        assert_eq!(d.as_unit_format("us", 0, Rounding::Trunc), "5400000000");
+        let d = TimeDelta::nanoseconds(-MINUTE_NS);
+        assert_eq!(d.as_unit_format("h", 3, Rounding::Trunc), "-0.016");
+        assert_eq!(d.as_unit_format("h", 3, Rounding::HalfEven), "-0.017");
+        assert_eq!(d.as_unit_format("h", 1, Rounding::HalfEven), "0.0");
+        assert_eq!(
+            TimeDelta::max_value().as_unit_format("ns", 9, Rounding::HalfEven),
+            "9223372036854775807000000.000000000"
+        );
+    }
+
+    #[test]
+    fn format_seconds_rounding() {
+        let check = |ms: i64, digits: usize, trunc: &str, half_even: &str| {
+            let d = TimeDelta::milliseconds(ms);
+            assert_eq!(
+                d.as_seconds_format_with(digits, Rounding::Trunc),
+                trunc,
+                "{}ms",
+                ms
+            );
+            assert_eq!(
+                d.as_seconds_format_with(digits, Rounding::HalfEven),
+                half_even,
+                "{}ms",
+                ms
+            );
+        };
+        check(1500, 0, "1", "2");
+        check(2500, 0, "2", "2");
+        check(2501, 0, "2", "3");
+        check(-1500, 0, "-1", "-2");
+        check(-2500, 0, "-2", "-2");
+        check(-400, 0, "0", "0");
+        check(999, 2, "0.99", "1.00");
+        check(1250, 1, "1.2", "1.2");
+        check(1350, 1, "1.3", "1.4");
+    }
+
+    #[test]
+    fn parse_weeks_and_years() {
+        assert_eq!(
+            TimeDelta::from_short_format("1y2w").unwrap(),
+            TimeDelta::days(365 + 14)
+        );
+        assert_eq!(match_duration("2w + 1d"), Ok(Some("2w")));
+    }
+
+    #[test]
+    fn format_metric() {
+        let check = |ns: i64, expected: &str| {
+            let d = TimeDelta::nanoseconds(ns);
+            assert_eq!(d.as_metric_format(Rounding::Trunc), expected, "{}ns", ns);
+        };
+        check(5400 * SECOND_NS, "90m");
+        check(5401 * SECOND_NS, "5401s");
+        check(1500 * MS_NS, "1500ms");
+        check(2 * DAY_NS, "2d");
+        check(14 * DAY_NS, "2w");
+        check(2 * YEAR_NS, "2y");
+        check(-90 * SECOND_NS, "-90s");
+        check(0, "0s");
+        check(999 * US_NS, "0s");
+        check(1500 * MS_NS + 700 * US_NS, "1500ms");
+        let d = TimeDelta::nanoseconds(1500 * MS_NS + 700 * US_NS);
+        assert_eq!(d.as_metric_format(Rounding::HalfEven), "1501ms");
+        // The output parses back as the same duration.
+        let d = TimeDelta::nanoseconds(3 * WEEK_NS + HOUR_NS);
+        let metric = d.as_metric_format(Rounding::Trunc);
+        assert_eq!(metric, "505h");
+        assert_eq!(TimeDelta::from_short_format(&metric), Ok(d));
+    }
+
+    #[test]
+    fn parse_go_format() {
+        let check = |s: &str, ns: i64| {
+            assert_eq!(
+                TimeDelta::from_short_format(s),
+                Ok(TimeDelta::nanoseconds(ns)),
+                "{}",
+                s
+            );
+        };
+        check("1h30m0s", HOUR_NS + 30 * MINUTE_NS);
+        check("1.5µs", 1500);
+        check("1.5μs", 1500);
+        check("-1.5h", -(HOUR_NS + 30 * MINUTE_NS));
+        check("2m0.25s", 2 * MINUTE_NS + 250 * MS_NS);
+        check("0.1234567899s", 123456789);
+        assert_eq!(match_duration("1.5s + 1s"), Ok(Some("1.5s")));
+        assert_eq!(match_duration("1.5 + 1s"), Ok(None));
+        let err = match_duration("1h2.5x").unwrap_err();
+        assert!(err.contains("\"x\""), "{}", err);
+    }
+
+    #[test]
+    fn format_go_format() {
+        let check = |ns: i64, expected: &str| {
+            let d = TimeDelta::nanoseconds(ns);
+            assert_eq!(d.as_go_format(), expected, "{}ns", ns);
+            assert_eq!(TimeDelta::from_short_format(expected), Ok(d));
+        };
+        check(0, "0s");
+        check(1, "1ns");
+        check(1500, "1.5µs");
+        check(1100 * US_NS, "1.1ms");
+        check(SECOND_NS, "1s");
+        check(1500 * MS_NS, "1.5s");
+        check(MINUTE_NS, "1m0s");
+        check(HOUR_NS + 30 * MINUTE_NS, "1h30m0s");
+        check(HOUR_NS + 5 * SECOND_NS + 1, "1h0m5.000000001s");
+        check(2 * DAY_NS, "48h0m0s");
+        check(-1500, "-1.5µs");
+        check(-90 * SECOND_NS, "-1m30s");
+    }
+
+    #[test]
+    fn format_beyond_nanoseconds_range() {
+        let d = TimeDelta::days(-200_000);
+        assert_eq!(d.as_short_format(), "-200000d");
+        assert_eq!(d.as_clock_format(), "-4800000:00:00");
+        assert_eq!(d.as_full_seconds_format(), "-17280000000");
+    }
+
+    #[test]
+    fn match_clock_duration_boundary() {
+        assert_eq!(match_clock_duration("01:30:00 + 1h"), Some("01:30:00"));
+        assert_eq!(match_clock_duration("90:00)"), Some("90:00"));
+        assert_eq!(match_clock_duration("90:00x"), None);
+        assert_eq!(match_clock_duration("1:30"), Some("1:30"));
+        assert_eq!(match_clock_duration("01:30:00:00"), None);
+    }
 }
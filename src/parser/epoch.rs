@@ -0,0 +1,152 @@
+use super::zone::Tz;
+use chrono::DateTime;
+
+use super::number::{split_scaled, SCALE};
+
+/// Platform timestamp counted in ticks of fixed length since some other epoch than Unix.
+#[derive(Debug)]
+pub struct AltEpoch {
+    /// Used in the function names, like "from_cocoa" and "to_cocoa".
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Seconds from the Unix epoch to this epoch, negative for epochs before 1970.
+    unix_offset_seconds: i64,
+    tick_ns: i128,
+    /// Can the timestamps be negative, i.e. before the epoch.
+    signed: bool,
+}
+
+/// The registry of the alternate epochs. Each of them gets "from_" and "to_" functions.
+pub const EPOCHS: [AltEpoch; 3] = [
+    AltEpoch {
+        name: "filetime",
+        description: "Windows FILETIME, 100ns ticks since 1601-01-01",
+        unix_offset_seconds: -11_644_473_600,
+        tick_ns: 100,
+        signed: false,
+    },
+    AltEpoch {
+        name: "cocoa",
+        description: "Cocoa / Core Data, seconds since 2001-01-01",
+        unix_offset_seconds: 978_307_200,
+        tick_ns: 1_000_000_000,
+        signed: true,
+    },
+    AltEpoch {
+        name: "hfs",
+        description: "Mac HFS+, seconds since 1904-01-01",
+        unix_offset_seconds: -2_082_844_800,
+        tick_ns: 1_000_000_000,
+        signed: false,
+    },
+];
+
+pub fn find_epoch(name: &str) -> Option<&'static AltEpoch> {
+    EPOCHS.iter().find(|epoch| epoch.name == name)
+}
+
+/// Names of all the "from_" and "to_" functions of the registered epochs.
+pub fn epoch_func_names() -> Vec<String> {
+    EPOCHS
+        .iter()
+        .flat_map(|epoch| [format!("from_{}", epoch.name), format!("to_{}", epoch.name)])
+        .collect()
+}
+
+impl AltEpoch {
+    /// Convert number of ticks, fixed-point scaled by number::SCALE, to date-time.
+    pub fn ticks_to_datetime(&self, ticks: i128) -> Result<DateTime<Tz>, String> {
+        let out_of_range = || {
+            format!(
+                "{} timestamp {} is out of range",
+                self.name,
+                ticks as f64 / SCALE as f64
+            )
+        };
+        if ticks < 0 && !self.signed {
+            return Err(format!(
+                "{} timestamp cannot be negative, was: {}",
+                self.name,
+                ticks as f64 / SCALE as f64
+            ));
+        }
+        let unix_nanos = ticks
+            .checked_mul(self.tick_ns)
+            .map(|ns| ns / SCALE)
+            .and_then(|ns| ns.checked_add(i128::from(self.unix_offset_seconds) * SCALE))
+            .ok_or_else(out_of_range)?;
+        let (secs, nsecs) = split_scaled(unix_nanos).map_err(|_| out_of_range())?;
+        let datetime = DateTime::from_timestamp(secs, nsecs).ok_or_else(out_of_range)?;
+        Ok(datetime.with_timezone(&super::zone::UTC))
+    }
+
+    /// Convert date-time to whole ticks since the epoch.
+    pub fn datetime_to_ticks(&self, datetime: &DateTime<Tz>) -> Result<i128, String> {
+        let unix_nanos = i128::from(datetime.timestamp()) * SCALE
+            + i128::from(datetime.timestamp_subsec_nanos());
+        let ticks =
+            (unix_nanos - i128::from(self.unix_offset_seconds) * SCALE).div_euclid(self.tick_ns);
+        if ticks < 0 && !self.signed {
+            return Err(format!("{} is before the {} epoch", datetime, self.name));
+        }
+        Ok(ticks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{epoch_func_names, find_epoch};
+    use crate::parser::number::SCALE;
+
+    #[test]
+    fn round_trip() {
+        for name in ["filetime", "cocoa", "hfs"] {
+            let epoch = find_epoch(name).unwrap();
+            let datetime = epoch.ticks_to_datetime(1_000_000 * SCALE).unwrap();
+            assert_eq!(
+                epoch.datetime_to_ticks(&datetime),
+                Ok(1_000_000),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn epoch_start() {
+        let check = |name: &str, expected: &str| {
+            let datetime = find_epoch(name).unwrap().ticks_to_datetime(0).unwrap();
+            assert_eq!(datetime.to_rfc3339(), expected);
+        };
+        check("filetime", "1601-01-01T00:00:00+00:00");
+        check("cocoa", "2001-01-01T00:00:00+00:00");
+        check("hfs", "1904-01-01T00:00:00+00:00");
+    }
+
+    #[test]
+    fn fractional_ticks() {
+        let cocoa = find_epoch("cocoa").unwrap();
+        assert_eq!(
+            cocoa.ticks_to_datetime(-SCALE / 2).unwrap().to_rfc3339(),
+            "2000-12-31T23:59:59.500+00:00"
+        );
+    }
+
+    #[test]
+    fn negative() {
+        assert!(find_epoch("hfs")
+            .unwrap()
+            .ticks_to_datetime(-SCALE)
+            .is_err());
+        assert!(find_epoch("filetime")
+            .unwrap()
+            .ticks_to_datetime(-SCALE)
+            .is_err());
+    }
+
+    #[test]
+    fn func_names() {
+        assert!(epoch_func_names().contains(&"from_hfs".to_string()));
+        assert!(epoch_func_names().contains(&"to_cocoa".to_string()));
+    }
+}
@@ -1,77 +1,618 @@
 use crate::log::debug_log;
 
-use super::{full_day, full_hour, Node, Oper};
-use chrono_tz::Tz;
+use super::zone::Tz;
+use super::{
+    interval,
+    locale::{month_from_name, Locale},
+    ntp_now,
+    number::{scaled_to_datetime, EpochUnit, SCALE},
+    numeric_date::{
+        has_two_digit_year, resolve_numeric_date, resolve_numeric_date_strict, DateOrder,
+    },
+    random::Random,
+    registry::Registry,
+    time_since_boot,
+    types::{oper_error, Type},
+    zone::from_local,
+    CompareOper, Node, Oper, ShortFormat,
+};
+use chrono::{Datelike, LocalResult, Offset, TimeZone};
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::rc::Rc;
 
 #[derive(Debug, PartialEq)]
 pub enum EvaluationResult {
     TimeDelta(chrono::TimeDelta),
     DateTime(chrono::DateTime<Tz>),
+    Integer(i128),
+    Bool(bool),
+    /// Time interval [start, end).
+    Interval(chrono::DateTime<Tz>, chrono::DateTime<Tz>),
+    /// Difference in calendar units, like age.
+    CalendarDelta {
+        years: i64,
+        months: i64,
+        days: i64,
+    },
+    Record(Vec<(String, EvaluationResult)>),
+    List(Vec<EvaluationResult>),
+    String(String),
 }
 
-pub fn evaluate(node: Node, now: chrono::DateTime<Tz>) -> Result<EvaluationResult, String> {
+/// The result as in the error messages: date-times in RFC 3339 and durations like 1h30m.
+impl fmt::Display for EvaluationResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvaluationResult::TimeDelta(delta) => write!(f, "{}", State::TimeDelta(*delta)),
+            EvaluationResult::DateTime(datetime) => write!(f, "{}", State::DateTime(*datetime)),
+            EvaluationResult::Integer(value) => write!(f, "{}", value),
+            EvaluationResult::Bool(value) => write!(f, "{}", value),
+            EvaluationResult::Interval(start, end) => {
+                write!(f, "{}", State::Interval(*start, *end))
+            }
+            EvaluationResult::CalendarDelta {
+                years,
+                months,
+                days,
+            } => write!(f, "{}y {}mo {}d", years, months, days),
+            EvaluationResult::Record(fields) => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(name, value)| format!("{}: {}", name, value))
+                    .collect();
+                write!(f, "{{{}}}", fields.join(", "))
+            }
+            EvaluationResult::List(elements) => {
+                let elements: Vec<String> = elements.iter().map(|e| e.to_string()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
+            EvaluationResult::String(s) => write!(f, "{:?}", s),
+        }
+    }
+}
+
+/// Everything the evaluation depends on apart from the expression itself.
+#[derive(Clone, Debug)]
+pub struct EvalContext {
+    /// Value of the "now" literal.
+    pub now: chrono::DateTime<Tz>,
+    /// How to interpret bare numbers as date-times.
+    pub epoch_unit: EpochUnit,
+    /// Server queried by the "ntp_now" literal, the default server if not set.
+    pub ntp_server: Option<String>,
+    /// Generator used by random_between, seeded with --seed or from the clock.
+    pub random: Random,
+    /// Variables bound by map, the innermost last.
+    pub variables: Vec<(String, State)>,
+    /// Locale of the month names, like "maja" in "17 maja 2024".
+    pub locale: Locale,
+    /// Order of the numeric dates like "5/17/24", inferred from the date if not set.
+    pub date_order: Option<DateOrder>,
+    /// Reject the inputs that need a guess, i.e. bare numbers as date-times with the auto epoch unit, and numeric
+    /// dates without the order or with two-digit year.
+    pub strict: bool,
+    /// Functions called by the expression, the built-in ones by default.
+    pub registry: Rc<Registry>,
+    /// Steps of the evaluation, recorded if set, for --trace-eval.
+    pub trace: Option<Rc<Trace>>,
+    /// Numbers taken as epoch timestamps, recorded if set, for --echo-input.
+    pub epochs: Option<Rc<RefCell<Vec<EpochNumber>>>>,
+    /// Warnings about the guesses of the evaluation, like the unit of an epoch timestamp or the century of a two-digit
+    /// year, recorded if set. Each warning is recorded once.
+    pub warnings: Option<Rc<RefCell<Vec<String>>>>,
+}
+
+/// A number of the input taken as epoch timestamp, with the unit, the guessed one if the unit is auto.
+#[derive(Clone, Debug)]
+pub struct EpochNumber {
+    pub value: i128,
+    pub unit: EpochUnit,
+    pub datetime: chrono::DateTime<Tz>,
+}
+
+/// Steps of the evaluation, like "2024-05-17T13:00:00+00:00 - 1h = 2024-05-17T12:00:00+00:00": the operators, the
+/// comparisons, the function calls and the numbers taken as epoch timestamps, with the operands and the result. The
+/// steps of the operands come before the step using them, indented one level deeper.
+#[derive(Debug, Default)]
+pub struct Trace {
+    depth: Cell<usize>,
+    steps: RefCell<Vec<String>>,
+}
+
+impl Trace {
+    /// Return the steps recorded so far, and start over.
+    pub fn take_steps(&self) -> Vec<String> {
+        self.steps.take()
+    }
+
+    fn step(&self, step: String) {
+        let indent = "  ".repeat(self.depth.get());
+        self.steps.borrow_mut().push(indent + &step);
+    }
+}
+
+impl EvalContext {
+    pub fn new(now: chrono::DateTime<Tz>) -> EvalContext {
+        EvalContext {
+            now,
+            epoch_unit: EpochUnit::Seconds,
+            ntp_server: None,
+            random: Random::from_clock(),
+            variables: vec![],
+            locale: Locale::default(),
+            date_order: None,
+            strict: false,
+            registry: Rc::new(Registry::builtin()),
+            trace: None,
+            epochs: None,
+            warnings: None,
+        }
+    }
+
+    /// Record the step of the evaluation if traced. The step is built only then.
+    fn trace(&self, step: impl FnOnce() -> String) {
+        if let Some(trace) = &self.trace {
+            trace.step(step());
+        }
+    }
+
+    /// Record the warning if the warnings are collected. The warning is built only then.
+    fn warn(&self, warning: impl FnOnce() -> String) {
+        if let Some(warnings) = &self.warnings {
+            let warning = warning();
+            let mut warnings = warnings.borrow_mut();
+            if !warnings.contains(&warning) {
+                warnings.push(warning);
+            }
+        }
+    }
+
+    /// Evaluate the operand of the step, with its own steps indented.
+    fn nested<T>(&self, f: impl FnOnce() -> T) -> T {
+        let Some(trace) = &self.trace else {
+            return f();
+        };
+        trace.depth.set(trace.depth.get() + 1);
+        let result = f();
+        trace.depth.set(trace.depth.get() - 1);
+        result
+    }
+
+    /// Interpret number as epoch timestamp, leave the other states as they are.
+    pub fn number_to_datetime(&self, state: State) -> Result<State, String> {
+        if let State::Number(value) = state {
+            if self.strict && self.epoch_unit == EpochUnit::Auto {
+                return Err(format!(
+                    "the epoch unit of bare number {} is a guess ({:?}), set --epoch-unit in strict mode",
+                    value as f64 / SCALE as f64,
+                    EpochUnit::guess(value)
+                ));
+            }
+            let datetime = scaled_to_datetime(value, self.epoch_unit)?;
+            let datetime = datetime.with_timezone(&self.now.timezone());
+            let number = || describe(&State::Number(value));
+            let unit = match self.epoch_unit {
+                EpochUnit::Auto => {
+                    let unit = EpochUnit::guess(value);
+                    self.warn(|| {
+                        format!(
+                            "the unit of epoch timestamp {} is guessed as {} from the magnitude, set --epoch-unit to be sure",
+                            number(),
+                            unit.name()
+                        )
+                    });
+                    unit
+                }
+                unit => {
+                    // Only the finer units are suspicious, the small numbers look like seconds in any unit.
+                    let guess = EpochUnit::guess(value);
+                    if guess.nanos() < unit.nanos() {
+                        self.warn(|| {
+                            format!(
+                                "epoch timestamp {} is taken as {}, but its magnitude suggests {}",
+                                number(),
+                                unit.name(),
+                                guess.name()
+                            )
+                        });
+                    }
+                    unit
+                }
+            };
+            if let Some(epochs) = &self.epochs {
+                epochs.borrow_mut().push(EpochNumber {
+                    value,
+                    unit,
+                    datetime,
+                });
+            }
+            let datetime = State::DateTime(datetime);
+            self.trace(|| {
+                format!(
+                    "{} as epoch {} = {}",
+                    describe(&State::Number(value)),
+                    format!("{:?}", unit).to_lowercase(),
+                    describe(&datetime)
+                )
+            });
+            Ok(datetime)
+        } else {
+            Ok(state)
+        }
+    }
+}
+
+/// Interpret the date-time without time zone in the zone, with a warning if the time is repeated or skipped by the
+/// DST change.
+fn local_to_datetime(
+    local: chrono::NaiveDateTime,
+    tz: &Tz,
+    ctx: &EvalContext,
+) -> Result<State, String> {
+    let datetime = from_local(local, tz)?;
+    match tz.from_local_datetime(&local) {
+        LocalResult::Ambiguous(..) => ctx.warn(|| {
+            format!(
+                "{} is repeated in {} by the DST change, taken as the earlier {}",
+                local,
+                tz,
+                describe(&State::DateTime(datetime))
+            )
+        }),
+        LocalResult::None => ctx.warn(|| {
+            format!(
+                "{} is skipped in {} by the DST change, taken as {}",
+                local,
+                tz,
+                describe(&State::DateTime(datetime))
+            )
+        }),
+        LocalResult::Single(_) => (),
+    }
+    Ok(State::DateTime(datetime))
+}
+
+pub fn evaluate(node: Node, ctx: &EvalContext) -> Result<EvaluationResult, String> {
     debug_log(format!("eval_to_date node {:?}", node));
-    match eval(&State::None, &node, now) {
-        Ok(state) => match state {
-            State::DateTime(datetime) => Ok(EvaluationResult::DateTime(datetime)),
-            State::TimeDelta(delta) => Ok(EvaluationResult::TimeDelta(delta)),
-            State::None => Err("BUG: the result of evaluation was State::None".to_string()),
-        },
+    match eval(&State::None, &node, ctx).and_then(|state| ctx.number_to_datetime(state)) {
+        Ok(state) => state_to_result(state),
         Err(m) => Err(m),
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+fn state_to_result(state: State) -> Result<EvaluationResult, String> {
+    match state {
+        State::DateTime(datetime) => Ok(EvaluationResult::DateTime(datetime)),
+        State::TimeDelta(delta) => Ok(EvaluationResult::TimeDelta(delta)),
+        State::Integer(value) => Ok(EvaluationResult::Integer(value)),
+        State::Bool(value) => Ok(EvaluationResult::Bool(value)),
+        State::Interval(start, end) => Ok(EvaluationResult::Interval(start, end)),
+        State::CalendarDelta {
+            years,
+            months,
+            days,
+        } => Ok(EvaluationResult::CalendarDelta {
+            years,
+            months,
+            days,
+        }),
+        State::Record(fields) => {
+            let fields: Result<Vec<(String, EvaluationResult)>, String> = fields
+                .into_iter()
+                .map(|(name, state)| state_to_result(state).map(|result| (name, result)))
+                .collect();
+            Ok(EvaluationResult::Record(fields?))
+        }
+        State::List(elements) => {
+            let elements: Result<Vec<EvaluationResult>, String> =
+                elements.into_iter().map(state_to_result).collect();
+            Ok(EvaluationResult::List(elements?))
+        }
+        State::String(s) => Ok(EvaluationResult::String(s)),
+        State::Number(_) => Err("BUG: the result of evaluation was State::Number".to_string()),
+        State::None => Err("BUG: the result of evaluation was State::None".to_string()),
+    }
+}
+
+#[derive(Clone, Debug)]
 pub enum State {
     TimeDelta(chrono::TimeDelta),
     DateTime(chrono::DateTime<Tz>),
+    String(String),
+    /// A number as written in the input, fixed-point scaled by number::SCALE. Used as epoch timestamp when a
+    /// date-time is expected.
+    Number(i128),
+    /// A number computed by a function, like FILETIME ticks. Unlike `Number` it is never used as epoch timestamp.
+    Integer(i128),
+    /// A result of a predicate, like whether two time windows intersect.
+    Bool(bool),
+    /// Time interval [start, end), with start not after end.
+    Interval(chrono::DateTime<Tz>, chrono::DateTime<Tz>),
+    /// Difference in calendar units, which unlike TimeDelta do not have fixed length. The units have the same sign.
+    CalendarDelta {
+        years: i64,
+        months: i64,
+        days: i64,
+    },
+    /// Several named values returned together by a function, like the counter and boundaries of a TOTP window.
+    Record(Vec<(String, State)>),
+    /// Values of a list, with numbers already converted to date-times.
+    List(Vec<State>),
     None,
 }
 
 /// Evaluation works by modifying state with node.
-fn eval(state: &State, node: &Node, now: chrono::DateTime<Tz>) -> Result<State, String> {
+fn eval(state: &State, node: &Node, ctx: &EvalContext) -> Result<State, String> {
     debug_log(format!("eval input: {:?} {:?}", state, node));
     let eval_result = match node {
-        Node::Expr(nodes) => eval_expr(&state, &nodes, now),
-        Node::OperNode { oper, node: expr } => apply_oper_node(state, oper, expr.as_ref(), now),
+        Node::Expr(nodes) => eval_expr(state, nodes, ctx),
+        Node::OperNode { oper, node: expr } => apply_oper_node(state, oper, expr.as_ref(), ctx),
         Node::Literal {
             literal: _,
             skip: _,
-        } => Ok((*state).clone()),
+        } => Ok(state.clone()),
         Node::Duration(duration) => {
             if let State::None = state {
-                Ok(State::TimeDelta(duration.clone()))
+                Ok(State::TimeDelta(*duration))
             } else {
                 Err(format!("cannot evaluate {:?} with {:?}", node, state))
             }
         }
         Node::DateTime(datetime) => {
             if let State::None = state {
-                Ok(State::DateTime(datetime.clone()))
+                Ok(State::DateTime(*datetime))
+            } else {
+                Err(format!("cannot evaluate {:?} with {:?}", node, state))
+            }
+        }
+        Node::LocalDateTime { date, time, tz } => {
+            if let State::None = state {
+                let tz = tz.unwrap_or(ctx.now.timezone());
+                let date = date.unwrap_or(ctx.now.with_timezone(&tz).date_naive());
+                local_to_datetime(date.and_time(*time), &tz, ctx)
+            } else {
+                Err(format!("cannot evaluate {:?} with {:?}", node, state))
+            }
+        }
+        Node::NamedMonthDate {
+            day,
+            month,
+            year,
+            time,
+            tz,
+        } => {
+            if let State::None = state {
+                // English month names are accepted in any locale.
+                let month_number = month_from_name(month, ctx.locale)
+                    .or_else(|| month_from_name(month, Locale::default()))
+                    .ok_or_else(|| {
+                        format!("unknown month name {:?} in locale {:?}", month, ctx.locale)
+                    })?;
+                let date = chrono::NaiveDate::from_ymd_opt(*year, month_number, *day)
+                    .ok_or_else(|| format!("no such date: {} {} {}", day, month, year))?;
+                let tz = tz.unwrap_or(ctx.now.timezone());
+                local_to_datetime(date.and_time(*time), &tz, ctx)
+            } else {
+                Err(format!("cannot evaluate {:?} with {:?}", node, state))
+            }
+        }
+        Node::NumericDate(s) => {
+            if let State::None = state {
+                let date = if ctx.strict {
+                    resolve_numeric_date_strict(s, ctx.date_order)?
+                } else {
+                    resolve_numeric_date(s, ctx.date_order)?
+                };
+                if has_two_digit_year(s, ctx.date_order) {
+                    ctx.warn(|| {
+                        format!(
+                            "two-digit year in {:?} is taken as {}, use four digits to be sure",
+                            s,
+                            date.year()
+                        )
+                    });
+                }
+                let tz = ctx.now.timezone();
+                local_to_datetime(date.and_time(chrono::NaiveTime::MIN), &tz, ctx)
             } else {
                 Err(format!("cannot evaluate {:?} with {:?}", node, state))
             }
         }
         Node::Now => {
             if let State::None = state {
-                Ok(State::DateTime(now.clone()))
+                Ok(State::DateTime(ctx.now))
             } else {
                 Err(format!("cannot evaluate {:?} with {:?}", node, state))
             }
         }
-        Node::FuncAry1 { name, arg1 } => {
-            let arg_evaluated = eval(&State::None, arg1, now)?;
-            eval_func_ary1(name, &arg_evaluated)
+        Node::Epoch | Node::MaxTime | Node::MinTime => {
+            if let State::None = state {
+                Ok(State::DateTime(named_datetime(node)))
+            } else {
+                Err(format!("cannot evaluate {:?} with {:?}", node, state))
+            }
         }
+        Node::Uptime => {
+            if let State::None = state {
+                Ok(State::DateTime(ctx.now - time_since_boot()?))
+            } else {
+                Err(format!("cannot evaluate {:?} with {:?}", node, state))
+            }
+        }
+        Node::NtpNow => {
+            if let State::None = state {
+                ntp_now(ctx.ntp_server.as_deref())
+            } else {
+                Err(format!("cannot evaluate {:?} with {:?}", node, state))
+            }
+        }
+        Node::String(s) => {
+            if let State::None = state {
+                Ok(State::String(s.clone()))
+            } else {
+                Err(format!("cannot evaluate {:?} with {:?}", node, state))
+            }
+        }
+        Node::Number(value) => {
+            if let State::None = state {
+                Ok(State::Number(*value))
+            } else {
+                Err(format!("cannot evaluate {:?} with {:?}", node, state))
+            }
+        }
+        Node::Spanned { node, .. } => eval(state, node, ctx),
+        Node::TypeAssertion {
+            node: inner,
+            expected,
+        } => {
+            if let State::None = state {
+                let result = ctx.number_to_datetime(eval(&State::None, inner, ctx)?)?;
+                let actual = Type::of_state(&result);
+                if expected.accepts(&actual) {
+                    Ok(result)
+                } else {
+                    Err(format!("expected {}, got {}", expected, actual))
+                }
+            } else {
+                Err(format!("cannot evaluate {:?} with {:?}", node, state))
+            }
+        }
+        Node::FuncAry1 { name, arg1 } => eval_func(name, &[arg1], ctx),
+        Node::FuncAry2 { name, arg1, arg2 } => eval_func(name, &[arg1, arg2], ctx),
+        Node::Interval { start, end } => {
+            if let State::None = state {
+                let start = ctx.number_to_datetime(eval(&State::None, start, ctx)?)?;
+                let end = ctx.number_to_datetime(eval(&State::None, end, ctx)?)?;
+                interval(&start, &end)
+            } else {
+                Err(format!("cannot evaluate {:?} with {:?}", node, state))
+            }
+        }
+        Node::List(elements) => {
+            if let State::None = state {
+                let elements = elements
+                    .iter()
+                    .map(|element| {
+                        eval(&State::None, element, ctx).and_then(|s| ctx.number_to_datetime(s))
+                    })
+                    .collect::<Result<Vec<State>, String>>()?;
+                Ok(State::List(elements))
+            } else {
+                Err(format!("cannot evaluate {:?} with {:?}", node, state))
+            }
+        }
+        Node::Variable(name) => {
+            if let State::None = state {
+                ctx.variables
+                    .iter()
+                    .rev()
+                    .find(|(variable, _)| variable == name)
+                    .map(|(_, value)| value.clone())
+                    .ok_or_else(|| format!("unknown variable {:?}", name))
+            } else {
+                Err(format!("cannot evaluate {:?} with {:?}", node, state))
+            }
+        }
+        Node::Map {
+            list,
+            variable,
+            body,
+        } => {
+            if let State::None = state {
+                eval_map(list, variable, body, ctx)
+            } else {
+                Err(format!("cannot evaluate {:?} with {:?}", node, state))
+            }
+        }
+        Node::Compare { oper, left, right } => {
+            if let State::None = state {
+                let left = ctx.nested(|| ctx.number_to_datetime(eval(&State::None, left, ctx)?))?;
+                let right =
+                    ctx.nested(|| ctx.number_to_datetime(eval(&State::None, right, ctx)?))?;
+                let result = compare(oper, &left, &right)?;
+                ctx.trace(|| {
+                    format!(
+                        "{} {} {} = {}",
+                        describe(&left),
+                        oper,
+                        describe(&right),
+                        describe(&result)
+                    )
+                });
+                Ok(result)
+            } else {
+                Err(format!("cannot evaluate {:?} with {:?}", node, state))
+            }
+        }
+        Node::If {
+            cond,
+            then,
+            otherwise,
+        } => {
+            if let State::None = state {
+                match eval(&State::None, cond, ctx)? {
+                    State::Bool(true) => eval(&State::None, then, ctx),
+                    State::Bool(false) => eval(&State::None, otherwise, ctx),
+                    other => Err(format!(
+                        "the condition of if should be bool, was: {:?}",
+                        other
+                    )),
+                }
+            } else {
+                Err(format!("cannot evaluate {:?} with {:?}", node, state))
+            }
+        }
+        Node::FuncAry3 {
+            name,
+            arg1,
+            arg2,
+            arg3,
+        } => eval_func(name, &[arg1, arg2, arg3], ctx),
+        Node::FuncAry4 {
+            name,
+            arg1,
+            arg2,
+            arg3,
+            arg4,
+        } => eval_func(name, &[arg1, arg2, arg3, arg4], ctx),
     };
     debug_log(format!("eval output: {:?}", eval_result));
     eval_result
 }
 
-fn eval_expr(state: &State, nodes: &Vec<Node>, now: chrono::DateTime<Tz>) -> Result<State, String> {
-    let mut state = (*state).clone();
+/// Evaluate the body for each element of the list, with the element bound to the variable.
+fn eval_map(list: &Node, variable: &str, body: &Node, ctx: &EvalContext) -> Result<State, String> {
+    let elements = match eval(&State::None, list, ctx)? {
+        State::List(elements) => elements,
+        other => return Err(format!("map expects a list, got {:?}", other)),
+    };
+    let mut ctx = ctx.clone();
+    let mut results = vec![];
+    for element in elements {
+        ctx.variables.push((variable.to_string(), element));
+        let result = eval(&State::None, body, &ctx).and_then(|s| ctx.number_to_datetime(s))?;
+        ctx.variables.pop();
+        results.push(result);
+    }
+    Ok(State::List(results))
+}
+
+/// Value of the named date-time literal, like "epoch".
+fn named_datetime(node: &Node) -> chrono::DateTime<Tz> {
+    let datetime = match node {
+        Node::Epoch => chrono::DateTime::UNIX_EPOCH,
+        Node::MaxTime => chrono::DateTime::<chrono::Utc>::MAX_UTC,
+        Node::MinTime => chrono::DateTime::<chrono::Utc>::MIN_UTC,
+        _ => panic!("BUG! {:?} is not a named date-time", node),
+    };
+    datetime.with_timezone(&super::zone::UTC)
+}
+
+fn eval_expr(state: &State, nodes: &Vec<Node>, ctx: &EvalContext) -> Result<State, String> {
+    let mut state = state.clone();
     for node in nodes {
-        state = eval(&state, node, now)?;
+        state = eval(&state, node, ctx)?;
     }
     Ok(state)
 }
@@ -81,60 +622,181 @@ fn apply_oper_node(
     state: &State,
     oper: &Oper,
     node: &Node,
-    now: chrono::DateTime<Tz>,
+    ctx: &EvalContext,
 ) -> Result<State, String> {
-    let sub_state = eval(&State::None, node, now)?;
-    if let (State::DateTime(left), Oper::Minus, State::DateTime(right)) = (&state, oper, &sub_state)
-    {
-        return Ok(State::TimeDelta(*left - *right));
+    let state = &ctx.number_to_datetime(state.clone())?;
+    let sub_state = ctx.nested(|| ctx.number_to_datetime(eval(&State::None, node, ctx)?))?;
+    let result = apply_oper(state, oper, &sub_state)?;
+    let operand = match (state, &sub_state) {
+        (State::DateTime(datetime), State::TimeDelta(_))
+        | (State::TimeDelta(_), State::DateTime(datetime)) => Some(datetime),
+        _ => None,
+    };
+    // The durations are exact time, so across the DST change the wall clock moves by more or less.
+    if let (Some(from), State::DateTime(to)) = (operand, &result) {
+        if from.offset().fix() != to.offset().fix() {
+            ctx.warn(|| {
+                format!(
+                    "{} {} {} crosses the DST change in {}, the wall clock moves by {}",
+                    describe(state),
+                    oper,
+                    describe(&sub_state),
+                    from.timezone(),
+                    describe(&State::TimeDelta(to.naive_local() - from.naive_local()))
+                )
+            });
+        }
+    }
+    ctx.trace(|| {
+        format!(
+            "{} {} {} = {}",
+            describe(state),
+            oper,
+            describe(&sub_state),
+            describe(&result)
+        )
+    });
+    Ok(result)
+}
+
+/// The result of the operator on the evaluated operands.
+/// The date-times beyond the years -262143 to +262142 and the durations beyond the range of chrono are errors.
+fn apply_oper(state: &State, oper: &Oper, sub_state: &State) -> Result<State, String> {
+    let out_of_range = || {
+        format!(
+            "{} {} {} is out of range",
+            describe(state),
+            oper,
+            describe(sub_state)
+        )
+    };
+    if let (State::DateTime(left), Oper::Minus, State::DateTime(right)) = (state, oper, sub_state) {
+        Ok(State::TimeDelta(*left - *right))
     } else if let (State::DateTime(left), Oper::Minus, State::TimeDelta(right)) =
-        (&state, oper, &sub_state)
+        (state, oper, sub_state)
     {
-        return Ok(State::DateTime(*left - *right));
+        let result = left.checked_sub_signed(*right).ok_or_else(out_of_range)?;
+        Ok(State::DateTime(result))
     } else if let (State::DateTime(left), Oper::Plus, State::TimeDelta(right)) =
-        (&state, oper, &sub_state)
+        (state, oper, sub_state)
     {
-        return Ok(State::DateTime(*left + *right));
+        let result = left.checked_add_signed(*right).ok_or_else(out_of_range)?;
+        Ok(State::DateTime(result))
     } else if let (State::TimeDelta(left), Oper::Plus, State::DateTime(right)) =
-        (&state, oper, &sub_state)
+        (state, oper, sub_state)
     {
-        return Ok(State::DateTime(*right + *left));
+        let result = right.checked_add_signed(*left).ok_or_else(out_of_range)?;
+        Ok(State::DateTime(result))
     } else if let (State::TimeDelta(left), Oper::Minus, State::TimeDelta(right)) =
-        (&state, oper, &sub_state)
+        (state, oper, sub_state)
     {
-        return Ok(State::TimeDelta(*left - *right));
+        let result = left.checked_sub(right).ok_or_else(out_of_range)?;
+        Ok(State::TimeDelta(result))
     } else if let (State::TimeDelta(left), Oper::Plus, State::TimeDelta(right)) =
-        (&state, oper, &sub_state)
+        (state, oper, sub_state)
     {
-        return Ok(State::TimeDelta(*left + *right));
+        let result = left.checked_add(right).ok_or_else(out_of_range)?;
+        Ok(State::TimeDelta(result))
     } else {
-        return Err(format!(
-            "Cannot evaluate operation {:?} {:?} {:?}",
-            state, oper, sub_state
-        ));
+        Err(oper_error(
+            oper,
+            &Type::of_state(state),
+            &Type::of_state(sub_state),
+        ))
     }
 }
 
-fn eval_func_ary1(name: &String, arg1: &State) -> Result<State, String> {
-    match name.as_str() {
-        "full_day" => full_day(arg1),
-        "full_hour" => full_hour(arg1),
-        _ => Err(format!("no such function {:?}", name)),
+/// Compare values of the same type. Date-times, durations, integers and strings are ordered, bools are only equal or
+/// not.
+fn compare(oper: &CompareOper, left: &State, right: &State) -> Result<State, String> {
+    let ordering = match (left, right) {
+        (State::DateTime(left), State::DateTime(right)) => left.cmp(right),
+        (State::TimeDelta(left), State::TimeDelta(right)) => left.cmp(right),
+        (State::Integer(left), State::Integer(right)) => left.cmp(right),
+        (State::String(left), State::String(right)) => left.cmp(right),
+        (State::Bool(left), State::Bool(right))
+            if matches!(oper, CompareOper::Eq | CompareOper::Ne) =>
+        {
+            left.cmp(right)
+        }
+        _ => return Err(format!("cannot compare {:?} {:?} {:?}", left, oper, right)),
+    };
+    let result = match oper {
+        CompareOper::Lt => ordering.is_lt(),
+        CompareOper::Le => ordering.is_le(),
+        CompareOper::Gt => ordering.is_gt(),
+        CompareOper::Ge => ordering.is_ge(),
+        CompareOper::Eq => ordering.is_eq(),
+        CompareOper::Ne => ordering.is_ne(),
+    };
+    Ok(State::Bool(result))
+}
+
+/// Evaluate the arguments and call the function of the registry.
+fn eval_func(name: &str, args: &[&Rc<Node>], ctx: &EvalContext) -> Result<State, String> {
+    let args = args
+        .iter()
+        .map(|arg| ctx.nested(|| eval(&State::None, arg, ctx)))
+        .collect::<Result<Vec<State>, String>>()?;
+    let result = ctx.registry.call(name, &args, ctx)?;
+    ctx.trace(|| {
+        let args: Vec<String> = args.iter().map(describe).collect();
+        format!("{}({}) = {}", name, args.join(", "), describe(&result))
+    });
+    Ok(result)
+}
+
+/// The state as in the trace of the evaluation and in the error messages.
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", describe(self))
+    }
+}
+
+/// The state as in the trace of the evaluation: date-times in RFC 3339 and durations like 1h30m.
+fn describe(state: &State) -> String {
+    match state {
+        State::DateTime(datetime) => datetime.to_rfc3339(),
+        State::TimeDelta(delta) => delta.as_short_format(),
+        State::String(s) => format!("{:?}", s),
+        State::Number(value) => (*value as f64 / SCALE as f64).to_string(),
+        State::Integer(value) => value.to_string(),
+        State::Bool(value) => value.to_string(),
+        State::Interval(start, end) => format!("{}..{}", start.to_rfc3339(), end.to_rfc3339()),
+        State::CalendarDelta {
+            years,
+            months,
+            days,
+        } => format!("{}y {}mo {}d", years, months, days),
+        State::Record(fields) => {
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|(name, value)| format!("{}: {}", name, describe(value)))
+                .collect();
+            format!("{{{}}}", fields.join(", "))
+        }
+        State::List(elements) => {
+            let elements: Vec<String> = elements.iter().map(describe).collect();
+            format!("[{}]", elements.join(", "))
+        }
+        State::None => "none".to_string(),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use chrono_tz::{Tz, UTC};
+    use crate::parser::zone::{Tz, UTC};
 
     use super::super::parse_expr;
-    use super::{evaluate, EvaluationResult};
+    use super::{evaluate, EvalContext, EvaluationResult, Trace};
+    use crate::parser::number::EpochUnit;
+    use std::rc::Rc;
 
     #[test]
     fn parse_and_eval_sums() {
         let input = "1d + 2h + 2000-01-01T00:00:00Z + 3m + 4s".to_string();
         let result_node = parse_expr(&input).unwrap().node;
-        let result = evaluate(result_node, now());
+        let result = evaluate(result_node, &EvalContext::new(now()));
         assert!(result.is_ok(), "result not ok: {:?}", result);
         assert_eq!(result.unwrap(), parse_from_rfc3339("2000-01-02T02:03:04Z"))
     }
@@ -143,7 +805,7 @@ mod tests {
     fn parse_and_eval_diff_duration() {
         let input = "1d + 2h + 2000-01-01T00:00:00Z - 1d - 2h".to_string();
         let result_node = parse_expr(&input).unwrap().node;
-        let result = evaluate(result_node, now());
+        let result = evaluate(result_node, &EvalContext::new(now()));
         assert!(result.is_ok(), "result not ok");
         assert_eq!(result.unwrap(), parse_from_rfc3339("2000-01-01T00:00:00Z"))
     }
@@ -153,7 +815,7 @@ mod tests {
         let input =
             "1999-01-01T01:00:00Z - 1999-01-01T00:00:00Z + 2000-01-01T00:00:00Z".to_string();
         let result_node = parse_expr(&input).unwrap().node;
-        let result = evaluate(result_node, now());
+        let result = evaluate(result_node, &EvalContext::new(now()));
         assert!(result.is_ok(), "result not ok");
         assert_eq!(result.unwrap(), parse_from_rfc3339("2000-01-01T01:00:00Z"))
     }
@@ -164,11 +826,74 @@ mod tests {
             "1s + 1999-01-01T01:00:00Z - 1m - 1999-01-01T00:00:00Z + -2s + 2000-01-01T00:00:00Z"
                 .to_string();
         let result_node = parse_expr(&input).unwrap().node;
-        let result = evaluate(result_node, now());
+        let result = evaluate(result_node, &EvalContext::new(now()));
         assert!(result.is_ok(), "result not ok");
         assert_eq!(result.unwrap(), parse_from_rfc3339("2000-01-01T00:58:59Z"))
     }
 
+    #[test]
+    fn trace_steps() {
+        let input = "2000-01-02T00:00:00Z - 2000-01-01T00:00:00Z + full_day(now + 1h) < max_time"
+            .to_string();
+        let ctx = EvalContext {
+            trace: Some(Rc::new(Trace::default())),
+            ..EvalContext::new(now())
+        };
+        let result = evaluate(parse_expr(&input).unwrap().node, &ctx);
+        assert_eq!(result, Ok(EvaluationResult::Bool(true)));
+        assert_eq!(
+            ctx.trace.unwrap().take_steps(),
+            vec![
+                "  2000-01-02T00:00:00+00:00 - 2000-01-01T00:00:00+00:00 = 1d",
+                "      2024-01-01T00:00:00+00:00 + 1h = 2024-01-01T01:00:00+00:00",
+                "    full_day(2024-01-01T01:00:00+00:00) = 2024-01-01T00:00:00+00:00",
+                "  1d + 2024-01-01T00:00:00+00:00 = 2024-01-02T00:00:00+00:00",
+                "2024-01-02T00:00:00+00:00 < +262142-12-31T23:59:59.999999999+00:00 = true",
+            ]
+        );
+    }
+
+    #[test]
+    fn warnings() {
+        let warnings = |input: &str, epoch_unit: EpochUnit| {
+            let ctx = EvalContext {
+                epoch_unit,
+                warnings: Some(Rc::default()),
+                ..EvalContext::new(now())
+            };
+            evaluate(parse_expr(&input.to_string()).unwrap().node, &ctx).unwrap();
+            ctx.warnings.unwrap().take()
+        };
+        assert_eq!(
+            warnings("now + 1h", EpochUnit::Seconds),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            warnings("1716000000123 - 1716000000123 + 1716000000123", EpochUnit::Auto),
+            vec!["the unit of epoch timestamp 1716000000123 is guessed as milliseconds from the magnitude, set --epoch-unit to be sure"]
+        );
+        assert_eq!(
+            warnings("1716000000123 - 1716000000", EpochUnit::Seconds),
+            vec!["epoch timestamp 1716000000123 is taken as seconds, but its magnitude suggests milliseconds"]
+        );
+        assert_eq!(warnings("0 + 1h", EpochUnit::Millis), Vec::<String>::new());
+        assert_eq!(
+            warnings("5/17/24 + 1h", EpochUnit::Seconds),
+            vec!["two-digit year in \"5/17/24\" is taken as 2024, use four digits to be sure"]
+        );
+        #[cfg(feature = "tz")]
+        assert_eq!(
+            warnings(
+                "2024-03-30 12:00 Europe/Warsaw + 1d - 2024-10-27 02:30 Europe/Warsaw",
+                EpochUnit::Seconds
+            ),
+            vec![
+                "2024-03-30T12:00:00+01:00 + 1d crosses the DST change in Europe/Warsaw, the wall clock moves by 1d1h",
+                "2024-10-27 02:30:00 is repeated in Europe/Warsaw by the DST change, taken as the earlier 2024-10-27T02:30:00+02:00",
+            ]
+        );
+    }
+
     fn parse_from_rfc3339(s: &str) -> EvaluationResult {
         EvaluationResult::DateTime(
             chrono::DateTime::parse_from_rfc3339(s)
@@ -180,6 +905,6 @@ mod tests {
     fn now() -> chrono::DateTime<Tz> {
         chrono::DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z")
             .unwrap()
-            .with_timezone(&chrono_tz::UTC)
+            .with_timezone(&UTC)
     }
 }
@@ -0,0 +1,183 @@
+use chrono::{DateTime, Utc};
+
+/// Seconds between the Gregorian reform (1582-10-15), the epoch of UUIDv1 timestamps, and the Unix epoch.
+const UUID_V1_EPOCH_OFFSET_SECONDS: i64 = 12_219_292_800;
+
+/// Named custom epochs of snowflake IDs, in Unix milliseconds.
+pub const SNOWFLAKE_EPOCHS: [(&str, i64); 2] = [
+    ("twitter", 1_288_834_974_657),
+    ("discord", 1_420_070_400_000),
+];
+
+/// Return creation time of MongoDB ObjectId. The first 4 bytes are big-endian Unix seconds.
+pub fn objectid_timestamp(id: &str) -> Result<DateTime<Utc>, String> {
+    let id = id.trim();
+    if id.len() != 24 || !id.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!(
+            "ObjectId should be 24 hexadecimal digits, was: {:?}",
+            id
+        ));
+    }
+    let secs = i64::from_str_radix(&id[..8], 16).unwrap();
+    DateTime::from_timestamp(secs, 0).ok_or_else(|| format!("bad ObjectId timestamp in {:?}", id))
+}
+
+/// Return the timestamp embedded in UUIDv1: 100ns ticks since 1582-10-15 split into low, mid and high fields.
+pub fn uuid1_timestamp(uuid: &str) -> Result<DateTime<Utc>, String> {
+    let hex = uuid_hex(uuid, 1)?;
+    let time_low = u64::from_str_radix(&hex[0..8], 16).unwrap();
+    let time_mid = u64::from_str_radix(&hex[8..12], 16).unwrap();
+    // Skip the version digit.
+    let time_high = u64::from_str_radix(&hex[13..16], 16).unwrap();
+    let ticks = (time_high << 48) | (time_mid << 32) | time_low;
+    let secs = (ticks / 10_000_000) as i64 - UUID_V1_EPOCH_OFFSET_SECONDS;
+    let nanos = (ticks % 10_000_000) as u32 * 100;
+    DateTime::from_timestamp(secs, nanos).ok_or_else(|| format!("bad UUID timestamp in {:?}", uuid))
+}
+
+/// Return the timestamp embedded in UUIDv7: the first 48 bits are big-endian Unix milliseconds.
+pub fn uuid7_timestamp(uuid: &str) -> Result<DateTime<Utc>, String> {
+    let hex = uuid_hex(uuid, 7)?;
+    let millis = i64::from_str_radix(&hex[0..12], 16).unwrap();
+    DateTime::from_timestamp_millis(millis)
+        .ok_or_else(|| format!("bad UUID timestamp in {:?}", uuid))
+}
+
+/// Return the timestamp of Twitter-style snowflake ID: the bits above the lowest 22 are milliseconds since the
+/// custom epoch.
+pub fn snowflake_timestamp(id: u64, epoch_millis: i64) -> Result<DateTime<Utc>, String> {
+    let millis = epoch_millis
+        .checked_add((id >> 22) as i64)
+        .ok_or_else(|| format!("snowflake {} is out of range", id))?;
+    DateTime::from_timestamp_millis(millis)
+        .ok_or_else(|| format!("snowflake {} is out of range", id))
+}
+
+/// Return the 32 hex digits of UUID, checking the version.
+fn uuid_hex(uuid: &str, version: u32) -> Result<String, String> {
+    let hex: String = uuid.trim().chars().filter(|c| *c != '-').collect();
+    if hex.len() != 32 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!(
+            "UUID should be 32 hexadecimal digits, was: {:?}",
+            uuid
+        ));
+    }
+    let actual_version = hex[12..13].parse::<u32>().unwrap_or(0);
+    if actual_version != version {
+        return Err(format!(
+            "expected UUID version {}, was version {} in {:?}",
+            version, actual_version, uuid
+        ));
+    }
+    Ok(hex)
+}
+
+/// Return the expiry time from the "exp" claim of JWT. The signature is not verified.
+pub fn jwt_exp_timestamp(token: &str) -> Result<DateTime<Utc>, String> {
+    let parts: Vec<&str> = token.trim().split('.').collect();
+    if parts.len() != 3 {
+        return Err(format!(
+            "JWT should have 3 parts separated with dots, had {}",
+            parts.len()
+        ));
+    }
+    let payload = base64_decode(parts[1])?;
+    let payload = String::from_utf8(payload).map_err(|_| "JWT payload is not UTF-8".to_string())?;
+    let re = regex::Regex::new(r#""exp"\s*:\s*(?<exp>-?\d+)"#).unwrap();
+    let exp = re
+        .captures(&payload)
+        .ok_or_else(|| format!("no numeric exp claim in JWT payload {}", payload))?;
+    let secs = exp["exp"]
+        .parse::<i64>()
+        .map_err(|_| format!("bad exp claim {}", &exp["exp"]))?;
+    DateTime::from_timestamp(secs, 0).ok_or_else(|| format!("exp claim {} is out of range", secs))
+}
+
+/// Decode base64, both the standard and the URL-safe alphabet, with or without padding. Whitespace is skipped.
+pub fn base64_decode(s: &str) -> Result<Vec<u8>, String> {
+    let mut out = vec![];
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for c in s.chars().filter(|c| !c.is_whitespace() && *c != '=') {
+        let value = match c {
+            'A'..='Z' => c as u32 - 'A' as u32,
+            'a'..='z' => c as u32 - 'a' as u32 + 26,
+            '0'..='9' => c as u32 - '0' as u32 + 52,
+            '+' | '-' => 62,
+            '/' | '_' => 63,
+            _ => return Err(format!("bad base64 character {:?}", c)),
+        };
+        buffer = (buffer << 6) | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        base64_decode, jwt_exp_timestamp, objectid_timestamp, snowflake_timestamp, uuid1_timestamp,
+        uuid7_timestamp, SNOWFLAKE_EPOCHS,
+    };
+
+    #[test]
+    fn objectid() {
+        let actual = objectid_timestamp("65f0c0ffee0123456789abcd").unwrap();
+        assert_eq!(actual.to_rfc3339(), "2024-03-12T20:54:23+00:00");
+        assert!(objectid_timestamp("65f0c0ffee").is_err());
+        assert!(objectid_timestamp("65f0c0ffee0123456789abcx").is_err());
+    }
+
+    #[test]
+    fn uuid1() {
+        let actual = uuid1_timestamp("c232ab00-9414-11ec-b3c8-9f6bdeced846").unwrap();
+        assert_eq!(actual.to_rfc3339(), "2022-02-22T19:22:22+00:00");
+    }
+
+    #[test]
+    fn uuid7() {
+        let actual = uuid7_timestamp("017f22e2-79b0-7cc3-98c4-dc0c0c07398f").unwrap();
+        assert_eq!(actual.to_rfc3339(), "2022-02-22T19:22:22+00:00");
+        assert!(uuid7_timestamp("017f22e279b07cc398c4dc0c0c07398f").is_ok());
+    }
+
+    #[test]
+    fn snowflake() {
+        let twitter = SNOWFLAKE_EPOCHS[0].1;
+        let actual = snowflake_timestamp(1_223_395_535_877_046_272, twitter).unwrap();
+        assert_eq!(actual.to_rfc3339(), "2020-02-01T00:00:00+00:00");
+        let discord = SNOWFLAKE_EPOCHS[1].1;
+        let actual = snowflake_timestamp(175_928_847_299_117_063, discord).unwrap();
+        assert_eq!(actual.to_rfc3339(), "2016-04-30T11:18:25.796+00:00");
+    }
+
+    #[test]
+    fn uuid_wrong_version() {
+        let err = uuid7_timestamp("c232ab00-9414-11ec-b3c8-9f6bdeced846").unwrap_err();
+        assert!(err.contains("version 1"), "{}", err);
+        assert!(uuid1_timestamp("017f22e2-79b0-7cc3-98c4").is_err());
+    }
+
+    #[test]
+    fn base64() {
+        assert_eq!(base64_decode("aGVsbG8="), Ok(b"hello".to_vec()));
+        assert_eq!(base64_decode("aGVsbG8"), Ok(b"hello".to_vec()));
+        assert_eq!(base64_decode("-_8"), Ok(vec![0xfb, 0xff]));
+        assert!(base64_decode("a*").is_err());
+    }
+
+    #[test]
+    fn jwt_exp() {
+        // {"alg":"HS256","typ":"JWT"}.{"sub":"1234567890","exp":1716000000}
+        let token = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.eyJzdWIiOiIxMjM0NTY3ODkwIiwiZXhwIjoxNzE2MDAwMDAwfQ.c2ln";
+        let actual = jwt_exp_timestamp(token).unwrap();
+        assert_eq!(actual.to_rfc3339(), "2024-05-18T02:40:00+00:00");
+        assert!(jwt_exp_timestamp("eyJhbGciOiJIUzI1NiJ9.e30.c2ln").is_err());
+        assert!(jwt_exp_timestamp("abc").is_err());
+    }
+}
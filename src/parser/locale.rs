@@ -0,0 +1,127 @@
+//! Month names of the date like "17 maja 2024", and localized output of the date-times. The locale data comes from
+//! glibc via pure-rust-locales, with the locale feature. Without the feature only the English month names are known.
+use super::zone::Tz;
+use chrono::DateTime;
+
+#[cfg(feature = "locale")]
+pub use chrono::Locale;
+
+/// Locale of the month names, only the POSIX locale, i.e. English, without the locale feature.
+#[cfg(not(feature = "locale"))]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum Locale {
+    #[default]
+    POSIX,
+}
+
+#[cfg(not(feature = "locale"))]
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+/// Parse the locale name like "pl_PL". The language alone, like "pl" or "de", is the locale of the country of the
+/// same code, i.e. "pl_PL" or "de_DE".
+#[cfg(feature = "locale")]
+pub fn parse_locale(s: &str) -> Result<Locale, String> {
+    Locale::try_from(s)
+        .or_else(|_| Locale::try_from(format!("{}_{}", s, s.to_uppercase()).as_str()))
+        .map_err(|_| format!("unknown locale {:?}, use a name like pl_PL or de_DE", s))
+}
+
+#[cfg(not(feature = "locale"))]
+pub fn parse_locale(s: &str) -> Result<Locale, String> {
+    Err(format!(
+        "locale {:?} is not supported, build with the locale feature",
+        s
+    ))
+}
+
+/// Return the number of the month, 1 for January, with the name or the abbreviation in the locale, ignoring case.
+/// Both the nominative and genitive forms are matched, like "maj" and "maja" in Polish.
+#[cfg(feature = "locale")]
+pub fn month_from_name(name: &str, locale: Locale) -> Option<u32> {
+    use pure_rust_locales::locale_match;
+    let names = [
+        Some(locale_match!(locale => LC_TIME::MON)),
+        Some(locale_match!(locale => LC_TIME::ABMON)),
+        locale_match!(locale => LC_TIME::ALT_MON),
+        locale_match!(locale => LC_TIME::AB_ALT_MON),
+    ];
+    find_month(name, names.iter().flatten().copied())
+}
+
+#[cfg(not(feature = "locale"))]
+pub fn month_from_name(name: &str, _locale: Locale) -> Option<u32> {
+    let abbreviations = MONTHS.map(|month| &month[..3]);
+    find_month(name, [&MONTHS[..], &abbreviations[..]].into_iter())
+}
+
+fn find_month<'a>(name: &str, names: impl Iterator<Item = &'a [&'a str]>) -> Option<u32> {
+    let name = name.to_lowercase();
+    for months in names {
+        // Some locales abbreviate with a trailing dot, like "janv." in French.
+        if let Some(i) = months
+            .iter()
+            .position(|month| month.to_lowercase().trim_end_matches('.') == name)
+        {
+            return Some(i as u32 + 1);
+        }
+    }
+    None
+}
+
+/// Format the date-time with the month and day names of the locale.
+#[cfg(feature = "locale")]
+pub fn format_localized(datetime: &DateTime<Tz>, format: &str, locale: Locale) -> String {
+    datetime.format_localized(format, locale).to_string()
+}
+
+#[cfg(not(feature = "locale"))]
+pub fn format_localized(datetime: &DateTime<Tz>, format: &str, _locale: Locale) -> String {
+    datetime.format(format).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{month_from_name, parse_locale, Locale};
+
+    #[test]
+    fn english_months() {
+        assert_eq!(month_from_name("May", Locale::default()), Some(5));
+        assert_eq!(month_from_name("sep", Locale::default()), Some(9));
+        assert_eq!(month_from_name("maja", Locale::default()), None);
+    }
+
+    #[cfg(feature = "locale")]
+    #[test]
+    fn localized_months() {
+        let pl = parse_locale("pl").unwrap();
+        assert_eq!(pl, parse_locale("pl_PL").unwrap());
+        assert_eq!(month_from_name("maja", pl), Some(5));
+        assert_eq!(month_from_name("Maj", pl), Some(5));
+        assert_eq!(month_from_name("października", pl), Some(10));
+        let de = parse_locale("de").unwrap();
+        assert_eq!(month_from_name("Mai", de), Some(5));
+        assert_eq!(month_from_name("März", de), Some(3));
+        assert_eq!(month_from_name("May", de), None);
+        assert!(parse_locale("xx").is_err());
+    }
+
+    #[cfg(not(feature = "locale"))]
+    #[test]
+    fn locale_not_supported() {
+        assert!(parse_locale("pl").is_err());
+    }
+}
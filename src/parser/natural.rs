@@ -0,0 +1,258 @@
+//! Scheduling phrases as at(1) accepts them and as people write them in chat, like "next tuesday 3pm", "noon
+//! tomorrow", "midnight" or "in 2 hours", mapped onto the expression nodes. A day is its midnight, a time without
+//! a day is its next occurrence.
+use std::rc::Rc;
+
+use super::{
+    core::{CompareOper, Node, Oper},
+    relative::{offset_node, snap_to_week_day, splunk_unit},
+};
+
+/// Time of day like "3pm", "3:30 pm" or "15:00".
+const RE_TIME: &str = r"^(?<hour>\d{1,2})(:(?<minute>\d{2}))?(?<meridiem>am|pm)?$";
+
+/// Days of week, from Sunday as in snap_to_week_day.
+const WEEKDAY_NAMES: [&str; 7] = [
+    "sunday",
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Day {
+    /// Days after today, like 1 for "tomorrow".
+    After(i64),
+    /// The day of week from today on, like "tuesday".
+    Weekday(u32),
+    /// The day of week after today, like "next tuesday".
+    NextWeekday(u32),
+}
+
+/// Convert the phrase to expression nodes, e.g. "noon tomorrow" becomes at(tomorrow, "12:00"), with the midnight of
+/// tomorrow from day_node. The day, the time and the offset like "in 2 days" or "3 hours ago" can be in any order.
+pub fn natural_to_node(s: &str) -> Result<Node, String> {
+    let words = split_words(s);
+    let mut day = None;
+    let mut time = None;
+    let mut offset = None;
+    let mut words = words.iter().map(|word| word.as_str()).peekable();
+    let unknown = |word: &str| format!("unknown word {:?} in {:?}", word, s);
+    let twice = |what: &str| format!("more than one {} in {:?}", what, s);
+    while let Some(word) = words.next() {
+        let (found_day, found_time) = match word {
+            "now" | "at" | "on" => (None, None),
+            "today" => (Some(Day::After(0)), None),
+            "tomorrow" => (Some(Day::After(1)), None),
+            "yesterday" => (Some(Day::After(-1)), None),
+            "next" => {
+                let next = words.next().unwrap_or_default();
+                let weekday = parse_weekday(next).ok_or_else(|| unknown(next))?;
+                (Some(Day::NextWeekday(weekday)), None)
+            }
+            "noon" => (None, Some("12:00".to_string())),
+            "midnight" => (None, Some("00:00".to_string())),
+            "teatime" => (None, Some("16:00".to_string())),
+            "in" => {
+                let (value, unit) = parse_amount(words.next(), words.next(), s)?;
+                if offset.replace((Oper::Plus, value, unit)).is_some() {
+                    return Err(twice("offset"));
+                }
+                (None, None)
+            }
+            _ if word.starts_with(|c: char| c.is_ascii_digit()) && words.peek().is_some() => {
+                if let Some(time) = parse_time(word, s)? {
+                    (None, Some(time))
+                } else {
+                    let (value, unit) = parse_amount(Some(word), words.next(), s)?;
+                    if words.next() != Some("ago") {
+                        return Err(format!("expected \"ago\" after {:?} in {:?}", word, s));
+                    }
+                    if offset.replace((Oper::Minus, value, unit)).is_some() {
+                        return Err(twice("offset"));
+                    }
+                    (None, None)
+                }
+            }
+            _ => match parse_weekday(word) {
+                Some(weekday) => (Some(Day::Weekday(weekday)), None),
+                None => (
+                    None,
+                    Some(parse_time(word, s)?.ok_or_else(|| unknown(word))?),
+                ),
+            },
+        };
+        if let Some(found_day) = found_day {
+            if day.replace(found_day).is_some() {
+                return Err(twice("day"));
+            }
+        }
+        if let Some(found_time) = found_time {
+            if time.replace(found_time).is_some() {
+                return Err(twice("time"));
+            }
+        }
+    }
+    match (day, time, offset) {
+        (None, None, None) => Err(format!("no day or time in {:?}", s)),
+        (Some(_), _, Some(_)) => Err(format!("both day and offset in {:?}", s)),
+        (None, time, Some((oper, value, unit))) => {
+            let node = offset_node(Node::Now, oper, value, unit)?;
+            Ok(match time {
+                Some(time) => at(node, &time),
+                None => node,
+            })
+        }
+        (Some(day), None, None) => day_node(day),
+        (Some(day), Some(time), None) => Ok(at(day_node(day)?, &time)),
+        (None, Some(time), None) => {
+            // Today if still ahead, tomorrow otherwise.
+            let today = at(Node::Now, &time);
+            Ok(Node::If {
+                cond: Rc::new(Node::Compare {
+                    oper: CompareOper::Gt,
+                    left: Rc::new(today.clone()),
+                    right: Rc::new(Node::Now),
+                }),
+                then: Rc::new(today),
+                otherwise: Rc::new(at(day_node(Day::After(1))?, &time)),
+            })
+        }
+    }
+}
+
+/// Split the phrase into lowercase words, with the meridiem written apart, like "3 pm", joined to the number.
+fn split_words(s: &str) -> Vec<String> {
+    let mut words: Vec<String> = vec![];
+    for word in s.to_lowercase().split_whitespace() {
+        match words.last_mut() {
+            Some(last)
+                if matches!(word, "am" | "pm") && last.ends_with(|c: char| c.is_ascii_digit()) =>
+            {
+                last.push_str(word)
+            }
+            _ => words.push(word.to_string()),
+        }
+    }
+    words
+}
+
+/// The midnight of the day. The days are counted from the noon and the time is set with "at", so neither the shift
+/// by the fixed 24 hours nor the clock change can move to another day.
+fn day_node(day: Day) -> Result<Node, String> {
+    let noon = |days: i64| offset_node(at(Node::Now, "12:00"), Oper::Plus, 24 * days, "h");
+    let node = match day {
+        Day::After(days) => noon(days)?,
+        // The last such day of week up to 6 days after today, or up to 7 days for the next one. The midnight of the
+        // week start can be off by the clock change, so it is moved to the noon of the day.
+        Day::Weekday(weekday) => {
+            offset_node(snap_to_week_day(noon(6)?, weekday)?, Oper::Plus, 12, "h")?
+        }
+        Day::NextWeekday(weekday) => {
+            offset_node(snap_to_week_day(noon(7)?, weekday)?, Oper::Plus, 12, "h")?
+        }
+    };
+    Ok(at(node, "00:00"))
+}
+
+/// Set the time of day of the date-time node, like "15:00".
+fn at(node: Node, time: &str) -> Node {
+    Node::FuncAry2 {
+        name: "at".to_string(),
+        arg1: Rc::new(node),
+        arg2: Rc::new(Node::String(time.to_string())),
+    }
+}
+
+/// Parse the day of week like "tuesday" or "tue".
+fn parse_weekday(word: &str) -> Option<u32> {
+    WEEKDAY_NAMES
+        .iter()
+        .position(|name| word.len() >= 3 && name.starts_with(word))
+        .map(|index| index as u32)
+}
+
+/// Parse the time of day like "3pm", "3:30pm" or "15:00" as HH:MM. A bare number is not a time.
+fn parse_time(word: &str, s: &str) -> Result<Option<String>, String> {
+    let re = regex::Regex::new(RE_TIME).unwrap();
+    let caps = match re.captures(word) {
+        Some(caps) if caps.name("minute").is_some() || caps.name("meridiem").is_some() => caps,
+        _ => return Ok(None),
+    };
+    let bad_time = || format!("bad time {:?} in {:?}", word, s);
+    let hour = caps["hour"].parse::<u32>().unwrap();
+    let minute = caps
+        .name("minute")
+        .map_or(0, |m| m.as_str().parse().unwrap());
+    let hour = match caps.name("meridiem").map(|m| m.as_str()) {
+        Some(_) if !(1..=12).contains(&hour) => return Err(bad_time()),
+        Some("am") => hour % 12,
+        Some(_) => hour % 12 + 12,
+        None => hour,
+    };
+    if hour > 23 || minute > 59 {
+        return Err(bad_time());
+    }
+    Ok(Some(format!("{:02}:{:02}", hour, minute)))
+}
+
+/// Parse the amount like "2 hours" of the offset.
+fn parse_amount(
+    value: Option<&str>,
+    unit: Option<&str>,
+    s: &str,
+) -> Result<(i64, &'static str), String> {
+    let (value, unit) = match (value, unit) {
+        (Some(value), Some(unit)) => (value, unit),
+        _ => return Err(format!("expected amount like \"2 hours\" in {:?}", s)),
+    };
+    let value = value
+        .parse::<i64>()
+        .map_err(|_| format!("bad number {:?} in {:?}", value, s))?;
+    Ok((value, splunk_unit(unit)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{natural_to_node, parse_time};
+
+    #[test]
+    fn times() {
+        let check = |word: &str, expected: Option<&str>| {
+            assert_eq!(
+                parse_time(word, word),
+                Ok(expected.map(|s| s.to_string())),
+                "{}",
+                word
+            );
+        };
+        check("3pm", Some("15:00"));
+        check("3:30am", Some("03:30"));
+        check("12am", Some("00:00"));
+        check("12pm", Some("12:00"));
+        check("15:00", Some("15:00"));
+        check("3", None);
+        assert!(parse_time("13pm", "13pm").is_err());
+        assert!(parse_time("25:00", "25:00").is_err());
+    }
+
+    #[test]
+    fn errors() {
+        for phrase in [
+            "",
+            "someday",
+            "next",
+            "next month",
+            "tomorrow yesterday",
+            "noon midnight",
+            "in 2 parsecs",
+            "2 hours",
+            "tomorrow in 2 hours",
+        ] {
+            assert!(natural_to_node(phrase).is_err(), "{}", phrase);
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use std::net::UdpSocket;
+use std::time::Duration;
+
+pub const DEFAULT_NTP_SERVER: &str = "pool.ntp.org";
+const NTP_PORT: u16 = 123;
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// Seconds between the NTP epoch (1900-01-01) and the Unix epoch.
+const NTP_EPOCH_OFFSET_SECONDS: i64 = 2_208_988_800;
+
+/// Network time with the offset of the local clock, positive when the local clock is behind.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NtpTime {
+    pub time: DateTime<Utc>,
+    pub offset: TimeDelta,
+}
+
+/// Query the server with SNTP (RFC 4330). The server is a host name with optional port.
+pub fn query(server: &str) -> Result<NtpTime, String> {
+    let address = if server.contains(':') {
+        server.to_string()
+    } else {
+        format!("{}:{}", server, NTP_PORT)
+    };
+    let err = |e: std::io::Error| format!("NTP query to {} failed: {}", address, e);
+    let socket = UdpSocket::bind("0.0.0.0:0").map_err(err)?;
+    socket.set_read_timeout(Some(TIMEOUT)).map_err(err)?;
+    socket.connect(&address).map_err(err)?;
+    let mut request = [0u8; 48];
+    // Leap indicator 0, version 3, mode 3 (client).
+    request[0] = 0x1b;
+    let sent = Utc::now();
+    socket.send(&request).map_err(err)?;
+    let mut response = [0u8; 48];
+    let len = socket.recv(&mut response).map_err(err)?;
+    let received = Utc::now();
+    if len < response.len() {
+        return Err(format!("NTP response from {} is too short", address));
+    }
+    parse_response(&response, sent, received)
+}
+
+/// Compute the offset from the server receive and transmit timestamps, and the local send and receive times.
+fn parse_response(
+    response: &[u8; 48],
+    sent: DateTime<Utc>,
+    received: DateTime<Utc>,
+) -> Result<NtpTime, String> {
+    let mode = response[0] & 0x7;
+    if mode != 4 {
+        return Err(format!("unexpected NTP response mode {}", mode));
+    }
+    let server_received = read_timestamp(&response[32..40])?;
+    let server_sent = read_timestamp(&response[40..48])?;
+    let offset = ((server_received - sent) + (server_sent - received)) / 2;
+    Ok(NtpTime {
+        time: received + offset,
+        offset,
+    })
+}
+
+fn read_timestamp(bytes: &[u8]) -> Result<DateTime<Utc>, String> {
+    let seconds = u32::from_be_bytes(bytes[0..4].try_into().unwrap());
+    let fraction = u32::from_be_bytes(bytes[4..8].try_into().unwrap());
+    let nanos = ((u64::from(fraction) * 1_000_000_000) >> 32) as u32;
+    DateTime::from_timestamp(i64::from(seconds) - NTP_EPOCH_OFFSET_SECONDS, nanos)
+        .ok_or_else(|| format!("bad NTP timestamp {}", seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_response, NTP_EPOCH_OFFSET_SECONDS};
+    use chrono::{DateTime, TimeDelta};
+
+    fn response(receive_unix: i64, transmit_unix: i64) -> [u8; 48] {
+        let mut response = [0u8; 48];
+        response[0] = 0x1c;
+        let receive = (receive_unix + NTP_EPOCH_OFFSET_SECONDS) as u32;
+        let transmit = (transmit_unix + NTP_EPOCH_OFFSET_SECONDS) as u32;
+        response[32..36].copy_from_slice(&receive.to_be_bytes());
+        response[40..44].copy_from_slice(&transmit.to_be_bytes());
+        // Half a second.
+        response[44] = 0x80;
+        response
+    }
+
+    #[test]
+    fn offset() {
+        let sent = DateTime::from_timestamp(1_716_000_000, 0).unwrap();
+        let received = DateTime::from_timestamp(1_716_000_002, 0).unwrap();
+        // The server clock is 10s ahead, and the round trip takes 2s.
+        let actual =
+            parse_response(&response(1_716_000_011, 1_716_000_011), sent, received).unwrap();
+        assert_eq!(actual.offset, TimeDelta::milliseconds(10_250));
+        assert_eq!(actual.time, received + TimeDelta::milliseconds(10_250));
+    }
+
+    #[test]
+    fn bad_mode() {
+        let mut bad = response(0, 0);
+        bad[0] = 0x1b;
+        let now = DateTime::from_timestamp(1_716_000_000, 0).unwrap();
+        assert!(parse_response(&bad, now, now).is_err());
+    }
+}
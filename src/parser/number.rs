@@ -0,0 +1,325 @@
+use chrono::{DateTime, Utc};
+use std::str::FromStr;
+
+/// Numbers are kept as fixed-point integers with this many fractional digits, so epoch timestamps keep nanosecond
+/// precision without going through floats.
+pub const FRACTION_DIGITS: u32 = 9;
+pub const SCALE: i128 = 10i128.pow(FRACTION_DIGITS);
+
+/// Number with optional underscore separators between digits. Either a decimal number with optional exponent, like
+/// "1_700_000_000", "1724606867.000" or "1.7e9", or a hexadecimal or binary integer like "0x65f00000" or "0b1010".
+pub const RE_NUMBER: &str = r"^-?(0[xX][0-9a-fA-F]+(_[0-9a-fA-F]+)*|0[bB][01]+(_[01]+)*|\d+(_\d+)*(\.\d+(_\d+)*)?([eE][+-]?\d+)?)";
+
+/// Parse number matched by RE_NUMBER into a fixed-point value scaled by SCALE. Digits beyond the precision are
+/// truncated.
+pub fn parse_scaled(s: &str) -> Result<i128, String> {
+    let (neg, unsigned) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+    let radix_digits = |prefixes: [&str; 2]| {
+        prefixes
+            .iter()
+            .find_map(|prefix| unsigned.strip_prefix(prefix))
+            .map(|digits| digits.replace('_', ""))
+    };
+    if let Some(digits) = radix_digits(["0x", "0X"]) {
+        return parse_radix_scaled(s, &digits, 16, neg);
+    }
+    if let Some(digits) = radix_digits(["0b", "0B"]) {
+        return parse_radix_scaled(s, &digits, 2, neg);
+    }
+    let (mantissa, exponent) = match unsigned.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (
+            mantissa,
+            exponent
+                .parse::<i32>()
+                .map_err(|_| format!("bad exponent in {:?}", s))?,
+        ),
+        None => (unsigned, 0),
+    };
+    let (int_part, frac_part) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let int_digits = int_part.replace('_', "");
+    let frac_digits = frac_part.replace('_', "");
+    if int_digits.is_empty()
+        || !(int_digits.clone() + &frac_digits)
+            .chars()
+            .all(|c| c.is_ascii_digit())
+    {
+        return Err(format!("bad number {:?}", s));
+    }
+    let overflow = || format!("number {:?} is out of range", s);
+    // The value is digits * 10^shift.
+    let shift = i64::from(FRACTION_DIGITS) + i64::from(exponent) - frac_digits.len() as i64;
+    let mut digits = int_digits + &frac_digits;
+    if shift < 0 {
+        digits.truncate(digits.len().saturating_sub(shift.unsigned_abs() as usize));
+    }
+    let mut value: i128 = 0;
+    for digit in digits.bytes() {
+        value = value
+            .checked_mul(10)
+            .and_then(|v| v.checked_add(i128::from(digit - b'0')))
+            .ok_or_else(overflow)?;
+    }
+    if value != 0 {
+        for _ in 0..shift.max(0) {
+            value = value.checked_mul(10).ok_or_else(overflow)?;
+        }
+    }
+    Ok(if neg { -value } else { value })
+}
+
+fn parse_radix_scaled(s: &str, digits: &str, radix: u32, neg: bool) -> Result<i128, String> {
+    let value = i128::from_str_radix(digits, radix)
+        .ok()
+        .and_then(|v| v.checked_mul(SCALE))
+        .ok_or_else(|| format!("number {:?} is out of range", s))?;
+    Ok(if neg { -value } else { value })
+}
+
+/// Format the fixed-point value as the decimal number it was parsed from, without the trailing zeros of the fraction,
+/// like "1716000000.5". Parsing the result gives the same value.
+pub fn format_scaled(value: i128) -> String {
+    let sign = if value < 0 { "-" } else { "" };
+    let magnitude = value.unsigned_abs();
+    let whole = magnitude / SCALE as u128;
+    let fraction = magnitude % SCALE as u128;
+    if fraction == 0 {
+        return format!("{}{}", sign, whole);
+    }
+    let fraction = format!("{:0width$}", fraction, width = FRACTION_DIGITS as usize);
+    format!("{}{}.{}", sign, whole, fraction.trim_end_matches('0'))
+}
+
+/// Split a fixed-point value into whole units and nanoseconds. Nanoseconds are always non-negative, like in
+/// chrono's timestamps.
+pub fn split_scaled(value: i128) -> Result<(i64, u32), String> {
+    let whole = i64::try_from(value.div_euclid(SCALE))
+        .map_err(|_| format!("number {} is out of range", value / SCALE))?;
+    Ok((whole, value.rem_euclid(SCALE) as u32))
+}
+
+/// How to interpret a bare number as a date-time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EpochUnit {
+    Seconds,
+    Millis,
+    Micros,
+    Nanos,
+    /// Guess the unit from the magnitude, see `EpochUnit::guess`.
+    Auto,
+}
+
+impl FromStr for EpochUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "s" => Ok(EpochUnit::Seconds),
+            "ms" => Ok(EpochUnit::Millis),
+            "us" => Ok(EpochUnit::Micros),
+            "ns" => Ok(EpochUnit::Nanos),
+            "auto" => Ok(EpochUnit::Auto),
+            _ => Err(format!(
+                "bad epoch unit {:?}, expected one of s, ms, us, ns, auto",
+                s
+            )),
+        }
+    }
+}
+
+impl EpochUnit {
+    /// Guess the unit from the magnitude of the number. Numbers below 10^11 are seconds (up to year 5138), below
+    /// 10^14 milliseconds, below 10^17 microseconds, and the larger ones nanoseconds. So all the units give dates
+    /// between 1973 and 5138 for positive numbers of the matching magnitude.
+    pub fn guess(value: i128) -> EpochUnit {
+        let whole = (value / SCALE).unsigned_abs();
+        if whole < 10u128.pow(11) {
+            EpochUnit::Seconds
+        } else if whole < 10u128.pow(14) {
+            EpochUnit::Millis
+        } else if whole < 10u128.pow(17) {
+            EpochUnit::Micros
+        } else {
+            EpochUnit::Nanos
+        }
+    }
+
+    /// Name of the unit, like "seconds".
+    pub fn name(&self) -> &'static str {
+        match self {
+            EpochUnit::Seconds => "seconds",
+            EpochUnit::Millis => "milliseconds",
+            EpochUnit::Micros => "microseconds",
+            EpochUnit::Nanos => "nanoseconds",
+            EpochUnit::Auto => "auto",
+        }
+    }
+
+    /// Length of the unit in nanoseconds.
+    pub fn nanos(&self) -> i128 {
+        match self {
+            EpochUnit::Seconds => 1_000_000_000,
+            EpochUnit::Millis => 1_000_000,
+            EpochUnit::Micros => 1_000,
+            EpochUnit::Nanos => 1,
+            EpochUnit::Auto => panic!("BUG! auto epoch unit has no length"),
+        }
+    }
+}
+
+/// Interpret fixed-point number as an epoch timestamp in the given unit.
+pub fn scaled_to_datetime(value: i128, unit: EpochUnit) -> Result<DateTime<Utc>, String> {
+    let unit = match unit {
+        EpochUnit::Auto => EpochUnit::guess(value),
+        unit => unit,
+    };
+    let nanos = value.div_euclid(SCALE / unit.nanos());
+    let out_of_range = || {
+        format!(
+            "timestamp {} ({:?}) is out of range",
+            value as f64 / SCALE as f64,
+            unit
+        )
+    };
+    let (secs, nsecs) = split_scaled(nanos).map_err(|_| out_of_range())?;
+    DateTime::from_timestamp(secs, nsecs).ok_or_else(out_of_range)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_scaled, parse_scaled, scaled_to_datetime, split_scaled, EpochUnit, SCALE};
+
+    #[test]
+    fn parse_integer() {
+        assert_eq!(parse_scaled("0"), Ok(0));
+        assert_eq!(parse_scaled("1724606867"), Ok(1724606867 * SCALE));
+        assert_eq!(parse_scaled("-5"), Ok(-5 * SCALE));
+    }
+
+    #[test]
+    fn parse_fraction() {
+        assert_eq!(parse_scaled("0.1"), Ok(SCALE / 10));
+        assert_eq!(parse_scaled("1.123456789"), Ok(1_123_456_789));
+        // Truncated beyond nanoseconds.
+        assert_eq!(parse_scaled("1.1234567899"), Ok(1_123_456_789));
+    }
+
+    #[test]
+    fn parse_underscores() {
+        assert_eq!(parse_scaled("1_700_000_000"), Ok(1_700_000_000 * SCALE));
+        assert_eq!(parse_scaled("0.000_001"), Ok(1000));
+    }
+
+    #[test]
+    fn parse_exponent() {
+        assert_eq!(parse_scaled("1.7e9"), Ok(1_700_000_000 * SCALE));
+        assert_eq!(parse_scaled("1.7E+9"), Ok(1_700_000_000 * SCALE));
+        assert_eq!(parse_scaled("17e-1"), Ok(17 * SCALE / 10));
+        assert_eq!(parse_scaled("1e-9"), Ok(1));
+        assert_eq!(parse_scaled("1e-10"), Ok(0));
+        assert_eq!(parse_scaled("-2.5e1"), Ok(-25 * SCALE));
+    }
+
+    #[test]
+    fn parse_hex() {
+        assert_eq!(parse_scaled("0x65f00000"), Ok(0x65f00000 * SCALE));
+        assert_eq!(parse_scaled("0X65F0_0000"), Ok(0x65f00000 * SCALE));
+        assert_eq!(parse_scaled("-0x10"), Ok(-16 * SCALE));
+        assert!(parse_scaled("0xffffffffffffffffffffffffffffffff").is_err());
+    }
+
+    #[test]
+    fn parse_binary() {
+        assert_eq!(parse_scaled("0b1010"), Ok(10 * SCALE));
+        assert_eq!(parse_scaled("0b1111_0000"), Ok(240 * SCALE));
+    }
+
+    #[test]
+    fn parse_out_of_range() {
+        assert!(parse_scaled("1e40").is_err());
+        assert!(parse_scaled("1e400").is_err());
+        assert!(parse_scaled("99999999999999999999999999999999999999999").is_err());
+        assert_eq!(parse_scaled("0e400"), Ok(0));
+    }
+
+    #[test]
+    fn split() {
+        assert_eq!(split_scaled(1_500_000_000), Ok((1, 500_000_000)));
+        assert_eq!(split_scaled(-1_500_000_000), Ok((-2, 500_000_000)));
+        assert!(split_scaled(i128::MAX).is_err());
+    }
+
+    #[test]
+    fn format() {
+        for s in [
+            "0",
+            "1716000000",
+            "-3",
+            "1716000000.5",
+            "-0.000000001",
+            "1.25",
+        ] {
+            assert_eq!(format_scaled(parse_scaled(s).unwrap()), s);
+        }
+        assert_eq!(format_scaled(parse_scaled("1.7e9").unwrap()), "1700000000");
+        assert_eq!(format_scaled(parse_scaled("0x10").unwrap()), "16");
+    }
+
+    #[test]
+    fn to_datetime() {
+        let check = |value: i128, unit: EpochUnit, expected: &str| {
+            assert_eq!(
+                scaled_to_datetime(value, unit).map(|d| d.to_rfc3339()),
+                Ok(expected.to_string())
+            )
+        };
+        check(
+            1_716_000_000 * SCALE,
+            EpochUnit::Seconds,
+            "2024-05-18T02:40:00+00:00",
+        );
+        check(
+            1_716_000_000_123 * SCALE,
+            EpochUnit::Millis,
+            "2024-05-18T02:40:00.123+00:00",
+        );
+        check(
+            1_716_000_000_123_456 * SCALE,
+            EpochUnit::Micros,
+            "2024-05-18T02:40:00.123456+00:00",
+        );
+        check(
+            1_716_000_000_123_456_789 * SCALE,
+            EpochUnit::Nanos,
+            "2024-05-18T02:40:00.123456789+00:00",
+        );
+        check(
+            1_500 * SCALE / 1000,
+            EpochUnit::Millis,
+            "1970-01-01T00:00:00.001500+00:00",
+        );
+        check(-SCALE, EpochUnit::Millis, "1969-12-31T23:59:59.999+00:00");
+        assert!(scaled_to_datetime(1_716_000_000_123_456 * SCALE, EpochUnit::Seconds).is_err());
+    }
+
+    #[test]
+    fn guess_unit() {
+        assert_eq!(EpochUnit::guess(1_716_000_000 * SCALE), EpochUnit::Seconds);
+        assert_eq!(
+            EpochUnit::guess(1_716_000_000_123 * SCALE),
+            EpochUnit::Millis
+        );
+        assert_eq!(
+            EpochUnit::guess(1_716_000_000_123_456 * SCALE),
+            EpochUnit::Micros
+        );
+        assert_eq!(
+            EpochUnit::guess(1_716_000_000_123_456_789 * SCALE),
+            EpochUnit::Nanos
+        );
+        assert_eq!(EpochUnit::guess(-1_716_000_000 * SCALE), EpochUnit::Seconds);
+        assert_eq!(EpochUnit::guess(0), EpochUnit::Seconds);
+    }
+}
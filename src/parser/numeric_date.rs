@@ -0,0 +1,223 @@
+//! Numeric dates like "5/17/24", "17.05.24" or "2024/05/17", where the order of the day, the month and the year is
+//! only a convention of the writer. The order is set with --date-order, otherwise the date must be valid in exactly
+//! one of the orders.
+use std::str::FromStr;
+
+use chrono::NaiveDate;
+
+/// Order of the parts of a numeric date.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DateOrder {
+    /// Month, day, year, like "5/17/24" in the US.
+    Mdy,
+    /// Day, month, year, like "17.05.24" in most of Europe.
+    Dmy,
+    /// Year, month, day, like "2024/05/17".
+    Ymd,
+}
+
+impl FromStr for DateOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mdy" => Ok(DateOrder::Mdy),
+            "dmy" => Ok(DateOrder::Dmy),
+            "ymd" => Ok(DateOrder::Ymd),
+            _ => Err(format!(
+                "bad date order {:?}, expected one of mdy, dmy, ymd",
+                s
+            )),
+        }
+    }
+}
+
+impl DateOrder {
+    fn name(&self) -> &'static str {
+        match self {
+            DateOrder::Mdy => "mdy",
+            DateOrder::Dmy => "dmy",
+            DateOrder::Ymd => "ymd",
+        }
+    }
+
+    /// The year, the month and the day from the parts in this order.
+    fn ymd<'a>(&self, parts: &[&'a str; 3]) -> (&'a str, &'a str, &'a str) {
+        match self {
+            DateOrder::Mdy => (parts[2], parts[0], parts[1]),
+            DateOrder::Dmy => (parts[2], parts[1], parts[0]),
+            DateOrder::Ymd => (parts[0], parts[1], parts[2]),
+        }
+    }
+
+    /// The date from the parts in this order, if the year has 2 or 4 digits and the date exists.
+    fn date(&self, parts: &[&str; 3]) -> Option<NaiveDate> {
+        let (year, month, day) = self.ymd(parts);
+        let year = match year.len() {
+            2 => pivot_year(year.parse().unwrap()),
+            4 => year.parse().unwrap(),
+            _ => return None,
+        };
+        NaiveDate::from_ymd_opt(year, month.parse().unwrap(), day.parse().unwrap())
+    }
+}
+
+/// Two-digit year as in POSIX strptime %y, 69 to 99 are 1969 to 1999, and 00 to 68 are 2000 to 2068.
+fn pivot_year(year: i32) -> i32 {
+    if year >= 69 {
+        1900 + year
+    } else {
+        2000 + year
+    }
+}
+
+/// Resolve the numeric date like "5/17/24" in the order, or without the order in the only order that gives a valid
+/// date. The year-first order is tried only for a four digit first part, so "05/06/07" is ambiguous only between
+/// month-first and day-first.
+pub fn resolve_numeric_date(s: &str, order: Option<DateOrder>) -> Result<NaiveDate, String> {
+    let parts = split_parts(s)?;
+    if let Some(order) = order {
+        return order
+            .date(&parts)
+            .ok_or_else(|| format!("no such date {:?} in {} order", s, order.name()));
+    }
+    let orders = if parts[0].len() == 4 {
+        vec![DateOrder::Ymd]
+    } else {
+        vec![DateOrder::Mdy, DateOrder::Dmy]
+    };
+    let mut candidates: Vec<(DateOrder, NaiveDate)> = vec![];
+    for order in orders {
+        if let Some(date) = order.date(&parts) {
+            // Same day and month give the same date in both orders.
+            if !candidates.iter().any(|(_, other)| *other == date) {
+                candidates.push((order, date));
+            }
+        }
+    }
+    match candidates.as_slice() {
+        [] => Err(format!("no such date {:?}", s)),
+        [(_, date)] => Ok(*date),
+        _ => Err(format!(
+            "ambiguous date {:?}, could be {}, set the order with --date-order",
+            s,
+            candidates
+                .iter()
+                .map(|(order, date)| format!("{} ({})", date, order.name()))
+                .collect::<Vec<String>>()
+                .join(" or ")
+        )),
+    }
+}
+
+/// Resolve the numeric date only with the order and the four digit year, for --strict-parse.
+pub fn resolve_numeric_date_strict(s: &str, order: Option<DateOrder>) -> Result<NaiveDate, String> {
+    let order = order.ok_or_else(|| {
+        format!(
+            "the order of day and month in {:?} is a guess, set --date-order in strict mode",
+            s
+        )
+    })?;
+    let (year, _, _) = order.ymd(&split_parts(s)?);
+    if year.len() != 4 {
+        return Err(format!(
+            "two-digit year {:?} in {:?} is a guess of the century, use four digits in strict mode",
+            year, s
+        ));
+    }
+    resolve_numeric_date(s, Some(order))
+}
+
+/// Whether the year of the numeric date has two digits, so the century is a guess, like "24" in "5/17/24".
+pub fn has_two_digit_year(s: &str, order: Option<DateOrder>) -> bool {
+    let Ok(parts) = split_parts(s) else {
+        return false;
+    };
+    // Without the order, the year is the last part unless the first one has four digits.
+    let order = order.unwrap_or(if parts[0].len() == 4 {
+        DateOrder::Ymd
+    } else {
+        DateOrder::Mdy
+    });
+    order.ymd(&parts).0.len() == 2
+}
+
+fn split_parts(s: &str) -> Result<[&str; 3], String> {
+    let parts: Vec<&str> = s.split(['/', '.']).collect();
+    parts
+        .try_into()
+        .map_err(|_| format!("bad numeric date {:?}", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{has_two_digit_year, resolve_numeric_date, resolve_numeric_date_strict, DateOrder};
+    use chrono::NaiveDate;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn inferred_order() {
+        assert_eq!(resolve_numeric_date("5/17/24", None), Ok(date(2024, 5, 17)));
+        assert_eq!(
+            resolve_numeric_date("17.05.24", None),
+            Ok(date(2024, 5, 17))
+        );
+        assert_eq!(
+            resolve_numeric_date("2024/05/17", None),
+            Ok(date(2024, 5, 17))
+        );
+        assert_eq!(resolve_numeric_date("5.5.1999", None), Ok(date(1999, 5, 5)));
+        assert!(resolve_numeric_date("1/2/69", None).is_err());
+        assert_eq!(
+            resolve_numeric_date("5/6/24", None),
+            Err("ambiguous date \"5/6/24\", could be 2024-05-06 (mdy) or 2024-06-05 (dmy), set the order with --date-order".to_string())
+        );
+        assert!(resolve_numeric_date("13/13/24", None).is_err());
+        assert!(resolve_numeric_date("5/17/024", None).is_err());
+    }
+
+    #[test]
+    fn explicit_order() {
+        let mdy = Some(DateOrder::Mdy);
+        let dmy = Some(DateOrder::Dmy);
+        let ymd = Some(DateOrder::Ymd);
+        assert_eq!(resolve_numeric_date("5/6/24", mdy), Ok(date(2024, 5, 6)));
+        assert_eq!(resolve_numeric_date("5/6/24", dmy), Ok(date(2024, 6, 5)));
+        assert_eq!(resolve_numeric_date("24/5/6", ymd), Ok(date(2024, 5, 6)));
+        assert_eq!(resolve_numeric_date("1/2/69", mdy), Ok(date(1969, 1, 2)));
+        assert_eq!(resolve_numeric_date("1/2/68", mdy), Ok(date(2068, 1, 2)));
+        assert_eq!(
+            resolve_numeric_date("17.05.24", mdy),
+            Err("no such date \"17.05.24\" in mdy order".to_string())
+        );
+    }
+
+    #[test]
+    fn two_digit_year() {
+        assert!(has_two_digit_year("5/17/24", None));
+        assert!(!has_two_digit_year("5/17/2024", None));
+        assert!(!has_two_digit_year("2024/05/17", None));
+        assert!(has_two_digit_year("24/5/6", Some(DateOrder::Ymd)));
+        assert!(!has_two_digit_year("5/17", None));
+    }
+
+    #[test]
+    fn strict() {
+        let dmy = Some(DateOrder::Dmy);
+        assert_eq!(
+            resolve_numeric_date_strict("17.05.2024", dmy),
+            Ok(date(2024, 5, 17))
+        );
+        assert!(resolve_numeric_date_strict("17.05.2024", None).is_err());
+        assert!(resolve_numeric_date_strict("17.05.24", dmy).is_err());
+    }
+
+    #[test]
+    fn parse_order() {
+        assert_eq!("dmy".parse(), Ok(DateOrder::Dmy));
+        assert!("md".parse::<DateOrder>().is_err());
+    }
+}
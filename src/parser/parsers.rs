@@ -1,361 +1,1582 @@
 use super::{
-    core::{InputPointer, Node, Oper, ParseErr, ParseOk, Parser},
-    match_duration, DisplayParseResult, ShortFormat,
+    core::{CompareOper, InputPointer, Node, Oper, ParseErr, ParseOk, Parser},
+    match_clock_duration, match_duration,
+    number::{parse_scaled, RE_NUMBER},
+    registry::Registry,
+    relative::{grafana_to_node, RE_GRAFANA},
+    suggest::closest,
+    time_delta,
+    types::{Type, TYPE_NAMES},
+    zone::{from_local, parse_tz, Abbreviation, Tz, ABBREVIATIONS, UTC},
+    ClockFormat, DisplayParseResult,
 };
-use crate::log::debug_nested_log;
-use chrono::{self, TimeDelta};
-use chrono_tz::UTC;
+use crate::{
+    log::debug_nested_log,
+    syntax::{parse_duration, DurationErr},
+};
+use chrono::{self, Offset, TimeDelta};
 use regex::Regex;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
 use std::rc::Rc;
 
+/// The regex compiled once, on the first use. The parsers run at many positions of the input, and compiling the
+/// regex each time dominated the parsing.
+macro_rules! regex {
+    ($re:expr) => {{
+        static RE: std::sync::LazyLock<Regex> =
+            std::sync::LazyLock::new(|| Regex::new($re).unwrap());
+        &*RE
+    }};
+}
+
+/// Literals and the keywords of map and if.
+pub const KEYWORDS: [&str; 8] = [
+    "now", "uptime", "ntp_now", "epoch", "max_time", "min_time", "map", "if",
+];
+
+/// Names of all the built-in functions, once each, e.g. for completion.
+pub fn function_names() -> Vec<String> {
+    Registry::builtin().names()
+}
+
+/// Match the value at the beginning of the input, i.e. date-time, relative time, duration, number or string, and
+/// return its node and length. Used for tokenization.
+pub fn match_value(input: &String) -> Option<(Node, usize)> {
+    let pointer = InputPointer::from_string(input);
+    let datetime = Map(&DateTime, Node::DateTime);
+    let duration = Map(&SignedDuration, Node::Duration);
+    let timestamp = Map(&Timestamp, Node::Number);
+    let string = Map(&StringLiteral, Node::String);
+    let value = FirstOf::new(vec![
+        &datetime,
+        &LocalDateTime { after_date: false },
+        &NamedMonthDate,
+        &NumericDate,
+        &GrafanaTime,
+        &duration,
+        &timestamp,
+        &string,
+    ]);
+    value
+        .parse(pointer, 0)
+        .ok()
+        .map(|parse_ok| (parse_ok.node, parse_ok.pointer.pos))
+}
+
 pub fn parse_expr<'a>(input: &'a String) -> Result<ParseOk<'a>, ParseErr<'a>> {
+    parse_expr_with(input, &Registry::builtin())
+}
+
+/// Parse the expression with the functions of the registry, e.g. with functions registered by library users.
+pub fn parse_expr_with<'a>(
+    input: &'a String,
+    registry: &Registry,
+) -> Result<ParseOk<'a>, ParseErr<'a>> {
+    parse_expr_impl(input, registry, false)
+}
+
+/// Parse the expression with each term wrapped in Node::Spanned with its position in the input, so the errors found
+/// later, like by check_types, can point at the terms.
+pub fn parse_expr_spanned<'a>(
+    input: &'a String,
+    registry: &Registry,
+) -> Result<ParseOk<'a>, ParseErr<'a>> {
+    parse_expr_impl(input, registry, true)
+}
+
+fn parse_expr_impl<'a>(
+    input: &'a String,
+    registry: &Registry,
+    spans: bool,
+) -> Result<ParseOk<'a>, ParseErr<'a>> {
     let pointer = InputPointer::from_string(input);
-    let result = ExprParser.parse(pointer, 0);
-    result.map(|parse_ok| {
+    let memo = Memo::default();
+    let result = ExprParser {
+        registry,
+        spans,
+        depth: 0,
+        memo: &memo,
+    }
+    .parse(pointer, 0);
+    let result = result.map(|parse_ok| {
         if parse_ok.pointer.is_end() {
             Ok(parse_ok)
         } else {
             Err(ParseErr {
                 pointer: parse_ok.pointer,
                 message: "not all input matched".to_string(),
+                fatal: false,
+                expected: vec!["end of input".to_string()],
+                len: 0,
             })
         }
-    })?
+    })?;
+    // An unknown identifier before the syntax error is more likely the cause, like "nwo" in "nwo + 1d zz".
+    match (check_identifiers(pointer, registry), result) {
+        (Err(ident_err), Err(parse_err)) if ident_err.pointer.pos < parse_err.pointer.pos => {
+            Err(ident_err)
+        }
+        (_, Err(parse_err)) => Err(parse_err),
+        (ident_result, Ok(parse_ok)) => ident_result.map(|_| parse_ok),
+    }
+}
+
+/// Check the identifiers of the input one by one, so a misspelled keyword or function name like "nwo" is reported
+/// with the closest known name, instead of as unknown variable at evaluation. An identifier is known if it is a
+/// keyword, a function, or a variable bound by an earlier "x ->" of map. The values like "1d" or "2024-05-17" are
+/// skipped as a whole.
+fn check_identifiers<'a>(
+    mut pointer: InputPointer<'a>,
+    registry: &Registry,
+) -> Result<(), ParseErr<'a>> {
+    let identifier = regex!(r"^[a-zA-Z_][a-zA-Z0-9_]*");
+    let binding = regex!(r"^\s*->");
+    let names: Vec<String> = KEYWORDS
+        .iter()
+        .map(|keyword| keyword.to_string())
+        .chain(registry.names())
+        .collect();
+    let mut variables: Vec<String> = vec![];
+    while let Some(c) = pointer.rest().chars().next() {
+        let rest = pointer.rest();
+        if let Some((_, len)) = match_value(&rest.to_string()) {
+            pointer = pointer.advance(len);
+            continue;
+        }
+        let word = match identifier.find(rest) {
+            Some(m) => m.as_str(),
+            None => {
+                pointer = pointer.advance(c.len_utf8());
+                continue;
+            }
+        };
+        if binding.is_match(&rest[word.len()..]) {
+            variables.push(word.to_string());
+        } else if pointer.input[..pointer.pos].trim_end().ends_with("::") {
+            // The type names are checked by TypeName.
+        } else if !variables.iter().chain(&names).any(|name| name == word) {
+            // The variables first, the innermost ones are the likeliest.
+            let candidates = variables
+                .iter()
+                .rev()
+                .chain(&names)
+                .map(|name| name.as_str());
+            let suggestion = closest(word, candidates);
+            let message = match suggestion {
+                Some(suggestion) => {
+                    format!(
+                        "unknown identifier {:?}, did you mean {:?}?",
+                        word, suggestion
+                    )
+                }
+                None => format!("unknown identifier {:?}", word),
+            };
+            return Err(ParseErr {
+                pointer,
+                message,
+                fatal: true,
+                expected: suggestion.into_iter().map(|s| s.to_string()).collect(),
+                len: 0,
+            });
+        }
+        pointer = pointer.advance(word.len());
+    }
+    Ok(())
+}
+
+/// Reject the time zone abbreviations like "PST" in the date-times, for --strict-parse. The zone of an abbreviation
+/// is a guess, "IST" alone is India, Ireland or Israel.
+pub fn check_strict_zones(input: &String) -> Result<(), ParseErr<'_>> {
+    match zone_abbreviations(input).into_iter().next() {
+        Some((pointer, (abbreviation, zones))) => Err(ParseErr {
+            pointer,
+            message: format!(
+                "time zone abbreviation {:?} is a guess of {}, use the zone name in strict mode",
+                abbreviation,
+                zones
+                    .iter()
+                    .map(|(name, _, _)| *name)
+                    .collect::<Vec<_>>()
+                    .join(" or ")
+            ),
+            fatal: true,
+            expected: vec!["time zone name".to_string()],
+            len: 0,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Warnings about the time zone abbreviations like "PST" in the date-times, taken as the offset of the only zone of
+/// the abbreviation. The ambiguous ones fail the parsing.
+pub fn zone_warnings(input: &String) -> Vec<String> {
+    zone_abbreviations(input)
+        .into_iter()
+        .filter_map(|(_, (abbreviation, zones))| match zones {
+            [(name, offset, _)] => Some(format!(
+                "time zone abbreviation {:?} is taken as {} of {}, use the zone name to be sure",
+                abbreviation, offset, name
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The time zone abbreviations in the date-times of the input, with their positions and their zones.
+fn zone_abbreviations(input: &String) -> Vec<(InputPointer<'_>, Abbreviation)> {
+    let zone = regex!(r"(\[!?(?<bracketed>[^\]]*)\]| (?<word>[A-Za-z]\w*))$");
+    let mut found = vec![];
+    let mut pointer = InputPointer::from_string(input);
+    while let Some(c) = pointer.rest().chars().next() {
+        let (node, len) = match match_value(&pointer.rest().to_string()) {
+            Some(value) => value,
+            None => {
+                pointer = pointer.advance(c.len_utf8());
+                continue;
+            }
+        };
+        let value = &pointer.rest()[..len];
+        let caps = match node {
+            Node::DateTime(_) | Node::LocalDateTime { .. } => zone.captures(value),
+            _ => None,
+        };
+        if let Some(name) = caps.and_then(|caps| caps.name("bracketed").or(caps.name("word"))) {
+            if let Some((abbreviation, zones)) = ABBREVIATIONS
+                .iter()
+                .find(|(abbreviation, _)| abbreviation.eq_ignore_ascii_case(name.as_str()))
+            {
+                found.push((pointer.advance(name.start()), (*abbreviation, *zones)));
+            }
+        }
+        pointer = pointer.advance(len);
+    }
+    found
 }
 
 /// Expression grammar is:
+///  pipeline (compare_oper pipeline)*
+/// where pipeline is:
+///  interval ("|>" func_name ("(" args ")")?)*
+/// where interval is:
+///  terms (".." terms)?
+/// where terms are:
 ///  (sighed_duration | date) (signed_duration | signed_date)*
-/// Validity of the expression is figured during evaluation.
-struct ExprParser;
+/// Validity of the expression is figured during evaluation. The function calls are matched with the names and the
+/// numbers of arguments of the registry.
+struct ExprParser<'r> {
+    registry: &'r Registry,
+    /// Wrap the terms in Node::Spanned.
+    spans: bool,
+    /// Number of the enclosing expressions, up to MAX_NESTING.
+    depth: usize,
+    memo: &'r Memo,
+}
+
+/// The result of ExprParser without the lifetime of the input: the end and the node, or the error with its position
+/// and the length of the marked input.
+type MemoResult = Result<(usize, Node), (usize, String, bool, Vec<String>, usize)>;
 
-impl Parser for ExprParser {
+/// Results of ExprParser by the position, the depth and the spans. The alternatives starting with an expression at
+/// the same position, like the calls of a function with different numbers of arguments, or the list and the
+/// brackets, parse it once, so the parsing stays linear in the input instead of repeating for each alternative and
+/// each level of nesting (packrat parsing).
+#[derive(Default)]
+struct Memo(RefCell<HashMap<(usize, usize, bool), MemoResult>>);
+
+impl Memo {
+    fn get<'a>(
+        &self,
+        key: (usize, usize, bool),
+        pointer: InputPointer<'a>,
+    ) -> Option<Result<ParseOk<'a>, ParseErr<'a>>> {
+        let at = |pos| InputPointer {
+            input: pointer.input,
+            pos,
+        };
+        let memo = self.0.borrow();
+        memo.get(&key).map(|result| match result {
+            Ok((pos, node)) => Ok(ParseOk {
+                pointer: at(*pos),
+                node: node.clone(),
+            }),
+            Err((pos, message, fatal, expected, len)) => Err(ParseErr {
+                pointer: at(*pos),
+                message: message.clone(),
+                fatal: *fatal,
+                expected: expected.clone(),
+                len: *len,
+            }),
+        })
+    }
+
+    fn insert(&self, key: (usize, usize, bool), result: &Result<ParseOk<'_>, ParseErr<'_>>) {
+        let result = match result {
+            Ok(parse_ok) => Ok((parse_ok.pointer.pos, parse_ok.node.clone())),
+            Err(parse_err) => Err((
+                parse_err.pointer.pos,
+                parse_err.message.clone(),
+                parse_err.fatal,
+                parse_err.expected.clone(),
+                parse_err.len,
+            )),
+        };
+        self.0.borrow_mut().insert(key, result);
+    }
+}
+
+/// Maximum nesting of the expressions in brackets, function arguments and lists. The parser recurses for each level,
+/// so the deeper input, like generated or hostile, would overflow the stack. The chained pipe stages, interval ends,
+/// comparisons and type assertions nest the nodes too, and the evaluation recurses for each, so they count as levels.
+pub const MAX_NESTING: usize = 32;
+
+fn nested_too_deeply(pointer: InputPointer<'_>) -> ParseErr<'_> {
+    ParseErr {
+        pointer,
+        message: format!(
+            "expression nested too deeply, more than {} levels of brackets, calls, lists or chained operations",
+            MAX_NESTING
+        ),
+        fatal: true,
+        expected: vec![],
+        len: 0,
+    }
+}
+
+impl Parser for ExprParser<'_> {
     fn parse<'a>(
         &self,
         pointer: InputPointer<'a>,
         nesting: usize,
     ) -> Result<ParseOk<'a>, ParseErr<'a>> {
         debug_nested_log(nesting, format!("ExprParer input={}", pointer));
-        let expr = ExprParser;
+        let key = (pointer.pos, self.depth, self.spans);
+        if let Some(result) = self.memo.get(key, pointer) {
+            return result;
+        }
+        let result = self.parse_expr(pointer, nesting);
+        self.memo.insert(key, &result);
+        result
+    }
+}
+
+impl ExprParser<'_> {
+    fn parse_expr<'a>(
+        &self,
+        pointer: InputPointer<'a>,
+        nesting: usize,
+    ) -> Result<ParseOk<'a>, ParseErr<'a>> {
+        if self.depth > MAX_NESTING {
+            return Err(nested_too_deeply(pointer));
+        }
+        // the levels left for the chained operations
+        let max_chain = MAX_NESTING - self.depth;
+        let expr = ExprParser {
+            registry: self.registry,
+            spans: self.spans,
+            depth: self.depth + 1,
+            memo: self.memo,
+        };
         let ws0 = Whitespace::new_optional();
         let ws1 = Whitespace::new_must_have();
         let now = LiteralNode::new("now", Node::Now);
-        let datetime = DateTime;
-        let timestamp = Timestamp;
-        //let datetime_or_now = FirstOf::new(vec![&datetime, &timestamp, &now]);
-        let signed_duration = SignedDuration;
-        let sign = Literal::new_any(&["+", "-"]).set_skip();
+        let grafana_time = GrafanaTime;
+        let uptime = LiteralNode::new("uptime", Node::Uptime);
+        let ntp_now = LiteralNode::new("ntp_now", Node::NtpNow);
+        let epoch = LiteralNode::new("epoch", Node::Epoch);
+        let max_time = LiteralNode::new("max_time", Node::MaxTime);
+        let min_time = LiteralNode::new("min_time", Node::MinTime);
+        let datetime = Map(&DateTime, Node::DateTime);
+        let local_datetime = LocalDateTime { after_date: false };
+        let named_month_date = NamedMonthDate;
+        let numeric_date = NumericDate;
+        let timestamp = Map(&Timestamp, Node::Number);
+        let signed_duration = Map(&SignedDuration, Node::Duration);
+        let sign = LiteralValue(&[("+", Oper::Plus), ("-", Oper::Minus)]);
         let left_bracket = Literal::new("(").set_skip();
         let right_bracket = Literal::new(")").set_skip();
-        let bracket_expr =
-            Sequence::new_as_expr(&vec![&left_bracket, &ws0, &expr, &ws0, &right_bracket]);
-        // The function names are hardcoded in the parser.
-        let func_ary1_literals = Literal::new_any(&["full_day", "full_hour"]);
-        let func_ary1 = Sequence::new(
-            &vec![&func_ary1_literals, &left_bracket, &expr, &right_bracket],
-            |nodes| sequence_to_func_ary1(nodes),
+        let comma = Literal::new(",").set_skip();
+        let bracket_expr = Map(
+            &Preceded(
+                &left_bracket,
+                &Preceded(&ws0, &Terminated(&expr, &Preceded(&ws0, &right_bracket))),
+            ),
+            |node| Node::Expr(vec![node]),
+        );
+
+        // The arguments in brackets, like "(now)" or "(now, 1h)", one after another.
+        let only_arg = Preceded(&left_bracket, &Terminated(&expr, &right_bracket));
+        let first_arg = Preceded(&left_bracket, &expr);
+        let next_arg = Preceded(&comma, &expr);
+        let last_arg = Preceded(&comma, &Terminated(&expr, &right_bracket));
+        let args2 = Pair(&first_arg, &last_arg);
+        let args3 = Pair(&first_arg, &Pair(&next_arg, &last_arg));
+        let args4 = Pair(&first_arg, &Pair(&next_arg, &Pair(&next_arg, &last_arg)));
+
+        let func_names = |arity| FuncName(Some(self.registry.names_with_arity(arity)));
+        let func_ary1_names = func_names(1);
+        let func_ary1 = Map(&Pair(&func_ary1_names, &only_arg), |(name, arg1)| {
+            Node::FuncAry1 {
+                name,
+                arg1: Rc::new(arg1),
+            }
+        });
+        let func_ary2_names = func_names(2);
+        let func_ary2 = Map(&Pair(&func_ary2_names, &args2), |(name, (arg1, arg2))| {
+            Node::FuncAry2 {
+                name,
+                arg1: Rc::new(arg1),
+                arg2: Rc::new(arg2),
+            }
+        });
+        let func_ary3_names = func_names(3);
+        let func_ary3 = Map(
+            &Pair(&func_ary3_names, &args3),
+            |(name, (arg1, (arg2, arg3)))| Node::FuncAry3 {
+                name,
+                arg1: Rc::new(arg1),
+                arg2: Rc::new(arg2),
+                arg3: Rc::new(arg3),
+            },
+        );
+        let func_ary4_names = func_names(4);
+        let func_ary4 = Map(
+            &Pair(&func_ary4_names, &args4),
+            |(name, (arg1, (arg2, (arg3, arg4))))| Node::FuncAry4 {
+                name,
+                arg1: Rc::new(arg1),
+                arg2: Rc::new(arg2),
+                arg3: Rc::new(arg3),
+                arg4: Rc::new(arg4),
+            },
+        );
+        let string = Map(&StringLiteral, Node::String);
+        let left_square_bracket = Literal::new("[").set_skip();
+        let right_square_bracket = Literal::new("]").set_skip();
+        let list = Map(
+            &Pair(
+                &Preceded(&left_square_bracket, &expr),
+                &Terminated(&Repeated(&next_arg), &right_square_bracket),
+            ),
+            |(first, rest)| Node::List(std::iter::once(first).chain(rest).collect()),
+        );
+        let variable = Map(&Variable, Node::Variable);
+        let bad_call = BadCall {
+            registry: self.registry,
+            depth: self.depth + 1,
+            memo: self.memo,
+        };
+        let map_literal = Literal::new("map").set_skip();
+        let arrow = Literal::new("->").set_skip();
+        let binding = Preceded(
+            &comma,
+            &Preceded(&ws0, &Terminated(&Variable, &Preceded(&ws0, &arrow))),
+        );
+        let map = Map(
+            &Pair(
+                &Preceded(&map_literal, &first_arg),
+                &Pair(&binding, &Terminated(&expr, &right_bracket)),
+            ),
+            |(list, (variable, body))| Node::Map {
+                list: Rc::new(list),
+                variable,
+                body: Rc::new(body),
+            },
+        );
+        let if_literal = Literal::new("if");
+        let if_expr = Map(
+            &Pair(
+                &Preceded(&if_literal, &first_arg),
+                &Pair(&next_arg, &last_arg),
+            ),
+            |(cond, (then, otherwise))| Node::If {
+                cond: Rc::new(cond),
+                then: Rc::new(then),
+                otherwise: Rc::new(otherwise),
+            },
         );
         // A "term" is datetime or now or duration or function call or expression in brackets.
         let term = FirstOf::new(vec![
-            //&datetime_or_now,
             &datetime,
+            &local_datetime, // before signed duration, otherwise "13:00" would be matched as MM:SS duration.
+            &named_month_date, // before timestamp, otherwise "17" would be matched leaving "May 2024" unparsed.
+            &numeric_date, // before timestamp, otherwise "5" would be matched leaving "/17/24" unparsed.
+            &grafana_time, // before now, otherwise "now" would be matched leaving "-1h" unparsed.
             &now,
+            &uptime,
+            &ntp_now,
+            &epoch,
+            &max_time, // before func_ary1, otherwise "max" would be tried as function.
+            &min_time,
             &signed_duration,
             &timestamp, // timestamp is after signed duration, otherwise 1s would be matched as "1" being timestamp and "s" possibly and causing error.
             &func_ary1,
+            &func_ary2,
+            &func_ary3,
+            &func_ary4,
+            &map,
+            &if_expr,
+            &string,
             &bracket_expr,
+            &list,
+            &bad_call, // after the calls of the registered functions and keywords, to report what did not match them.
+            &variable, // the last one, otherwise function names and keywords would be matched as variables.
         ]);
-        let oper_term = Sequence::new(&vec![&ws1, &sign, &ws1, &term], |nodes| {
-            nodes_to_oper_expr(nodes)
+        let spanned_term = Spanned(&term);
+        let term: &dyn Parser = if self.spans { &spanned_term } else { &term };
+        let spaced_sign = Terminated(&Preceded(&ws1, &sign), &ws1);
+        let signed_term = Pair(&spaced_sign, term);
+        let oper_term = Map(&signed_term, |(oper, node)| Node::OperNode {
+            oper,
+            node: Rc::new(node),
         });
-        let repeated_terms = RepeatedAsExpr(&oper_term);
 
         // list of terms that are either added or subtracted
-        let list_of_terms = Sequence::new_as_expr(&vec![&ws0, &term, &repeated_terms, &ws0]);
-        list_of_terms.parse(pointer, nesting + 1)
+        let list_of_terms = Map(
+            &Preceded(&ws0, &Terminated(&Pair(term, &Repeated(&oper_term)), &ws0)),
+            terms_node,
+        );
+
+        // optional end of interval, like "now..now + 1d"
+        let interval_sep = Literal::new("..").set_skip();
+        let interval_end = Preceded(&interval_sep, &list_of_terms);
+        let interval = Map(
+            &Pair(&list_of_terms, &Chained(&interval_end, max_chain)),
+            interval_node,
+        );
+
+        // pipeline of function calls, like "now |> full_day |> add_months(1)"
+        let pipe = Literal::new("|>").set_skip();
+        let func_name = FuncName(None);
+        let stage_args_0 = Map(&func_name, |name| (name, vec![]));
+        let stage_args_1 = Map(&Pair(&func_name, &only_arg), |(name, arg)| {
+            (name, vec![arg])
+        });
+        let stage_args_2 = Map(&Pair(&func_name, &args2), |(name, (arg1, arg2))| {
+            (name, vec![arg1, arg2])
+        });
+        let stage_args_3 = Map(&Pair(&func_name, &args3), |(name, (arg1, (arg2, arg3)))| {
+            (name, vec![arg1, arg2, arg3])
+        });
+        let stage = FirstOf::new(vec![
+            &stage_args_3,
+            &stage_args_2,
+            &stage_args_1,
+            &stage_args_0,
+        ]);
+        let stage = PipeStage {
+            stage: &stage,
+            registry: self.registry,
+        };
+        let piped_stage = Preceded(&pipe, &Preceded(&ws0, &Terminated(&stage, &ws0)));
+        let piped_stages = Chained(&piped_stage, max_chain);
+        let pipeline = Map(&Pair(&interval, &piped_stages), pipeline_node);
+
+        // optional comparison, like "now > deadline"
+        let compare_oper = LiteralValue(&[
+            ("<=", CompareOper::Le),
+            (">=", CompareOper::Ge),
+            ("==", CompareOper::Eq),
+            ("!=", CompareOper::Ne),
+            ("<", CompareOper::Lt),
+            (">", CompareOper::Gt),
+        ]);
+        let compare_right = Pair(&compare_oper, &pipeline);
+        let compare_rights = Chained(&compare_right, max_chain);
+        let compared = Pair(&pipeline, &compare_rights);
+        let comparison = Map(&compared, comparison_node);
+
+        // optional assertion of the type of the result, like "now - deadline :: duration"
+        let type_sep = Literal::new("::").set_skip();
+        let spaced_type_sep = Preceded(&ws0, &Terminated(&type_sep, &ws0));
+        let type_name = TypeName;
+        let type_assertion = Terminated(&Preceded(&spaced_type_sep, &type_name), &ws0);
+        let type_assertions = Chained(&type_assertion, max_chain);
+        let asserted_types = Pair(&comparison, &type_assertions);
+        let asserted = Map(&asserted_types, type_assertion_node);
+        asserted.parse(pointer, nesting + 1)
+    }
+}
+
+/// Convert the first term followed by the terms with the operators to an expression, with the operator nodes in an
+/// inner expression, like "now" and "+ 1h" of "now + 1h".
+fn terms_node((first, opers): (Node, Vec<Node>)) -> Node {
+    match opers.is_empty() {
+        true => Node::Expr(vec![first]),
+        false => Node::Expr(vec![first, Node::Expr(opers)]),
+    }
+}
+
+/// Convert the terms followed by the interval ends to an interval, or return the terms if there is no end. The
+/// chained intervals like "a..b..c" are nested, and rejected at evaluation.
+fn interval_node((start, ends): (Node, Vec<Node>)) -> Node {
+    ends.into_iter().fold(start, |start, end| Node::Interval {
+        start: Rc::new(start),
+        end: Rc::new(end),
+    })
+}
+
+/// Convert the input followed by the piped stages to nested function calls, where the input is the first argument of
+/// the first stage, and so on. Each stage is the function name with the other arguments, at most three.
+fn pipeline_node((input, stages): (Node, Vec<(String, Vec<Node>)>)) -> Node {
+    stages.into_iter().fold(input, |input, (name, args)| {
+        let arg1 = Rc::new(input);
+        let mut args = args.into_iter().map(Rc::new);
+        match (args.next(), args.next(), args.next()) {
+            (None, _, _) => Node::FuncAry1 { name, arg1 },
+            (Some(arg2), None, _) => Node::FuncAry2 { name, arg1, arg2 },
+            (Some(arg2), Some(arg3), None) => Node::FuncAry3 {
+                name,
+                arg1,
+                arg2,
+                arg3,
+            },
+            (Some(arg2), Some(arg3), Some(arg4)) => Node::FuncAry4 {
+                name,
+                arg1,
+                arg2,
+                arg3,
+                arg4,
+            },
+        }
+    })
+}
+
+/// Convert the left side followed by the compared right sides to comparisons. Chained comparisons like "a < b < c"
+/// are parsed, but fail at evaluation, since bool is compared with the right side.
+fn comparison_node((left, rights): (Node, Vec<(CompareOper, Node)>)) -> Node {
+    rights
+        .into_iter()
+        .fold(left, |left, (oper, right)| Node::Compare {
+            oper,
+            left: Rc::new(left),
+            right: Rc::new(right),
+        })
+}
+
+/// Convert the expression followed by the asserted types to type assertions, the innermost first.
+fn type_assertion_node((node, types): (Node, Vec<Type>)) -> Node {
+    types
+        .into_iter()
+        .fold(node, |node, expected| Node::TypeAssertion {
+            node: Rc::new(node),
+            expected,
+        })
+}
+
+struct SignedDuration;
+
+impl Parser<TimeDelta> for SignedDuration {
+    fn parse<'a>(
+        &self,
+        pointer: InputPointer<'a>,
+        nesting: usize,
+    ) -> Result<ParseOk<'a, TimeDelta>, ParseErr<'a>> {
+        debug_nested_log(nesting, format!("SignedDuration input={}", pointer));
+
+        let matched = match_duration(pointer.rest()).map_err(|message| ParseErr {
+            pointer,
+            message,
+            fatal: true,
+            expected: vec![],
+            len: 0,
+        })?;
+        match matched {
+            Some(matched) => match parse_duration(matched) {
+                Ok(duration) => Ok(ParseOk {
+                    pointer: pointer.advance(matched.len()),
+                    node: time_delta(duration),
+                }),
+                // The duration is well-formed, so no other parser should take it, like "110000" of "110000d" as a
+                // timestamp.
+                Err(DurationErr::OutOfRange(message)) => Err(ParseErr {
+                    pointer,
+                    message,
+                    fatal: true,
+                    expected: vec![],
+                    len: matched.len(),
+                }),
+                Err(DurationErr::Invalid(message)) => Err(ParseErr {
+                    pointer,
+                    message,
+                    fatal: false,
+                    expected: vec!["duration".to_string()],
+                    len: 0,
+                }),
+            },
+            None => match match_clock_duration(pointer.rest()) {
+                Some(matched) => match TimeDelta::from_clock_format(matched) {
+                    Ok(duration) => Ok(ParseOk {
+                        pointer: pointer.advance(matched.len()),
+                        node: duration,
+                    }),
+                    Err(message) => Err(ParseErr {
+                        pointer,
+                        message,
+                        fatal: true,
+                        expected: vec![],
+                        len: matched.len(),
+                    }),
+                },
+                None => Err(ParseErr {
+                    pointer,
+                    message: String::from("did not match any duration"),
+                    fatal: false,
+                    expected: vec!["duration".to_string()],
+                    len: 0,
+                }),
+            },
+        }
+    }
+}
+
+/// Number, evaluated as epoch timestamp when used as a date-time.
+struct Timestamp;
+
+impl Parser<i128> for Timestamp {
+    fn parse<'a>(
+        &self,
+        pointer: InputPointer<'a>,
+        nesting: usize,
+    ) -> Result<ParseOk<'a, i128>, ParseErr<'a>> {
+        debug_nested_log(nesting, format!("Timestamp input={}", pointer));
+        let pat = regex!(RE_NUMBER);
+        let matched = if let Some(matched) = pat.find(pointer.rest()) {
+            matched.as_str()
+        } else {
+            return Err(ParseErr {
+                pointer,
+                message: "not a timestamp".to_string(),
+                fatal: false,
+                expected: vec!["number".to_string()],
+                len: 0,
+            });
+        };
+        let value = parse_scaled(matched).map_err(|message| ParseErr {
+            pointer,
+            message,
+            fatal: true,
+            expected: vec![],
+            len: 0,
+        })?;
+        debug_nested_log(nesting, format!("Timestamp parsed value={}", value));
+        Ok(ParseOk {
+            pointer: pointer.advance(matched.len()),
+            node: value,
+        })
+    }
+}
+
+/// Grafana relative time like "now-7d/d", see relative::RE_GRAFANA.
+struct GrafanaTime;
+
+impl Parser for GrafanaTime {
+    fn parse<'a>(
+        &self,
+        pointer: InputPointer<'a>,
+        nesting: usize,
+    ) -> Result<ParseOk<'a>, ParseErr<'a>> {
+        debug_nested_log(nesting, format!("GrafanaTime input={}", pointer));
+        let pat = regex!(RE_GRAFANA);
+        let matched = if let Some(matched) = pat.find(pointer.rest()) {
+            matched.as_str()
+        } else {
+            return Err(ParseErr {
+                pointer,
+                message: "not a relative time".to_string(),
+                fatal: false,
+                expected: vec!["relative time".to_string()],
+                len: 0,
+            });
+        };
+        let node = grafana_to_node(matched).map_err(|message| ParseErr {
+            pointer,
+            message,
+            fatal: true,
+            expected: vec![],
+            len: 0,
+        })?;
+        Ok(ParseOk {
+            pointer: pointer.advance(matched.len()),
+            node,
+        })
+    }
+}
+
+/// A string in double quotes. There is no escaping, the string ends at the first closing quote.
+struct StringLiteral;
+
+impl Parser<String> for StringLiteral {
+    fn parse<'a>(
+        &self,
+        pointer: InputPointer<'a>,
+        nesting: usize,
+    ) -> Result<ParseOk<'a, String>, ParseErr<'a>> {
+        debug_nested_log(nesting, format!("StringLiteral input={}", pointer));
+        let rest = pointer.rest();
+        if !rest.starts_with('"') {
+            return Err(ParseErr {
+                pointer,
+                message: "not a string".to_string(),
+                fatal: false,
+                expected: vec!["string".to_string()],
+                len: 0,
+            });
+        }
+        match rest[1..].find('"') {
+            Some(end) => Ok(ParseOk {
+                pointer: pointer.advance(end + 2),
+                node: rest[1..end + 1].to_string(),
+            }),
+            None => Err(ParseErr {
+                pointer,
+                message: "missing closing quote".to_string(),
+                fatal: false,
+                expected: vec!["closing quote".to_string()],
+                len: 0,
+            }),
+        }
+    }
+}
+
+struct DateTime;
+
+impl Parser<chrono::DateTime<Tz>> for DateTime {
+    fn parse<'a>(
+        &self,
+        pointer: InputPointer<'a>,
+        nesting: usize,
+    ) -> Result<ParseOk<'a, chrono::DateTime<Tz>>, ParseErr<'a>> {
+        debug_nested_log(nesting, format!("DateTime input={}", pointer));
+        // The offset, the zone name in brackets (RFC 9557), or both, like "2024-05-17T13:00:00+02:00[Europe/Warsaw]".
+        let pat = regex!(
+            r"^(?<local>(?<year>[+-]\d{4,6}|\d{4})-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}(\.\d+)?)(?<offset>Z|([+-]\d{2}:\d{2}))?(\[!?(?<zone>[^\]]*)\])?"
+        );
+        let not_datetime = |message: &str| ParseErr {
+            pointer,
+            message: message.to_string(),
+            fatal: false,
+            expected: vec!["date-time".to_string()],
+            len: 0,
+        };
+        let bad_zone = |message: String| ParseErr {
+            pointer,
+            message,
+            fatal: true,
+            expected: vec![],
+            len: 0,
+        };
+        let caps = match pat.captures(pointer.rest()) {
+            Some(caps) if caps.name("offset").is_some() || caps.name("zone").is_some() => caps,
+            _ => return Err(not_datetime("not a datetime")),
+        };
+        check_year(pointer, &caps["year"])?;
+        let match_ = caps.get(0).unwrap().as_str();
+        let tz = match caps.name("zone") {
+            Some(zone) => Some(parse_tz(zone.as_str()).map_err(bad_zone)?),
+            None => None,
+        };
+        let datetime = match (caps.name("offset"), tz) {
+            (Some(offset), tz) => {
+                // Not parsed as RFC 3339, which has only the years 0000 to 9999, but with the extended years of ISO 8601
+                // like "+12024" or "-0044".
+                let utc_offset = match offset.as_str() {
+                    "Z" => "+00:00",
+                    offset => offset,
+                };
+                let datetime = chrono::DateTime::parse_from_str(
+                    &format!("{}{}", &caps["local"], utc_offset),
+                    "%Y-%m-%dT%H:%M:%S%.f%:z",
+                )
+                .map_err(|_| not_datetime("bad datetime"))?;
+                match tz {
+                    // "Z" is the instant without the local offset, so it fits any zone.
+                    Some(tz) => {
+                        let in_zone = datetime.with_timezone(&tz);
+                        if offset.as_str() != "Z" && in_zone.offset().fix() != *datetime.offset() {
+                            return Err(bad_zone(format!(
+                                "offset {} does not match {} at {}",
+                                offset.as_str(),
+                                tz,
+                                &caps["local"]
+                            )));
+                        }
+                        in_zone
+                    }
+                    None => datetime.with_timezone(&UTC),
+                }
+            }
+            (None, Some(tz)) => {
+                let local =
+                    chrono::NaiveDateTime::parse_from_str(&caps["local"], "%Y-%m-%dT%H:%M:%S%.f")
+                        .map_err(|_| not_datetime("bad datetime"))?;
+                from_local(local, &tz).map_err(bad_zone)?
+            }
+            (None, None) => unreachable!("checked above"),
+        };
+        Ok(ParseOk {
+            pointer: pointer.advance(match_.len()),
+            node: datetime,
+        })
+    }
+}
+
+/// Fail on the year beyond the range of the date-times, like "+300000", rather than leaving it to the other parsers.
+fn check_year<'a>(pointer: InputPointer<'a>, year: &str) -> Result<(), ParseErr<'a>> {
+    use chrono::{Datelike, NaiveDate};
+    let range = NaiveDate::MIN.year()..=NaiveDate::MAX.year();
+    if year.parse::<i32>().is_ok_and(|year| range.contains(&year)) {
+        return Ok(());
+    }
+    Err(ParseErr {
+        pointer,
+        message: format!(
+            "year {} is out of range, expected {} to {}",
+            year,
+            range.start(),
+            range.end()
+        ),
+        fatal: true,
+        expected: vec![],
+        len: 0,
+    })
+}
+
+/// Name of function, like "full_day" in "full_day(now)". With the names, one of them, tried in order like the
+/// literals, like the registered functions of an arity. Otherwise any name, like the stage of a pipeline, where the
+/// unknown names are reported by PipeStage.
+struct FuncName(Option<Vec<String>>);
+
+impl Parser<String> for FuncName {
+    fn parse<'a>(
+        &self,
+        pointer: InputPointer<'a>,
+        nesting: usize,
+    ) -> Result<ParseOk<'a, String>, ParseErr<'a>> {
+        debug_nested_log(nesting, format!("FuncName input={}", pointer));
+        let pat = regex!(r"^[a-zA-Z_][a-zA-Z0-9_]*");
+        let name = match &self.0 {
+            Some(names) => names
+                .iter()
+                .find(|name| pointer.rest().starts_with(name.as_str()))
+                .map(|name| name.as_str()),
+            None => pat.find(pointer.rest()).map(|m| m.as_str()),
+        };
+        match name {
+            Some(name) => Ok(ParseOk {
+                pointer: pointer.advance(name.len()),
+                node: name.to_string(),
+            }),
+            None => Err(ParseErr {
+                pointer,
+                message: "not a function name".to_string(),
+                fatal: false,
+                expected: match &self.0 {
+                    Some(names) => names.clone(),
+                    None => vec!["function name".to_string()],
+                },
+                len: 0,
+            }),
+        }
+    }
+}
+
+/// Function call not matched by the registered functions, like "full_day(now, 1)" or "ful_day(now)". Reported as a
+/// fatal error with the numbers of arguments of the function or the closest function name. Fails without the error if
+/// the arguments do not parse, so the errors inside the brackets are reported as they are.
+struct BadCall<'r> {
+    registry: &'r Registry,
+    /// The depth of the arguments, as ExprParser::depth.
+    depth: usize,
+    memo: &'r Memo,
+}
+
+impl Parser for BadCall<'_> {
+    fn parse<'a>(
+        &self,
+        pointer: InputPointer<'a>,
+        nesting: usize,
+    ) -> Result<ParseOk<'a>, ParseErr<'a>> {
+        debug_nested_log(nesting, format!("BadCall input={}", pointer));
+        let not_call = ParseErr {
+            pointer,
+            message: "not a function call".to_string(),
+            fatal: false,
+            expected: vec![],
+            len: 0,
+        };
+        let pat = regex!(r"^([a-zA-Z_][a-zA-Z0-9_]*)\(");
+        let name = match pat.captures(pointer.rest()) {
+            // The keywords with brackets have their own parsers.
+            Some(caps) if !["map", "if"].contains(&&caps[1]) => caps[1].to_string(),
+            _ => return Err(not_call),
+        };
+        let expr = ExprParser {
+            registry: self.registry,
+            spans: false,
+            depth: self.depth,
+            memo: self.memo,
+        };
+        let mut args_pointer = pointer.advance(name.len() + 1);
+        let mut arity = 0;
+        if !args_pointer.rest().trim_start().starts_with(')') {
+            loop {
+                args_pointer = expr.parse(args_pointer, nesting + 1)?.pointer;
+                arity += 1;
+                match args_pointer.rest().chars().next() {
+                    Some(',') => args_pointer = args_pointer.advance(1),
+                    Some(')') => break,
+                    _ => return Err(not_call),
+                }
+            }
+        }
+        match self.registry.check_call(&name, arity) {
+            Ok(()) => Err(not_call),
+            Err(message) => {
+                let names = self.registry.names();
+                let suggestion = closest(&name, names.iter().map(|name| name.as_str()));
+                Err(ParseErr {
+                    pointer,
+                    message,
+                    fatal: true,
+                    expected: suggestion.into_iter().map(|s| s.to_string()).collect(),
+                    len: 0,
+                })
+            }
+        }
+    }
+}
+
+/// Pipeline stage, like "add_months(1)" in "now |> add_months(1)", checked against the registered functions. The
+/// piped value is the first argument, so the stage has one argument more than written in the brackets.
+struct PipeStage<'p, 'r> {
+    stage: &'p dyn Parser<(String, Vec<Node>)>,
+    registry: &'r Registry,
+}
+
+impl Parser<(String, Vec<Node>)> for PipeStage<'_, '_> {
+    fn parse<'a>(
+        &self,
+        pointer: InputPointer<'a>,
+        nesting: usize,
+    ) -> Result<ParseOk<'a, (String, Vec<Node>)>, ParseErr<'a>> {
+        debug_nested_log(nesting, format!("PipeStage input={}", pointer));
+        let parse_ok = self.stage.parse(pointer, nesting + 1)?;
+        let (name, args) = &parse_ok.node;
+        match self.registry.check_call(name, args.len() + 1) {
+            Ok(()) => Ok(parse_ok),
+            Err(message) => {
+                let names = self.registry.names();
+                let suggestion = closest(name, names.iter().map(|name| name.as_str()));
+                Err(ParseErr {
+                    pointer,
+                    message,
+                    fatal: true,
+                    expected: suggestion.into_iter().map(|s| s.to_string()).collect(),
+                    len: 0,
+                })
+            }
+        }
+    }
+}
+
+/// Name of the type in the type assertion, like "duration" in "now - deadline :: duration".
+struct TypeName;
+
+impl Parser<Type> for TypeName {
+    fn parse<'a>(
+        &self,
+        pointer: InputPointer<'a>,
+        nesting: usize,
+    ) -> Result<ParseOk<'a, Type>, ParseErr<'a>> {
+        debug_nested_log(nesting, format!("TypeName input={}", pointer));
+        let pat = regex!(r"^[a-zA-Z_][a-zA-Z0-9_]*");
+        let name = pat.find(pointer.rest()).map_or("", |m| m.as_str());
+        let expected = Type::from_name(name).ok_or_else(|| ParseErr {
+            pointer,
+            message: format!(
+                "unknown type {:?}, expected one of {}",
+                name,
+                TYPE_NAMES.join(", ")
+            ),
+            fatal: true,
+            expected: TYPE_NAMES.iter().map(|name| name.to_string()).collect(),
+            len: 0,
+        })?;
+        Ok(ParseOk {
+            pointer: pointer.advance(name.len()),
+            node: expected,
+        })
+    }
+}
+
+/// Name of variable, like "x". A name followed by bracket is not a variable but an unknown function.
+struct Variable;
+
+impl Parser<String> for Variable {
+    fn parse<'a>(
+        &self,
+        pointer: InputPointer<'a>,
+        nesting: usize,
+    ) -> Result<ParseOk<'a, String>, ParseErr<'a>> {
+        debug_nested_log(nesting, format!("Variable input={}", pointer));
+        let pat = regex!(r"^[a-zA-Z_][a-zA-Z0-9_]*");
+        match pat.find(pointer.rest()) {
+            Some(m) if !pointer.rest()[m.len()..].starts_with('(') => Ok(ParseOk {
+                pointer: pointer.advance(m.len()),
+                node: m.as_str().to_string(),
+            }),
+            _ => Err(ParseErr {
+                pointer,
+                message: "not a variable".to_string(),
+                fatal: false,
+                expected: vec!["variable".to_string()],
+                len: 0,
+            }),
+        }
+    }
+}
+
+/// Date-time without time zone, like "2024-05-17 13:00" or "2024-05-17 13:00:05", or only time of day like "13:00".
+/// Time of day that is not valid, like "90:00", is left to be matched as MM:SS duration. The seconds can have a fraction,
+/// like "2024-05-17 13:00:05.25".
+struct LocalDateTime {
+    /// The time follows a date parsed elsewhere, like in "17 May 2024 13:00:00", so H:MM:SS is the time of day.
+    after_date: bool,
+}
+
+impl Parser for LocalDateTime {
+    fn parse<'a>(
+        &self,
+        pointer: InputPointer<'a>,
+        nesting: usize,
+    ) -> Result<ParseOk<'a>, ParseErr<'a>> {
+        debug_nested_log(nesting, format!("LocalDateTime input={}", pointer));
+        let pat = regex!(
+            r"^(?<date>(?<year>[+-]\d{4,6}|\d{4})-\d{2}-\d{2} )?(?<time>\d{1,2}(?<minutes>:\d{2}(?<seconds>:\d{2}(\.\d{1,9})?)?)?)(?<meridiem> ?(?i:am|pm)\b)?( (?<zone>[A-Za-z][A-Za-z0-9_+-]*(/[A-Za-z0-9_+-]+)*))?"
+        );
+        let not_local_datetime = |message: &str| ParseErr {
+            pointer,
+            message: message.to_string(),
+            fatal: false,
+            expected: vec!["time of day".to_string()],
+            len: 0,
+        };
+        let caps = pat
+            .captures(pointer.rest())
+            .ok_or_else(|| not_local_datetime("not a local datetime"))?;
+        let meridiem = caps
+            .name("meridiem")
+            .map(|m| m.as_str().trim().to_lowercase());
+        // A bare number is not a time, unless with AM or PM, like "3pm".
+        if caps.name("minutes").is_none() && meridiem.is_none() {
+            return Err(not_local_datetime("not a local datetime"));
+        }
+        // A word after the time is the zone, if it looks like one. Names with slash and abbreviations that are not
+        // zones are errors, the other words are left for the next parsers.
+        let (match_, tz) = match caps.name("zone") {
+            Some(zone) => match parse_tz(zone.as_str()) {
+                Ok(tz) => (caps.get(0).unwrap().as_str(), Some(tz)),
+                Err(message)
+                    if zone.as_str().contains('/')
+                        || zone.as_str().chars().all(|c| c.is_ascii_uppercase()) =>
+                {
+                    return Err(ParseErr {
+                        pointer: pointer.advance(zone.start()),
+                        message,
+                        fatal: true,
+                        expected: vec![],
+                        len: 0,
+                    })
+                }
+                Err(_) => (&pointer.rest()[..zone.start() - 1], None),
+            },
+            None => (caps.get(0).unwrap().as_str(), None),
+        };
+        let next = pointer.rest()[match_.len()..].chars().next();
+        if next.is_some_and(|c| c.is_alphanumeric() || c == ':' || c == '.' || c == '_') {
+            return Err(not_local_datetime("not a local datetime"));
+        }
+        if caps.name("date").is_some() {
+            check_year(pointer, &caps["year"])?;
+        }
+        let date = match caps.name("date") {
+            Some(date) => Some(
+                chrono::NaiveDate::parse_from_str(date.as_str().trim(), "%Y-%m-%d")
+                    .map_err(|_| not_local_datetime("bad date"))?,
+            ),
+            // Without date, only HH:MM is a time of day, HH:MM:SS is a duration unless with AM or PM.
+            None if caps.name("seconds").is_some() && meridiem.is_none() && !self.after_date => {
+                return Err(not_local_datetime("not a time of day"))
+            }
+            None => None,
+        };
+        let time = match &meridiem {
+            Some(meridiem) => {
+                let (hour, rest) = caps["time"]
+                    .split_once(':')
+                    .unwrap_or((&caps["time"], "00"));
+                let hour = hour.parse::<u32>().unwrap();
+                if !(1..=12).contains(&hour) {
+                    return Err(ParseErr {
+                        pointer: pointer.advance(caps.name("time").unwrap().start()),
+                        message: format!(
+                            "hour {} should be 1 to 12 with {}",
+                            hour,
+                            meridiem.to_uppercase()
+                        ),
+                        fatal: true,
+                        expected: vec![],
+                        len: 0,
+                    });
+                }
+                let hour = if meridiem == "am" {
+                    hour % 12
+                } else {
+                    hour % 12 + 12
+                };
+                format!("{}:{}", hour, rest)
+            }
+            None => caps["time"].to_string(),
+        };
+        let time = chrono::NaiveTime::parse_from_str(&time, "%H:%M:%S%.f")
+            .or_else(|_| chrono::NaiveTime::parse_from_str(&time, "%H:%M"))
+            .map_err(|_| not_local_datetime("bad time of day"))?;
+        Ok(ParseOk {
+            pointer: pointer.advance(match_.len()),
+            node: Node::LocalDateTime { date, time, tz },
+        })
+    }
+}
+
+/// Date with the month name, like "17 May 2024", "17 maja 2024" or "17. Mai 2024", and as written in English prose,
+/// with the month first, the ordinal suffix and the commas, like "May 17th, 2024". The date can be followed by the
+/// time of day with the seconds and the zone, like "May 17th, 2024 1:00 PM UTC" or "17 May 2024 13:00:00", after a
+/// space only, since a comma after the year separates the elements of a list. The month name is not checked
+/// here, since the month names depend on the locale known at evaluation.
+struct NamedMonthDate;
+
+impl Parser for NamedMonthDate {
+    fn parse<'a>(
+        &self,
+        pointer: InputPointer<'a>,
+        nesting: usize,
+    ) -> Result<ParseOk<'a>, ParseErr<'a>> {
+        debug_nested_log(nesting, format!("NamedMonthDate input={}", pointer));
+        let pat = regex!(
+            r"^(?:(?<day>\d{1,2})(?<ordinal>st|nd|rd|th)?\.?,? (?<month>\p{L}+)\.?,?|(?<month_first>\p{L}+)\.? (?<day_second>\d{1,2})(?<ordinal_second>st|nd|rd|th)?,?) (?<year>\d{4})"
+        );
+        let not_named_month_date = || ParseErr {
+            pointer,
+            message: "not a date with month name".to_string(),
+            fatal: false,
+            expected: vec!["date with month name".to_string()],
+            len: 0,
+        };
+        let caps = pat
+            .captures(pointer.rest())
+            .ok_or_else(not_named_month_date)?;
+        let match_ = caps.get(0).unwrap().as_str();
+        let next = pointer.rest()[match_.len()..].chars().next();
+        if next.is_some_and(|c| c.is_alphanumeric() || c == ':' || c == '.' || c == '_') {
+            return Err(not_named_month_date());
+        }
+        let (day, month, ordinal) = match caps.name("day") {
+            Some(day) => (day, &caps["month"], caps.name("ordinal")),
+            None => (
+                caps.name("day_second").unwrap(),
+                &caps["month_first"],
+                caps.name("ordinal_second"),
+            ),
+        };
+        let day_number: u32 = day.as_str().parse().unwrap();
+        if let Some(ordinal) = ordinal {
+            let expected = ordinal_suffix(day_number);
+            if ordinal.as_str() != expected {
+                return Err(ParseErr {
+                    pointer: pointer.advance(day.start()),
+                    message: format!(
+                        "bad ordinal {}{}, expected {}{}",
+                        day_number,
+                        ordinal.as_str(),
+                        day_number,
+                        expected
+                    ),
+                    fatal: true,
+                    expected: vec![],
+                    len: 0,
+                });
+            }
+        }
+        // The time of day after the date, separated with a space, like "17 May 2024 13:00". Not with a comma, which
+        // separates the elements of a list and the arguments of a function, like in "[17 May 2024, 13:00]".
+        let time = match pointer.rest()[match_.len()..].starts_with(' ') {
+            true => LocalDateTime { after_date: true }
+                .parse(pointer.advance(match_.len() + 1), nesting + 1),
+            false => Err(not_named_month_date()),
+        };
+        let (end, time, tz) = match time {
+            Ok(ParseOk {
+                pointer: end,
+                node:
+                    Node::LocalDateTime {
+                        date: None,
+                        time,
+                        tz,
+                    },
+            }) => (end, time, tz),
+            Err(parse_err) if parse_err.fatal => return Err(parse_err),
+            _ => (pointer.advance(match_.len()), chrono::NaiveTime::MIN, None),
+        };
+        Ok(ParseOk {
+            pointer: end,
+            node: Node::NamedMonthDate {
+                day: day_number,
+                month: month.to_string(),
+                year: caps["year"].parse().unwrap(),
+                time,
+                tz,
+            },
+        })
     }
 }
 
-/// Convert a parsed sequence to function call. The order and set of the nodes is well-determined by the parser.
-fn sequence_to_func_ary1(nodes: &[Node]) -> Node {
-    let nodes = filter_insignificant_nodes(nodes);
-    if nodes.len() != 2 {
-        panic!("expected exactly two nodes got {:?}", nodes);
-    }
-    let name = if let Node::Literal { literal, skip: _ } = nodes.get(0).unwrap() {
-        literal.to_owned()
-    } else {
-        panic!(
-            "expected the first node to be literal with func name, got {:?}",
-            nodes
-        );
-    };
-    let arg1 = nodes.get(1).unwrap().to_owned();
-    Node::FuncAry1 {
-        name,
-        arg1: Rc::new(arg1),
+/// The English ordinal suffix of the day, like "st" of "1st" or "th" of "11th".
+fn ordinal_suffix(day: u32) -> &'static str {
+    match (day % 10, day % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
     }
 }
 
-fn nodes_to_oper_expr(nodes: &Vec<Node>) -> Node {
-    let oper = nodes.iter().find_map(|node| {
-        if let Node::Literal { literal, skip: _ } = node {
-            return match literal.as_str() {
-                "+" => Some(Oper::Plus),
-                "-" => Some(Oper::Minus),
-                _ => None,
-            };
+/// Numeric date like "5/17/24", "17.05.24" or "2024/05/17", with the same separator between the parts. The order of
+/// the parts is resolved at evaluation.
+struct NumericDate;
+
+impl Parser for NumericDate {
+    fn parse<'a>(
+        &self,
+        pointer: InputPointer<'a>,
+        nesting: usize,
+    ) -> Result<ParseOk<'a>, ParseErr<'a>> {
+        debug_nested_log(nesting, format!("NumericDate input={}", pointer));
+        let pat = regex!(r"^\d{1,4}(?<sep1>[./])\d{1,2}(?<sep2>[./])\d{1,4}");
+        let not_numeric_date = || ParseErr {
+            pointer,
+            message: "not a numeric date".to_string(),
+            fatal: false,
+            expected: vec!["numeric date".to_string()],
+            len: 0,
+        };
+        let caps = pat.captures(pointer.rest()).ok_or_else(not_numeric_date)?;
+        if caps["sep1"] != caps["sep2"] {
+            return Err(not_numeric_date());
         }
-        return None;
-    });
-    let oper = oper.expect(
-        format!(
-            "BUG! Expected operator at input to nodes_to_oper_expr, got {:?}",
-            nodes
-        )
-        .as_str(),
-    );
-    let nodes = filter_insignificant_nodes(nodes);
-    if nodes.len() != 1 {
-        panic!(
-            "BUG! There must be exactly one node for nodes_to_oper_expr, was: {:?}",
-            nodes
-        )
-    }
-    Node::OperNode {
-        oper,
-        node: Rc::new(nodes.get(0).unwrap().clone()),
+        let match_ = caps.get(0).unwrap().as_str();
+        let next = pointer.rest()[match_.len()..].chars().next();
+        if next.is_some_and(|c| c.is_alphanumeric() || matches!(c, ':' | '.' | '_' | '/')) {
+            return Err(not_numeric_date());
+        }
+        Ok(ParseOk {
+            pointer: pointer.advance(match_.len()),
+            node: Node::NumericDate(match_.to_string()),
+        })
     }
 }
 
-fn filter_insignificant_nodes(nodes: &[Node]) -> Vec<Node> {
-    let mut filtered_nodes: Vec<Node> = vec![];
-    for node in nodes {
-        match node {
-            Node::Duration(_)
-            | Node::DateTime(_)
-            | Node::Now
-            | Node::FuncAry1 { name: _, arg1: _ }
-            | Node::OperNode { oper: _, node: _ } => filtered_nodes.push(node.clone()),
-            Node::Expr(nodes) => {
-                if !nodes.is_empty() {
-                    filtered_nodes.push(node.clone())
-                }
-            }
-            Node::Literal { literal: _, skip } => {
-                if !skip {
-                    filtered_nodes.push(node.clone())
-                }
-            }
-        }
-    }
-    return filtered_nodes;
+#[derive(Debug)]
+struct RepeatedOk<'a, T = Node> {
+    pointer: InputPointer<'a>,
+    nodes: Vec<T>,
 }
 
-struct SignedDuration;
+/// The node of the parser wrapped in Node::Spanned with the position of the match.
+struct Spanned<'p>(&'p dyn Parser);
 
-impl Parser for SignedDuration {
+impl<'p> Parser for Spanned<'p> {
     fn parse<'a>(
         &self,
         pointer: InputPointer<'a>,
         nesting: usize,
     ) -> Result<ParseOk<'a>, ParseErr<'a>> {
-        debug_nested_log(nesting, format!("SignedDuration input={}", pointer));
-
-        match match_duration(pointer.rest()) {
-            Some(matched) => {
-                let duration = TimeDelta::from_short_format(matched)
-                    .expect("failed to parse previously matched timedelta");
-                Ok(ParseOk {
-                    pointer: pointer.advance(matched.len()),
-                    node: Node::Duration(duration),
-                })
-            }
-            None => Err(ParseErr {
-                pointer,
-                message: String::from("did not match any duration"),
-            }),
-        }
+        let parse_ok = self.0.parse(pointer, nesting + 1)?;
+        Ok(ParseOk {
+            node: Node::Spanned {
+                start: pointer.pos,
+                end: parse_ok.pointer.pos,
+                node: Rc::new(parse_ok.node),
+            },
+            pointer: parse_ok.pointer,
+        })
     }
 }
 
-/// Datetime as epoch-timestamp (seconds).
-struct Timestamp;
+/// The outputs of the parser repeated zero or more times.
+struct Repeated<'p, T>(&'p dyn Parser<T>);
 
-impl Parser for Timestamp {
+impl<T: fmt::Debug> Parser<Vec<T>> for Repeated<'_, T> {
     fn parse<'a>(
         &self,
         pointer: InputPointer<'a>,
         nesting: usize,
-    ) -> Result<ParseOk<'a>, ParseErr<'a>> {
-        debug_nested_log(nesting, format!("Timestamp input={}", pointer));
-        let pat = Regex::new(r"^(-?\d+)(\.(\d+))?").unwrap();
-        let (match_len, secs_str, nsecs_str) = if let Some(captures) = pat.captures(&pointer.rest())
-        {
-            (
-                captures.get(0).unwrap().len(),
-                captures.get(1).unwrap().as_str(),
-                captures.get(3).map_or("0", |v| v.as_str()),
-            )
-        } else {
-            return Err(ParseErr {
-                pointer,
-                message: "not a timestamp".to_string(),
-            });
-        };
-        let unix_secs = secs_str.parse::<i64>().unwrap();
-        let nsecs_str = format!("{:0<9}", nsecs_str);
-        let unix_nsecs = nsecs_str.parse::<u32>().unwrap();
-        debug_nested_log(
-            nesting,
-            format!("Timestamp parsed secs={} nsecs={}", unix_secs, unix_nsecs),
-        );
-
-        match chrono::DateTime::from_timestamp(unix_secs, unix_nsecs) {
-            Some(d) => Ok(ParseOk {
-                pointer: pointer.advance(match_len),
-                node: Node::DateTime(d.with_timezone(&UTC)),
-            }),
-            None => Err(ParseErr {
-                pointer,
-                message: format!("bad datetime for {:?} {:?}", unix_secs, unix_nsecs),
-            }),
-        }
+    ) -> Result<ParseOk<'a, Vec<T>>, ParseErr<'a>> {
+        let repeated_ok = consume_repeated(
+            self.0,
+            pointer,
+            ConsumeRepeated::ZeroOrMore,
+            nesting + 1,
+            "failed to match repeated",
+        )?;
+        Ok(ParseOk {
+            pointer: repeated_ok.pointer,
+            node: repeated_ok.nodes,
+        })
     }
 }
 
-struct DateTime;
+/// The outputs of the parser repeated zero or more times, but at most the number of times. Each output nests the
+/// previous ones, like the stages of a pipeline, so one more is an error as if nested too deeply.
+struct Chained<'p, T>(&'p dyn Parser<T>, usize);
 
-impl Parser for DateTime {
+impl<T: fmt::Debug> Parser<Vec<T>> for Chained<'_, T> {
     fn parse<'a>(
         &self,
         pointer: InputPointer<'a>,
         nesting: usize,
-    ) -> Result<ParseOk<'a>, ParseErr<'a>> {
-        debug_nested_log(nesting, format!("DateTime input={}", pointer));
-        let pat = Regex::new(r"^\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}(\.\d+)?(Z|([+-]\d{2}:\d{2}))")
-            .unwrap();
-        let match_ = if let Some(match_) = pat.find(&pointer.rest()) {
-            match_.as_str()
-        } else {
-            return Err(ParseErr {
-                pointer,
-                message: "not a datetime".to_string(),
-            });
-        };
-        if let Ok(d) = chrono::DateTime::parse_from_rfc3339(match_) {
-            return Ok(ParseOk {
-                pointer: pointer.advance(match_.len()),
-                node: Node::DateTime(d.with_timezone(&UTC)),
-            });
-        } else {
-            return Err(ParseErr {
-                pointer,
-                message: "bad datetime".to_string(),
-            });
+    ) -> Result<ParseOk<'a, Vec<T>>, ParseErr<'a>> {
+        let mut nodes: Vec<T> = Vec::new();
+        let mut current_pointer = pointer;
+        loop {
+            match self.0.parse(current_pointer, nesting + 1) {
+                Ok(_) if nodes.len() == self.1 => return Err(nested_too_deeply(current_pointer)),
+                Ok(result_ok) => {
+                    nodes.push(result_ok.node);
+                    current_pointer = result_ok.pointer;
+                }
+                Err(parse_err) if parse_err.fatal => return Err(parse_err),
+                Err(parse_err) => {
+                    return Ok(ParseOk {
+                        pointer: parse_err.pointer,
+                        node: nodes,
+                    })
+                }
+            }
         }
     }
 }
 
-/// Sequence of parsers. All the parsers must match.
-struct Sequence<'a> {
-    parsers: Vec<&'a dyn Parser>,
-    node_fn: fn(&Vec<Node>) -> Node,
-}
+/// The output of the parser converted with the function, like the operator and the right side to a node.
+struct Map<'p, T, U>(&'p dyn Parser<T>, fn(T) -> U);
 
-impl<'a> Sequence<'a> {
-    /// Return sequence as Expr node.
-    fn new_as_expr(parsers: &Vec<&'a dyn Parser>) -> Sequence<'a> {
-        Sequence::new(parsers, |nodes| {
-            Node::Expr(filter_insignificant_nodes(nodes).to_vec())
+impl<T, U> Parser<U> for Map<'_, T, U> {
+    fn parse<'a>(
+        &self,
+        pointer: InputPointer<'a>,
+        nesting: usize,
+    ) -> Result<ParseOk<'a, U>, ParseErr<'a>> {
+        let parse_ok = self.0.parse(pointer, nesting + 1)?;
+        Ok(ParseOk {
+            pointer: parse_ok.pointer,
+            node: (self.1)(parse_ok.node),
         })
     }
-
-    fn new(parsers: &Vec<&'a dyn Parser>, node_fn: fn(&Vec<Node>) -> Node) -> Sequence<'a> {
-        Sequence {
-            parsers: parsers.clone(),
-            node_fn,
-        }
-    }
 }
 
-impl<'p> Parser for Sequence<'p> {
+/// Two parsers one after another, with both outputs. As in Sequence, the error of the second parser points at the
+/// beginning of the first one, unless it is fatal.
+struct Pair<'p, A, B>(&'p dyn Parser<A>, &'p dyn Parser<B>);
+
+impl<A, B> Parser<(A, B)> for Pair<'_, A, B> {
     fn parse<'a>(
         &self,
         pointer: InputPointer<'a>,
         nesting: usize,
-    ) -> Result<ParseOk<'a>, ParseErr<'a>> {
-        debug_nested_log(nesting, format!("Sequence input={}", pointer));
-        let result = consume_sequence(&self.parsers, pointer, nesting + 1);
-        result.map(|result| {
-            let result_node = (self.node_fn)(&result.nodes);
+    ) -> Result<ParseOk<'a, (A, B)>, ParseErr<'a>> {
+        let first = self.0.parse(pointer, nesting + 1);
+        let second = first.and_then(|first| {
+            let second = self.1.parse(first.pointer, nesting + 1)?;
             Ok(ParseOk {
-                pointer: result.pointer,
-                node: result_node,
+                pointer: second.pointer,
+                node: (first.node, second.node),
             })
-        })?
+        });
+        second.map_err(|parse_err| match parse_err.fatal {
+            true => parse_err,
+            false => ParseErr {
+                pointer,
+                ..parse_err
+            },
+        })
     }
 }
 
-#[derive(Debug)]
-struct RepeatedOk<'a> {
-    pointer: InputPointer<'a>,
-    nodes: Vec<Node>,
+/// The output of the second parser, after the first one whose output is dropped, like the type name after "::".
+struct Preceded<'p, T>(&'p dyn Parser, &'p dyn Parser<T>);
+
+impl<T> Parser<T> for Preceded<'_, T> {
+    fn parse<'a>(
+        &self,
+        pointer: InputPointer<'a>,
+        nesting: usize,
+    ) -> Result<ParseOk<'a, T>, ParseErr<'a>> {
+        Map(&Pair(self.0, self.1), |(_, second)| second).parse(pointer, nesting)
+    }
 }
 
-struct RepeatedAsExpr<'p>(&'p dyn Parser);
+/// The output of the first parser, followed by the second one whose output is dropped, like the trailing whitespace.
+struct Terminated<'p, T>(&'p dyn Parser<T>, &'p dyn Parser);
 
-impl<'p> Parser for RepeatedAsExpr<'p> {
+impl<T> Parser<T> for Terminated<'_, T> {
     fn parse<'a>(
         &self,
         pointer: InputPointer<'a>,
         nesting: usize,
-    ) -> Result<ParseOk<'a>, ParseErr<'a>> {
-        consume_repeated(
-            self.0,
-            pointer,
-            ConsumeRepeated::ZeroOrMore,
-            nesting + 1,
-            "failed to match repeated",
-        )
-        .map(|repeated_ok| {
-            Ok(ParseOk {
-                pointer: repeated_ok.pointer,
-                node: Node::Expr(repeated_ok.nodes),
-            })
-        })?
+    ) -> Result<ParseOk<'a, T>, ParseErr<'a>> {
+        Map(&Pair(self.0, self.1), |(first, _)| first).parse(pointer, nesting)
     }
 }
 
 enum ConsumeRepeated {
     ZeroOrMore,
+    #[allow(dead_code)]
     OneOrMore,
 }
 
-fn consume_repeated<'a, 'p>(
-    parser: &'p dyn Parser,
+fn consume_repeated<'a, T: fmt::Debug>(
+    parser: &dyn Parser<T>,
     pointer: InputPointer<'a>,
     zero_config: ConsumeRepeated,
     nesting: usize,
     error_message: &str,
-) -> Result<RepeatedOk<'a>, ParseErr<'a>> {
-    let mut nodes: Vec<Node> = Vec::new();
+) -> Result<RepeatedOk<'a, T>, ParseErr<'a>> {
+    let mut nodes: Vec<T> = Vec::new();
     let mut current_pointer = Some(pointer);
+    let mut last_expected = vec![];
     loop {
         let result = parser.parse(current_pointer.take().unwrap(), nesting + 1);
         debug_nested_log(
             nesting,
             format!("consume_repeated result {}", result.to_string()),
         );
-        if let Ok(result_ok) = result {
-            nodes.push(result_ok.node);
-            current_pointer = Some(result_ok.pointer);
-        } else {
-            current_pointer = Some(result.unwrap_err().pointer);
-            break;
+        match result {
+            Ok(result_ok) => {
+                nodes.push(result_ok.node);
+                current_pointer = Some(result_ok.pointer);
+            }
+            Err(parse_err) if parse_err.fatal => return Err(parse_err),
+            Err(parse_err) => {
+                current_pointer = Some(parse_err.pointer);
+                last_expected = parse_err.expected;
+                break;
+            }
         }
     }
     if nodes.is_empty() {
-        return match zero_config {
+        match zero_config {
             ConsumeRepeated::ZeroOrMore => Ok(RepeatedOk {
                 pointer: current_pointer.unwrap(),
                 nodes: vec![],
@@ -363,49 +1584,54 @@ fn consume_repeated<'a, 'p>(
             ConsumeRepeated::OneOrMore => Err(ParseErr {
                 pointer: current_pointer.unwrap(),
                 message: String::from(error_message),
+                fatal: false,
+                expected: last_expected,
+                len: 0,
             }),
-        };
+        }
     } else {
         assert_ne!(
             current_pointer.unwrap(),
             pointer,
             "BUG, nodes not empty but the pointers are equal"
         );
-        return Ok(RepeatedOk {
+        Ok(RepeatedOk {
             nodes,
             pointer: current_pointer.unwrap(),
-        });
+        })
     }
 }
 
-struct FirstOf<'a> {
-    parsers: Vec<&'a dyn Parser>,
+/// The output of the first parser matching, like one of the terms.
+struct FirstOf<'a, T = Node> {
+    parsers: Vec<&'a dyn Parser<T>>,
 }
 
-impl<'p> FirstOf<'p> {
-    fn new<'a>(parsers: Vec<&'a dyn Parser>) -> FirstOf<'a> {
-        FirstOf { parsers: parsers }
+impl<'p, T> FirstOf<'p, T> {
+    fn new(parsers: Vec<&'p dyn Parser<T>>) -> FirstOf<'p, T> {
+        FirstOf { parsers }
     }
 }
 
-impl<'p> Parser for FirstOf<'p> {
+impl<T: fmt::Debug> Parser<T> for FirstOf<'_, T> {
     fn parse<'a>(
         &self,
         pointer: InputPointer<'a>,
         nesting: usize,
-    ) -> Result<ParseOk<'a>, ParseErr<'a>> {
+    ) -> Result<ParseOk<'a, T>, ParseErr<'a>> {
         debug_nested_log(nesting, format!("FirstOf input={}", pointer));
-        return consume_first(&self.parsers, pointer, nesting + 1);
+        consume_first(&self.parsers, pointer, nesting + 1)
     }
 }
 
 /// Try the parsers one after one and return the result of the first one matching.
-fn consume_first<'a, 'p>(
-    parsers: &Vec<&'p dyn Parser>,
+fn consume_first<'a, T: fmt::Debug>(
+    parsers: &[&dyn Parser<T>],
     pointer: InputPointer<'a>,
     nesting: usize,
-) -> Result<ParseOk<'a>, ParseErr<'a>> {
+) -> Result<ParseOk<'a, T>, ParseErr<'a>> {
     let mut furthest_err_pointer = None;
+    let mut expected: Vec<String> = vec![];
     for i in 0..parsers.len() {
         let parser = parsers.get(i).unwrap();
         let result = parser.parse(pointer, nesting + 1);
@@ -415,73 +1641,39 @@ fn consume_first<'a, 'p>(
         );
         match result {
             Ok(parse_ok) => return Ok(parse_ok),
+            Err(parse_err) if parse_err.fatal => return Err(parse_err),
             Err(parse_err) => {
                 if furthest_err_pointer.is_none() {
-                    furthest_err_pointer = Some(parse_err.pointer)
+                    furthest_err_pointer = Some(parse_err.pointer);
+                    expected = parse_err.expected;
                 } else {
                     // If all the parsers fail, as an error reason return the error that advanced the most in the parsing.
+                    // The expected tokens are those of all the errors that advanced the most.
                     let curr_err_pointer = furthest_err_pointer.take().unwrap();
                     if parse_err.pointer.pos > curr_err_pointer.pos {
-                        furthest_err_pointer = Some(parse_err.pointer)
+                        furthest_err_pointer = Some(parse_err.pointer);
+                        expected = parse_err.expected;
                     } else {
-                        furthest_err_pointer = Some(curr_err_pointer)
+                        furthest_err_pointer = Some(curr_err_pointer);
+                        if parse_err.pointer.pos == curr_err_pointer.pos {
+                            for token in parse_err.expected {
+                                if !expected.contains(&token) {
+                                    expected.push(token);
+                                }
+                            }
+                        }
                     }
                 }
             }
         }
     }
-    return Err(ParseErr {
+    Err(ParseErr {
         pointer: furthest_err_pointer.unwrap(),
         message: "none of the parsers matched".to_string(),
-    });
-}
-
-#[derive(Debug)]
-struct SequenceOk<'a> {
-    nodes: Vec<Node>,
-    pointer: InputPointer<'a>,
-}
-
-/// Succeed only if all the parses succeed one after another.
-fn consume_sequence<'a, 'p>(
-    parsers: &Vec<&'p dyn Parser>,
-    pointer: InputPointer<'a>,
-    nesting: usize,
-) -> Result<SequenceOk<'a>, ParseErr<'a>> {
-    debug_nested_log(nesting, format!("consume_sequence input {}", pointer));
-    let mut nodes: Vec<Node> = vec![];
-    let mut current_pointer = Some(pointer);
-    for i in 0..parsers.len() {
-        let parser = parsers.get(i).unwrap();
-        let result = parser.parse(current_pointer.take().unwrap(), nesting + 1);
-        debug_nested_log(
-            nesting,
-            format!(
-                "consume_sequence result [{}/{}] {}",
-                i + 1,
-                parsers.len(),
-                result.to_string()
-            ),
-        );
-        match result {
-            Ok(parse_ok) => {
-                nodes.push(parse_ok.node);
-                current_pointer = Some(parse_ok.pointer);
-            }
-            Err(parse_err) => {
-                return Err(ParseErr {
-                    pointer, // Pass the original pointer so when the sequence fails, pointer does not move.
-                    message: parse_err.message,
-                });
-            }
-        }
-    }
-    let pointer = current_pointer.take().unwrap();
-    debug_nested_log(
-        nesting,
-        format!("consume_sequence ok, nodes={:?}, output={}", nodes, pointer,),
-    );
-    Ok(SequenceOk { nodes, pointer })
+        fatal: false,
+        expected,
+        len: 0,
+    })
 }
 
 /// Match any of the literal strings.
@@ -498,14 +1690,6 @@ impl Literal {
         }
     }
 
-    fn new_any(literals: &[&str]) -> Literal {
-        let literals: Vec<String> = literals.iter().map(|s| s.to_string()).collect();
-        Literal {
-            literals,
-            skip: false,
-        }
-    }
-
     fn set_skip(self) -> Literal {
         Literal { skip: true, ..self }
     }
@@ -533,10 +1717,44 @@ impl Parser for Literal {
                 });
             }
         }
-        return Err(ParseErr {
+        Err(ParseErr {
             pointer,
             message: format!("expected {:?}", self.literals),
-        });
+            fatal: false,
+            expected: self.literals.clone(),
+            len: 0,
+        })
+    }
+}
+
+/// Match any of the literals and return the value of the matched one, like Oper::Plus for "+". The literals are
+/// tried in order, so the longer ones should come before their prefixes, like "<=" before "<".
+struct LiteralValue<T: 'static>(&'static [(&'static str, T)]);
+
+impl<T: Clone> Parser<T> for LiteralValue<T> {
+    fn parse<'a>(
+        &self,
+        pointer: InputPointer<'a>,
+        nesting: usize,
+    ) -> Result<ParseOk<'a, T>, ParseErr<'a>> {
+        let literals: Vec<String> = self.0.iter().map(|(s, _)| s.to_string()).collect();
+        debug_nested_log(
+            nesting,
+            format!("LiteralValue {:?} input={}", literals, pointer),
+        );
+        match self.0.iter().find(|(s, _)| pointer.rest().starts_with(s)) {
+            Some((s, value)) => Ok(ParseOk {
+                pointer: pointer.advance(s.len()),
+                node: value.clone(),
+            }),
+            None => Err(ParseErr {
+                pointer,
+                message: format!("expected {:?}", literals),
+                fatal: false,
+                expected: literals,
+                len: 0,
+            }),
+        }
     }
 }
 
@@ -583,6 +1801,9 @@ impl Parser for Whitespace {
             Err(ParseErr {
                 pointer,
                 message: "whitespace not matched".to_string(),
+                fatal: false,
+                expected: vec!["whitespace".to_string()],
+                len: 0,
             })
         }
     }
@@ -618,6 +1839,9 @@ impl Parser for LiteralNode {
             Err(ParseErr {
                 pointer,
                 message: format!("expected literal {:?}", self.literal),
+                fatal: false,
+                expected: vec![self.literal.clone()],
+                len: 0,
             })
         }
     }
@@ -626,14 +1850,17 @@ impl Parser for LiteralNode {
 #[cfg(test)]
 mod tests {
     use super::{
-        consume_repeated, consume_sequence, ConsumeRepeated, DateTime, ExprParser, FirstOf,
-        InputPointer, Node, Oper, Parser, SignedDuration,
+        consume_repeated, parse_expr, ConsumeRepeated, DateTime, ExprParser, FirstOf, InputPointer,
+        LiteralValue, Map, Memo, Node, Oper, Pair, Parser, Preceded, Registry, Repeated,
+        SignedDuration, Terminated, TypeName, Whitespace, MAX_NESTING,
     };
+    use crate::parser::number::SCALE;
     use crate::parser::parsers::Literal;
+    use crate::parser::types::Type;
+    use crate::parser::zone::{Tz, UTC};
     use crate::parser::{DAY_NS, HOUR_NS, SECOND_NS};
     use chrono;
     use chrono::{Duration, TimeDelta};
-    use chrono_tz::UTC;
     use std::rc::Rc;
 
     #[test]
@@ -645,6 +1872,8 @@ mod tests {
         check_parse_duration("x123d", None);
         check_parse_duration("123", None);
         check_parse_duration("-1d2s3ns", Some(-(DAY_NS + 2 * SECOND_NS + 3)));
+        check_parse_duration("2s1h", Some(HOUR_NS + 2 * SECOND_NS));
+        check_parse_duration("1h30m1h", Some(2 * HOUR_NS + 30 * 60 * SECOND_NS));
     }
 
     fn check_parse_duration(input: &str, expected_ns: Option<i64>) {
@@ -654,10 +1883,7 @@ mod tests {
         let result = parser.parse(p, 0);
         if let Some(ns) = expected_ns {
             assert!(result.is_ok(), "result not ok: {:?}", result);
-            assert_eq!(
-                result.unwrap().node,
-                Node::Duration(Duration::nanoseconds(ns))
-            );
+            assert_eq!(result.unwrap().node, Duration::nanoseconds(ns));
         } else {
             assert!(result.is_err(), "result not err: {:?}", result);
         }
@@ -673,6 +1899,37 @@ mod tests {
         check_parse_datetime("2000-01-01T00:00:ZZZ", None);
     }
 
+    /// The date-time in UTC from "%Y-%m-%dT%H:%M:%S", which unlike RFC 3339 has the years beyond 0000 to 9999.
+    fn expected_datetime(s: &str) -> chrono::DateTime<Tz> {
+        chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S")
+            .unwrap()
+            .and_utc()
+            .with_timezone(&UTC)
+    }
+
+    #[test]
+    fn test_parse_datetime_extended_years() {
+        let check = |input: &str, expected: &str| {
+            let s = String::from(input);
+            match DateTime.parse(InputPointer::from_string(&s), 0) {
+                Ok(ok) => assert_eq!(ok.node, expected_datetime(expected)),
+                Err(err) => panic!("not a date-time: {:?}", err),
+            }
+        };
+        check("+12024-01-01T00:00:00Z", "+12024-01-01T00:00:00");
+        check("-0044-03-15T12:00:00+01:00", "-0044-03-15T11:00:00");
+        check("0000-01-01T00:00:00Z", "0000-01-01T00:00:00");
+        let s = String::from("+262143-01-01T00:00:00Z");
+        let err = DateTime
+            .parse(InputPointer::from_string(&s), 0)
+            .unwrap_err();
+        assert!(err.fatal);
+        assert_eq!(
+            err.message,
+            "year +262143 is out of range, expected -262143 to 262142"
+        );
+    }
+
     fn check_parse_datetime(input: &str, expected: Option<&str>) {
         let parser = DateTime;
         let s = String::from(input);
@@ -684,7 +1941,7 @@ mod tests {
             let expected = chrono::DateTime::parse_from_rfc3339(expected)
                 .unwrap()
                 .with_timezone(&UTC);
-            assert_eq!(actual_node, Node::DateTime(expected),);
+            assert_eq!(actual_node, expected);
         } else {
             assert!(result.is_err(), "result not err: {:?}", result);
         }
@@ -692,9 +1949,11 @@ mod tests {
 
     #[test]
     fn test_consume_repeated_1() {
-        let input = "1s2s3s".to_string();
+        let input = "1s 2s 3s".to_string();
+        let ws0 = Whitespace::new_optional();
+        let duration_ws = Terminated(&SignedDuration, &ws0);
         let result = consume_repeated(
-            &SignedDuration,
+            &duration_ws,
             InputPointer::from_string(&input),
             ConsumeRepeated::OneOrMore,
             0,
@@ -703,9 +1962,9 @@ mod tests {
         assert!(result.is_ok(), "expected ok, was: {:?}", result);
         let result = result.unwrap();
         let expected_nodes = vec![
-            Node::Duration(TimeDelta::seconds(1)),
-            Node::Duration(TimeDelta::seconds(2)),
-            Node::Duration(TimeDelta::seconds(3)),
+            TimeDelta::seconds(1),
+            TimeDelta::seconds(2),
+            TimeDelta::seconds(3),
         ];
         assert_eq!(result.nodes, expected_nodes);
         assert_eq!(result.pointer.rest(), "");
@@ -713,9 +1972,11 @@ mod tests {
 
     #[test]
     fn test_consume_repeated_2() {
-        let input = "1s2sxx".to_string();
+        let input = "1s 2s xx".to_string();
+        let ws0 = Whitespace::new_optional();
+        let duration_ws = Terminated(&SignedDuration, &ws0);
         let result = consume_repeated(
-            &SignedDuration,
+            &duration_ws,
             InputPointer::from_string(&input),
             ConsumeRepeated::OneOrMore,
             0,
@@ -723,17 +1984,16 @@ mod tests {
         );
         assert!(result.is_ok(), "expected ok, was: {:?}", result);
         let result = result.unwrap();
-        let expected_nodes = vec![
-            Node::Duration(TimeDelta::seconds(1)),
-            Node::Duration(TimeDelta::seconds(2)),
-        ];
+        let expected_nodes = vec![TimeDelta::seconds(1), TimeDelta::seconds(2)];
         assert_eq!(result.nodes, expected_nodes);
         assert_eq!(result.pointer.rest(), "xx");
     }
 
     #[test]
     fn test_parse_first_of() {
-        let parser = FirstOf::new(vec![&SignedDuration, &DateTime]);
+        let duration = Map(&SignedDuration, Node::Duration);
+        let datetime = Map(&DateTime, Node::DateTime);
+        let parser = FirstOf::new(vec![&duration, &datetime]);
         let input = String::from("1s + bla");
         let p = InputPointer::from_string(&input);
         let result = parser.parse(p, 0);
@@ -742,38 +2002,108 @@ mod tests {
     }
 
     #[test]
-    fn test_consume_sequence_1() {
+    fn test_parse_pair_1() {
         let input = "1s+2s+3s".to_string();
         let p = InputPointer::from_string(&input);
         let plus = Literal::new("+");
-        let parsers: Vec<&dyn Parser> = vec![&SignedDuration, &plus, &SignedDuration];
-        let result = consume_sequence(&parsers, p, 0);
+        let parser = Pair(&SignedDuration, &Preceded(&plus, &SignedDuration));
+        let result = parser.parse(p, 0);
         let result = result.expect("expected ok");
-        assert_eq!(
-            result.nodes,
-            vec![
-                Node::Duration(Duration::seconds(1)),
-                Node::Literal {
-                    literal: "+".to_string(),
-                    skip: false
-                },
-                Node::Duration(Duration::seconds(2)),
-            ]
-        );
+        assert_eq!(result.node, (Duration::seconds(1), Duration::seconds(2)));
         assert_eq!(result.pointer.rest(), "+3s");
     }
 
     #[test]
-    fn test_consume_sequence_2() {
+    fn test_parse_pair_2() {
         let input = "1s-2s-3s".to_string();
         let p = InputPointer::from_string(&input);
         let plus = Literal::new("+");
-        let parsers: Vec<&dyn Parser> = vec![&SignedDuration, &plus, &SignedDuration];
-        let result = consume_sequence(&parsers, p, 0);
+        let parser = Pair(&SignedDuration, &Preceded(&plus, &SignedDuration));
+        let result = parser.parse(p, 0);
         let result = result.expect_err("expected err");
         assert_eq!(result.pointer.rest(), "1s-2s-3s");
     }
 
+    #[test]
+    fn test_typed_parsers() {
+        let input = "+ 1s :: duration".to_string();
+        let p = InputPointer::from_string(&input);
+        let sign = LiteralValue(&[("+", Oper::Plus), ("-", Oper::Minus)]);
+        let signed = Pair(
+            &sign,
+            &Preceded(&Whitespace::new_must_have(), &SignedDuration),
+        );
+        let result = signed.parse(p, 0).expect("expected ok");
+        assert_eq!(result.node, (Oper::Plus, Duration::seconds(1)));
+        let type_sep = Literal::new(" :: ");
+        let types = Repeated(&Preceded(&type_sep, &TypeName));
+        let result = types.parse(result.pointer, 0).expect("expected ok");
+        assert_eq!(result.node, vec![Type::Duration]);
+        assert!(result.pointer.is_end());
+
+        // The error of the second parser points at the beginning of the pair.
+        let input = "-x".to_string();
+        let p = InputPointer::from_string(&input);
+        let result = signed.parse(p, 0).expect_err("expected err");
+        assert_eq!(result.pointer.rest(), "-x");
+    }
+
+    #[test]
+    fn test_parse_nesting_limit() {
+        let nested = |depth| format!("{}1s{}", "(".repeat(depth), ")".repeat(depth));
+        assert!(parse_expr(&nested(MAX_NESTING)).is_ok());
+        let input = nested(MAX_NESTING + 1);
+        let err = parse_expr(&input).unwrap_err();
+        assert!(err.fatal);
+        assert_eq!(err.pointer.pos, MAX_NESTING + 1);
+        assert_eq!(
+            err.message,
+            "expression nested too deeply, more than 32 levels of brackets, calls, lists or chained operations"
+        );
+    }
+
+    #[test]
+    fn test_parse_chain_limit() {
+        // Each chained operation nests the previous ones, which would overflow the stack in the evaluation.
+        let chains = [
+            ("now", " |> full_day"),
+            ("now", "..now"),
+            ("1", " < 1"),
+            ("1s", " :: duration"),
+        ];
+        for (first, chained) in chains {
+            let input = first.to_string() + &chained.repeat(MAX_NESTING);
+            assert!(parse_expr(&input).is_ok(), "{}", input);
+            let input = first.to_string() + &chained.repeat(3000);
+            let err = parse_expr(&input).unwrap_err();
+            assert!(err.fatal, "{}", chained);
+            assert_eq!(
+                err.pointer.rest().trim_start(),
+                chained.repeat(3000 - MAX_NESTING).trim_start()
+            );
+        }
+        // The chains in brackets have as many levels less.
+        let input = format!(
+            "({})",
+            "now".to_string() + &" |> full_day".repeat(MAX_NESTING)
+        );
+        assert!(parse_expr(&input).unwrap_err().fatal);
+        let input = format!(
+            "({})",
+            "now".to_string() + &" |> full_day".repeat(MAX_NESTING - 1)
+        );
+        assert!(parse_expr(&input).is_ok());
+    }
+
+    #[test]
+    fn test_parse_long_chains() {
+        // Took minutes before the memoization of the expressions.
+        let input = "1s".to_string() + &" :: duration".repeat(300);
+        assert!(parse_expr(&input).unwrap_err().fatal);
+        let input = vec!["1s"; 300].join(" + ") + " zz";
+        assert!(!parse_expr(&input).unwrap_err().fatal);
+    }
+
     #[test]
     fn test_expr_parser_1() {
         check_expr_parser(
@@ -980,7 +2310,172 @@ mod tests {
 
     #[test]
     fn test_timestamp_1() {
-        check_expr_parser("946684800.000", Some(Node::Expr(vec![datetime_node()])));
+        check_expr_parser(
+            "946684800.000",
+            Some(Node::Expr(vec![Node::Number(946684800 * SCALE)])),
+        );
+    }
+
+    #[test]
+    fn test_parse_bad_duration_unit() {
+        let input = "now + 1dxyz + 1h".to_string();
+        let parse_err = parse_expr(&input).expect_err("expected err");
+        assert_eq!(parse_err.pointer.rest(), "1dxyz + 1h");
+        assert!(
+            parse_err.message.contains("\"dxyz\""),
+            "{}",
+            parse_err.message
+        );
+    }
+
+    #[test]
+    fn test_parse_expected_tokens() {
+        let input = ")".to_string();
+        let parse_err = parse_expr(&input).expect_err("expected err");
+        for token in [
+            "now",
+            "duration",
+            "date-time",
+            "full_day",
+            "(",
+            "[",
+            "string",
+        ] {
+            assert!(
+                parse_err.expected.contains(&token.to_string()),
+                "{:?} not in {:?}",
+                token,
+                parse_err.expected
+            );
+        }
+        let input = "now + 1x".to_string();
+        let parse_err = parse_expr(&input).expect_err("expected err");
+        assert_eq!(parse_err.pointer.rest(), "x");
+        assert_eq!(parse_err.expected, vec!["end of input".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_bad_call() {
+        let check = |input: &str, message: &str, expected: &[&str]| {
+            let input = input.to_string();
+            let parse_err = parse_expr(&input).expect_err("expected err");
+            assert_eq!(parse_err.message, message);
+            assert_eq!(parse_err.expected, expected);
+            parse_err.pointer.pos
+        };
+        assert_eq!(
+            check(
+                "now + full_day(now, 1)",
+                "full_day expects 1 argument, got 2",
+                &[]
+            ),
+            6
+        );
+        check("full_day()", "full_day expects 1 argument, got 0", &[]);
+        check(
+            "age(now, now, now)",
+            "age expects 1 or 2 arguments, got 3",
+            &[],
+        );
+        check(
+            "ful_day(now)",
+            "no such function \"ful_day\", did you mean \"full_day\"?",
+            &["full_day"],
+        );
+        check("xyzzy(1)", "no such function \"xyzzy\"", &[]);
+        check(
+            "map([now], x -> full_day(x, 1))",
+            "full_day expects 1 argument, got 2",
+            &[],
+        );
+        assert_eq!(
+            check(
+                "now |> full_day(1)",
+                "full_day expects 1 argument, got 2",
+                &[]
+            ),
+            7
+        );
+        check(
+            "now |> ful_day",
+            "no such function \"ful_day\", did you mean \"full_day\"?",
+            &["full_day"],
+        );
+        // Errors inside of the brackets are reported as before.
+        let input = "full_day(now +)".to_string();
+        let parse_err = parse_expr(&input).expect_err("expected err");
+        assert!(
+            !parse_err.message.contains("expects"),
+            "{}",
+            parse_err.message
+        );
+    }
+
+    #[test]
+    fn test_parse_unknown_identifier() {
+        let check = |input: &str, message: &str, expected: &[&str]| {
+            let input = input.to_string();
+            let parse_err = parse_expr(&input).expect_err("expected err");
+            assert_eq!(parse_err.message, message);
+            assert_eq!(parse_err.expected, expected);
+            parse_err.pointer.pos
+        };
+        check(
+            "nwo",
+            "unknown identifier \"nwo\", did you mean \"now\"?",
+            &["now"],
+        );
+        check(
+            "ful_day - 1d",
+            "unknown identifier \"ful_day\", did you mean \"full_day\"?",
+            &["full_day"],
+        );
+        assert_eq!(
+            check(
+                "map([now], item -> itme + 1h)",
+                "unknown identifier \"itme\", did you mean \"item\"?",
+                &["item"],
+            ),
+            19
+        );
+        // The variable is not known before it is bound.
+        check("map([x], x -> x)", "unknown identifier \"x\"", &[]);
+        // Reported before the syntax error that follows.
+        check(
+            "nwo + 1d zz",
+            "unknown identifier \"nwo\", did you mean \"now\"?",
+            &["now"],
+        );
+        let input = "now + 1d zz".to_string();
+        let parse_err = parse_expr(&input).expect_err("expected err");
+        assert_eq!(parse_err.message, "not all input matched");
+        for input in [
+            "map([now], x -> fmt(x, \"%Y abc\"))",
+            "17 May 2024 + 0x10 - 1e3",
+            "now-1d/d |> full_day",
+        ] {
+            assert!(parse_expr(&input.to_string()).is_ok(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_memo_keeps_error_length() {
+        let registry = Registry::builtin();
+        let memo = Memo::default();
+        let parser = ExprParser {
+            registry: &registry,
+            spans: false,
+            depth: 1,
+            memo: &memo,
+        };
+        let input = "full_day(now + 300000000y)".to_string();
+        let pointer = InputPointer::from_string(&input).advance(9);
+        let first = parser.parse(pointer, 0).unwrap_err();
+        assert_eq!((first.pointer.pos, first.len), (15, 10));
+        // The argument at the same position and depth again, as for the other arities of the function.
+        let memoized = parser.parse(pointer, 0).unwrap_err();
+        assert_eq!((memoized.pointer.pos, memoized.len), (15, 10));
+        assert_eq!(memoized.message, first.message);
     }
 
     #[test]
@@ -989,7 +2484,14 @@ mod tests {
     }
 
     fn check_expr_parser(input: &str, expected: Option<Node>) {
-        let parser = ExprParser;
+        let registry = Registry::builtin();
+        let memo = Memo::default();
+        let parser = ExprParser {
+            registry: &registry,
+            spans: false,
+            depth: 0,
+            memo: &memo,
+        };
         let input = input.to_string();
         let pointer = InputPointer::from_string(&input);
         let result = parser.parse(pointer, 0);
@@ -0,0 +1,65 @@
+use std::{cell::Cell, rc::Rc};
+
+/// Pseudo-random generator (SplitMix64). Not suitable for cryptography, only for generating test data. The state is
+/// in a shared cell so the generator can be used through the evaluation context, and its clones continue the same
+/// sequence.
+#[derive(Clone, Debug)]
+pub struct Random {
+    state: Rc<Cell<u64>>,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        Random {
+            state: Rc::new(Cell::new(seed)),
+        }
+    }
+
+    /// Seed from the system clock, so each run is different.
+    pub fn from_clock() -> Random {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        Random::new(nanos)
+    }
+
+    pub fn next_u64(&self) -> u64 {
+        let state = self.state.get().wrapping_add(0x9e37_79b9_7f4a_7c15);
+        self.state.set(state);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Return a number in [low, high). The range should not be empty.
+    pub fn between(&self, low: i128, high: i128) -> i128 {
+        let range = (high - low) as u128;
+        let value = (u128::from(self.next_u64()) << 64) | u128::from(self.next_u64());
+        low + (value % range) as i128
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Random;
+
+    #[test]
+    fn reproducible() {
+        let a = Random::new(42);
+        let b = Random::new(42);
+        let a: Vec<u64> = (0..3).map(|_| a.next_u64()).collect();
+        let b: Vec<u64> = (0..3).map(|_| b.next_u64()).collect();
+        assert_eq!(a, b);
+        assert_ne!(a[0], a[1]);
+    }
+
+    #[test]
+    fn between() {
+        let random = Random::new(1);
+        for _ in 0..1000 {
+            let value = random.between(-5, 5);
+            assert!((-5..5).contains(&value), "{}", value);
+        }
+    }
+}
@@ -0,0 +1,685 @@
+//! Registry of the functions, consulted by the parser for the names and the numbers of arguments, and by the
+//! evaluator for the handlers. Adding a function is one registration. Library users can register their own functions
+//! and parse with parse_expr_with.
+use chrono::TimeDelta;
+use std::fmt;
+
+use super::{
+    add_months, age, aggregate, at, bucket, bucket_index, ceil_to, clamp,
+    cron::CronSchedule,
+    epoch::{find_epoch, EPOCHS},
+    eval::{EvalContext, State},
+    fmt, from_alt_epoch, from_objectid, from_snowflake, from_uuid1, from_uuid7, full_day,
+    full_hour, full_minute, full_month, full_quarter, full_week, full_year, intersects,
+    interval_contains, interval_duration, interval_shift, jwt_exp, next_cron, next_rrule,
+    next_systemd, on, overlap, prev_cron, random_between, retention_floor, round_delta,
+    rrule::RecurrenceRule,
+    seq, subsec,
+    suggest::closest,
+    sun_event,
+    systemd::{parse_timespan, CalendarEvent},
+    time_of_day, timespan, to_alt_epoch, totp_window, trunc_delta, truncate_to,
+    types::Type,
+    with_component,
+};
+
+/// Most arguments of a function, the grammar has no calls with more.
+pub const MAX_ARITY: usize = 4;
+
+/// Handler of a function, called with the name of the function, so one handler can serve several names, and with the
+/// evaluated arguments.
+pub type Handler = fn(&str, &[State], &EvalContext) -> Result<State, String>;
+
+#[derive(Clone, Debug)]
+struct Function {
+    name: String,
+    arity: usize,
+    handler: Handler,
+    /// Convert the numbers in the arguments to date-times before calling the handler, like the bare numbers
+    /// elsewhere.
+    numbers_to_datetimes: bool,
+    /// Types of the built-in functions, none for the functions registered by library users.
+    signature: Option<Signature>,
+}
+
+/// Type of an argument or of the result of a built-in function, for the type checks without evaluation.
+#[derive(Clone, Debug)]
+pub enum Param {
+    /// Value of the type. The bare numbers are date-times unless the function takes them as they are, and the
+    /// integers are numbers.
+    Of(Type),
+    /// Value of any of the types, like the string or the number of a snowflake ID.
+    OneOf(Vec<Type>),
+    /// String of the syntax checked by the function, like the cron expression of next_cron. The literal strings are
+    /// checked without evaluation.
+    Parsed(fn(&str) -> Result<(), String>),
+    /// Date-time or duration, the same in all the arguments declared so, and in the result.
+    Quantity,
+    /// List of date-times or of durations, with the elements as Quantity.
+    QuantityList,
+}
+
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Param::Of(expected) => write!(f, "{}", expected),
+            Param::OneOf(types) => {
+                let types: Vec<String> = types.iter().map(Type::to_string).collect();
+                write!(f, "{}", types.join(" or "))
+            }
+            Param::Parsed(_) => write!(f, "{}", Type::String),
+            Param::Quantity => write!(f, "date-time or duration"),
+            Param::QuantityList => write!(f, "list of date-times or of durations"),
+        }
+    }
+}
+
+/// Types of the arguments and of the result of a function.
+#[derive(Clone, Debug)]
+pub struct Signature {
+    pub params: Vec<Param>,
+    pub result: Param,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Registry {
+    functions: Vec<Function>,
+}
+
+impl Registry {
+    /// Registry without any functions.
+    pub fn empty() -> Registry {
+        Registry::default()
+    }
+
+    /// Registry of the built-in functions.
+    pub fn builtin() -> Registry {
+        let mut registry = Registry::empty();
+        registry.add_builtins();
+        registry
+    }
+
+    /// Register the function with 1 to 4 arguments. The numbers in the arguments are converted to date-times, like
+    /// "1700000000" in "full_day(1700000000)". Registering a name with the arity again replaces the function.
+    pub fn register(&mut self, name: &str, arity: usize, handler: Handler) -> Result<(), String> {
+        self.register_function(name, arity, handler, true, None)
+    }
+
+    /// Register the function that gets the numbers in the arguments as they are, e.g. counts or coordinates. The
+    /// handler converts the arguments that are date-times with EvalContext::number_to_datetime.
+    pub fn register_raw(
+        &mut self,
+        name: &str,
+        arity: usize,
+        handler: Handler,
+    ) -> Result<(), String> {
+        self.register_function(name, arity, handler, false, None)
+    }
+
+    fn register_function(
+        &mut self,
+        name: &str,
+        arity: usize,
+        handler: Handler,
+        numbers_to_datetimes: bool,
+        signature: Option<Signature>,
+    ) -> Result<(), String> {
+        let identifier = regex::Regex::new(r"^[a-zA-Z_][a-zA-Z0-9_]*$").unwrap();
+        if !identifier.is_match(name) {
+            return Err(format!("bad function name {:?}", name));
+        }
+        if !(1..=MAX_ARITY).contains(&arity) {
+            return Err(format!(
+                "function {} should have 1 to {} arguments, has {}",
+                name, MAX_ARITY, arity
+            ));
+        }
+        self.functions
+            .retain(|function| !(function.name == name && function.arity == arity));
+        self.functions.push(Function {
+            name: name.to_string(),
+            arity,
+            handler,
+            numbers_to_datetimes,
+            signature,
+        });
+        Ok(())
+    }
+
+    /// Names of the functions with the number of arguments. The longer names go first, since the names are matched as
+    /// prefixes, and "time" would be matched in "time_of_day" otherwise.
+    pub fn names_with_arity(&self, arity: usize) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .functions
+            .iter()
+            .filter(|function| function.arity == arity)
+            .map(|function| function.name.clone())
+            .collect();
+        names.sort_by_key(|name| std::cmp::Reverse(name.len()));
+        names
+    }
+
+    /// Names of all the functions, once each, in the order of registration.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = vec![];
+        for function in &self.functions {
+            if !names.contains(&function.name) {
+                names.push(function.name.clone());
+            }
+        }
+        names
+    }
+
+    /// Check that the function is registered with the number of arguments. The error tells the numbers of arguments
+    /// of the function, or suggests the closest name for a misspelled one.
+    pub fn check_call(&self, name: &str, arity: usize) -> Result<(), String> {
+        let mut arities: Vec<usize> = self
+            .functions
+            .iter()
+            .filter(|function| function.name == name)
+            .map(|function| function.arity)
+            .collect();
+        arities.sort();
+        if arities.contains(&arity) {
+            return Ok(());
+        }
+        if arities.is_empty() {
+            let names = self.names();
+            return Err(
+                match closest(name, names.iter().map(|name| name.as_str())) {
+                    Some(suggestion) => format!(
+                        "no such function {:?}, did you mean {:?}?",
+                        name, suggestion
+                    ),
+                    None => format!("no such function {:?}", name),
+                },
+            );
+        }
+        let arities: Vec<String> = arities.iter().map(|arity| arity.to_string()).collect();
+        let plural = if arities == ["1"] { "" } else { "s" };
+        Err(format!(
+            "{} expects {} argument{}, got {}",
+            name,
+            arities.join(" or "),
+            plural,
+            arity
+        ))
+    }
+
+    /// Types of the function with the number of arguments, if declared.
+    pub fn signature(&self, name: &str, arity: usize) -> Option<&Signature> {
+        self.functions
+            .iter()
+            .find(|function| function.name == name && function.arity == arity)
+            .and_then(|function| function.signature.as_ref())
+    }
+
+    /// Whether the function with the number of arguments gets the numbers as they are, see register_raw.
+    pub fn takes_raw_numbers(&self, name: &str, arity: usize) -> bool {
+        self.functions.iter().any(|function| {
+            function.name == name && function.arity == arity && !function.numbers_to_datetimes
+        })
+    }
+
+    /// Call the function with the evaluated arguments.
+    pub fn call(&self, name: &str, args: &[State], ctx: &EvalContext) -> Result<State, String> {
+        self.check_call(name, args.len())?;
+        let function = self
+            .functions
+            .iter()
+            .find(|function| function.name == name && function.arity == args.len())
+            .unwrap();
+        if function.numbers_to_datetimes {
+            let args = args
+                .iter()
+                .map(|arg| ctx.number_to_datetime(arg.clone()))
+                .collect::<Result<Vec<State>, String>>()?;
+            (function.handler)(name, &args, ctx)
+        } else {
+            (function.handler)(name, args, ctx)
+        }
+    }
+
+    /// Add the built-in function with the types of the arguments and of the result.
+    fn add<const N: usize>(
+        &mut self,
+        name: &str,
+        params: [Param; N],
+        result: Param,
+        handler: Handler,
+    ) {
+        let signature = Signature {
+            params: params.into(),
+            result,
+        };
+        self.register_function(name, N, handler, true, Some(signature))
+            .unwrap()
+    }
+
+    /// Add the built-in function that gets the numbers as they are, see register_raw.
+    fn add_raw<const N: usize>(
+        &mut self,
+        name: &str,
+        params: [Param; N],
+        result: Param,
+        handler: Handler,
+    ) {
+        let signature = Signature {
+            params: params.into(),
+            result,
+        };
+        self.register_function(name, N, handler, false, Some(signature))
+            .unwrap()
+    }
+
+    fn add_builtins(&mut self) {
+        use Param::{Of, OneOf, Parsed, Quantity, QuantityList};
+        let datetime = || Of(Type::DateTime);
+        let duration = || Of(Type::Duration);
+        let string = || Of(Type::String);
+        let number = || Of(Type::Number);
+        let interval = || Of(Type::Interval);
+
+        self.add("full_day", [datetime()], datetime(), |_, args, _| {
+            full_day(&args[0])
+        });
+        self.add("full_hour", [datetime()], datetime(), |_, args, _| {
+            full_hour(&args[0])
+        });
+        self.add("full_minute", [datetime()], datetime(), |_, args, _| {
+            full_minute(&args[0])
+        });
+        self.add("full_second", [datetime()], datetime(), |name, args, _| {
+            truncate_to(name, &args[0], TimeDelta::seconds(1))
+        });
+        self.add("full_millis", [datetime()], datetime(), |name, args, _| {
+            truncate_to(name, &args[0], TimeDelta::milliseconds(1))
+        });
+        self.add("full_micros", [datetime()], datetime(), |name, args, _| {
+            truncate_to(name, &args[0], TimeDelta::microseconds(1))
+        });
+        self.add("full_week", [datetime()], datetime(), |_, args, _| {
+            full_week(&args[0])
+        });
+        self.add("full_month", [datetime()], datetime(), |_, args, _| {
+            full_month(&args[0])
+        });
+        self.add("full_quarter", [datetime()], datetime(), |_, args, _| {
+            full_quarter(&args[0])
+        });
+        self.add("full_year", [datetime()], datetime(), |_, args, _| {
+            full_year(&args[0])
+        });
+        self.add("ceil_day", [datetime()], datetime(), |name, args, _| {
+            ceil_to(name, &args[0], TimeDelta::days(1))
+        });
+        self.add("ceil_hour", [datetime()], datetime(), |name, args, _| {
+            ceil_to(name, &args[0], TimeDelta::hours(1))
+        });
+        self.add("ceil_minute", [datetime()], datetime(), |name, args, _| {
+            ceil_to(name, &args[0], TimeDelta::minutes(1))
+        });
+        self.add("ceil_second", [datetime()], datetime(), |name, args, _| {
+            ceil_to(name, &args[0], TimeDelta::seconds(1))
+        });
+        self.add("ceil_millis", [datetime()], datetime(), |name, args, _| {
+            ceil_to(name, &args[0], TimeDelta::milliseconds(1))
+        });
+        self.add("ceil_micros", [datetime()], datetime(), |name, args, _| {
+            ceil_to(name, &args[0], TimeDelta::microseconds(1))
+        });
+        self.add("from_objectid", [string()], datetime(), |_, args, _| {
+            from_objectid(&args[0])
+        });
+        let snowflake_id = || OneOf(vec![Type::String, Type::Number]);
+        self.add_raw(
+            "from_snowflake",
+            [snowflake_id()],
+            datetime(),
+            |_, args, _| from_snowflake(&args[0], None),
+        );
+        self.add("from_uuid1", [string()], datetime(), |_, args, _| {
+            from_uuid1(&args[0])
+        });
+        self.add("from_uuid7", [string()], datetime(), |_, args, _| {
+            from_uuid7(&args[0])
+        });
+        self.add("jwt_exp", [string()], datetime(), |_, args, _| {
+            jwt_exp(&args[0])
+        });
+        self.add("duration", [interval()], duration(), |_, args, _| {
+            interval_duration(&args[0])
+        });
+        self.add("time_of_day", [datetime()], duration(), |name, args, _| {
+            time_of_day(name, &args[0])
+        });
+        self.add("time", [datetime()], duration(), |name, args, _| {
+            time_of_day(name, &args[0])
+        });
+        self.add("subsec", [datetime()], duration(), |_, args, _| {
+            subsec(&args[0])
+        });
+        let timespan_string = || Parsed(|s| parse_timespan(s).map(|_| ()));
+        self.add("timespan", [timespan_string()], duration(), |_, args, _| {
+            timespan(&args[0])
+        });
+        let calendar_event = || Parsed(|s| CalendarEvent::parse(s).map(|_| ()));
+        self.add(
+            "next_systemd",
+            [calendar_event()],
+            datetime(),
+            |_, args, ctx| next_systemd(&args[0], &State::DateTime(ctx.now)),
+        );
+        self.add(
+            "age",
+            [datetime()],
+            Of(Type::CalendarDelta),
+            |_, args, ctx| age(&args[0], &State::DateTime(ctx.now)),
+        );
+        self.add(
+            "sum",
+            [Of(Type::List(Box::new(Type::Duration)))],
+            duration(),
+            |name, args, _| aggregate(name, &args[0]),
+        );
+        for name in ["mean", "min", "max"] {
+            self.add(name, [QuantityList], Quantity, |name, args, _| {
+                aggregate(name, &args[0])
+            });
+        }
+        self.add("span", [QuantityList], duration(), |name, args, _| {
+            aggregate(name, &args[0])
+        });
+        for epoch in EPOCHS {
+            // The ticks are numbers, only the date-time converted to ticks is a date-time.
+            self.add_raw(
+                &format!("from_{}", epoch.name),
+                [number()],
+                datetime(),
+                |name, args, _| from_alt_epoch(epoch_of(name)?, &args[0]),
+            );
+            self.add(
+                &format!("to_{}", epoch.name),
+                [datetime()],
+                Of(Type::Integer),
+                |name, args, _| to_alt_epoch(epoch_of(name)?, &args[0]),
+            );
+        }
+        #[cfg(feature = "fs")]
+        {
+            self.add("mtime", [string()], datetime(), |_, args, _| {
+                super::mtime(&args[0])
+            });
+            self.add("ctime", [string()], datetime(), |_, args, _| {
+                super::ctime(&args[0])
+            });
+        }
+
+        let cron = || Parsed(|s| CronSchedule::parse(s).map(|_| ()));
+        self.add(
+            "next_cron",
+            [cron(), datetime()],
+            datetime(),
+            |_, args, _| next_cron(&args[0], &args[1]),
+        );
+        self.add(
+            "prev_cron",
+            [cron(), datetime()],
+            datetime(),
+            |_, args, _| prev_cron(&args[0], &args[1]),
+        );
+        let rrule = || Parsed(|s| RecurrenceRule::parse(s).map(|_| ()));
+        self.add(
+            "next_rrule",
+            [rrule(), datetime()],
+            datetime(),
+            |_, args, _| next_rrule(&args[0], &args[1]),
+        );
+        self.add(
+            "next_systemd",
+            [calendar_event(), datetime()],
+            datetime(),
+            |_, args, _| next_systemd(&args[0], &args[1]),
+        );
+        self.add_raw(
+            "from_snowflake",
+            [snowflake_id(), OneOf(vec![Type::String, Type::DateTime])],
+            datetime(),
+            |_, args, ctx| {
+                from_snowflake(&args[0], Some(&ctx.number_to_datetime(args[1].clone())?))
+            },
+        );
+        self.add(
+            "totp_window",
+            [datetime(), duration()],
+            Of(Type::Record),
+            |_, args, _| totp_window(&args[0], &args[1]),
+        );
+        // The number of months, the components and the format are not date-times.
+        self.add_raw(
+            "add_months",
+            [datetime(), number()],
+            datetime(),
+            |_, args, ctx| add_months(&ctx.number_to_datetime(args[0].clone())?, &args[1]),
+        );
+        self.add(
+            "age",
+            [datetime(), datetime()],
+            Of(Type::CalendarDelta),
+            |_, args, _| age(&args[0], &args[1]),
+        );
+        self.add(
+            "bucket_index",
+            [datetime(), duration()],
+            Of(Type::Integer),
+            |_, args, _| bucket_index(&args[0], &args[1], None),
+        );
+        self.add(
+            "bucket",
+            [datetime(), duration()],
+            datetime(),
+            |_, args, _| bucket(&args[0], &args[1]),
+        );
+        self.add(
+            "random_between",
+            [Quantity, Quantity],
+            Quantity,
+            |_, args, ctx| random_between(&args[0], &args[1], &ctx.random),
+        );
+        self.add(
+            "retention_floor",
+            [datetime(), duration()],
+            datetime(),
+            |_, args, ctx| retention_floor(&args[0], &args[1], &ctx.now),
+        );
+        self.add(
+            "round_delta",
+            [duration(), duration()],
+            duration(),
+            |_, args, _| round_delta(&args[0], &args[1]),
+        );
+        self.add(
+            "trunc_delta",
+            [duration(), duration()],
+            duration(),
+            |_, args, _| trunc_delta(&args[0], &args[1]),
+        );
+        for name in [
+            "with_year",
+            "with_month",
+            "with_day",
+            "with_hour",
+            "with_minute",
+            "with_second",
+        ] {
+            self.add_raw(
+                name,
+                [datetime(), number()],
+                datetime(),
+                |name, args, ctx| {
+                    let component = name.strip_prefix("with_").unwrap();
+                    with_component(
+                        component,
+                        &ctx.number_to_datetime(args[0].clone())?,
+                        &args[1],
+                    )
+                },
+            );
+        }
+        self.add(
+            "interval",
+            [datetime(), datetime()],
+            interval(),
+            |_, args, _| super::interval(&args[0], &args[1]),
+        );
+        self.add("at", [datetime(), string()], datetime(), |_, args, _| {
+            at(&args[0], &args[1])
+        });
+        self.add("on", [datetime(), string()], datetime(), |_, args, _| {
+            on(&args[0], &args[1])
+        });
+        self.add(
+            "contains",
+            [interval(), OneOf(vec![Type::DateTime, Type::Interval])],
+            Of(Type::Bool),
+            |_, args, _| interval_contains(&args[0], &args[1]),
+        );
+        self.add(
+            "shift",
+            [interval(), duration()],
+            interval(),
+            |_, args, _| interval_shift(&args[0], &args[1]),
+        );
+        self.add_raw("fmt", [datetime(), string()], string(), |_, args, ctx| {
+            fmt(&ctx.number_to_datetime(args[0].clone())?, &args[1])
+        });
+
+        self.add(
+            "bucket_index",
+            [datetime(), duration(), datetime()],
+            Of(Type::Integer),
+            |_, args, _| bucket_index(&args[0], &args[1], Some(&args[2])),
+        );
+        self.add(
+            "seq",
+            [datetime(), datetime(), duration()],
+            Of(Type::List(Box::new(Type::DateTime))),
+            |_, args, _| seq(&args[0], &args[1], &args[2]),
+        );
+        self.add(
+            "clamp",
+            [Quantity, Quantity, Quantity],
+            Quantity,
+            |_, args, _| clamp(&args[0], &args[1], &args[2]),
+        );
+        // The coordinates are numbers, not timestamps.
+        for name in ["sunrise", "sunset"] {
+            self.add_raw(
+                name,
+                [datetime(), number(), number()],
+                datetime(),
+                |name, args, ctx| {
+                    sun_event(
+                        name,
+                        &ctx.number_to_datetime(args[0].clone())?,
+                        &args[1],
+                        &args[2],
+                    )
+                },
+            );
+        }
+
+        let windows = || [datetime(), datetime(), datetime(), datetime()];
+        self.add("overlap", windows(), duration(), |_, args, _| {
+            overlap(&args[0], &args[1], &args[2], &args[3])
+        });
+        self.add("intersects", windows(), Of(Type::Bool), |_, args, _| {
+            intersects(&args[0], &args[1], &args[2], &args[3])
+        });
+    }
+}
+
+/// Return the epoch of the "from_" or "to_" function.
+fn epoch_of(name: &str) -> Result<&'static super::epoch::AltEpoch, String> {
+    name.strip_prefix("from_")
+        .or_else(|| name.strip_prefix("to_"))
+        .and_then(find_epoch)
+        .ok_or_else(|| format!("no such epoch in {:?}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Registry, State};
+    use crate::parser::{evaluate, parse_expr_with, EvalContext, EvaluationResult};
+    use std::rc::Rc;
+
+    #[test]
+    fn register_function() {
+        let mut registry = Registry::builtin();
+        registry
+            .register("double", 1, |_, args, _| match &args[0] {
+                State::TimeDelta(delta) => Ok(State::TimeDelta(*delta * 2)),
+                other => Err(format!("expected duration, was: {:?}", other)),
+            })
+            .unwrap();
+        let input = "double(1h) + 1m".to_string();
+        let node = parse_expr_with(&input, &registry).unwrap().node;
+        let ctx = EvalContext {
+            registry: Rc::new(registry),
+            ..EvalContext::new(
+                chrono::DateTime::UNIX_EPOCH.with_timezone(&crate::parser::zone::UTC),
+            )
+        };
+        assert_eq!(
+            evaluate(node, &ctx),
+            Ok(EvaluationResult::TimeDelta(chrono::TimeDelta::minutes(121)))
+        );
+        assert!(crate::parser::parse_expr(&input).is_err());
+    }
+
+    #[test]
+    fn bad_registration() {
+        let mut registry = Registry::empty();
+        assert!(registry
+            .register("1x", 1, |_, _, _| Ok(State::None))
+            .is_err());
+        assert!(registry
+            .register("f", 0, |_, _, _| Ok(State::None))
+            .is_err());
+        assert!(registry
+            .register("f", 5, |_, _, _| Ok(State::None))
+            .is_err());
+    }
+
+    #[test]
+    fn check_call() {
+        let registry = Registry::builtin();
+        assert_eq!(registry.check_call("full_day", 1), Ok(()));
+        assert_eq!(
+            registry.check_call("full_day", 2),
+            Err("full_day expects 1 argument, got 2".to_string())
+        );
+        assert_eq!(
+            registry.check_call("age", 3),
+            Err("age expects 1 or 2 arguments, got 3".to_string())
+        );
+        assert_eq!(
+            registry.check_call("ful_day", 1),
+            Err("no such function \"ful_day\", did you mean \"full_day\"?".to_string())
+        );
+        assert_eq!(
+            registry.check_call("xyzzy", 1),
+            Err("no such function \"xyzzy\"".to_string())
+        );
+    }
+
+    #[test]
+    fn prefixes_go_last() {
+        let names = Registry::builtin().names_with_arity(1);
+        let position = |name: &str| names.iter().position(|n| n == name).unwrap();
+        assert!(position("time_of_day") < position("time"));
+        let names = Registry::builtin().names_with_arity(2);
+        let position = |name: &str| names.iter().position(|n| n == name).unwrap();
+        assert!(position("bucket_index") < position("bucket"));
+    }
+}
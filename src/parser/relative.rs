@@ -0,0 +1,289 @@
+use std::{rc::Rc, str::FromStr};
+
+use chrono::TimeDelta;
+
+use super::{
+    core::{Node, Oper},
+    number::SCALE,
+    parsers::parse_expr,
+};
+
+/// Grafana (and Elasticsearch date math) relative time, like "now-7d/d": "now" followed by offsets and snaps to the
+/// beginning of a unit, without whitespace.
+pub const RE_GRAFANA: &str = r"^now(([+-]\d+[smhdwMy])|(/[smhdwMy]))+";
+/// Elasticsearch date math after "now" or after the "||" anchor. Both "h" and "H" are hours.
+const RE_ELASTIC_MATH: &str = r"^(([+-]\d+[smhHdwMy])|(/[smhHdwMy]))*$";
+const RE_DATE_MATH_PART: &str =
+    r"(?<offset>(?<sign>[+-])(?<value>\d+)(?<unit>[smhHdwMy]))|/(?<snap>[smhHdwMy])";
+const RE_SPLUNK_OFFSET: &str = r"^(?<sign>[+-])(?<value>\d*)(?<unit>[a-zA-Z]+)";
+const RE_SPLUNK_SNAP: &str = r"^@(?<unit>[a-zA-Z]+)(?<day>\d)?";
+
+/// Input language of the whole expression.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dialect {
+    /// The tscalc expressions, which also accept Grafana relative time.
+    Tscalc,
+    /// Splunk relative time modifiers, like "-24h@h" or "@d+8h".
+    Splunk,
+    /// Elasticsearch date math, like "now-1d/d" or "2024-01-01T00:00:00Z||+1M/d".
+    Elastic,
+}
+
+impl FromStr for Dialect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tscalc" => Ok(Dialect::Tscalc),
+            "splunk" => Ok(Dialect::Splunk),
+            "elastic" => Ok(Dialect::Elastic),
+            _ => Err(format!(
+                "bad dialect {:?}, expected one of tscalc, splunk, elastic",
+                s
+            )),
+        }
+    }
+}
+
+/// Convert relative time matched by RE_GRAFANA to expression nodes, e.g. "now-1d/d" becomes full_day(now - 1d).
+pub fn grafana_to_node(s: &str) -> Result<Node, String> {
+    date_math_to_node(Node::Now, &s["now".len()..])
+}
+
+/// Convert Elasticsearch date math to expression nodes. The anchor before "||" is a tscalc expression.
+pub fn elastic_to_node(s: &str) -> Result<Node, String> {
+    let s = s.trim();
+    let (anchor, math) = if let Some((anchor, math)) = s.split_once("||") {
+        let anchor = anchor.to_string();
+        let node = parse_expr(&anchor)
+            .map_err(|e| format!("bad anchor {:?}: {}", anchor, e.message))?
+            .node;
+        (node, math)
+    } else if let Some(math) = s.strip_prefix("now") {
+        (Node::Now, math)
+    } else {
+        return Err(format!(
+            "date math should start with \"now\" or an anchor followed by \"||\", was: {:?}",
+            s
+        ));
+    };
+    if !regex::Regex::new(RE_ELASTIC_MATH).unwrap().is_match(math) {
+        return Err(format!("bad date math {:?}", math));
+    }
+    date_math_to_node(anchor, math)
+}
+
+fn date_math_to_node(mut node: Node, math: &str) -> Result<Node, String> {
+    let part_pat = regex::Regex::new(RE_DATE_MATH_PART).unwrap();
+    for caps in part_pat.captures_iter(math) {
+        node = if caps.name("offset").is_some() {
+            let value = caps["value"]
+                .parse::<i64>()
+                .map_err(|_| format!("offset {:?} is out of range", &caps["offset"]))?;
+            offset_node(node, sign_to_oper(&caps["sign"]), value, &caps["unit"])?
+        } else {
+            snap_node(node, &caps["snap"])?
+        };
+    }
+    Ok(node)
+}
+
+/// Convert Splunk relative time modifier to expression nodes, e.g. "-24h@h" becomes full_hour(now - 24h). Offsets
+/// and snaps can follow each other in any order, like "@d+8h". Snapping to "w0" to "w6" snaps to the beginning of
+/// week starting on Sunday to Saturday.
+pub fn splunk_to_node(s: &str) -> Result<Node, String> {
+    let s = s.trim();
+    let mut rest = s
+        .strip_prefix("now()")
+        .or_else(|| s.strip_prefix("now"))
+        .unwrap_or(s);
+    let offset_pat = regex::Regex::new(RE_SPLUNK_OFFSET).unwrap();
+    let snap_pat = regex::Regex::new(RE_SPLUNK_SNAP).unwrap();
+    let mut node = Node::Now;
+    while !rest.is_empty() {
+        if let Some(caps) = offset_pat.captures(rest) {
+            let unit = splunk_unit(&caps["unit"])?;
+            let value = match &caps["value"] {
+                "" => 1,
+                value => value
+                    .parse::<i64>()
+                    .map_err(|_| format!("offset {:?} is out of range", &caps[0]))?,
+            };
+            node = offset_node(node, sign_to_oper(&caps["sign"]), value, unit)?;
+            rest = &rest[caps[0].len()..];
+        } else if let Some(caps) = snap_pat.captures(rest) {
+            let unit = splunk_unit(&caps["unit"])?;
+            node = match caps.name("day") {
+                Some(day) if unit == "w" => snap_to_week_day(node, day.as_str().parse().unwrap())?,
+                Some(_) => {
+                    return Err(format!(
+                        "only week can be snapped to a day, was: {:?}",
+                        &caps[0]
+                    ))
+                }
+                None => snap_node(node, unit)?,
+            };
+            rest = &rest[caps[0].len()..];
+        } else {
+            return Err(format!("bad relative time modifier {:?} in {:?}", rest, s));
+        }
+    }
+    Ok(node)
+}
+
+/// Unit of Splunk offsets and snaps, like "hrs", as the unit of offset_node and snap_node.
+pub fn splunk_unit(unit: &str) -> Result<&'static str, String> {
+    match unit {
+        "s" | "sec" | "secs" | "second" | "seconds" => Ok("s"),
+        "m" | "min" | "mins" | "minute" | "minutes" => Ok("m"),
+        "h" | "hr" | "hrs" | "hour" | "hours" => Ok("h"),
+        "d" | "day" | "days" => Ok("d"),
+        "w" | "week" | "weeks" => Ok("w"),
+        "mon" | "month" | "months" => Ok("M"),
+        "q" | "qtr" | "qtrs" | "quarter" | "quarters" => Ok("q"),
+        "y" | "yr" | "yrs" | "year" | "years" => Ok("y"),
+        _ => Err(format!("bad time unit {:?}", unit)),
+    }
+}
+
+fn sign_to_oper(sign: &str) -> Oper {
+    if sign == "-" {
+        Oper::Minus
+    } else {
+        Oper::Plus
+    }
+}
+
+/// Snap to the beginning of week starting on the day, 0 is Sunday. This is full_week (starting on Monday) of the
+/// date-time shifted by the days between the week starts.
+pub fn snap_to_week_day(node: Node, day: u32) -> Result<Node, String> {
+    if day > 6 {
+        return Err(format!("week day should be 0 to 6, was: {}", day));
+    }
+    let shift = (1 - i64::from(day)).rem_euclid(7);
+    if shift == 0 {
+        return snap_node(node, "w");
+    }
+    let shifted = offset_node(node, Oper::Plus, shift, "d")?;
+    offset_node(snap_node(shifted, "w")?, Oper::Minus, shift, "d")
+}
+
+/// Shift the node by the value of calendar or fixed-length units.
+pub fn offset_node(node: Node, oper: Oper, value: i64, unit: &str) -> Result<Node, String> {
+    let out_of_range = || format!("offset {}{} is out of range", value, unit);
+    if unit == "M" || unit == "q" || unit == "y" {
+        let months_in_unit = match unit {
+            "M" => 1,
+            "q" => 3,
+            _ => 12,
+        };
+        let months = value.checked_mul(months_in_unit).ok_or_else(out_of_range)?;
+        let months = if oper == Oper::Minus { -months } else { months };
+        return Ok(Node::FuncAry2 {
+            name: "add_months".to_string(),
+            arg1: Rc::new(node),
+            arg2: Rc::new(Node::Number(i128::from(months) * SCALE)),
+        });
+    }
+    let duration = match unit {
+        "s" => TimeDelta::try_seconds(value),
+        "m" => TimeDelta::try_minutes(value),
+        "h" | "H" => TimeDelta::try_hours(value),
+        "d" => TimeDelta::try_days(value),
+        "w" => TimeDelta::try_weeks(value),
+        _ => return Err(format!("bad time unit {:?}", unit)),
+    }
+    .ok_or_else(out_of_range)?;
+    Ok(Node::Expr(vec![
+        node,
+        Node::OperNode {
+            oper,
+            node: Rc::new(Node::Duration(duration)),
+        },
+    ]))
+}
+
+/// Truncate the node to the beginning of the unit, e.g. "d" to full_day. Snapping to seconds is a no-op, because
+/// "now" is already rounded to seconds.
+pub fn snap_node(node: Node, unit: &str) -> Result<Node, String> {
+    let name = match unit {
+        "s" => return Ok(node),
+        "m" => "full_minute",
+        "h" | "H" => "full_hour",
+        "d" => "full_day",
+        "w" => "full_week",
+        "M" => "full_month",
+        "q" => "full_quarter",
+        "y" => "full_year",
+        _ => return Err(format!("cannot snap to unit {:?}", unit)),
+    };
+    Ok(Node::FuncAry1 {
+        name: name.to_string(),
+        arg1: Rc::new(node),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use chrono::TimeDelta;
+
+    use super::{elastic_to_node, grafana_to_node, splunk_to_node};
+    use crate::parser::core::{Node, Oper};
+
+    #[test]
+    fn offset_and_snap() {
+        let expected = Node::FuncAry1 {
+            name: "full_day".to_string(),
+            arg1: Rc::new(Node::Expr(vec![
+                Node::Now,
+                Node::OperNode {
+                    oper: Oper::Minus,
+                    node: Rc::new(Node::Duration(TimeDelta::days(7))),
+                },
+            ])),
+        };
+        assert_eq!(grafana_to_node("now-7d/d"), Ok(expected));
+    }
+
+    #[test]
+    fn calendar_offset() {
+        let actual = grafana_to_node("now+1y").unwrap();
+        if let Node::FuncAry2 { name, arg2, .. } = actual {
+            assert_eq!(name, "add_months");
+            assert_eq!(*arg2, Node::Number(12 * crate::parser::number::SCALE));
+        } else {
+            panic!("unexpected node {:?}", actual);
+        }
+    }
+
+    #[test]
+    fn out_of_range() {
+        assert!(grafana_to_node("now-99999999999999999999d").is_err());
+        assert!(grafana_to_node("now-9999999999999999d").is_err());
+    }
+
+    #[test]
+    fn splunk() {
+        assert_eq!(splunk_to_node("-24h@h"), grafana_to_node("now-24h/h"));
+        assert_eq!(splunk_to_node("@d+8h"), grafana_to_node("now/d+8h"));
+        assert_eq!(splunk_to_node("now"), Ok(Node::Now));
+        assert_eq!(splunk_to_node("-1mon@mon"), grafana_to_node("now-1M/M"));
+        assert_eq!(splunk_to_node("-h"), grafana_to_node("now-1h"));
+        assert_eq!(splunk_to_node("@w1"), grafana_to_node("now/w"));
+        assert!(splunk_to_node("-1x").is_err());
+        assert!(splunk_to_node("@d3").is_err());
+        assert!(splunk_to_node("@w7").is_err());
+        assert!(splunk_to_node("1d").is_err());
+    }
+
+    #[test]
+    fn elastic() {
+        assert_eq!(elastic_to_node("now-1d/d"), grafana_to_node("now-1d/d"));
+        assert_eq!(elastic_to_node("now-1H"), grafana_to_node("now-1h"));
+        assert!(elastic_to_node("2024-01-01T00:00:00Z||+1M/d").is_ok());
+        assert!(elastic_to_node("now-1x").is_err());
+        assert!(elastic_to_node("yesterday").is_err());
+    }
+}
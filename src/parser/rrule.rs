@@ -0,0 +1,655 @@
+use chrono::{
+    DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc, Weekday,
+};
+
+/// How many periods (years, months, weeks...) to scan before giving up on finding an occurrence.
+const MAX_SCAN_PERIODS: i64 = 200_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+enum Frequency {
+    Yearly,
+    Monthly,
+    Weekly,
+    Daily,
+    Hourly,
+    Minutely,
+    Secondly,
+}
+
+/// A point in time as written in the rule (DTSTART, UNTIL). Values with "Z" suffix are UTC, the others are local
+/// to the time zone the rule is evaluated in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum RuleTime {
+    Utc(NaiveDateTime),
+    Local(NaiveDateTime),
+}
+
+impl RuleTime {
+    fn to_local<T: TimeZone>(self, tz: &T) -> NaiveDateTime {
+        match self {
+            RuleTime::Utc(naive) => Utc
+                .from_utc_datetime(&naive)
+                .with_timezone(tz)
+                .naive_local(),
+            RuleTime::Local(naive) => naive,
+        }
+    }
+}
+
+/// A recurrence rule as in RFC 5545, e.g. "FREQ=WEEKLY;BYDAY=MO,WE". The rule can be preceded by DTSTART, e.g.
+/// "DTSTART:20240101T090000Z;RRULE:FREQ=DAILY;COUNT=3". BYWEEKNO is not supported.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecurrenceRule {
+    frequency: Frequency,
+    interval: i64,
+    count: Option<u32>,
+    until: Option<RuleTime>,
+    dtstart: Option<RuleTime>,
+    by_month: Vec<u32>,
+    by_month_day: Vec<i32>,
+    by_year_day: Vec<i32>,
+    /// Week day with an optional ordinal, e.g. -1FR is the last Friday.
+    by_day: Vec<(Option<i32>, Weekday)>,
+    by_hour: Vec<u32>,
+    by_minute: Vec<u32>,
+    by_second: Vec<u32>,
+    by_set_pos: Vec<i32>,
+    week_start: Weekday,
+}
+
+impl RecurrenceRule {
+    pub fn parse(s: &str) -> Result<RecurrenceRule, String> {
+        let mut frequency = None;
+        let mut rule = RecurrenceRule {
+            frequency: Frequency::Daily,
+            interval: 1,
+            count: None,
+            until: None,
+            dtstart: None,
+            by_month: vec![],
+            by_month_day: vec![],
+            by_year_day: vec![],
+            by_day: vec![],
+            by_hour: vec![],
+            by_minute: vec![],
+            by_second: vec![],
+            by_set_pos: vec![],
+            week_start: Weekday::Mon,
+        };
+        let parts = s
+            .split(|c: char| c == ';' || c.is_whitespace())
+            .filter(|part| !part.is_empty());
+        for part in parts {
+            let part = part.strip_prefix("RRULE:").unwrap_or(part);
+            if let Some(value) = part
+                .strip_prefix("DTSTART:")
+                .or_else(|| part.strip_prefix("DTSTART="))
+            {
+                rule.dtstart = Some(parse_rule_time(value)?);
+                continue;
+            }
+            let (name, value) = part
+                .split_once('=')
+                .ok_or(format!("expected NAME=VALUE in rule, got {:?}", part))?;
+            match name {
+                "FREQ" => frequency = Some(parse_frequency(value)?),
+                "INTERVAL" => {
+                    rule.interval = parse_number(name, value, 1, i64::from(u32::MAX))?;
+                }
+                "COUNT" => rule.count = Some(parse_number(name, value, 1, u32::MAX)?),
+                "UNTIL" => rule.until = Some(parse_rule_time(value)?),
+                "BYMONTH" => rule.by_month = parse_list(name, value, 1, 12)?,
+                "BYMONTHDAY" => rule.by_month_day = parse_signed_list(name, value, 31)?,
+                "BYYEARDAY" => rule.by_year_day = parse_signed_list(name, value, 366)?,
+                "BYDAY" => {
+                    rule.by_day = value
+                        .split(',')
+                        .map(parse_by_day)
+                        .collect::<Result<_, _>>()?
+                }
+                "BYHOUR" => rule.by_hour = parse_list(name, value, 0, 23)?,
+                "BYMINUTE" => rule.by_minute = parse_list(name, value, 0, 59)?,
+                "BYSECOND" => rule.by_second = parse_list(name, value, 0, 59)?,
+                "BYSETPOS" => rule.by_set_pos = parse_signed_list(name, value, 366)?,
+                "WKST" => rule.week_start = parse_weekday(value)?,
+                _ => return Err(format!("unsupported rule part {:?}", name)),
+            }
+        }
+        rule.frequency = frequency.ok_or("rule must have FREQ".to_string())?;
+        if rule.count.is_some() && rule.until.is_some() {
+            return Err("rule cannot have both COUNT and UNTIL".to_string());
+        }
+        let has_ordinals = rule.by_day.iter().any(|(n, _)| n.is_some());
+        if has_ordinals && !matches!(rule.frequency, Frequency::Monthly | Frequency::Yearly) {
+            return Err(
+                "BYDAY with ordinals is only valid with FREQ=MONTHLY or FREQ=YEARLY".to_string(),
+            );
+        }
+        Ok(rule)
+    }
+
+    /// Return the first occurrence strictly after `from`. The rule is evaluated in the time zone of `from`. If the
+    /// rule has no DTSTART, it starts at `from`.
+    pub fn next_after<T: TimeZone>(&self, from: &DateTime<T>) -> Option<DateTime<T>> {
+        let tz = from.timezone();
+        let from_local = from.naive_local();
+        let dtstart = self.dtstart.map_or(from_local, |t| t.to_local(&tz));
+        let until = self.until.map(|t| t.to_local(&tz));
+        // Without COUNT the earlier periods do not matter, so skip right before the period of `from`.
+        let first_period = if self.count.is_none() {
+            (self.periods_between(&dtstart, &from_local) - 1).max(0)
+        } else {
+            0
+        };
+        let mut emitted = 0;
+        for period in first_period..first_period + MAX_SCAN_PERIODS {
+            for candidate in self.expand_period(&dtstart, period)? {
+                if candidate < dtstart {
+                    continue;
+                }
+                if until.is_some_and(|until| candidate > until) {
+                    return None;
+                }
+                emitted += 1;
+                if self.count.is_some_and(|count| emitted > count) {
+                    return None;
+                }
+                if let Some(t) = tz.from_local_datetime(&candidate).earliest() {
+                    if t > *from {
+                        return Some(t);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Number of whole periods between dtstart and the given time.
+    fn periods_between(&self, dtstart: &NaiveDateTime, to: &NaiveDateTime) -> i64 {
+        let months = |d: &NaiveDateTime| i64::from(d.year()) * 12 + i64::from(d.month0());
+        let n = match self.frequency {
+            Frequency::Yearly => i64::from(to.year() - dtstart.year()),
+            Frequency::Monthly => months(to) - months(dtstart),
+            Frequency::Weekly => (*to - *dtstart).num_weeks(),
+            Frequency::Daily => (*to - *dtstart).num_days(),
+            Frequency::Hourly => (*to - *dtstart).num_hours(),
+            Frequency::Minutely => (*to - *dtstart).num_minutes(),
+            Frequency::Secondly => (*to - *dtstart).num_seconds(),
+        };
+        n / self.interval
+    }
+
+    /// Return sorted occurrences within the n-th period since dtstart. Return None on date overflow.
+    fn expand_period(&self, dtstart: &NaiveDateTime, n: i64) -> Option<Vec<NaiveDateTime>> {
+        let step = n.checked_mul(self.interval)?;
+        let start_date = dtstart.date();
+        let (days, period_start) = match self.frequency {
+            Frequency::Yearly => {
+                let year = i32::try_from(i64::from(start_date.year()) + step).ok()?;
+                let first = NaiveDate::from_ymd_opt(year, 1, 1)?;
+                (self.days_of_year(first, dtstart), None)
+            }
+            Frequency::Monthly => {
+                let month0 =
+                    i64::from(start_date.year()) * 12 + i64::from(start_date.month0()) + step;
+                let year = i32::try_from(month0.div_euclid(12)).ok()?;
+                let first = NaiveDate::from_ymd_opt(year, month0.rem_euclid(12) as u32 + 1, 1)?;
+                (self.days_of_month(first, dtstart), None)
+            }
+            Frequency::Weekly => {
+                let since_week_start = (start_date.weekday().num_days_from_monday() + 7
+                    - self.week_start.num_days_from_monday())
+                    % 7;
+                let week_start = start_date
+                    .checked_sub_signed(Duration::days(i64::from(since_week_start)))?
+                    .checked_add_signed(Duration::try_weeks(step)?)?;
+                let days = (0..7)
+                    .filter_map(|i| week_start.checked_add_signed(Duration::days(i)))
+                    .filter(|d| self.matches_month(d))
+                    .filter(|d| {
+                        if self.by_day.is_empty() {
+                            d.weekday() == start_date.weekday()
+                        } else {
+                            self.by_day.iter().any(|(_, w)| *w == d.weekday())
+                        }
+                    })
+                    .collect();
+                (days, None)
+            }
+            Frequency::Daily => {
+                let day = start_date.checked_add_signed(Duration::try_days(step)?)?;
+                (self.filter_day(day), None)
+            }
+            Frequency::Hourly | Frequency::Minutely | Frequency::Secondly => {
+                let unit = match self.frequency {
+                    Frequency::Hourly => Duration::try_hours(step)?,
+                    Frequency::Minutely => Duration::try_minutes(step)?,
+                    _ => Duration::try_seconds(step)?,
+                };
+                let t = dtstart.checked_add_signed(unit)?;
+                (self.filter_day(t.date()), Some(t))
+            }
+        };
+        let pick = |by: &Vec<u32>, period_value: Option<u32>, default: u32| -> Vec<u32> {
+            match period_value {
+                Some(v) => {
+                    if by.is_empty() || by.contains(&v) {
+                        vec![v]
+                    } else {
+                        vec![]
+                    }
+                }
+                None if by.is_empty() => vec![default],
+                None => {
+                    let mut by = by.clone();
+                    by.sort();
+                    by
+                }
+            }
+        };
+        let hours = pick(
+            &self.by_hour,
+            period_start
+                .filter(|_| self.frequency >= Frequency::Hourly)
+                .map(|t| t.hour()),
+            dtstart.hour(),
+        );
+        let minutes = pick(
+            &self.by_minute,
+            period_start
+                .filter(|_| self.frequency >= Frequency::Minutely)
+                .map(|t| t.minute()),
+            dtstart.minute(),
+        );
+        let seconds = pick(
+            &self.by_second,
+            period_start
+                .filter(|_| self.frequency >= Frequency::Secondly)
+                .map(|t| t.second()),
+            dtstart.second(),
+        );
+        let mut occurrences = vec![];
+        for day in days {
+            for hour in &hours {
+                for minute in &minutes {
+                    for second in &seconds {
+                        occurrences.push(day.and_hms_opt(*hour, *minute, *second)?);
+                    }
+                }
+            }
+        }
+        if self.by_set_pos.is_empty() {
+            return Some(occurrences);
+        }
+        let mut selected: Vec<NaiveDateTime> = self
+            .by_set_pos
+            .iter()
+            .filter_map(|pos| {
+                let index = if *pos > 0 {
+                    *pos as usize - 1
+                } else {
+                    occurrences.len().checked_sub(pos.unsigned_abs() as usize)?
+                };
+                occurrences.get(index).copied()
+            })
+            .collect();
+        selected.sort();
+        selected.dedup();
+        Some(selected)
+    }
+
+    fn days_of_year(&self, first: NaiveDate, dtstart: &NaiveDateTime) -> Vec<NaiveDate> {
+        let no_day_rules =
+            self.by_month_day.is_empty() && self.by_year_day.is_empty() && self.by_day.is_empty();
+        let year_len: u32 = if first.leap_year() { 366 } else { 365 };
+        first
+            .iter_days()
+            .take(year_len as usize)
+            .filter(|d| self.matches_month(d))
+            .filter(|d| {
+                if no_day_rules {
+                    // Only BYMONTH or nothing: the day of month (and month) of dtstart.
+                    d.day() == dtstart.day()
+                        && (!self.by_month.is_empty() || d.month() == dtstart.month())
+                } else {
+                    let year_day = matches_ordinal(&self.by_year_day, d.ordinal0(), year_len);
+                    let month_day = self.matches_month_day(d);
+                    let week_day = if self.by_month.is_empty() {
+                        self.matches_week_day(d, d.ordinal0(), year_len)
+                    } else {
+                        self.matches_week_day(d, d.day0(), days_in_month(d))
+                    };
+                    year_day && month_day && week_day
+                }
+            })
+            .collect()
+    }
+
+    fn days_of_month(&self, first: NaiveDate, dtstart: &NaiveDateTime) -> Vec<NaiveDate> {
+        if !self.matches_month(&first) {
+            return vec![];
+        }
+        let month_len = days_in_month(&first);
+        first
+            .iter_days()
+            .take(month_len as usize)
+            .filter(|d| {
+                if self.by_month_day.is_empty() && self.by_day.is_empty() {
+                    d.day() == dtstart.day()
+                } else {
+                    self.matches_month_day(d) && self.matches_week_day(d, d.day0(), month_len)
+                }
+            })
+            .collect()
+    }
+
+    /// For daily and shorter frequencies the BYxxx day rules only limit the days.
+    fn filter_day(&self, day: NaiveDate) -> Vec<NaiveDate> {
+        let year_len = if day.leap_year() { 366 } else { 365 };
+        if self.matches_month(&day)
+            && self.matches_month_day(&day)
+            && matches_ordinal(&self.by_year_day, day.ordinal0(), year_len)
+            && self.matches_week_day(&day, 0, 0)
+        {
+            vec![day]
+        } else {
+            vec![]
+        }
+    }
+
+    fn matches_month(&self, d: &NaiveDate) -> bool {
+        self.by_month.is_empty() || self.by_month.contains(&d.month())
+    }
+
+    fn matches_month_day(&self, d: &NaiveDate) -> bool {
+        matches_ordinal(&self.by_month_day, d.day0(), days_in_month(d))
+    }
+
+    /// Check BYDAY. `index0` and `len` give position of the day within the month or year, for ordinals like -1FR.
+    fn matches_week_day(&self, d: &NaiveDate, index0: u32, len: u32) -> bool {
+        if self.by_day.is_empty() {
+            return true;
+        }
+        self.by_day.iter().any(|(ordinal, weekday)| {
+            if *weekday != d.weekday() {
+                return false;
+            }
+            match ordinal {
+                None => true,
+                Some(n) if *n > 0 => (index0 / 7 + 1) as i32 == *n,
+                Some(n) => -(((len - 1 - index0) / 7 + 1) as i32) == *n,
+            }
+        })
+    }
+}
+
+/// Check if the (zero based) index is listed, where negative values count from the end.
+fn matches_ordinal(ordinals: &[i32], index0: u32, len: u32) -> bool {
+    ordinals.is_empty()
+        || ordinals.iter().any(|n| {
+            if *n > 0 {
+                (*n - 1) as u32 == index0
+            } else {
+                len as i32 + *n == index0 as i32
+            }
+        })
+}
+
+fn days_in_month(d: &NaiveDate) -> u32 {
+    let (year, month) = if d.month() == 12 {
+        (d.year() + 1, 1)
+    } else {
+        (d.year(), d.month() + 1)
+    };
+    NaiveDate::from_ymd_opt(year, month, 1)
+        .and_then(|next| next.pred_opt())
+        .map_or(31, |last| last.day())
+}
+
+fn parse_frequency(s: &str) -> Result<Frequency, String> {
+    match s {
+        "YEARLY" => Ok(Frequency::Yearly),
+        "MONTHLY" => Ok(Frequency::Monthly),
+        "WEEKLY" => Ok(Frequency::Weekly),
+        "DAILY" => Ok(Frequency::Daily),
+        "HOURLY" => Ok(Frequency::Hourly),
+        "MINUTELY" => Ok(Frequency::Minutely),
+        "SECONDLY" => Ok(Frequency::Secondly),
+        _ => Err(format!("bad FREQ {:?}", s)),
+    }
+}
+
+fn parse_weekday(s: &str) -> Result<Weekday, String> {
+    match s {
+        "MO" => Ok(Weekday::Mon),
+        "TU" => Ok(Weekday::Tue),
+        "WE" => Ok(Weekday::Wed),
+        "TH" => Ok(Weekday::Thu),
+        "FR" => Ok(Weekday::Fri),
+        "SA" => Ok(Weekday::Sat),
+        "SU" => Ok(Weekday::Sun),
+        _ => Err(format!("bad week day {:?}", s)),
+    }
+}
+
+/// Parse BYDAY item like "MO", "1MO" or "-1FR".
+fn parse_by_day(s: &str) -> Result<(Option<i32>, Weekday), String> {
+    if s.len() < 2 || !s.is_char_boundary(s.len() - 2) {
+        return Err(format!("bad BYDAY {:?}", s));
+    }
+    let (ordinal, weekday) = s.split_at(s.len() - 2);
+    let weekday = parse_weekday(weekday)?;
+    if ordinal.is_empty() {
+        return Ok((None, weekday));
+    }
+    let ordinal = ordinal
+        .trim_start_matches('+')
+        .parse::<i32>()
+        .map_err(|_| format!("bad BYDAY {:?}", s))?;
+    if ordinal == 0 || ordinal.abs() > 53 {
+        return Err(format!("bad BYDAY {:?}", s));
+    }
+    Ok((Some(ordinal), weekday))
+}
+
+fn parse_number<N: std::str::FromStr + PartialOrd + std::fmt::Display>(
+    name: &str,
+    s: &str,
+    min: N,
+    max: N,
+) -> Result<N, String> {
+    match s.parse::<N>() {
+        Ok(n) if n >= min && n <= max => Ok(n),
+        _ => Err(format!("bad {} {:?}, expected {}-{}", name, s, min, max)),
+    }
+}
+
+fn parse_list(name: &str, s: &str, min: u32, max: u32) -> Result<Vec<u32>, String> {
+    s.split(',')
+        .map(|v| parse_number(name, v, min, max))
+        .collect()
+}
+
+/// Parse a list of non-zero values in range -max..=max.
+fn parse_signed_list(name: &str, s: &str, max: i32) -> Result<Vec<i32>, String> {
+    s.split(',')
+        .map(|v| match parse_number(name, v, -max, max)? {
+            0 => Err(format!("bad {} {:?}, cannot be 0", name, v)),
+            n => Ok(n),
+        })
+        .collect()
+}
+
+/// Parse times like "20240101T090000Z", "20240101T090000" or "20240101".
+fn parse_rule_time(s: &str) -> Result<RuleTime, String> {
+    let (s_local, utc) = match s.strip_suffix('Z') {
+        Some(s) => (s, true),
+        None => (s, false),
+    };
+    let naive = NaiveDateTime::parse_from_str(s_local, "%Y%m%dT%H%M%S")
+        .or_else(|_| {
+            NaiveDate::parse_from_str(s_local, "%Y%m%d").map(|d| d.and_hms_opt(0, 0, 0).unwrap())
+        })
+        .map_err(|_| format!("bad date-time {:?} in rule", s))?;
+    Ok(if utc {
+        RuleTime::Utc(naive)
+    } else {
+        RuleTime::Local(naive)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RecurrenceRule;
+    use crate::parser::zone::{Tz, UTC};
+
+    #[test]
+    fn weekly_by_day() {
+        // 2024-01-03 is Wednesday.
+        check_next(
+            "FREQ=WEEKLY;BYDAY=MO,WE",
+            "2024-01-03T10:00:00Z",
+            Some("2024-01-08T10:00:00Z"),
+        );
+        check_next(
+            "FREQ=WEEKLY;BYDAY=MO,WE;BYHOUR=12;BYMINUTE=0;BYSECOND=0",
+            "2024-01-03T10:00:00Z",
+            Some("2024-01-03T12:00:00Z"),
+        );
+    }
+
+    #[test]
+    fn weekly_interval() {
+        check_next(
+            "DTSTART:20240101T090000Z;RRULE:FREQ=WEEKLY;INTERVAL=2",
+            "2024-01-02T00:00:00Z",
+            Some("2024-01-15T09:00:00Z"),
+        );
+        check_next(
+            "DTSTART:20240101T090000Z;RRULE:FREQ=WEEKLY;INTERVAL=2",
+            "2024-03-01T00:00:00Z",
+            Some("2024-03-11T09:00:00Z"),
+        );
+    }
+
+    #[test]
+    fn monthly_last_friday() {
+        check_next(
+            "FREQ=MONTHLY;BYDAY=-1FR;BYHOUR=17;BYMINUTE=0;BYSECOND=0",
+            "2024-01-01T00:00:00Z",
+            Some("2024-01-26T17:00:00Z"),
+        );
+        check_next(
+            "FREQ=MONTHLY;BYDAY=-1FR;BYHOUR=17;BYMINUTE=0;BYSECOND=0",
+            "2024-01-26T17:00:00Z",
+            Some("2024-02-23T17:00:00Z"),
+        );
+    }
+
+    #[test]
+    fn monthly_last_workday() {
+        check_next(
+            "DTSTART:20240101T000000Z FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=-1",
+            "2024-03-01T00:00:00Z",
+            Some("2024-03-29T00:00:00Z"),
+        );
+    }
+
+    #[test]
+    fn monthly_by_month_day() {
+        check_next(
+            "DTSTART:20240131T080000Z;FREQ=MONTHLY",
+            "2024-02-01T00:00:00Z",
+            Some("2024-03-31T08:00:00Z"),
+        );
+        check_next(
+            "DTSTART:20240101T080000Z;FREQ=MONTHLY;BYMONTHDAY=-1",
+            "2024-02-01T00:00:00Z",
+            Some("2024-02-29T08:00:00Z"),
+        );
+    }
+
+    #[test]
+    fn yearly_thanksgiving() {
+        check_next(
+            "DTSTART:20200101T000000Z;FREQ=YEARLY;BYMONTH=11;BYDAY=4TH",
+            "2024-01-01T00:00:00Z",
+            Some("2024-11-28T00:00:00Z"),
+        );
+    }
+
+    #[test]
+    fn yearly_default_day() {
+        check_next(
+            "DTSTART:20200229T000000Z;FREQ=YEARLY",
+            "2021-01-01T00:00:00Z",
+            Some("2024-02-29T00:00:00Z"),
+        );
+    }
+
+    #[test]
+    fn daily_count_and_until() {
+        check_next(
+            "DTSTART:20240101T090000Z;FREQ=DAILY;COUNT=3",
+            "2024-01-02T12:00:00Z",
+            Some("2024-01-03T09:00:00Z"),
+        );
+        check_next(
+            "DTSTART:20240101T090000Z;FREQ=DAILY;COUNT=3",
+            "2024-01-03T09:00:00Z",
+            None,
+        );
+        check_next(
+            "DTSTART:20240101T090000Z;FREQ=DAILY;UNTIL=20240105T000000Z",
+            "2024-01-05T00:00:00Z",
+            None,
+        );
+    }
+
+    #[test]
+    fn hourly_by_day() {
+        // 2024-01-06 is Saturday.
+        check_next(
+            "DTSTART:20240101T000000Z;FREQ=HOURLY;INTERVAL=6;BYDAY=MO",
+            "2024-01-06T00:00:00Z",
+            Some("2024-01-08T00:00:00Z"),
+        );
+    }
+
+    #[test]
+    fn minutely() {
+        check_next(
+            "DTSTART:20240101T000000Z;FREQ=MINUTELY;INTERVAL=15",
+            "2024-05-01T10:07:00Z",
+            Some("2024-05-01T10:15:00Z"),
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(RecurrenceRule::parse("BYDAY=MO").is_err());
+        assert!(RecurrenceRule::parse("FREQ=FORTNIGHTLY").is_err());
+        assert!(RecurrenceRule::parse("FREQ=WEEKLY;BYDAY=XX").is_err());
+        assert!(RecurrenceRule::parse("FREQ=WEEKLY;BYDAY=1MO").is_err());
+        assert!(RecurrenceRule::parse("FREQ=DAILY;COUNT=0").is_err());
+        assert!(RecurrenceRule::parse("FREQ=DAILY;BYMONTHDAY=0").is_err());
+        assert!(RecurrenceRule::parse("FREQ=DAILY;BYWEEKNO=1").is_err());
+    }
+
+    fn check_next(rule: &str, from: &str, expected: Option<&str>) {
+        let rule = RecurrenceRule::parse(rule).unwrap();
+        assert_eq!(
+            rule.next_after(&parse(from)),
+            expected.map(parse),
+            "{:?} after {}",
+            rule,
+            from
+        );
+    }
+
+    fn parse(s: &str) -> chrono::DateTime<Tz> {
+        chrono::DateTime::parse_from_rfc3339(s)
+            .unwrap()
+            .with_timezone(&UTC)
+    }
+}
@@ -0,0 +1,63 @@
+//! Suggestions for misspelled names, like "full_day" for "ful_day".
+
+/// Return the number of characters inserted, deleted, replaced or swapped with the neighbour to turn one word into the
+/// other, the optimal string alignment distance. Swaps count as one edit, so "nwo" is close to "now".
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // d[i][j] is the distance between the first i characters of a and the first j characters of b.
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let replace = d[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = replace.min(d[i - 1][j] + 1).min(d[i][j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// Return the candidate closest to the word, if it is close enough to be a likely misspelling: at most one edit for
+/// short words, and one edit per three characters for longer ones. The earlier candidate wins a tie.
+pub fn closest<'a>(word: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let max_distance = (word.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != word)
+        .map(|candidate| (edit_distance(word, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{closest, edit_distance};
+
+    #[test]
+    fn distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("now", "nwo"), 1);
+        assert_eq!(edit_distance("ful_day", "full_day"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("żółw", "zolw"), 3);
+    }
+
+    #[test]
+    fn closest_candidate() {
+        let names = ["full_day", "full_hour", "now", "max"];
+        assert_eq!(closest("ful_day", names), Some("full_day"));
+        assert_eq!(closest("full_hours", names), Some("full_hour"));
+        assert_eq!(closest("nwo", names), Some("now"));
+        assert_eq!(closest("xyz", names), None);
+        assert_eq!(closest("now", names), None);
+    }
+}
@@ -0,0 +1,427 @@
+//! systemd time spans like "2weeks 1day" and calendar events like "Mon..Fri 10:00", as in systemd.time(7), so the
+//! timers of the unit files can be reasoned about.
+use super::zone::Tz;
+use chrono::{DateTime, Datelike, NaiveDate, TimeDelta, TimeZone};
+
+use super::{
+    duration::{DAY_NS, HOUR_NS, MINUTE_NS, MS_NS, SECOND_NS, US_NS, WEEK_NS},
+    zone::parse_tz,
+};
+
+/// How many days to scan before giving up, the full Gregorian cycle as for the cron schedules.
+const MAX_SCAN_DAYS: i64 = 146097;
+
+/// Year of 365.25 days and month of 1/12 of that, as in systemd.
+const YEAR_NS: i64 = 31_557_600 * SECOND_NS;
+const MONTH_NS: i64 = YEAR_NS / 12;
+
+/// Units of the time spans. A number without unit is seconds.
+const TIMESPAN_UNITS: [(&[&str], i64); 10] = [
+    (&["usec", "us", "µs"], US_NS),
+    (&["msec", "ms"], MS_NS),
+    (&["seconds", "second", "sec", "s", ""], SECOND_NS),
+    (&["minutes", "minute", "min", "m"], MINUTE_NS),
+    (&["hours", "hour", "hr", "h"], HOUR_NS),
+    (&["days", "day", "d"], DAY_NS),
+    (&["weeks", "week", "w"], WEEK_NS),
+    (&["months", "month", "M"], MONTH_NS),
+    (&["years", "year", "y"], YEAR_NS),
+    (&["nsec", "ns"], 1),
+];
+
+const RE_TIMESPAN_PART: &str = r"^\s*(?<whole>\d+)(\.(?<frac>\d+))?\s*(?<unit>[a-zA-Zµ]*)";
+
+const WEEKDAY_NAMES: [&str; 7] = [
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
+
+/// Parse the time span like "2weeks 1day", "1h 30min" or "1.5h". Months and years have the average length, 30.44 and
+/// 365.25 days.
+pub fn parse_timespan(s: &str) -> Result<TimeDelta, String> {
+    let re = regex::Regex::new(RE_TIMESPAN_PART).unwrap();
+    let overflow = || format!("time span {:?} is out of range", s);
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return Err(format!("empty time span {:?}", s));
+    }
+    let mut total: i128 = 0;
+    while !rest.is_empty() {
+        let caps = re
+            .captures(rest)
+            .ok_or_else(|| format!("could not match {:?} in time span {:?}", rest, s))?;
+        let unit = &caps["unit"];
+        let unit_ns = TIMESPAN_UNITS
+            .iter()
+            .find(|(names, _)| names.contains(&unit))
+            .map(|(_, unit_ns)| i128::from(*unit_ns))
+            .ok_or_else(|| format!("unknown unit {:?} in time span {:?}", unit, s))?;
+        let whole = caps["whole"].parse::<i128>().map_err(|_| overflow())?;
+        let frac = caps.name("frac").map_or(0, |m| {
+            let digits = &m.as_str()[..m.len().min(18)];
+            digits.parse::<i128>().unwrap() * unit_ns / 10_i128.pow(digits.len() as u32)
+        });
+        total = whole
+            .checked_mul(unit_ns)
+            .and_then(|n| n.checked_add(frac))
+            .and_then(|n| n.checked_add(total))
+            .ok_or_else(overflow)?;
+        rest = &rest[caps.get(0).unwrap().len()..];
+    }
+    i64::try_from(total)
+        .map(TimeDelta::nanoseconds)
+        .map_err(|_| overflow())
+}
+
+/// Values of a component of the calendar event, like "1..5", "1,15" or "0/15", as inclusive ranges with step.
+/// Empty for "*", which is any value.
+type Component = Vec<(u32, u32, u32)>;
+
+/// A calendar event like "Mon..Fri 10:00", "*-*-01 00:00:00" or "daily", evaluated in the time zone of the event if
+/// given, like "10:00 Europe/Warsaw", or in the time zone of the date-time it is evaluated from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CalendarEvent {
+    /// Bit mask of the days of week, from Monday.
+    weekdays: u8,
+    years: Component,
+    months: Component,
+    days: Component,
+    hours: Component,
+    minutes: Component,
+    seconds: Component,
+    tz: Option<Tz>,
+}
+
+impl CalendarEvent {
+    pub fn parse(s: &str) -> Result<CalendarEvent, String> {
+        let s = s.trim();
+        let expanded = match s.to_lowercase().as_str() {
+            "minutely" => "*-*-* *:*:00",
+            "hourly" => "*-*-* *:00:00",
+            "daily" => "*-*-* 00:00:00",
+            "weekly" => "Mon *-*-* 00:00:00",
+            "monthly" => "*-*-01 00:00:00",
+            "quarterly" => "*-01,04,07,10-01 00:00:00",
+            "semiannually" => "*-01,07-01 00:00:00",
+            "yearly" | "annually" => "*-01-01 00:00:00",
+            _ => s,
+        };
+        let mut tokens: Vec<&str> = expanded.split_whitespace().collect();
+        if tokens.is_empty() {
+            return Err(format!("empty calendar event {:?}", s));
+        }
+        let starts_with_letter = |token: &str| token.starts_with(|c: char| c.is_alphabetic());
+        let tz = match tokens.last() {
+            Some(last) if tokens.len() > 1 && starts_with_letter(last) => {
+                let tz = parse_tz(last)?;
+                tokens.pop();
+                Some(tz)
+            }
+            _ => None,
+        };
+        let mut tokens = tokens.into_iter().peekable();
+        let weekdays = match tokens.next_if(|token| starts_with_letter(token)) {
+            Some(token) => parse_weekdays(token)?,
+            None => 0b111_1111,
+        };
+        let (years, months, days) = match tokens.next_if(|token| token.contains('-')) {
+            Some(token) => match token.split('-').collect::<Vec<_>>()[..] {
+                [year, month, day] => (
+                    parse_component(year, 1, 9999, "year")?,
+                    parse_component(month, 1, 12, "month")?,
+                    parse_component(day, 1, 31, "day")?,
+                ),
+                [month, day] => (
+                    vec![],
+                    parse_component(month, 1, 12, "month")?,
+                    parse_component(day, 1, 31, "day")?,
+                ),
+                _ => return Err(format!("bad date {:?} in calendar event {:?}", token, s)),
+            },
+            None => (vec![], vec![], vec![]),
+        };
+        let (hours, minutes, seconds) = match tokens.next_if(|token| token.contains(':')) {
+            Some(token) => match token.split(':').collect::<Vec<_>>()[..] {
+                [hour, minute, second] => (
+                    parse_component(hour, 0, 23, "hour")?,
+                    parse_component(minute, 0, 59, "minute")?,
+                    parse_component(second, 0, 59, "second")?,
+                ),
+                [hour, minute] => (
+                    parse_component(hour, 0, 23, "hour")?,
+                    parse_component(minute, 0, 59, "minute")?,
+                    vec![(0, 0, 1)],
+                ),
+                _ => return Err(format!("bad time {:?} in calendar event {:?}", token, s)),
+            },
+            None => (vec![(0, 0, 1)], vec![(0, 0, 1)], vec![(0, 0, 1)]),
+        };
+        if let Some(token) = tokens.next() {
+            return Err(format!(
+                "unexpected {:?} in calendar event {:?}, expected [weekdays] [date] [time] [time zone]",
+                token, s
+            ));
+        }
+        Ok(CalendarEvent {
+            weekdays,
+            years,
+            months,
+            days,
+            hours,
+            minutes,
+            seconds,
+            tz,
+        })
+    }
+
+    /// Return the first time the event elapses strictly after `from`, in the time zone of `from`.
+    pub fn next_after(&self, from: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let tz = self.tz.unwrap_or(from.timezone());
+        let start = from.with_timezone(&tz).date_naive();
+        for day_offset in 0..MAX_SCAN_DAYS {
+            let date = start.checked_add_signed(TimeDelta::days(day_offset))?;
+            if !self.matches_date(&date) {
+                continue;
+            }
+            for hour in values(&self.hours, 23) {
+                for minute in values(&self.minutes, 59) {
+                    for second in values(&self.seconds, 59) {
+                        let candidate = date.and_hms_opt(hour, minute, second)?;
+                        if let Some(t) = tz.from_local_datetime(&candidate).earliest() {
+                            if t > *from {
+                                return Some(t.with_timezone(&from.timezone()));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn matches_date(&self, date: &NaiveDate) -> bool {
+        self.weekdays & (1 << date.weekday().num_days_from_monday()) != 0
+            && matches(&self.years, date.year() as u32)
+            && matches(&self.months, date.month())
+            && matches(&self.days, date.day())
+    }
+}
+
+fn matches(component: &Component, value: u32) -> bool {
+    component.is_empty()
+        || component.iter().any(|(start, end, step)| {
+            value >= *start && value <= *end && (value - start).is_multiple_of(*step)
+        })
+}
+
+/// Values of the time component from 0 up to max, in order.
+fn values(component: &Component, max: u32) -> impl Iterator<Item = u32> + '_ {
+    (0..=max).filter(|value| matches(component, *value))
+}
+
+/// Parse the component like "*", "1..5", "1,15", "0/15" or "*/2".
+fn parse_component(field: &str, min: u32, max: u32, what: &str) -> Result<Component, String> {
+    if field == "*" {
+        return Ok(vec![]);
+    }
+    let parse_value = |s: &str| {
+        let value = s
+            .parse::<u32>()
+            .map_err(|_| format!("bad {} {:?} in {:?}", what, s, field))?;
+        if value < min || value > max {
+            return Err(format!(
+                "{} {} out of range {}..{} in {:?}",
+                what, value, min, max, field
+            ));
+        }
+        Ok(value)
+    };
+    let mut component = Component::new();
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => match step.parse::<u32>() {
+                Ok(step) if step > 0 => (range, step),
+                _ => return Err(format!("bad step {:?} of {} in {:?}", step, what, field)),
+            },
+            None => (item, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once("..") {
+            (parse_value(start)?, parse_value(end)?)
+        } else {
+            let start = parse_value(range)?;
+            // "0/15" is from 0 to the end of range every 15.
+            (start, if item.contains('/') { max } else { start })
+        };
+        if start > end {
+            return Err(format!("bad range {:?} of {} in {:?}", range, what, field));
+        }
+        component.push((start, end, step));
+    }
+    Ok(component)
+}
+
+/// Parse the days of week like "Mon..Fri" or "Sat,Sun" into a bit mask, from Monday. The names can be abbreviated
+/// to three letters.
+fn parse_weekdays(field: &str) -> Result<u8, String> {
+    let parse_weekday = |s: &str| {
+        let lower = s.to_lowercase();
+        WEEKDAY_NAMES
+            .iter()
+            .position(|name| lower.len() >= 3 && name.starts_with(&lower))
+            .ok_or_else(|| format!("bad day of week {:?} in {:?}", s, field))
+    };
+    let mut mask = 0;
+    for item in field.split(',') {
+        let (start, end) = match item.split_once("..") {
+            Some((start, end)) => (parse_weekday(start)?, parse_weekday(end)?),
+            None => (parse_weekday(item)?, parse_weekday(item)?),
+        };
+        if start > end {
+            return Err(format!("bad range {:?} of days of week", item));
+        }
+        for day in start..=end {
+            mask |= 1 << day;
+        }
+    }
+    Ok(mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_timespan, CalendarEvent};
+    use crate::parser::zone::{Tz, UTC};
+    use chrono::TimeDelta;
+
+    #[test]
+    fn timespans() {
+        let check = |s: &str, expected: TimeDelta| {
+            assert_eq!(parse_timespan(s), Ok(expected), "{}", s);
+        };
+        check("2weeks 1day", TimeDelta::days(15));
+        check("1h 30min", TimeDelta::minutes(90));
+        check("1 hour 30 minutes", TimeDelta::minutes(90));
+        check("1.5h", TimeDelta::minutes(90));
+        check("90", TimeDelta::seconds(90));
+        check("3months", TimeDelta::seconds(3 * 2_629_800));
+        check("1y", TimeDelta::seconds(31_557_600));
+        check("1M 1m", TimeDelta::seconds(2_629_800 + 60));
+        check("500ms 20us", TimeDelta::microseconds(500_020));
+        assert!(parse_timespan("").is_err());
+        assert!(parse_timespan("1 fortnight").is_err());
+        assert!(parse_timespan("-1h").is_err());
+    }
+
+    #[test]
+    fn next_weekdays() {
+        // 2024-01-06 is Saturday.
+        check_next(
+            "Mon..Fri 10:00",
+            "2024-01-06T12:00:00Z",
+            "2024-01-08T10:00:00Z",
+        );
+        check_next(
+            "Mon..Fri 10:00",
+            "2024-01-08T09:59:59Z",
+            "2024-01-08T10:00:00Z",
+        );
+        check_next("Sat,Sun", "2024-01-06T12:00:00Z", "2024-01-07T00:00:00Z");
+        check_next(
+            "saturday *-*-* 08:30",
+            "2024-01-01T00:00:00Z",
+            "2024-01-06T08:30:00Z",
+        );
+    }
+
+    #[test]
+    fn next_dates_and_times() {
+        check_next(
+            "*-*-01 00:00:00",
+            "2024-01-15T00:00:00Z",
+            "2024-02-01T00:00:00Z",
+        );
+        check_next("*:0/15", "2024-01-01T10:07:00Z", "2024-01-01T10:15:00Z");
+        check_next(
+            "*-*-* *:*:30",
+            "2024-01-01T10:07:45Z",
+            "2024-01-01T10:08:30Z",
+        );
+        check_next(
+            "2030-*-* 12:00",
+            "2024-01-01T00:00:00Z",
+            "2030-01-01T12:00:00Z",
+        );
+        check_next(
+            "02-29 06:00",
+            "2024-03-01T00:00:00Z",
+            "2028-02-29T06:00:00Z",
+        );
+        check_next(
+            "*-*-1..5 8,20:00",
+            "2024-01-05T12:00:00Z",
+            "2024-01-05T20:00:00Z",
+        );
+    }
+
+    #[test]
+    fn next_shorthands() {
+        check_next("daily", "2024-01-01T10:00:00Z", "2024-01-02T00:00:00Z");
+        check_next("weekly", "2024-01-01T00:00:00Z", "2024-01-08T00:00:00Z");
+        check_next("quarterly", "2024-01-01T00:00:00Z", "2024-04-01T00:00:00Z");
+    }
+
+    #[test]
+    #[cfg(feature = "tz")]
+    fn next_in_timezone() {
+        check_next(
+            "10:00 Europe/Warsaw",
+            "2024-01-01T00:00:00Z",
+            "2024-01-01T09:00:00Z",
+        );
+        let from =
+            parse("2024-07-01T00:00:00Z").with_timezone(&Tz::from(chrono_tz::America::New_York));
+        let actual = CalendarEvent::parse("12:00").unwrap().next_after(&from);
+        assert_eq!(actual, Some(parse("2024-07-01T16:00:00Z")));
+    }
+
+    #[test]
+    fn never_fires() {
+        let event = CalendarEvent::parse("02-30").unwrap();
+        assert_eq!(event.next_after(&parse("2024-01-01T00:00:00Z")), None);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(CalendarEvent::parse("").is_err());
+        assert!(CalendarEvent::parse("Mon..Fry 10:00").is_err());
+        assert!(CalendarEvent::parse("Fri..Mon").is_err());
+        assert!(CalendarEvent::parse("25:00").is_err());
+        assert!(CalendarEvent::parse("*:0/0").is_err());
+        assert!(CalendarEvent::parse("10:00 Mars/Olympus").is_err());
+        assert!(CalendarEvent::parse("10:00 11:00").is_err());
+        assert!(CalendarEvent::parse("1-2-3-4").is_err());
+    }
+
+    fn check_next(expr: &str, from: &str, expected: &str) {
+        let event = CalendarEvent::parse(expr).unwrap();
+        assert_eq!(
+            event.next_after(&parse(from)),
+            Some(parse(expected)),
+            "{} after {}",
+            expr,
+            from
+        );
+    }
+
+    fn parse(s: &str) -> chrono::DateTime<Tz> {
+        chrono::DateTime::parse_from_rfc3339(s)
+            .unwrap()
+            .with_timezone(&UTC)
+    }
+}
@@ -0,0 +1,673 @@
+//! Types of the expressions, checked without evaluation for --dry-run. The checks follow the evaluation: date-time
+//! minus date-time is a duration, date-time plus duration is a date-time, and so on, with bare numbers being
+//! date-times. Nothing is evaluated, so "now", the network and the clock are not needed. The expressions parsed with
+//! parse_expr_spanned get the errors with the positions of the terms.
+use std::fmt;
+
+use super::{
+    core::{CompareOper, Node, Oper},
+    eval::State,
+    registry::{Param, Registry},
+};
+
+/// Names of the types in the type assertions, like "now - deadline :: duration".
+pub const TYPE_NAMES: [&str; 8] = [
+    "datetime", "duration", "integer", "bool", "interval", "string", "list", "record",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Type {
+    DateTime,
+    Duration,
+    /// A bare number, a date-time unless a function takes it as it is.
+    Number,
+    Integer,
+    Bool,
+    Interval,
+    CalendarDelta,
+    Record,
+    String,
+    List(Box<Type>),
+    /// Result of a function with the type depending on the values, or of a function registered by library users.
+    Unknown,
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::DateTime => write!(f, "date-time"),
+            Type::Duration => write!(f, "duration"),
+            Type::Number => write!(f, "number"),
+            Type::Integer => write!(f, "integer"),
+            Type::Bool => write!(f, "bool"),
+            Type::Interval => write!(f, "interval"),
+            Type::CalendarDelta => write!(f, "calendar delta"),
+            Type::Record => write!(f, "record"),
+            Type::String => write!(f, "string"),
+            Type::List(element) => write!(f, "list of {}", element),
+            Type::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// Position in the input as bytes [start, end).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Type error, like "cannot add date-time and date-time", with the spans of the values involved. The spans are empty
+/// for the expressions parsed without the spans.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeErr {
+    pub message: String,
+    pub spans: Vec<Span>,
+}
+
+impl TypeErr {
+    fn new(message: String, nodes: &[&Node]) -> TypeErr {
+        TypeErr {
+            message,
+            spans: nodes.iter().filter_map(|node| span_of(node)).collect(),
+        }
+    }
+}
+
+impl Type {
+    /// The type of the name in TYPE_NAMES, with "list" being the list of any elements.
+    pub fn from_name(name: &str) -> Option<Type> {
+        match name {
+            "datetime" => Some(Type::DateTime),
+            "duration" => Some(Type::Duration),
+            "integer" => Some(Type::Integer),
+            "bool" => Some(Type::Bool),
+            "interval" => Some(Type::Interval),
+            "string" => Some(Type::String),
+            "list" => Some(Type::List(Box::new(Type::Unknown))),
+            "record" => Some(Type::Record),
+            _ => None,
+        }
+    }
+
+    /// The name of the type in the type assertions, the inverse of from_name.
+    pub fn name(&self) -> Option<&'static str> {
+        match self {
+            Type::DateTime => Some("datetime"),
+            Type::Duration => Some("duration"),
+            Type::Integer => Some("integer"),
+            Type::Bool => Some("bool"),
+            Type::Interval => Some("interval"),
+            Type::String => Some("string"),
+            Type::List(_) => Some("list"),
+            Type::Record => Some("record"),
+            _ => None,
+        }
+    }
+
+    /// The type of the evaluated value, with the elements of the list only if they have the same type.
+    pub fn of_state(state: &State) -> Type {
+        match state {
+            State::TimeDelta(_) => Type::Duration,
+            State::DateTime(_) => Type::DateTime,
+            State::String(_) => Type::String,
+            State::Number(_) => Type::Number,
+            State::Integer(_) => Type::Integer,
+            State::Bool(_) => Type::Bool,
+            State::Interval(_, _) => Type::Interval,
+            State::CalendarDelta { .. } => Type::CalendarDelta,
+            State::Record(_) => Type::Record,
+            State::List(elements) => {
+                let types: Vec<Type> = elements.iter().map(Type::of_state).collect();
+                let element = match types.split_first() {
+                    Some((first, rest)) if rest.iter().all(|other| other == first) => first.clone(),
+                    _ => Type::Unknown,
+                };
+                Type::List(Box::new(element))
+            }
+            State::None => Type::Unknown,
+        }
+    }
+
+    /// Check whether the value of the type passes the assertion of this type. The unknown type passes any
+    /// assertion, and any list passes the assertion of the list of unknown elements.
+    pub fn accepts(&self, actual: &Type) -> bool {
+        match (self, actual) {
+            (_, Type::Unknown) | (Type::Unknown, _) => true,
+            (Type::List(expected), Type::List(actual)) => expected.accepts(actual),
+            (expected, actual) => expected == actual,
+        }
+    }
+
+    /// The type as the operators and the comparisons see it, with the bare numbers as date-times.
+    fn into_value(self) -> Type {
+        match self {
+            Type::Number => Type::DateTime,
+            other => other,
+        }
+    }
+}
+
+/// Check the types in the expression and return the type of the result, or the first type error.
+pub fn check_types(node: &Node) -> Result<Type, TypeErr> {
+    check_types_with(node, &Registry::builtin())
+}
+
+/// Check the types like check_types, with the functions of the registry. The functions registered by library users
+/// have no declared types, so their results are unknown.
+pub fn check_types_with(node: &Node, registry: &Registry) -> Result<Type, TypeErr> {
+    node_type(node, registry, &mut vec![]).map(Type::into_value)
+}
+
+/// Span of the node from its spanned terms, if any.
+fn span_of(node: &Node) -> Option<Span> {
+    let union = |nodes: &[&Node]| {
+        nodes
+            .iter()
+            .filter_map(|node| span_of(node))
+            .reduce(|a, b| Span {
+                start: a.start.min(b.start),
+                end: a.end.max(b.end),
+            })
+    };
+    match node {
+        Node::Spanned { start, end, .. } => Some(Span {
+            start: *start,
+            end: *end,
+        }),
+        Node::Expr(nodes) => union(&nodes.iter().collect::<Vec<&Node>>()),
+        Node::OperNode { node, .. } | Node::TypeAssertion { node, .. } => span_of(node),
+        Node::Compare { left, right, .. } => union(&[left, right]),
+        Node::Interval { start, end } => union(&[start, end]),
+        // Calls of the piped stages, like "now |> full_day", where only the piped value is spanned.
+        Node::FuncAry1 { arg1, .. } | Node::FuncAry2 { arg1, .. } => span_of(arg1),
+        Node::FuncAry3 { arg1, .. } | Node::FuncAry4 { arg1, .. } => span_of(arg1),
+        _ => None,
+    }
+}
+
+fn node_type(
+    node: &Node,
+    registry: &Registry,
+    variables: &mut Vec<(String, Type)>,
+) -> Result<Type, TypeErr> {
+    match node {
+        Node::Expr(nodes) => expr_type(None, nodes, registry, variables)?
+            .map(|(result, _)| result)
+            .ok_or_else(|| TypeErr::new("empty expression".to_string(), &[node])),
+        Node::OperNode { node, .. } | Node::Spanned { node, .. } => {
+            node_type(node, registry, variables)
+        }
+        Node::TypeAssertion {
+            node: inner,
+            expected,
+        } => {
+            let actual = node_type(inner, registry, variables)?.into_value();
+            if !expected.accepts(&actual) {
+                return Err(TypeErr::new(
+                    format!("expected {}, got {}", expected, actual),
+                    &[inner],
+                ));
+            }
+            Ok(match actual {
+                Type::Unknown => expected.clone(),
+                actual => actual,
+            })
+        }
+        Node::Literal { literal, .. } => Err(TypeErr::new(
+            format!("unexpected literal {:?}", literal),
+            &[node],
+        )),
+        Node::Duration(_) => Ok(Type::Duration),
+        Node::DateTime(_)
+        | Node::LocalDateTime { .. }
+        | Node::NamedMonthDate { .. }
+        | Node::NumericDate(_)
+        | Node::Now
+        | Node::Uptime
+        | Node::NtpNow
+        | Node::Epoch
+        | Node::MaxTime
+        | Node::MinTime => Ok(Type::DateTime),
+        Node::String(_) => Ok(Type::String),
+        Node::Number(_) => Ok(Type::Number),
+        Node::FuncAry1 { name, arg1 } => func_type(name, &[arg1], registry, variables),
+        Node::FuncAry2 { name, arg1, arg2 } => func_type(name, &[arg1, arg2], registry, variables),
+        Node::FuncAry3 {
+            name,
+            arg1,
+            arg2,
+            arg3,
+        } => func_type(name, &[arg1, arg2, arg3], registry, variables),
+        Node::FuncAry4 {
+            name,
+            arg1,
+            arg2,
+            arg3,
+            arg4,
+        } => func_type(name, &[arg1, arg2, arg3, arg4], registry, variables),
+        Node::Interval { start, end } => {
+            for bound in [start, end] {
+                match node_type(bound, registry, variables)?.into_value() {
+                    Type::DateTime | Type::Unknown => (),
+                    other => {
+                        return Err(TypeErr::new(
+                            format!("interval bound should be date-time, was {}", other),
+                            &[bound],
+                        ))
+                    }
+                }
+            }
+            Ok(Type::Interval)
+        }
+        Node::List(elements) => {
+            let mut types = vec![];
+            for element in elements {
+                types.push(node_type(element, registry, variables)?.into_value());
+            }
+            let element = match types.split_first() {
+                Some((first, rest)) if rest.iter().all(|other| other == first) => first.clone(),
+                _ => Type::Unknown,
+            };
+            Ok(Type::List(Box::new(element)))
+        }
+        Node::Variable(name) => variables
+            .iter()
+            .rev()
+            .find(|(variable, _)| variable == name)
+            .map(|(_, value)| value.clone())
+            .ok_or_else(|| TypeErr::new(format!("unknown variable {:?}", name), &[node])),
+        Node::Map {
+            list,
+            variable,
+            body,
+        } => {
+            let element = match node_type(list, registry, variables)? {
+                Type::List(element) => *element,
+                Type::Unknown => Type::Unknown,
+                other => {
+                    return Err(TypeErr::new(
+                        format!("map expects a list, got {}", other),
+                        &[list],
+                    ))
+                }
+            };
+            variables.push((variable.clone(), element));
+            let body = node_type(body, registry, variables).map(Type::into_value);
+            variables.pop();
+            Ok(Type::List(Box::new(body?)))
+        }
+        Node::Compare { oper, left, right } => {
+            let nodes = [left.as_ref(), right.as_ref()];
+            let left = node_type(left, registry, variables)?.into_value();
+            let right = node_type(right, registry, variables)?.into_value();
+            match (&left, &right) {
+                (Type::Unknown, _) | (_, Type::Unknown) => Ok(Type::Bool),
+                (Type::DateTime | Type::Duration | Type::Integer | Type::String, _)
+                    if left == right =>
+                {
+                    Ok(Type::Bool)
+                }
+                (Type::Bool, Type::Bool) if matches!(oper, CompareOper::Eq | CompareOper::Ne) => {
+                    Ok(Type::Bool)
+                }
+                _ => Err(TypeErr::new(
+                    format!("cannot compare {} {} {}", left, compare_symbol(oper), right),
+                    &nodes,
+                )),
+            }
+        }
+        Node::If {
+            cond,
+            then,
+            otherwise,
+        } => {
+            match node_type(cond, registry, variables)? {
+                Type::Bool | Type::Unknown => (),
+                other => {
+                    return Err(TypeErr::new(
+                        format!("the condition of if should be bool, was {}", other),
+                        &[cond],
+                    ))
+                }
+            }
+            let then = node_type(then, registry, variables)?;
+            let otherwise = node_type(otherwise, registry, variables)?;
+            // Only the taken branch is evaluated, so the branches of different types are not an error.
+            Ok(if then == otherwise {
+                then
+            } else {
+                Type::Unknown
+            })
+        }
+    }
+}
+
+/// Type of the nodes applied in turn to the type so far, as eval_expr evaluates them. The type so far comes with
+/// the span of the terms that gave it, for the errors.
+fn expr_type(
+    mut state: Option<(Type, Option<Span>)>,
+    nodes: &[Node],
+    registry: &Registry,
+    variables: &mut Vec<(String, Type)>,
+) -> Result<Option<(Type, Option<Span>)>, TypeErr> {
+    for node in nodes {
+        state = match (node, state) {
+            (Node::Literal { .. }, state) => state,
+            (Node::Expr(nodes), state) => expr_type(state, nodes, registry, variables)?,
+            (Node::OperNode { oper, node }, Some((left, left_span))) => {
+                let right = node_type(node, registry, variables)?;
+                let right_span = span_of(node);
+                let result = oper_type(oper, left, right).map_err(|message| TypeErr {
+                    message,
+                    spans: [left_span, right_span].into_iter().flatten().collect(),
+                })?;
+                let span = match (left_span, right_span) {
+                    (Some(left), Some(right)) => Some(Span {
+                        start: left.start,
+                        end: right.end,
+                    }),
+                    (left, right) => left.or(right),
+                };
+                Some((result, span))
+            }
+            (node, None) => Some((node_type(node, registry, variables)?, span_of(node))),
+            (node, Some((state, _))) => {
+                return Err(TypeErr::new(
+                    format!("unexpected {:?} after {}", node, state),
+                    &[node],
+                ))
+            }
+        };
+    }
+    Ok(state)
+}
+
+fn compare_symbol(oper: &CompareOper) -> &'static str {
+    match oper {
+        CompareOper::Lt => "<",
+        CompareOper::Le => "<=",
+        CompareOper::Gt => ">",
+        CompareOper::Ge => ">=",
+        CompareOper::Eq => "==",
+        CompareOper::Ne => "!=",
+    }
+}
+
+/// Type of "left oper right", as apply_oper_node evaluates it.
+fn oper_type(oper: &Oper, left: Type, right: Type) -> Result<Type, String> {
+    match (left.into_value(), oper, right.into_value()) {
+        (Type::DateTime, Oper::Minus, Type::DateTime) => Ok(Type::Duration),
+        (Type::DateTime, _, Type::Duration) => Ok(Type::DateTime),
+        (Type::Duration, Oper::Plus, Type::DateTime) => Ok(Type::DateTime),
+        (Type::Duration, _, Type::Duration) => Ok(Type::Duration),
+        (Type::Unknown, _, Type::DateTime | Type::Duration | Type::Unknown)
+        | (Type::DateTime | Type::Duration, _, Type::Unknown) => Ok(Type::Unknown),
+        (left, oper, right) => Err(oper_error(oper, &left, &right)),
+    }
+}
+
+/// Message of "left oper right" with the types that cannot be added or subtracted, with what was likely meant.
+pub fn oper_error(oper: &Oper, left: &Type, right: &Type) -> String {
+    match (left, oper, right) {
+        (Type::DateTime, Oper::Plus, Type::DateTime) => "cannot add date-time and date-time, \
+            subtract them for the duration between, or add a duration like + 1h"
+            .to_string(),
+        (Type::Duration, Oper::Minus, Type::DateTime) => {
+            "cannot subtract date-time from duration, \
+            subtract the duration from the date-time instead, like date-time - 1h"
+                .to_string()
+        }
+        (left, Oper::Plus, right) => format!("cannot add {} and {}", left, right),
+        (left, Oper::Minus, right) => format!("cannot subtract {} from {}", right, left),
+    }
+}
+
+/// Type of the result of the function, after checking the arguments against the types declared in the registry.
+fn func_type(
+    name: &str,
+    args: &[&std::rc::Rc<Node>],
+    registry: &Registry,
+    variables: &mut Vec<(String, Type)>,
+) -> Result<Type, TypeErr> {
+    let raw = registry.takes_raw_numbers(name, args.len());
+    let mut arg_types = vec![];
+    for arg in args {
+        let arg_type = node_type(arg, registry, variables)?;
+        arg_types.push(if raw { arg_type } else { arg_type.into_value() });
+    }
+    let Some(signature) = registry.signature(name, args.len()) else {
+        return Ok(Type::Unknown);
+    };
+    // The date-time or the duration of the arguments declared as Quantity, unknown until one is known.
+    let mut quantity = Type::Unknown;
+    for (index, ((arg, actual), param)) in args
+        .iter()
+        .zip(&arg_types)
+        .zip(&signature.params)
+        .enumerate()
+    {
+        let mismatch = |expected: &dyn fmt::Display| {
+            TypeErr::new(
+                format!(
+                    "argument {} of {} should be {}, got {}",
+                    index + 1,
+                    name,
+                    expected,
+                    actual
+                ),
+                &[arg],
+            )
+        };
+        match param {
+            Param::Of(expected) => {
+                if !param_accepts(expected, actual) {
+                    return Err(mismatch(param));
+                }
+            }
+            Param::OneOf(expected) => {
+                if !expected
+                    .iter()
+                    .any(|expected| param_accepts(expected, actual))
+                {
+                    return Err(mismatch(param));
+                }
+            }
+            Param::Parsed(parse) => {
+                if !Type::String.accepts(actual) {
+                    return Err(mismatch(param));
+                }
+                if let Some(literal) = string_literal(arg) {
+                    parse(literal).map_err(|message| {
+                        TypeErr::new(
+                            format!("argument {} of {}: {}", index + 1, name, message),
+                            &[arg],
+                        )
+                    })?;
+                }
+            }
+            Param::Quantity | Param::QuantityList => {
+                let element = match (param, actual.clone().into_value()) {
+                    (Param::QuantityList, Type::List(element)) => element.into_value(),
+                    (Param::QuantityList, Type::Unknown) => Type::Unknown,
+                    (Param::QuantityList, _) => return Err(mismatch(param)),
+                    (_, actual) => actual,
+                };
+                match element {
+                    Type::Unknown => (),
+                    Type::DateTime | Type::Duration if quantity.accepts(&element) => {
+                        quantity = element
+                    }
+                    // Like a duration after a date-time in clamp.
+                    _ if quantity != Type::Unknown => {
+                        return Err(match param {
+                            Param::QuantityList => {
+                                mismatch(&Type::List(Box::new(quantity.clone())))
+                            }
+                            _ => mismatch(&quantity),
+                        })
+                    }
+                    _ => return Err(mismatch(param)),
+                }
+            }
+        }
+    }
+    Ok(match &signature.result {
+        Param::Of(result) => result.clone(),
+        Param::Quantity => quantity,
+        Param::QuantityList => Type::List(Box::new(quantity)),
+        Param::OneOf(_) | Param::Parsed(_) => Type::Unknown,
+    })
+}
+
+/// Whether the argument of the type passes as the parameter of the expected type. The bare numbers are date-times,
+/// and the integers are numbers.
+fn param_accepts(expected: &Type, actual: &Type) -> bool {
+    match (expected, actual) {
+        (Type::DateTime, Type::Number) | (Type::Number, Type::Integer) => true,
+        (expected, actual) => expected.accepts(actual),
+    }
+}
+
+/// The string of the argument if it is a string literal, to check it without evaluation.
+fn string_literal(node: &Node) -> Option<&str> {
+    match node {
+        Node::String(string) => Some(string),
+        Node::Spanned { node, .. } => string_literal(node),
+        Node::Expr(nodes) if nodes.len() == 1 => string_literal(&nodes[0]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_types, Span, Type, TypeErr};
+    use crate::parser::{parse_expr, parse_expr_spanned, registry::Registry};
+
+    fn check(input: &str) -> Result<Type, String> {
+        check_types(&parse_expr(&input.to_string()).unwrap().node).map_err(|err| err.message)
+    }
+
+    fn check_spanned(input: &str) -> Result<Type, TypeErr> {
+        let input = input.to_string();
+        check_types(
+            &parse_expr_spanned(&input, &Registry::builtin())
+                .unwrap()
+                .node,
+        )
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!(check("now - 1d"), Ok(Type::DateTime));
+        assert_eq!(check("now - 2024-01-01T00:00:00Z"), Ok(Type::Duration));
+        assert_eq!(check("1h + now"), Ok(Type::DateTime));
+        assert_eq!(check("1h - 1m"), Ok(Type::Duration));
+        assert_eq!(check("1700000000 + 1h"), Ok(Type::DateTime));
+        assert_eq!(check("1700000000"), Ok(Type::DateTime));
+        assert_eq!(
+            check("now + now"),
+            Err(
+                "cannot add date-time and date-time, subtract them for the duration between, \
+                or add a duration like + 1h"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            check("1h - now"),
+            Err("cannot subtract date-time from duration, \
+                subtract the duration from the date-time instead, like date-time - 1h"
+                .to_string())
+        );
+        assert_eq!(
+            check("\"a\" + 1h"),
+            Err("cannot add string and duration".to_string())
+        );
+    }
+
+    #[test]
+    fn functions_and_comparisons() {
+        assert_eq!(check("full_day(now) + 1h"), Ok(Type::DateTime));
+        assert_eq!(check("now > now - 1d"), Ok(Type::Bool));
+        assert_eq!(check("to_filetime(now)"), Ok(Type::Integer));
+        assert_eq!(check("age(2000-01-01T00:00:00Z)"), Ok(Type::CalendarDelta));
+        assert_eq!(check("max([1h, 2h]) + now"), Ok(Type::DateTime));
+        assert_eq!(
+            check("map([now, 1h], x -> x)"),
+            Ok(Type::List(Box::new(Type::Unknown)))
+        );
+        assert_eq!(
+            check("map([now], x -> x - 1d)"),
+            Ok(Type::List(Box::new(Type::DateTime)))
+        );
+        assert!(check("now > 1h").is_err());
+        assert!(check("fmt(now, \"%Y\") + 1h").is_err());
+        assert!(check("if(now, 1h, 2h)").is_err());
+        assert!(check("full_day(now) - now - now").is_err());
+    }
+
+    #[test]
+    fn function_arguments() {
+        assert_eq!(check("full_day(1700000000)"), Ok(Type::DateTime));
+        assert_eq!(check("with_year(now, 2020)"), Ok(Type::DateTime));
+        assert_eq!(check("from_filetime(to_filetime(now))"), Ok(Type::DateTime));
+        assert_eq!(check("clamp(1h, 2h, 3h)"), Ok(Type::Duration));
+        assert_eq!(check("sum(map([now], x -> now - x))"), Ok(Type::Duration));
+        assert_eq!(check("next_cron(\"0 * * * *\", now)"), Ok(Type::DateTime));
+        assert_eq!(
+            check("full_day(1h)"),
+            Err("argument 1 of full_day should be date-time, got duration".to_string())
+        );
+        assert_eq!(
+            check("sum([now])"),
+            Err("argument 1 of sum should be list of duration, got list of date-time".to_string())
+        );
+        assert_eq!(
+            check("clamp(now, now, 1h)"),
+            Err("argument 3 of clamp should be date-time, got duration".to_string())
+        );
+        assert_eq!(
+            check("mean(now)"),
+            Err(
+                "argument 1 of mean should be list of date-times or of durations, got date-time"
+                    .to_string()
+            )
+        );
+        assert!(check("next_cron(\"bad\", now)")
+            .unwrap_err()
+            .starts_with("argument 1 of next_cron: "));
+        assert!(check("timespan(\"5 parsecs\")").is_err());
+        let span = |start, end| Span { start, end };
+        assert_eq!(
+            check_spanned("interval(now, 1h)").map_err(|err| err.spans),
+            Err(vec![span(14, 16)])
+        );
+    }
+
+    #[test]
+    fn spans() {
+        let span = |start, end| Span { start, end };
+        assert_eq!(check_spanned("(now - 1d) + 1h"), Ok(Type::DateTime));
+        assert_eq!(
+            check_spanned("now + 1h + full_day(now)"),
+            Err(TypeErr {
+                message:
+                    "cannot add date-time and date-time, subtract them for the duration between, \
+                    or add a duration like + 1h"
+                        .to_string(),
+                spans: vec![span(0, 8), span(11, 24)],
+            })
+        );
+        assert_eq!(
+            check_spanned("1d > now - 1h"),
+            Err(TypeErr {
+                message: "cannot compare duration > date-time".to_string(),
+                spans: vec![span(0, 2), span(5, 13)],
+            })
+        );
+        assert_eq!(
+            check("now + now").map_err(|_| ()),
+            check_spanned("now + now").map_err(|_| ())
+        );
+    }
+}
@@ -0,0 +1,571 @@
+//! Time zones by the IANA name like "Europe/Warsaw", or by the abbreviation like "PST", as they appear in logs and
+//! emails. The abbreviation is the fixed offset it stands for, whatever the date, so "PST" is -08:00 also in summer and
+//! "PDT" is -07:00 also in winter. The names that are also IANA zones, like "EST", "MST", "HST" and "CET", keep their
+//! IANA meaning with the tz feature.
+//!
+//! The zones come from chrono-tz with the tz feature. Without the feature, for the smaller builds of the library, Tz is
+//! only UTC or a fixed offset like "+02:00", including the offsets of the abbreviations. The fixed offsets are zones
+//! also with the feature.
+use chrono::{offset::LocalResult, FixedOffset, NaiveDate, Offset, TimeZone};
+use chrono::{DateTime, NaiveDateTime, TimeDelta};
+use std::fmt;
+use std::str::FromStr;
+
+/// Time zone, the zone of the tz database like "Europe/Warsaw" with the tz feature, or a fixed offset like "+02:00".
+/// The same type with and without the feature, so the code using the library builds with both.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Tz(Zone);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Zone {
+    #[cfg(feature = "tz")]
+    Named(chrono_tz::Tz),
+    /// The offset, with the abbreviation it was given by, like "PST".
+    Fixed(FixedOffset, Option<&'static str>),
+}
+
+#[cfg(feature = "tz")]
+pub const UTC: Tz = Tz(Zone::Named(chrono_tz::UTC));
+
+#[cfg(not(feature = "tz"))]
+pub const UTC: Tz = Tz(Zone::Fixed(east(0, 0), None));
+
+impl Tz {
+    /// The zone of the fixed offset, like "+02:00".
+    pub fn fixed(offset: FixedOffset) -> Tz {
+        Tz(Zone::Fixed(offset, None))
+    }
+
+    /// The IANA name like "Europe/Warsaw", "UTC", the abbreviation like "PST", or the offset like "+02:00".
+    pub fn name(&self) -> String {
+        match self.0 {
+            #[cfg(feature = "tz")]
+            Zone::Named(tz) => tz.name().to_string(),
+            Zone::Fixed(_, None) if *self == UTC => "UTC".to_string(),
+            Zone::Fixed(_, Some(abbreviation)) => abbreviation.to_string(),
+            Zone::Fixed(offset, None) => offset.to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "tz")]
+impl From<chrono_tz::Tz> for Tz {
+    fn from(tz: chrono_tz::Tz) -> Self {
+        Tz(Zone::Named(tz))
+    }
+}
+
+/// The names of the zones to complete, only UTC without the tz feature.
+pub fn zone_names() -> Vec<&'static str> {
+    #[cfg(feature = "tz")]
+    return chrono_tz::TZ_VARIANTS.iter().map(|tz| tz.name()).collect();
+    #[cfg(not(feature = "tz"))]
+    return vec!["UTC"];
+}
+
+impl fmt::Display for Tz {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Tz {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "UTC" || s == "Z" {
+            return Ok(UTC);
+        }
+        #[cfg(feature = "tz")]
+        let err = match chrono_tz::Tz::from_str(s) {
+            Ok(tz) => return Ok(Tz::from(tz)),
+            Err(err) => err.to_string(),
+        };
+        #[cfg(not(feature = "tz"))]
+        let err =
+            "only UTC and the offsets like +02:00 are known without the tz feature".to_string();
+        FixedOffset::from_str(s).map(Tz::fixed).map_err(|_| err)
+    }
+}
+
+/// The offset of Tz at a time, the offset of the tz database with its abbreviation like "CEST", or the fixed offset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TzOffset(ZoneOffset);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ZoneOffset {
+    #[cfg(feature = "tz")]
+    Named(<chrono_tz::Tz as TimeZone>::Offset),
+    Fixed(FixedOffset, Option<&'static str>),
+}
+
+impl Offset for TzOffset {
+    fn fix(&self) -> FixedOffset {
+        match self.0 {
+            #[cfg(feature = "tz")]
+            ZoneOffset::Named(offset) => offset.fix(),
+            ZoneOffset::Fixed(offset, _) => offset,
+        }
+    }
+}
+
+impl fmt::Display for TzOffset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            #[cfg(feature = "tz")]
+            ZoneOffset::Named(offset) => write!(f, "{}", offset),
+            ZoneOffset::Fixed(_, Some(abbreviation)) => write!(f, "{}", abbreviation),
+            ZoneOffset::Fixed(offset, None) => write!(f, "{}", offset),
+        }
+    }
+}
+
+impl TimeZone for Tz {
+    type Offset = TzOffset;
+
+    fn from_offset(offset: &TzOffset) -> Self {
+        match offset.0 {
+            #[cfg(feature = "tz")]
+            ZoneOffset::Named(offset) => Tz(Zone::Named(chrono_tz::Tz::from_offset(&offset))),
+            ZoneOffset::Fixed(offset, abbreviation) => Tz(Zone::Fixed(offset, abbreviation)),
+        }
+    }
+
+    fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<TzOffset> {
+        match self.0 {
+            #[cfg(feature = "tz")]
+            Zone::Named(tz) => tz
+                .offset_from_local_date(local)
+                .map(|offset| TzOffset(ZoneOffset::Named(offset))),
+            Zone::Fixed(offset, abbreviation) => offset
+                .offset_from_local_date(local)
+                .map(|offset| TzOffset(ZoneOffset::Fixed(offset, abbreviation))),
+        }
+    }
+
+    fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<TzOffset> {
+        match self.0 {
+            #[cfg(feature = "tz")]
+            Zone::Named(tz) => tz
+                .offset_from_local_datetime(local)
+                .map(|offset| TzOffset(ZoneOffset::Named(offset))),
+            Zone::Fixed(offset, abbreviation) => offset
+                .offset_from_local_datetime(local)
+                .map(|offset| TzOffset(ZoneOffset::Fixed(offset, abbreviation))),
+        }
+    }
+
+    fn offset_from_utc_date(&self, utc: &NaiveDate) -> TzOffset {
+        match self.0 {
+            #[cfg(feature = "tz")]
+            Zone::Named(tz) => TzOffset(ZoneOffset::Named(tz.offset_from_utc_date(utc))),
+            Zone::Fixed(offset, abbreviation) => TzOffset(ZoneOffset::Fixed(
+                offset.offset_from_utc_date(utc),
+                abbreviation,
+            )),
+        }
+    }
+
+    fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> TzOffset {
+        match self.0 {
+            #[cfg(feature = "tz")]
+            Zone::Named(tz) => TzOffset(ZoneOffset::Named(tz.offset_from_utc_datetime(utc))),
+            Zone::Fixed(offset, abbreviation) => TzOffset(ZoneOffset::Fixed(
+                offset.offset_from_utc_datetime(utc),
+                abbreviation,
+            )),
+        }
+    }
+}
+
+/// The offset of the hours and minutes east of UTC, for the constants.
+const fn east(hours: i32, minutes: i32) -> FixedOffset {
+    match FixedOffset::east_opt(hours * 3600 + minutes * 60) {
+        Some(offset) => offset,
+        None => panic!("offset out of range"),
+    }
+}
+
+/// The abbreviation and its zones, each with the IANA name, the offset and the description.
+pub type Abbreviation = (
+    &'static str,
+    &'static [(&'static str, FixedOffset, &'static str)],
+);
+
+/// Abbreviations, their offsets and the IANA names of the zones that use them. The abbreviation is the fixed offset,
+/// the zones only tell where it is used. The abbreviations of more than one zone are ambiguous, and fail with the list
+/// of the zones to use instead.
+pub const ABBREVIATIONS: [Abbreviation; 37] = [
+    // North America. "PST" is also the Philippine Standard Time, but rarely written so outside of the Philippines.
+    ("PST", &[("America/Los_Angeles", east(-8, 0), "Pacific")]),
+    ("PDT", &[("America/Los_Angeles", east(-7, 0), "Pacific")]),
+    ("MDT", &[("America/Denver", east(-6, 0), "Mountain")]),
+    (
+        "CST",
+        &[
+            ("America/Chicago", east(-6, 0), "US Central"),
+            ("Asia/Shanghai", east(8, 0), "China"),
+            ("America/Havana", east(-5, 0), "Cuba"),
+        ],
+    ),
+    ("CDT", &[("America/Chicago", east(-5, 0), "US Central")]),
+    ("EDT", &[("America/New_York", east(-4, 0), "Eastern")]),
+    ("AKST", &[("America/Anchorage", east(-9, 0), "Alaska")]),
+    ("AKDT", &[("America/Anchorage", east(-8, 0), "Alaska")]),
+    (
+        "AST",
+        &[
+            ("America/Halifax", east(-4, 0), "Atlantic"),
+            ("Asia/Riyadh", east(3, 0), "Arabia"),
+        ],
+    ),
+    ("ADT", &[("America/Halifax", east(-3, 0), "Atlantic")]),
+    (
+        "NST",
+        &[("America/St_Johns", east(-3, -30), "Newfoundland")],
+    ),
+    (
+        "NDT",
+        &[("America/St_Johns", east(-2, -30), "Newfoundland")],
+    ),
+    // South America
+    ("BRT", &[("America/Sao_Paulo", east(-3, 0), "Brasilia")]),
+    (
+        "ART",
+        &[("America/Argentina/Buenos_Aires", east(-3, 0), "Argentina")],
+    ),
+    // Europe and Africa
+    (
+        "BST",
+        &[
+            ("Europe/London", east(1, 0), "British Summer"),
+            ("Asia/Dhaka", east(6, 0), "Bangladesh"),
+        ],
+    ),
+    (
+        "IST",
+        &[
+            ("Asia/Kolkata", east(5, 30), "India"),
+            ("Europe/Dublin", east(1, 0), "Irish"),
+            ("Asia/Jerusalem", east(2, 0), "Israel"),
+        ],
+    ),
+    ("WEST", &[("WET", east(1, 0), "Western European Summer")]),
+    ("CEST", &[("CET", east(2, 0), "Central European Summer")]),
+    ("EEST", &[("EET", east(3, 0), "Eastern European Summer")]),
+    ("MSK", &[("Europe/Moscow", east(3, 0), "Moscow")]),
+    (
+        "SAST",
+        &[("Africa/Johannesburg", east(2, 0), "South Africa")],
+    ),
+    ("WAT", &[("Africa/Lagos", east(1, 0), "West Africa")]),
+    ("EAT", &[("Africa/Nairobi", east(3, 0), "East Africa")]),
+    // Asia and Oceania
+    ("PKT", &[("Asia/Karachi", east(5, 0), "Pakistan")]),
+    ("WIB", &[("Asia/Jakarta", east(7, 0), "Western Indonesia")]),
+    ("ICT", &[("Asia/Bangkok", east(7, 0), "Indochina")]),
+    ("SGT", &[("Asia/Singapore", east(8, 0), "Singapore")]),
+    ("HKT", &[("Asia/Hong_Kong", east(8, 0), "Hong Kong")]),
+    ("JST", &[("Asia/Tokyo", east(9, 0), "Japan")]),
+    ("KST", &[("Asia/Seoul", east(9, 0), "Korea")]),
+    (
+        "AWST",
+        &[("Australia/Perth", east(8, 0), "Australian Western")],
+    ),
+    (
+        "ACST",
+        &[("Australia/Adelaide", east(9, 30), "Australian Central")],
+    ),
+    (
+        "ACDT",
+        &[("Australia/Adelaide", east(10, 30), "Australian Central")],
+    ),
+    (
+        "AEST",
+        &[("Australia/Sydney", east(10, 0), "Australian Eastern")],
+    ),
+    (
+        "AEDT",
+        &[("Australia/Sydney", east(11, 0), "Australian Eastern")],
+    ),
+    ("NZST", &[("Pacific/Auckland", east(12, 0), "New Zealand")]),
+    ("NZDT", &[("Pacific/Auckland", east(13, 0), "New Zealand")]),
+];
+
+/// Parse the time zone by the IANA name, or by the abbreviation from ABBREVIATIONS ignoring case.
+pub fn parse_tz(name: &str) -> Result<Tz, String> {
+    let err = match Tz::from_str(name) {
+        Ok(tz) => return Ok(tz),
+        Err(err) => err,
+    };
+    match ABBREVIATIONS
+        .iter()
+        .find(|(abbreviation, _)| abbreviation.eq_ignore_ascii_case(name))
+    {
+        Some((abbreviation, [(_, offset, _)])) => Ok(Tz(Zone::Fixed(*offset, Some(abbreviation)))),
+        Some((abbreviation, zones)) => {
+            let zones: Vec<String> = zones
+                .iter()
+                .map(|(name, _, description)| format!("{} ({})", name, description))
+                .collect();
+            Err(format!(
+                "ambiguous time zone abbreviation {:?}, use one of: {}",
+                abbreviation,
+                zones.join(", ")
+            ))
+        }
+        None => Err(format!("failed to parse {:?}: {}", name, err)),
+    }
+}
+
+/// Interpret the date-time without time zone in the zone. On the DST change back, the earlier of the repeated times.
+/// The times in the DST gap are an error.
+#[cfg(not(feature = "jiff"))]
+pub fn from_local(local: NaiveDateTime, tz: &Tz) -> Result<DateTime<Tz>, String> {
+    chrono_from_local(local, tz)
+}
+
+fn chrono_from_local(local: NaiveDateTime, tz: &Tz) -> Result<DateTime<Tz>, String> {
+    match tz.from_local_datetime(&local) {
+        LocalResult::Single(datetime) => Ok(datetime),
+        LocalResult::Ambiguous(datetime, _) => Ok(datetime),
+        LocalResult::None => Err(format!("{} does not exist in {}", local, tz)),
+    }
+}
+
+/// Interpret the date-time without time zone in the zone, with the "compatible" disambiguation of jiff and RFC 5545:
+/// on the DST change back the earlier of the repeated times, and the times in the DST gap move forward by the gap,
+/// so 02:30 on the day of the change to summer time is 03:30.
+#[cfg(feature = "jiff")]
+pub fn from_local(local: NaiveDateTime, tz: &Tz) -> Result<DateTime<Tz>, String> {
+    use chrono::{Datelike, Timelike};
+    // jiff has only the years -9999 to 9999, beyond them the zone is as in chrono.
+    if !in_jiff_range(local.year()) {
+        return chrono_from_local(local, tz);
+    }
+    let civil = jiff::civil::DateTime::new(
+        local.year() as i16,
+        local.month() as i8,
+        local.day() as i8,
+        local.hour() as i8,
+        local.minute() as i8,
+        local.second() as i8,
+        local.nanosecond().min(999_999_999) as i32,
+    )
+    .map_err(|e| format!("{} is out of range: {}", local, e))?;
+    let zoned = civil
+        .to_zoned(jiff_zone(tz)?)
+        .map_err(|e| format!("{} does not exist in {}: {}", local, tz, e))?;
+    from_zoned(&zoned, tz)
+}
+
+/// Truncate the date-time to a multiple of the span in its local time, like the DurationRound of chrono. The
+/// nanoseconds are counted in i128, so all the years of the date-times work, not only 1677 to 2262.
+pub fn trunc_local(datetime: &DateTime<Tz>, span: TimeDelta) -> Result<DateTime<Tz>, String> {
+    let span_nanos = match span.num_nanoseconds() {
+        Some(nanos) if nanos > 0 => i128::from(nanos),
+        _ => return Err(format!("cannot truncate to {:?}", span)),
+    };
+    let local = datetime.naive_local().and_utc();
+    let nanos =
+        i128::from(local.timestamp()) * 1_000_000_000 + i128::from(local.timestamp_subsec_nanos());
+    let delta = TimeDelta::nanoseconds(nanos.rem_euclid(span_nanos) as i64);
+    datetime
+        .checked_sub_signed(delta)
+        .ok_or_else(|| format!("{} truncated to {:?} is out of range", datetime, span))
+}
+
+/// The start of the day of the date-time in its zone. This is midnight, unless the DST change skips midnight.
+#[cfg(not(feature = "jiff"))]
+pub fn start_of_day(datetime: &DateTime<Tz>) -> Result<DateTime<Tz>, String> {
+    trunc_local(datetime, TimeDelta::days(1))
+}
+
+/// The start of the day of the date-time in its zone, the first instant of the date also on the DST change days.
+#[cfg(feature = "jiff")]
+pub fn start_of_day(datetime: &DateTime<Tz>) -> Result<DateTime<Tz>, String> {
+    use chrono::Datelike;
+    if !in_jiff_range(datetime.year()) {
+        return trunc_local(datetime, TimeDelta::days(1));
+    }
+    let tz = datetime.timezone();
+    let timestamp = jiff::Timestamp::new(
+        datetime.timestamp(),
+        datetime.timestamp_subsec_nanos().min(999_999_999) as i32,
+    )
+    .map_err(|e| e.to_string())?;
+    let start = timestamp
+        .to_zoned(jiff_zone(&tz)?)
+        .start_of_day()
+        .map_err(|e| e.to_string())?;
+    from_zoned(&start, &tz)
+}
+
+#[cfg(feature = "jiff")]
+fn in_jiff_range(year: i32) -> bool {
+    (-9999..=9999).contains(&year)
+}
+
+/// The zone of jiff with the IANA name of the zone.
+#[cfg(feature = "jiff")]
+fn jiff_zone(tz: &Tz) -> Result<jiff::tz::TimeZone, String> {
+    if let Zone::Fixed(offset, _) = tz.0 {
+        let offset = jiff::tz::Offset::from_seconds(offset.local_minus_utc())
+            .map_err(|e| format!("bad offset {}: {}", offset, e))?;
+        return Ok(jiff::tz::TimeZone::fixed(offset));
+    }
+    jiff::tz::TimeZone::get(&tz.name()).map_err(|e| format!("unknown time zone {}: {}", tz, e))
+}
+
+#[cfg(feature = "jiff")]
+fn from_zoned(zoned: &jiff::Zoned, tz: &Tz) -> Result<DateTime<Tz>, String> {
+    let timestamp = zoned.timestamp();
+    DateTime::from_timestamp(timestamp.as_second(), timestamp.subsec_nanosecond() as u32)
+        .map(|datetime| datetime.with_timezone(tz))
+        .ok_or_else(|| format!("{} is out of range", zoned))
+}
+
+#[cfg(all(test, feature = "tz"))]
+mod tests {
+    use super::{parse_tz, Tz, ABBREVIATIONS, UTC};
+    use chrono::{NaiveDate, Offset, TimeZone};
+    use chrono_tz::OffsetName;
+    use std::str::FromStr;
+
+    #[test]
+    fn names_and_abbreviations() {
+        assert_eq!(
+            parse_tz("Europe/Warsaw"),
+            Ok(Tz::from(chrono_tz::Tz::Europe__Warsaw))
+        );
+        assert_eq!(parse_tz("EST"), Ok(Tz::from(chrono_tz::Tz::EST)));
+        let offset = |name| {
+            let tz = parse_tz(name).unwrap();
+            let datetime = tz.with_ymd_and_hms(2024, 7, 1, 13, 0, 0).unwrap();
+            (tz.name(), datetime.offset().fix().to_string())
+        };
+        assert_eq!(offset("PST"), ("PST".to_string(), "-08:00".to_string()));
+        assert_eq!(offset("pdt"), ("PDT".to_string(), "-07:00".to_string()));
+        assert_eq!(offset("JST"), ("JST".to_string(), "+09:00".to_string()));
+        assert_eq!(offset("NST"), ("NST".to_string(), "-03:30".to_string()));
+        // The fixed offsets are zones also with the tz database, like without it.
+        assert_eq!(parse_tz("+02:00").unwrap().name(), "+02:00");
+        assert_eq!(parse_tz("UTC"), Ok(UTC));
+        let err = parse_tz("Mars/Olympus").unwrap_err();
+        assert!(
+            err.starts_with("failed to parse \"Mars/Olympus\""),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn ambiguous() {
+        assert_eq!(
+            parse_tz("IST"),
+            Err(
+                "ambiguous time zone abbreviation \"IST\", use one of: Asia/Kolkata (India), \
+                 Europe/Dublin (Irish), Asia/Jerusalem (Israel)"
+                    .to_string()
+            )
+        );
+        let err = parse_tz("cst").unwrap_err();
+        assert!(err.contains("America/Chicago (US Central)"), "{}", err);
+    }
+
+    #[test]
+    fn offsets_match_abbreviations() {
+        // The offset of the abbreviation is the standard or the summer time of each of its zones, named so by the tz
+        // database, unless it names the offset only by the number, like "-03".
+        let winter = NaiveDate::from_ymd_opt(2024, 1, 15)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        let summer = NaiveDate::from_ymd_opt(2024, 7, 15)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        for (abbreviation, zones) in ABBREVIATIONS {
+            assert!(
+                chrono_tz::Tz::from_str(abbreviation).is_err(),
+                "{}",
+                abbreviation
+            );
+            for (name, offset, _) in zones.iter() {
+                let tz = chrono_tz::Tz::from_str(name).unwrap();
+                let names: Vec<String> = [winter, summer]
+                    .iter()
+                    .map(|date| tz.from_utc_datetime(date))
+                    .filter(|datetime| datetime.offset().fix() == *offset)
+                    .map(|datetime| datetime.offset().abbreviation().to_string())
+                    .collect();
+                assert!(
+                    names
+                        .iter()
+                        .any(|a| a == abbreviation || a.starts_with(['+', '-'])),
+                    "{} {} in {} is {:?}",
+                    abbreviation,
+                    offset,
+                    name,
+                    names
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn truncate_in_all_years() {
+        use super::trunc_local;
+        use chrono::TimeDelta;
+        let tz = Tz::from(chrono_tz::Tz::Europe__Warsaw);
+        let datetime = tz.with_ymd_and_hms(2024, 5, 17, 13, 30, 0).unwrap();
+        assert_eq!(
+            trunc_local(&datetime, TimeDelta::days(1))
+                .unwrap()
+                .to_rfc3339(),
+            "2024-05-17T00:00:00+02:00"
+        );
+        // Beyond the nanoseconds in i64, which end in 1677 and 2262.
+        for year in [-262143, -44, 1000, 3000, 262142] {
+            let datetime = UTC.with_ymd_and_hms(year, 5, 17, 13, 30, 0).unwrap();
+            let expected = UTC.with_ymd_and_hms(year, 5, 17, 13, 0, 0).unwrap();
+            assert_eq!(trunc_local(&datetime, TimeDelta::hours(1)), Ok(expected));
+        }
+        assert!(trunc_local(&datetime, TimeDelta::zero()).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "jiff")]
+    fn dst_changes_with_jiff() {
+        use super::{from_local, start_of_day};
+        let tz = Tz::from(chrono_tz::Tz::Europe__Warsaw);
+        // The clocks move from 02:00 to 03:00 on 2024-03-31 in Warsaw.
+        let gap = NaiveDate::from_ymd_opt(2024, 3, 31)
+            .unwrap()
+            .and_hms_opt(2, 30, 0)
+            .unwrap();
+        assert_eq!(
+            from_local(gap, &tz).unwrap().to_rfc3339(),
+            "2024-03-31T03:30:00+02:00"
+        );
+        let noon = tz.with_ymd_and_hms(2024, 3, 31, 12, 0, 0).unwrap();
+        assert_eq!(
+            start_of_day(&noon).unwrap().to_rfc3339(),
+            "2024-03-31T00:00:00+01:00"
+        );
+    }
+}
+
+#[cfg(all(test, not(feature = "tz")))]
+mod fixed_tests {
+    use super::{parse_tz, UTC};
+
+    #[test]
+    fn fixed_offsets() {
+        assert_eq!(parse_tz("UTC"), Ok(UTC));
+        assert_eq!(parse_tz("+02:00").unwrap().name(), "+02:00");
+        assert!(parse_tz("Europe/Warsaw").is_err());
+        // The abbreviations are fixed offsets, known without the tz database.
+        assert_eq!(parse_tz("PST").unwrap().name(), "PST");
+    }
+}
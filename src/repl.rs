@@ -0,0 +1,117 @@
+//! Interactive prompt, with completion of the expressions and the history kept between the sessions.
+use std::path::PathBuf;
+
+#[cfg(feature = "repl")]
+use rustyline::{
+    completion::Completer, error::ReadlineError, highlight::Highlighter, hint::Hinter,
+    history::DefaultHistory, validate::Validator, Context, Editor, Helper,
+};
+#[cfg(feature = "repl")]
+use tscalc::analysis;
+
+/// Return the path of the history file, $TSCALC_HISTORY, or $XDG_DATA_HOME/tscalc/history, or
+/// %APPDATA%\tscalc\history on Windows and ~/.local/share/tscalc/history elsewhere.
+#[cfg_attr(not(feature = "repl"), allow(dead_code))]
+pub fn history_file() -> Result<PathBuf, String> {
+    if let Some(path) = std::env::var_os("TSCALC_HISTORY") {
+        return Ok(PathBuf::from(path));
+    }
+    let data_dir = match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        // Windows has no HOME by default, and keeps the user data in the roaming application data.
+        None if cfg!(windows) => {
+            let app_data = std::env::var_os("APPDATA")
+                .ok_or("cannot find the history file, set APPDATA or TSCALC_HISTORY".to_string())?;
+            PathBuf::from(app_data)
+        }
+        None => {
+            let home = std::env::var_os("HOME")
+                .ok_or("cannot find the history file, set HOME or TSCALC_HISTORY".to_string())?;
+            PathBuf::from(home).join(".local").join("share")
+        }
+    };
+    Ok(data_dir.join("tscalc").join("history"))
+}
+
+/// Complete the function names, keywords, duration units, time zone names and variables, see analysis::complete.
+#[cfg(feature = "repl")]
+struct ExprHelper;
+
+#[cfg(feature = "repl")]
+impl Completer for ExprHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let completions = analysis::complete(line, pos);
+        let start = completions
+            .first()
+            .map_or(pos, |completion| completion.start);
+        let candidates = completions
+            .into_iter()
+            .map(|completion| completion.text)
+            .collect();
+        Ok((start, candidates))
+    }
+}
+
+#[cfg(feature = "repl")]
+impl Hinter for ExprHelper {
+    type Hint = String;
+}
+
+#[cfg(feature = "repl")]
+impl Highlighter for ExprHelper {}
+
+#[cfg(feature = "repl")]
+impl Validator for ExprHelper {}
+
+#[cfg(feature = "repl")]
+impl Helper for ExprHelper {}
+
+/// Read the expressions line by line until end of input, and print the result of each one. The errors are printed
+/// and do not end the session.
+#[cfg(feature = "repl")]
+pub fn run(eval: impl Fn(&String) -> Result<String, String>) -> Result<(), String> {
+    let mut editor: Editor<ExprHelper, DefaultHistory> =
+        Editor::new().map_err(|err| err.to_string())?;
+    editor.set_helper(Some(ExprHelper));
+    let history = history_file()?;
+    // There is no history on the first run.
+    let _ = editor.load_history(&history);
+    loop {
+        match editor.readline("> ") {
+            Ok(line) => {
+                let line = line.trim().to_string();
+                if line.is_empty() {
+                    continue;
+                }
+                editor
+                    .add_history_entry(line.as_str())
+                    .map_err(|err| err.to_string())?;
+                match eval(&line) {
+                    Ok(output) => println!("{}", output),
+                    Err(message) => println!("{}", message),
+                }
+            }
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(err.to_string()),
+        }
+    }
+    if let Some(dir) = history.parent() {
+        std::fs::create_dir_all(dir).map_err(|err| format!("cannot create {:?}: {}", dir, err))?;
+    }
+    editor
+        .save_history(&history)
+        .map_err(|err| format!("cannot save history to {:?}: {}", history, err))
+}
+
+#[cfg(not(feature = "repl"))]
+pub fn run(_eval: impl Fn(&String) -> Result<String, String>) -> Result<(), String> {
+    Err("repl is not supported, build with the repl feature".to_string())
+}
@@ -0,0 +1,631 @@
+//! The literals of the expressions: durations like "1h30m", stopwatch durations like "01:30:00", and date-times like
+//! "2024-05-17T13:00:00+02:00". The module uses only core and alloc, without regex, chrono and the tz database, so
+//! firmware and other no_std tooling can embed it to accept the same syntax as tscalc. The parser of the expressions
+//! scans the durations with it.
+use alloc::{format, string::String};
+use core::fmt;
+
+pub const NS: i64 = 1;
+pub const US_NS: i64 = 1000 * NS;
+pub const MS_NS: i64 = 1000 * US_NS;
+pub const SECOND_NS: i64 = 1000 * MS_NS;
+pub const MINUTE_NS: i64 = 60 * SECOND_NS;
+pub const HOUR_NS: i64 = 60 * MINUTE_NS;
+pub const DAY_NS: i64 = 24 * HOUR_NS;
+pub const WEEK_NS: i64 = 7 * DAY_NS;
+/// Year of 365 days, as in Prometheus.
+pub const YEAR_NS: i64 = 365 * DAY_NS;
+
+/// Duration as the whole seconds and the nanoseconds within the second, like chrono's TimeDelta, and in the same range
+/// of i64 milliseconds either way, about 292 million years.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Duration {
+    pub secs: i64,
+    /// Below one second, also for the negative durations, so -1.5s is -2 seconds and 500_000_000 nanoseconds.
+    pub nanos: u32,
+}
+
+/// The longest duration in nanoseconds, i64::MAX milliseconds as in TimeDelta.
+const MAX_DURATION_NS: i128 = i64::MAX as i128 * MS_NS as i128;
+
+impl Duration {
+    /// The duration from nanoseconds, which always fit the range.
+    pub fn from_nanos(nanos: i64) -> Duration {
+        Duration::from_wide_nanos(i128::from(nanos)).unwrap()
+    }
+
+    /// The duration from nanoseconds, None when out of range.
+    fn from_wide_nanos(nanos: i128) -> Option<Duration> {
+        (nanos.abs() <= MAX_DURATION_NS).then(|| Duration {
+            secs: nanos.div_euclid(i128::from(SECOND_NS)) as i64,
+            nanos: nanos.rem_euclid(i128::from(SECOND_NS)) as u32,
+        })
+    }
+}
+
+/// Units of duration, like "h" in "1h".
+pub const DURATION_UNITS: [&str; 9] = ["y", "w", "d", "h", "m", "s", "ms", "us", "ns"];
+
+/// Units and their length. Microseconds can be written with the micro sign, as in Go, like "1.5µs".
+const UNITS: [(&str, i64); 11] = [
+    ("ms", MS_NS),
+    ("us", US_NS),
+    ("µs", US_NS),
+    ("μs", US_NS),
+    ("ns", NS),
+    ("y", YEAR_NS),
+    ("w", WEEK_NS),
+    ("d", DAY_NS),
+    ("h", HOUR_NS),
+    ("m", MINUTE_NS),
+    ("s", SECOND_NS),
+];
+
+/// Long names of the units, as in tickets and chats, like "1 day 2 hours" or "5 mins". Unlike the short units they
+/// can be separated from the number with spaces.
+const LONG_UNITS: [(&str, i64); 37] = [
+    ("years", YEAR_NS),
+    ("year", YEAR_NS),
+    ("yrs", YEAR_NS),
+    ("yr", YEAR_NS),
+    ("weeks", WEEK_NS),
+    ("week", WEEK_NS),
+    ("wks", WEEK_NS),
+    ("wk", WEEK_NS),
+    ("days", DAY_NS),
+    ("day", DAY_NS),
+    ("hours", HOUR_NS),
+    ("hour", HOUR_NS),
+    ("hrs", HOUR_NS),
+    ("hr", HOUR_NS),
+    ("minutes", MINUTE_NS),
+    ("minute", MINUTE_NS),
+    ("mins", MINUTE_NS),
+    ("min", MINUTE_NS),
+    ("seconds", SECOND_NS),
+    ("second", SECOND_NS),
+    ("secs", SECOND_NS),
+    ("sec", SECOND_NS),
+    ("milliseconds", MS_NS),
+    ("millisecond", MS_NS),
+    ("millis", MS_NS),
+    ("msecs", MS_NS),
+    ("msec", MS_NS),
+    ("microseconds", US_NS),
+    ("microsecond", US_NS),
+    ("micros", US_NS),
+    ("usecs", US_NS),
+    ("usec", US_NS),
+    ("nanoseconds", NS),
+    ("nanosecond", NS),
+    ("nanos", NS),
+    ("nsecs", NS),
+    ("nsec", NS),
+];
+
+/// Length of the unit in nanoseconds, None for an unknown unit.
+pub fn unit_nanos(unit: &str) -> Option<i64> {
+    UNITS
+        .iter()
+        .chain(LONG_UNITS.iter())
+        .find(|(name, _)| *name == unit)
+        .map(|(_, nanos)| *nanos)
+}
+
+/// The unit at the beginning of the input, the longest name that matches, so "ms" is not scanned as "m", nor "mins"
+/// as "m" followed by garbage. After spaces only the long names are accepted.
+fn scan_unit(s: &str, after_spaces: bool) -> Option<&'static str> {
+    let long_units = LONG_UNITS.iter();
+    let units = UNITS.iter().filter(|_| !after_spaces).chain(long_units);
+    units
+        .map(|(name, _)| *name)
+        .filter(|name| s.starts_with(name))
+        .max_by_key(|name| name.len())
+}
+
+/// Number of the spaces at the beginning of the input.
+fn spaces(s: &str) -> usize {
+    s.bytes().take_while(|b| *b == b' ').count()
+}
+
+/// Number of the ASCII digits at the beginning of the input.
+fn digits(s: &str) -> usize {
+    s.bytes().take_while(u8::is_ascii_digit).count()
+}
+
+/// The digits after the decimal point at the beginning of the input, like "5" in ".5h", up to `max` digits.
+fn fraction(s: &str, max: usize) -> Option<&str> {
+    let rest = s.strip_prefix('.')?;
+    match digits(rest).min(max) {
+        0 => None,
+        n => Some(&rest[..n]),
+    }
+}
+
+/// One number-unit part of a duration, like "1.5h" in "1.5h30m", or "2 hours" in "1 day 2 hours".
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DurationPart<'a> {
+    pub value: &'a str,
+    /// The digits after the decimal point.
+    pub frac: Option<&'a str>,
+    /// The spaces between the number and the long unit name, like " " in "2 hours".
+    pub space: &'a str,
+    pub unit: &'a str,
+}
+
+impl DurationPart<'_> {
+    /// Length of the part in the input, in bytes.
+    pub fn input_len(&self) -> usize {
+        self.value.len()
+            + self.frac.map_or(0, |frac| frac.len() + 1)
+            + self.space.len()
+            + self.unit.len()
+    }
+
+    /// The part in nanoseconds, None on overflow. The fraction is below the unit, and the digits beyond nanoseconds
+    /// are dropped, as in Go.
+    pub fn nanos(&self) -> Option<i128> {
+        let unit_ns = i128::from(unit_nanos(self.unit).unwrap());
+        let frac_nanos = self.frac.map_or(0, |frac| {
+            let digits = &frac[..frac.len().min(18)];
+            digits.parse::<i128>().unwrap() * unit_ns / 10_i128.pow(digits.len() as u32)
+        });
+        self.value
+            .parse::<i128>()
+            .ok()?
+            .checked_mul(unit_ns)?
+            .checked_add(frac_nanos)
+    }
+}
+
+/// Scan the number-unit part of a duration at the beginning of the input.
+pub fn scan_duration_part(s: &str) -> Option<DurationPart<'_>> {
+    let value = &s[..digits(s)];
+    if value.is_empty() {
+        return None;
+    }
+    let frac = fraction(&s[value.len()..], usize::MAX);
+    let rest = &s[value.len() + frac.map_or(0, |frac| frac.len() + 1)..];
+    let space = &rest[..spaces(rest)];
+    let unit = scan_unit(&rest[space.len()..], !space.is_empty())?;
+    Some(DurationPart {
+        value,
+        frac,
+        space,
+        unit,
+    })
+}
+
+/// Scan the part of a duration after the previous one, like " 2 hours" in "1 day 2 hours". Return the part and the
+/// length of the spaces before it. The spaces are accepted only next to the long unit names, so "1s 2s" are two
+/// durations, as in a list of values.
+fn scan_next_part<'a>(s: &'a str, previous: &DurationPart) -> Option<(DurationPart<'a>, usize)> {
+    let gap = spaces(s);
+    scan_duration_part(&s[gap..])
+        .filter(|part| gap == 0 || is_long_unit(previous.unit) || is_long_unit(part.unit))
+        .map(|part| (part, gap))
+}
+
+fn is_long_unit(unit: &str) -> bool {
+    LONG_UNITS.iter().any(|(name, _)| *name == unit)
+}
+
+/// Scan the duration at the beginning of the input, an optional minus and a sequence of number-unit parts in any
+/// order, and return its length in bytes. The parts with the long unit names can be separated with spaces.
+pub fn scan_duration(s: &str) -> Option<usize> {
+    let sign_len = usize::from(s.starts_with('-'));
+    let mut part = scan_duration_part(&s[sign_len..])?;
+    let mut len = sign_len + part.input_len();
+    while let Some((next, gap)) = scan_next_part(&s[len..], &part) {
+        len += gap + next.input_len();
+        part = next;
+    }
+    Some(len)
+}
+
+/// Error of parse_duration, telling the durations out of range from the malformed ones, so the range error can be
+/// reported over the whole literal.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DurationErr {
+    /// Not a duration, like "1h2x" with the bad unit, with the message of the bad part.
+    Invalid(String),
+    /// The duration does not fit Duration, about 292 million years, like "300000000y", with the message.
+    OutOfRange(String),
+}
+
+impl fmt::Display for DurationErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DurationErr::Invalid(message) | DurationErr::OutOfRange(message) => {
+                write!(f, "{}", message)
+            }
+        }
+    }
+}
+
+/// Parse the duration like "-1h30m". Repeated units are summed up, e.g. "1h30m1h" is 2h30m.
+pub fn parse_duration(s: &str) -> Result<Duration, DurationErr> {
+    let (neg, mut rest) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+    if rest.is_empty() {
+        return Err(DurationErr::Invalid(format!("could not match {:?}", s)));
+    }
+    let overflow = || {
+        DurationErr::OutOfRange(format!(
+            "duration {:?} is out of range, the limit is about 292 million years",
+            s
+        ))
+    };
+    let mut total_nanos: i128 = 0;
+    let mut previous: Option<DurationPart> = None;
+    while !rest.is_empty() {
+        let (part, gap) = match &previous {
+            Some(previous) => scan_next_part(rest, previous),
+            None => scan_duration_part(rest).map(|part| (part, 0)),
+        }
+        .ok_or_else(|| DurationErr::Invalid(bad_part_message(s, rest.trim_start_matches(' '))))?;
+        let nanos = part.nanos().ok_or_else(overflow)?;
+        total_nanos = total_nanos.checked_add(nanos).ok_or_else(overflow)?;
+        rest = &rest[gap + part.input_len()..];
+        previous = Some(part);
+    }
+    Duration::from_wide_nanos(if neg { -total_nanos } else { total_nanos }).ok_or_else(overflow)
+}
+
+/// Explain why the remainder of the duration could not be scanned, like the bad unit "x" of "2.5x" in "1h2.5x".
+pub fn bad_part_message(input: &str, rest: &str) -> String {
+    let value_len = digits(rest);
+    if value_len == 0 {
+        return format!("could not match {:?}", input);
+    }
+    let number_len =
+        value_len + fraction(&rest[value_len..], usize::MAX).map_or(0, |f| f.len() + 1);
+    let unit = rest[number_len..].trim_start_matches(' ');
+    let unit_len = unit
+        .find(|c: char| c.is_ascii_digit() || c.is_whitespace())
+        .unwrap_or(unit.len());
+    if unit_len == 0 {
+        format!("missing duration unit at {:?} in {:?}", rest, input)
+    } else {
+        format!(
+            "invalid duration unit {:?} in {:?}, expected one of {}",
+            &unit[..unit_len],
+            input,
+            DURATION_UNITS.join(", ")
+        )
+    }
+}
+
+/// Stopwatch-style duration, HH:MM:SS or MM:SS with optional fraction of second, like "01:30:00" or "90:00.5".
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClockDuration<'a> {
+    pub negative: bool,
+    /// The leading field, hours of HH:MM:SS or minutes of MM:SS. It is not limited, so "90:00" is 90 minutes.
+    pub first: &'a str,
+    pub second: &'a str,
+    pub third: Option<&'a str>,
+    /// Up to 9 digits after the decimal point.
+    pub frac: Option<&'a str>,
+}
+
+impl ClockDuration<'_> {
+    /// Length of the duration in the input, in bytes.
+    pub fn input_len(&self) -> usize {
+        usize::from(self.negative)
+            + self.first.len()
+            + 1
+            + self.second.len()
+            + self.third.map_or(0, |third| third.len() + 1)
+            + self.frac.map_or(0, |frac| frac.len() + 1)
+    }
+}
+
+/// Two ASCII digits after the colon at the beginning of the input, like "30" in ":30".
+fn two_digits_after_colon(s: &str) -> Option<&str> {
+    let rest = s.strip_prefix(':')?;
+    (digits(rest) >= 2).then(|| &rest[..2])
+}
+
+/// Scan the stopwatch-style duration at the beginning of the input.
+pub fn scan_clock_duration(s: &str) -> Option<ClockDuration<'_>> {
+    let negative = s.starts_with('-');
+    let rest = &s[usize::from(negative)..];
+    let first = &rest[..digits(rest)];
+    if first.is_empty() {
+        return None;
+    }
+    let second = two_digits_after_colon(&rest[first.len()..])?;
+    let rest = &rest[first.len() + 3..];
+    let third = two_digits_after_colon(rest);
+    let rest = &rest[third.map_or(0, |_| 3)..];
+    let frac = fraction(rest, 9);
+    Some(ClockDuration {
+        negative,
+        first,
+        second,
+        third,
+        frac,
+    })
+}
+
+/// Parse HH:MM:SS or MM:SS. Minutes and seconds after the leading field should be below 60.
+pub fn parse_clock_duration(s: &str) -> Result<Duration, String> {
+    let clock = scan_clock_duration(s)
+        .filter(|clock| clock.input_len() == s.len())
+        .ok_or_else(|| format!("could not match {:?} as HH:MM:SS or MM:SS", s))?;
+    let overflow = || format!("duration {:?} is out of range", s);
+    let first = clock.first.parse::<i128>().map_err(|_| overflow())?;
+    let second = clock.second.parse::<i128>().unwrap();
+    let (hours, minutes, seconds) = match clock.third {
+        Some(third) => (first, second, third.parse::<i128>().unwrap()),
+        None => (0, first, second),
+    };
+    if (hours > 0 && minutes >= 60) || seconds >= 60 {
+        return Err(format!("minutes and seconds should be below 60 in {:?}", s));
+    }
+    let frac_nanos = clock.frac.map_or(0, |digits| {
+        digits.parse::<i128>().unwrap() * 10_i128.pow(9 - digits.len() as u32)
+    });
+    let total_nanos = hours
+        .checked_mul(i128::from(HOUR_NS))
+        .and_then(|n| n.checked_add(minutes.checked_mul(i128::from(MINUTE_NS))?))
+        .and_then(|n| n.checked_add(seconds * i128::from(SECOND_NS) + frac_nanos))
+        .ok_or_else(overflow)?;
+    Duration::from_wide_nanos(if clock.negative {
+        -total_nanos
+    } else {
+        total_nanos
+    })
+    .ok_or_else(overflow)
+}
+
+/// RFC 3339 date-time with the offset, like "2024-05-17T13:00:00.5+02:00" or "2024-05-17T13:00:00Z".
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DateTimeLiteral {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    pub nanosecond: u32,
+    /// Offset from UTC, 0 for "Z".
+    pub offset_seconds: i32,
+}
+
+impl DateTimeLiteral {
+    /// Seconds since 1970-01-01T00:00:00Z, with the nanosecond on top.
+    pub fn unix_seconds(&self) -> i64 {
+        let days = days_from_civil(self.year, self.month, self.day);
+        days * 86_400 + i64::from(self.hour * 3600 + self.minute * 60 + self.second)
+            - i64::from(self.offset_seconds)
+    }
+}
+
+/// Days since 1970-01-01 of the date in the proleptic Gregorian calendar, by Howard Hinnant's days_from_civil.
+fn days_from_civil(year: i32, month: u32, day: u32) -> i64 {
+    let year = i64::from(year) - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = (i64::from(month) + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn is_leap_year(year: i32) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Scan the RFC 3339 date-time at the beginning of the input, and return it with its length in bytes. The fields
+/// should be in range, so "2024-02-30T00:00:00Z" is not a date-time. Leap seconds are not supported.
+pub fn scan_rfc3339(s: &str) -> Option<(DateTimeLiteral, usize)> {
+    let b = s.as_bytes();
+    let number = |start: usize, len: usize| -> Option<u32> {
+        let field = s.get(start..start + len)?;
+        (digits(field) == len).then(|| field.parse().unwrap())
+    };
+    let separators = [(4, b'-'), (7, b'-'), (10, b'T'), (13, b':'), (16, b':')];
+    if separators.iter().any(|(i, c)| b.get(*i) != Some(c)) {
+        return None;
+    }
+    let (year, month, day) = (number(0, 4)? as i32, number(5, 2)?, number(8, 2)?);
+    let (hour, minute, second) = (number(11, 2)?, number(14, 2)?, number(17, 2)?);
+    if !(1..=12).contains(&month)
+        || !(1..=days_in_month(year, month)).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return None;
+    }
+    let mut len = 19;
+    let nanosecond = match fraction(&s[len..], usize::MAX) {
+        Some(frac) => {
+            len += frac.len() + 1;
+            let digits = &frac[..frac.len().min(9)];
+            digits.parse::<u32>().unwrap() * 10_u32.pow(9 - digits.len() as u32)
+        }
+        None => 0,
+    };
+    let offset_seconds = match b.get(len)? {
+        b'Z' => {
+            len += 1;
+            0
+        }
+        sign @ (b'+' | b'-') => {
+            let (hours, minutes) = (number(len + 1, 2)?, number(len + 4, 2)?);
+            if b.get(len + 3) != Some(&b':') || hours > 23 || minutes > 59 {
+                return None;
+            }
+            len += 6;
+            let offset = (hours * 3600 + minutes * 60) as i32;
+            if *sign == b'-' {
+                -offset
+            } else {
+                offset
+            }
+        }
+        _ => return None,
+    };
+    let datetime = DateTimeLiteral {
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+        nanosecond,
+        offset_seconds,
+    };
+    Some((datetime, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        assert_eq!(scan_duration("1h30m + 1s"), Some(5));
+        assert_eq!(scan_duration("-1.5µs"), Some(7));
+        assert_eq!(scan_duration("1x"), None);
+        assert_eq!(scan_duration("-"), None);
+        assert_eq!(
+            parse_duration("1h30m1h"),
+            Ok(Duration::from_nanos(150 * MINUTE_NS))
+        );
+        assert_eq!(
+            parse_duration("-1.5ms"),
+            Ok(Duration::from_nanos(-1_500_000))
+        );
+        assert_eq!(
+            parse_duration("0.1234567891s"),
+            Ok(Duration::from_nanos(123_456_789))
+        );
+        assert_eq!(
+            parse_duration("1h2.5x"),
+            Err(DurationErr::Invalid("invalid duration unit \"x\" in \"1h2.5x\", expected one of y, w, d, h, m, s, ms, us, ns".into()))
+        );
+        assert_eq!(
+            parse_duration("1h2"),
+            Err(DurationErr::Invalid(
+                "missing duration unit at \"2\" in \"1h2\"".into()
+            ))
+        );
+        assert_eq!(
+            parse_duration("99999999999y"),
+            Err(DurationErr::OutOfRange(
+                "duration \"99999999999y\" is out of range, the limit is about 292 million years"
+                    .into()
+            ))
+        );
+        for s in [
+            "300000000y",
+            "-300000000y",
+            "292471208y1y",
+            "1 day 300000000 years",
+            "99999999999999999999s",
+        ] {
+            assert!(
+                matches!(parse_duration(s), Err(DurationErr::OutOfRange(_))),
+                "{}",
+                s
+            );
+        }
+        assert_eq!(
+            parse_duration("292y"),
+            Ok(Duration::from_nanos(292 * YEAR_NS))
+        );
+        // Beyond i64 nanoseconds, in the range of TimeDelta.
+        assert_eq!(
+            parse_duration("300y"),
+            Ok(Duration {
+                secs: 300 * 365 * 86_400,
+                nanos: 0
+            })
+        );
+        assert_eq!(
+            parse_duration("-292471208y1.5s"),
+            Ok(Duration {
+                secs: -292_471_208 * 365 * 86_400 - 2,
+                nanos: 500_000_000
+            })
+        );
+    }
+
+    #[test]
+    fn long_unit_names() {
+        assert_eq!(scan_duration("1 day 2 hours 3 minutes ago"), Some(23));
+        assert_eq!(scan_duration("90 seconds + 1h"), Some(10));
+        assert_eq!(scan_duration("1h 30 mins 2"), Some(10));
+        assert_eq!(scan_duration("1 day 2h 3m"), Some(8));
+        assert_eq!(scan_duration("1s 2s"), Some(2));
+        assert_eq!(scan_duration("5 m"), None);
+        assert_eq!(
+            parse_duration("1 day 2 hours 3 minutes"),
+            Ok(Duration::from_nanos(DAY_NS + 2 * HOUR_NS + 3 * MINUTE_NS))
+        );
+        assert_eq!(parse_duration("1hr"), Ok(Duration::from_nanos(HOUR_NS)));
+        assert_eq!(
+            parse_duration("5 mins"),
+            Ok(Duration::from_nanos(5 * MINUTE_NS))
+        );
+        assert_eq!(
+            parse_duration("1.5 weeks"),
+            Ok(Duration::from_nanos(10 * DAY_NS + 12 * HOUR_NS))
+        );
+        assert_eq!(
+            parse_duration("2msecs"),
+            Ok(Duration::from_nanos(2 * MS_NS))
+        );
+        assert_eq!(parse_duration("1m"), Ok(Duration::from_nanos(MINUTE_NS)));
+        assert!(parse_duration("1h ").is_err());
+        assert!(parse_duration("1s 2s").is_err());
+        assert_eq!(
+            parse_duration("1 day 2 x"),
+            Err(DurationErr::Invalid("invalid duration unit \"x\" in \"1 day 2 x\", expected one of y, w, d, h, m, s, ms, us, ns".into()))
+        );
+    }
+
+    #[test]
+    fn clock_durations() {
+        let clock = scan_clock_duration("-01:30:00.5 later").unwrap();
+        assert_eq!(clock.input_len(), 11);
+        assert_eq!(clock.third, Some("00"));
+        assert_eq!(
+            parse_clock_duration("90:00"),
+            Ok(Duration::from_nanos(90 * MINUTE_NS))
+        );
+        assert_eq!(
+            parse_clock_duration("-01:30:00.5"),
+            Ok(Duration::from_nanos(-(90 * MINUTE_NS + 500 * MS_NS)))
+        );
+        assert!(parse_clock_duration("01:60:00").is_err());
+        assert!(parse_clock_duration("1:2").is_err());
+    }
+
+    #[test]
+    fn rfc3339() {
+        let (datetime, len) = scan_rfc3339("2024-05-17T13:00:00.25+02:00 + 1h").unwrap();
+        assert_eq!(len, 28);
+        assert_eq!(datetime.nanosecond, 250_000_000);
+        assert_eq!(datetime.unix_seconds(), 1_715_943_600);
+        let (datetime, len) = scan_rfc3339("1969-12-31T23:59:59Z").unwrap();
+        assert_eq!((datetime.unix_seconds(), len), (-1, 20));
+        assert_eq!(scan_rfc3339("2024-02-30T00:00:00Z"), None);
+        assert_eq!(scan_rfc3339("2024-05-17T13:00:00"), None);
+        assert_eq!(scan_rfc3339("2024-05-17T13:00:00+2:00"), None);
+    }
+}
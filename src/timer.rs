@@ -0,0 +1,121 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+/// Return the file with the running timers: $TSCALC_TIMERS, or "tscalc/timers" in $XDG_STATE_HOME, or in
+/// %LOCALAPPDATA% on Windows and in ~/.local/state elsewhere.
+pub fn state_file() -> Result<PathBuf, String> {
+    if let Some(path) = std::env::var_os("TSCALC_TIMERS") {
+        return Ok(PathBuf::from(path));
+    }
+    let state_dir = match std::env::var_os("XDG_STATE_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        // Windows has no HOME by default, and keeps the state of the programs in the local application data.
+        None if cfg!(windows) => {
+            let app_data = std::env::var_os("LOCALAPPDATA").ok_or(
+                "cannot find the timers file, set LOCALAPPDATA or TSCALC_TIMERS".to_string(),
+            )?;
+            PathBuf::from(app_data)
+        }
+        None => {
+            let home = std::env::var_os("HOME")
+                .ok_or("cannot find the timers file, set HOME or TSCALC_TIMERS".to_string())?;
+            PathBuf::from(home).join(".local").join("state")
+        }
+    };
+    Ok(state_dir.join("tscalc").join("timers"))
+}
+
+/// Start the named timer. Starting a running timer is an error, so the measurement is not lost by accident.
+pub fn start(path: &Path, name: &str, now: DateTime<Utc>) -> Result<(), String> {
+    validate_name(name)?;
+    let mut timers = read_timers(path)?;
+    if timers.iter().any(|(timer, _)| timer == name) {
+        return Err(format!("timer {:?} is already running", name));
+    }
+    timers.push((name.to_string(), now));
+    write_timers(path, &timers)
+}
+
+/// Stop the named timer and return the elapsed time.
+pub fn stop(path: &Path, name: &str, now: DateTime<Utc>) -> Result<TimeDelta, String> {
+    let mut timers = read_timers(path)?;
+    let index = timers
+        .iter()
+        .position(|(timer, _)| timer == name)
+        .ok_or_else(|| format!("timer {:?} is not running", name))?;
+    let (_, started) = timers.remove(index);
+    write_timers(path, &timers)?;
+    Ok(now - started)
+}
+
+fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(format!(
+            "timer name should be non-empty and without whitespace, was: {:?}",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// The file has a line per timer, with the name and the start time separated with a space.
+fn read_timers(path: &Path) -> Result<Vec<(String, DateTime<Utc>)>, String> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(format!("cannot read {:?}: {}", path, e)),
+    };
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (name, started) = line
+                .split_once(' ')
+                .ok_or_else(|| format!("bad line {:?} in {:?}", line, path))?;
+            let started = DateTime::parse_from_rfc3339(started)
+                .map_err(|e| format!("bad line {:?} in {:?}: {}", line, path, e))?;
+            Ok((name.to_string(), started.with_timezone(&Utc)))
+        })
+        .collect()
+}
+
+fn write_timers(path: &Path, timers: &[(String, DateTime<Utc>)]) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("cannot create {:?}: {}", dir, e))?;
+    }
+    let content: String = timers
+        .iter()
+        .map(|(name, started)| format!("{} {}\n", name, started.to_rfc3339()))
+        .collect();
+    fs::write(path, content).map_err(|e| format!("cannot write {:?}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{start, stop};
+    use chrono::{DateTime, TimeDelta};
+
+    #[test]
+    fn start_stop() {
+        let path = std::env::temp_dir()
+            .join(format!("tscalc-timers-{}", std::process::id()))
+            .join("timers");
+        let t0 = DateTime::from_timestamp(1_716_000_000, 0).unwrap();
+        start(&path, "build", t0).unwrap();
+        start(&path, "test", t0 + TimeDelta::seconds(1)).unwrap();
+        assert!(start(&path, "build", t0).is_err());
+        let elapsed = stop(&path, "build", t0 + TimeDelta::milliseconds(90_500)).unwrap();
+        assert_eq!(elapsed, TimeDelta::milliseconds(90_500));
+        assert!(stop(&path, "build", t0).is_err());
+        assert_eq!(
+            stop(&path, "test", t0 + TimeDelta::seconds(2)),
+            Ok(TimeDelta::seconds(1))
+        );
+        assert!(start(&path, "bad name", t0).is_err());
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,153 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+use crate::parser::ids::base64_decode;
+
+/// Validity period of X.509 certificate.
+#[derive(Debug, PartialEq)]
+pub struct Validity {
+    pub not_before: DateTime<Utc>,
+    pub not_after: DateTime<Utc>,
+}
+
+/// Read the validity of the first certificate in the PEM file.
+#[cfg(feature = "x509")]
+pub fn read_validity(path: &str) -> Result<Validity, String> {
+    let pem =
+        std::fs::read_to_string(path).map_err(|e| format!("cannot read {:?}: {}", path, e))?;
+    let der = pem_to_der(&pem)?;
+    parse_validity(&der)
+}
+
+#[cfg(not(feature = "x509"))]
+pub fn read_validity(_path: &str) -> Result<Validity, String> {
+    Err("x509 is not supported, build with the x509 feature".to_string())
+}
+
+/// Return the DER bytes of the first certificate in PEM.
+#[cfg_attr(not(feature = "x509"), allow(dead_code))]
+fn pem_to_der(pem: &str) -> Result<Vec<u8>, String> {
+    let begin = "-----BEGIN CERTIFICATE-----";
+    let end = "-----END CERTIFICATE-----";
+    let start = pem.find(begin).ok_or("no certificate in PEM".to_string())? + begin.len();
+    let length = pem[start..]
+        .find(end)
+        .ok_or("certificate in PEM is not terminated".to_string())?;
+    base64_decode(&pem[start..start + length])
+}
+
+/// Find the validity in DER encoded certificate. The certificate is a sequence with the "to be signed" sequence
+/// first, where the validity follows the optional version, the serial number, the signature algorithm and the
+/// issuer.
+#[cfg_attr(not(feature = "x509"), allow(dead_code))]
+fn parse_validity(der: &[u8]) -> Result<Validity, String> {
+    let (_, certificate, _) = read_tlv(der, 0x30)?;
+    let (_, tbs, _) = read_tlv(certificate, 0x30)?;
+    let mut rest = tbs;
+    if rest.first() == Some(&0xa0) {
+        rest = read_tlv(rest, 0xa0)?.2;
+    }
+    for tag in [0x02, 0x30, 0x30] {
+        rest = read_tlv(rest, tag)?.2;
+    }
+    let (_, validity, _) = read_tlv(rest, 0x30)?;
+    let (tag, not_before, rest) = read_any_tlv(validity)?;
+    let not_before = parse_asn1_time(tag, not_before)?;
+    let (tag, not_after, _) = read_any_tlv(rest)?;
+    let not_after = parse_asn1_time(tag, not_after)?;
+    Ok(Validity {
+        not_before,
+        not_after,
+    })
+}
+
+/// Read the tag-length-value with the expected tag. Return the tag, the value and the rest of the input.
+fn read_tlv(data: &[u8], expected_tag: u8) -> Result<(u8, &[u8], &[u8]), String> {
+    let (tag, value, rest) = read_any_tlv(data)?;
+    if tag != expected_tag {
+        return Err(format!(
+            "bad certificate, expected DER tag {:#04x}, got {:#04x}",
+            expected_tag, tag
+        ));
+    }
+    Ok((tag, value, rest))
+}
+
+fn read_any_tlv(data: &[u8]) -> Result<(u8, &[u8], &[u8]), String> {
+    let truncated = || "bad certificate, truncated DER".to_string();
+    let tag = *data.first().ok_or_else(truncated)?;
+    let first = *data.get(1).ok_or_else(truncated)? as usize;
+    let (length, header) = if first < 0x80 {
+        (first, 2)
+    } else {
+        let count = first & 0x7f;
+        if count == 0 || count > 4 {
+            return Err(format!(
+                "bad certificate, unsupported DER length {:#04x}",
+                first
+            ));
+        }
+        let bytes = data.get(2..2 + count).ok_or_else(truncated)?;
+        let length = bytes.iter().fold(0, |acc, b| (acc << 8) | *b as usize);
+        (length, 2 + count)
+    };
+    let value = data.get(header..header + length).ok_or_else(truncated)?;
+    Ok((tag, value, &data[header + length..]))
+}
+
+/// Parse UTCTime like "240101000000Z", where years below 50 are 20xx, or GeneralizedTime like "20500101000000Z".
+fn parse_asn1_time(tag: u8, value: &[u8]) -> Result<DateTime<Utc>, String> {
+    let s = std::str::from_utf8(value).map_err(|_| "bad certificate time".to_string())?;
+    let s = match tag {
+        0x17 => {
+            let century = if s.get(..2).and_then(|yy| yy.parse::<u32>().ok()) < Some(50) {
+                "20"
+            } else {
+                "19"
+            };
+            format!("{}{}", century, s)
+        }
+        0x18 => s.to_string(),
+        _ => return Err(format!("bad certificate, unexpected time tag {:#04x}", tag)),
+    };
+    NaiveDateTime::parse_from_str(&s, "%Y%m%d%H%M%SZ")
+        .map(|datetime| datetime.and_utc())
+        .map_err(|e| format!("bad certificate time {:?}: {}", s, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_asn1_time, parse_validity, pem_to_der};
+
+    const PEM: &str = "-----BEGIN CERTIFICATE-----
+MIIBeDCCAR2gAwIBAgIUSLIQgmXpWf6atVdzWn6g7Rd39iowCgYIKoZIzj0EAwIw
+ETEPMA0GA1UEAwwGdHNjYWxjMB4XDTI0MDEwMTAwMDAwMFoXDTM0MDEwMTAwMDAw
+MFowETEPMA0GA1UEAwwGdHNjYWxjMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAE
+o5gmfo8LgECZ8/3YVjXinZ85zpmEkabpgglXRW8RRSiEKzSqvMLBPPcxtrWSUeBG
+sq+GTUfSm9nK+si6P66Zk6NTMFEwHQYDVR0OBBYEFBzd06BZsI6yoKnxPqLo67RX
+DeiPMB8GA1UdIwQYMBaAFBzd06BZsI6yoKnxPqLo67RXDeiPMA8GA1UdEwEB/wQF
+MAMBAf8wCgYIKoZIzj0EAwIDSQAwRgIhAK4kJBgrak+bKhIeJrOCdy2HoNp6VpyQ
+05/bZdr30t4AAiEA8yHM7mvivejP0hmJmuFQHNGBHSdWdYMz/zRXN+aX2v8=
+-----END CERTIFICATE-----
+";
+
+    #[test]
+    fn validity() {
+        let validity = parse_validity(&pem_to_der(PEM).unwrap()).unwrap();
+        assert_eq!(
+            validity.not_before.to_rfc3339(),
+            "2024-01-01T00:00:00+00:00"
+        );
+        assert_eq!(validity.not_after.to_rfc3339(), "2034-01-01T00:00:00+00:00");
+        assert!(parse_validity(&pem_to_der(PEM).unwrap()[..40]).is_err());
+        assert!(pem_to_der("garbage").is_err());
+    }
+
+    #[test]
+    fn asn1_time() {
+        let actual = parse_asn1_time(0x17, b"991231235959Z").unwrap();
+        assert_eq!(actual.to_rfc3339(), "1999-12-31T23:59:59+00:00");
+        let actual = parse_asn1_time(0x18, b"20500101000000Z").unwrap();
+        assert_eq!(actual.to_rfc3339(), "2050-01-01T00:00:00+00:00");
+        assert!(parse_asn1_time(0x04, b"20500101000000Z").is_err());
+    }
+}